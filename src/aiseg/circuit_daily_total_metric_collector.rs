@@ -363,6 +363,25 @@ mod tests {
             let data_points = result.unwrap();
             assert_eq!(data_points.len(), 4);
         }
+
+        #[tokio::test]
+        async fn test_collect_against_simulator() {
+            use crate::simulator::{SimClock, SimulatorConfig};
+            use crate::test_utils::mocks::simulator_client;
+
+            let now = Local.with_ymd_and_hms(2024, 6, 8, 18, 0, 0).unwrap();
+            let (simulator, client) = simulator_client(SimulatorConfig {
+                clock: SimClock::Fixed(now),
+                ..SimulatorConfig::default()
+            })
+            .await;
+            let collector = CircuitDailyTotalMetricCollector::new(client);
+
+            let metrics = collector.collect(now).await.unwrap();
+
+            assert_eq!(metrics.len(), 4);
+            assert_eq!(simulator.requests(), 4);
+        }
     }
 
     mod fails {
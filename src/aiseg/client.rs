@@ -3,11 +3,21 @@
 //! This module provides a client that handles HTTP requests to the AiSEG2
 //! web interface using digest authentication. The AiSEG2 system requires
 //! digest auth for all API endpoints.
+//!
+//! Responses are kept in a short-TTL page cache so that collectors reading
+//! the same page within one cycle only hit the device once. Response bodies
+//! are decoded with charset detection because older firmware serves
+//! Shift_JIS or EUC-JP pages without declaring it.
 
+use crate::aiseg::encoding::{decode_body, encoding_for_label};
+use crate::aiseg::page_cache::{CacheStats, PageCache};
 use crate::config;
 use crate::error::{AisegError, Result};
 use diqwest::WithDigestAuth;
+use encoding_rs::Encoding;
+use reqwest::header::CONTENT_TYPE;
 use reqwest::Client as HttpClient;
+use std::time::Duration;
 
 /// HTTP client for AiSEG2 API communication.
 ///
@@ -25,6 +35,10 @@ pub struct Client {
     http_client: HttpClient,
     /// Configuration containing base URL and credentials
     config: config::Aiseg2Config,
+    /// Per-cycle response cache shared by all collectors using this client
+    page_cache: PageCache,
+    /// Character encoding forced by configuration, bypassing detection
+    encoding_override: Option<&'static Encoding>,
 }
 
 impl Client {
@@ -43,18 +57,38 @@ impl Client {
     ///     url: "http://192.168.1.100".to_string(),
     ///     user: "admin".to_string(),
     ///     password: "password".to_string(),
+    ///     cache_ttl_ms: 2000,
+    ///     encoding: None,
     /// };
     ///
     /// let client = Client::new(config);
     /// ```
     pub fn new(config: config::Aiseg2Config) -> Self {
         let http_client = HttpClient::new();
+        let page_cache = PageCache::new(Duration::from_millis(config.cache_ttl_ms));
+        let encoding_override = config.encoding.as_deref().and_then(|label| {
+            let encoding = encoding_for_label(label);
+            if encoding.is_none() {
+                tracing::warn!(
+                    encoding = label,
+                    "Unknown AiSEG2 encoding override, falling back to detection"
+                );
+            }
+            encoding
+        });
         Self {
             http_client,
             config,
+            page_cache,
+            encoding_override,
         }
     }
 
+    /// Returns the hit/miss counters of the page cache.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.page_cache.stats()
+    }
+
     /// Returns the base URL of the AiSEG2 system.
     #[cfg(test)]
     pub fn base_url(&self) -> &str {
@@ -65,7 +99,8 @@ impl Client {
     ///
     /// This method constructs the full URL by combining the base URL from
     /// configuration with the provided path, then sends a GET request with
-    /// digest authentication.
+    /// digest authentication. Successful responses are served from the page
+    /// cache for subsequent requests to the same path within the cache TTL.
     ///
     /// # Arguments
     ///
@@ -98,6 +133,27 @@ impl Client {
     /// # }
     /// ```
     pub async fn get(&self, path: &str) -> Result<String, AisegError> {
+        self.page_cache
+            .get_or_fetch(path, || self.fetch(path))
+            .await
+    }
+
+    /// Fetches the page at `path`, bypassing the page cache, and returns
+    /// the name of the character encoding it was decoded with, e.g.
+    /// "Shift_JIS" for older firmware.
+    pub async fn page_encoding(&self, path: &str) -> Result<&'static str, AisegError> {
+        let (_, encoding) = self.fetch_decoded(path).await?;
+        Ok(encoding.name())
+    }
+
+    /// Sends the GET request to the device, bypassing the page cache.
+    async fn fetch(&self, path: &str) -> Result<String, AisegError> {
+        let (body, _) = self.fetch_decoded(path).await?;
+        Ok(body)
+    }
+
+    /// Sends the GET request and returns the body with its encoding.
+    async fn fetch_decoded(&self, path: &str) -> Result<(String, &'static Encoding), AisegError> {
         let url = format!("{}{}", self.config.url, path);
         let response = self
             .http_client
@@ -108,8 +164,17 @@ impl Client {
             .map_err(|e| AisegError::DigestAuth(e.to_string()))?;
 
         if response.status().is_success() {
-            let body = response.text().await?;
-            Ok(body)
+            let content_type = response
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string);
+            let bytes = response.bytes().await?;
+            Ok(decode_body(
+                &bytes,
+                content_type.as_deref(),
+                self.encoding_override,
+            ))
         } else {
             let status = response.status();
             let body = response.text().await.unwrap_or_default();
@@ -121,7 +186,7 @@ impl Client {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_utils::config::test_aiseg2_config_with_url;
+    use crate::test_utils::config::{test_aiseg2_config_with_url, TestAiseg2ConfigBuilder};
 
     #[test]
     fn test_client_new() {
@@ -259,6 +324,99 @@ mod tests {
         assert!(body.contains("太陽光発電"));
     }
 
+    #[tokio::test]
+    async fn test_get_decodes_undeclared_japanese_encodings() {
+        let test_cases = vec![
+            ("shift_jis", encoding_rs::SHIFT_JIS, None),
+            ("euc-jp", encoding_rs::EUC_JP, None),
+            ("override", encoding_rs::EUC_JP, Some("EUC-JP")),
+        ];
+
+        for (name, encoding, override_label) in test_cases {
+            let mut server = mockito::Server::new_async().await;
+            let html = r#"<html><body><div class="txt_name">リビング</div></body></html>"#;
+            let (body, _, _) = encoding.encode(html);
+
+            let _mock = server
+                .mock("GET", "/page/airenvironment/41?page=1")
+                .with_status(200)
+                .with_header("content-type", "text/html")
+                .with_body(&body)
+                .create_async()
+                .await;
+
+            let mut builder = TestAiseg2ConfigBuilder::new().with_url(server.url());
+            if let Some(label) = override_label {
+                builder = builder.with_encoding(label);
+            }
+            let client = Client::new(builder.build());
+            let result = client.get("/page/airenvironment/41?page=1").await;
+
+            assert_eq!(result.unwrap(), html, "case: {}", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_page_encoding() {
+        let test_cases = vec![
+            ("utf-8", encoding_rs::UTF_8, "UTF-8"),
+            ("shift_jis", encoding_rs::SHIFT_JIS, "Shift_JIS"),
+        ];
+
+        for (name, encoding, expected) in test_cases {
+            let mut server = mockito::Server::new_async().await;
+            let (body, _, _) = encoding.encode("<html><body>太陽光</body></html>");
+            let _mock = server
+                .mock("GET", "/page/electricflow/111")
+                .with_status(200)
+                .with_header("content-type", "text/html")
+                .with_body(&body)
+                .create_async()
+                .await;
+
+            let client = Client::new(test_aiseg2_config_with_url(server.url()));
+            let result = client.page_encoding("/page/electricflow/111").await;
+
+            assert_eq!(result.unwrap(), expected, "case: {}", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_uses_page_cache() {
+        let mut server = mockito::Server::new_async().await;
+        let mock_url = server.url();
+
+        // The device must only be asked once while the cached copy is fresh
+        let mock = server
+            .mock("GET", "/page/electricflow/111")
+            .with_status(200)
+            .with_body("<html><body>cached</body></html>")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let config = TestAiseg2ConfigBuilder::new()
+            .with_url(mock_url)
+            .with_cache_ttl_ms(60_000)
+            .build();
+
+        let client = Client::new(config);
+        let (first, second) = tokio::join!(
+            client.get("/page/electricflow/111"),
+            client.get("/page/electricflow/111")
+        );
+        let third = client.get("/page/electricflow/111").await;
+
+        assert_eq!(first.unwrap(), "<html><body>cached</body></html>");
+        assert_eq!(second.unwrap(), "<html><body>cached</body></html>");
+        assert_eq!(third.unwrap(), "<html><body>cached</body></html>");
+        mock.assert_async().await;
+
+        let stats = client.cache_stats();
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.hits, 2);
+    }
+
     #[tokio::test]
     async fn test_get_connection_error() {
         // Use a non-existent server URL
@@ -4,6 +4,7 @@ use async_trait::async_trait;
 use chrono::{DateTime, Local};
 use std::sync::Arc;
 
+use super::MAX_PAGES;
 use crate::aiseg::client::Client;
 use crate::aiseg::collector_base::CollectorBase;
 use crate::aiseg::metrics::climate::climate_metrics_to_builders;
@@ -50,7 +51,7 @@ impl MetricCollector for ClimateMetricCollector {
         let client = Arc::clone(&self.client);
 
         let paginator = PaginatorBuilder::new()
-            .max_pages(20)
+            .max_pages(MAX_PAGES)
             .fetch_with(move |page| {
                 let client = Arc::clone(&client);
                 Box::pin(async move {
@@ -85,4 +86,60 @@ mod tests {
         // Verify collector is created
         assert!(!collector.client().base_url().is_empty());
     }
+
+    #[tokio::test]
+    async fn test_climate_collector_against_simulator() {
+        use crate::simulator::{Markup, SimulatorConfig};
+        use crate::test_utils::mocks::simulator_client;
+
+        let test_cases = vec![Markup::Standard, Markup::ShiftJis, Markup::EucJp];
+
+        for markup in test_cases {
+            let config = SimulatorConfig {
+                markup,
+                ..SimulatorConfig::default()
+            };
+            let rooms = config.rooms.clone();
+            let (_simulator, client) = simulator_client(config).await;
+            let collector = ClimateMetricCollector::new(client);
+
+            let metrics = collector.collect(Local::now()).await.unwrap();
+
+            // Temperature and humidity for every room across both pages
+            assert_eq!(metrics.len(), rooms.len() * 2, "case: {:?}", markup);
+            let points = format!(
+                "{:?}",
+                metrics
+                    .iter()
+                    .map(|metric| metric.to_point().unwrap())
+                    .collect::<Vec<_>>()
+            );
+            for room in &rooms {
+                assert!(
+                    points.contains(room.as_str()),
+                    "case: {:?} {}",
+                    markup,
+                    room
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_climate_collector_fails_on_simulated_server_error() {
+        use crate::simulator::{Faults, SimulatorConfig};
+        use crate::test_utils::mocks::simulator_client;
+
+        let (_simulator, client) = simulator_client(SimulatorConfig {
+            faults: Faults {
+                error_every: Some(1),
+                ..Faults::default()
+            },
+            ..SimulatorConfig::default()
+        })
+        .await;
+        let collector = ClimateMetricCollector::new(client);
+
+        assert!(collector.collect(Local::now()).await.is_err());
+    }
 }
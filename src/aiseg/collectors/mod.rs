@@ -5,3 +5,6 @@ pub mod power_collector;
 
 pub use climate_collector::ClimateMetricCollector;
 pub use power_collector::PowerMetricCollector;
+
+/// Upper bound on the number of pages read from a paginated list page.
+const MAX_PAGES: usize = 20;
@@ -5,6 +5,7 @@ use chrono::{DateTime, Local};
 use scraper::Html;
 use std::sync::Arc;
 
+use super::MAX_PAGES;
 use crate::aiseg::client::Client;
 use crate::aiseg::collector_base::{CollectorBase, MetricResult};
 use crate::aiseg::metrics::power::{
@@ -66,7 +67,7 @@ impl PowerMetricCollector {
         let client = Arc::clone(&self.client);
 
         let paginator = PaginatorBuilder::new()
-            .max_pages(20)
+            .max_pages(MAX_PAGES)
             .fetch_with(move |page| {
                 let client = Arc::clone(&client);
                 Box::pin(async move {
@@ -133,4 +134,30 @@ mod tests {
         // Verify collector is created
         assert!(!collector.client().base_url().is_empty());
     }
+
+    #[tokio::test]
+    async fn test_power_collector_against_simulator() {
+        use crate::simulator::{Markup, SimulatorConfig};
+        use crate::test_utils::mocks::simulator_client;
+
+        let test_cases = vec![Markup::Standard, Markup::ShiftJis];
+
+        for markup in test_cases {
+            let config = SimulatorConfig {
+                markup,
+                ..SimulatorConfig::default()
+            };
+            let device_count = config.devices.len();
+            let (simulator, client) = simulator_client(config).await;
+            let collector = PowerMetricCollector::new(client);
+
+            let metrics = collector.collect(Local::now()).await.unwrap();
+
+            // 3 totals, 1 generation source and every device across both pages
+            assert_eq!(metrics.len(), 3 + 1 + device_count, "case: {:?}", markup);
+            // the flow page, both device pages and the repeated last page
+            // that ends the pagination
+            assert_eq!(simulator.requests(), 4, "case: {:?}", markup);
+        }
+    }
 }
@@ -408,6 +408,31 @@ mod tests {
             let data_points = result.unwrap();
             assert_eq!(data_points.len(), 6);
         }
+
+        #[tokio::test]
+        async fn test_collect_against_simulator() {
+            use crate::simulator::{SimClock, SimulatorConfig};
+            use crate::test_utils::mocks::simulator_client;
+
+            let now = Local.with_ymd_and_hms(2024, 6, 8, 18, 0, 0).unwrap();
+            let (simulator, client) = simulator_client(SimulatorConfig {
+                clock: SimClock::Fixed(now),
+                ..SimulatorConfig::default()
+            })
+            .await;
+            let collector = DailyTotalMetricCollector::new(client);
+
+            let today = collector.collect(now).await.unwrap();
+            let yesterday = collector
+                .collect_by_graph_id(now - chrono::Duration::days(1), "51111", Unit::Kwh)
+                .await
+                .unwrap();
+
+            assert_eq!(today.len(), 6);
+            assert_eq!(yesterday.name, "発電量(kWh)");
+            assert!(yesterday.value > 0.0);
+            assert_eq!(simulator.requests(), 7);
+        }
     }
 
     mod fails {
@@ -0,0 +1,149 @@
+//! What an AiSEG2 device reports about itself, for the `probe` command.
+//!
+//! The pages the forwarder reads do not show the firmware version, so only
+//! what the forwarder depends on is reported: the character encoding of the
+//! pages, the rooms with climate readings and the names of the circuits the
+//! forwarder is configured to collect daily totals for.
+
+use crate::aiseg::client::Client;
+use crate::aiseg::{CircuitDailyTotalMetricCollector, ClimateMetricCollector};
+use crate::error::{CollectorError, Result};
+use crate::model::MetricCollector;
+use chrono::{DateTime, Local};
+use serde_derive::Serialize;
+use std::sync::Arc;
+
+/// Page the power collector starts from; every firmware serves it.
+const STATUS_PAGE: &str = "/page/electricflow/111";
+
+/// Tag naming the room or circuit of a point.
+const SECTION_TAG: &str = "detail-section";
+
+/// Encoding, rooms and circuits as AiSEG2 serves them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeviceInfo {
+    /// Character encoding of the pages, e.g. "Shift_JIS" on older firmware
+    pub encoding: String,
+    /// Rooms with temperature and humidity readings
+    pub rooms: Vec<String>,
+    /// Circuits the daily total collector is configured with, named as in
+    /// the stored points. These are not discovered from the device.
+    pub configured_circuits: Vec<String>,
+}
+
+/// Reads the encoding, rooms and configured circuits from AiSEG2.
+///
+/// Connects and authenticates with the first request, so a wrong URL or
+/// password fails before anything else is read.
+///
+/// # Returns
+/// - `Ok(DeviceInfo)` if every page could be read
+/// - `Err(CollectorError::Source)` with the first request or parse error
+pub async fn device_info(
+    client: &Arc<Client>,
+    timestamp: DateTime<Local>,
+) -> Result<DeviceInfo, CollectorError> {
+    let encoding = client.page_encoding(STATUS_PAGE).await?;
+
+    let rooms = sections(&ClimateMetricCollector::new(Arc::clone(client)), timestamp).await?;
+    let configured_circuits = sections(
+        &CircuitDailyTotalMetricCollector::new(Arc::clone(client)),
+        timestamp,
+    )
+    .await?;
+
+    Ok(DeviceInfo {
+        encoding: encoding.to_string(),
+        rooms,
+        configured_circuits,
+    })
+}
+
+/// Distinct sections of the points `collector` collects, in their order.
+async fn sections(
+    collector: &dyn MetricCollector,
+    timestamp: DateTime<Local>,
+) -> Result<Vec<String>, CollectorError> {
+    let mut sections: Vec<String> = Vec::new();
+    for builder in collector.collect(timestamp).await? {
+        let point = builder
+            .to_point()
+            .map_err(|e| CollectorError::ValidationFailed(e.to_string()))?;
+        if let Some(section) = point.tags.get(SECTION_TAG) {
+            if !sections.contains(section) {
+                sections.push(section.clone());
+            }
+        }
+    }
+    Ok(sections)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::AisegError;
+    use crate::simulator::{self, Markup, SimulatorConfig};
+    use crate::test_utils::config::TestAiseg2ConfigBuilder;
+    use crate::test_utils::mocks::simulator_client;
+
+    mod succeeds {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_device_info() {
+            let test_cases = vec![
+                (Markup::Standard, "UTF-8"),
+                (Markup::ShiftJis, "Shift_JIS"),
+                (Markup::EucJp, "EUC-JP"),
+            ];
+
+            for (markup, encoding) in test_cases {
+                let config = SimulatorConfig {
+                    markup,
+                    ..SimulatorConfig::default()
+                };
+                let rooms = config.rooms.clone();
+                let (_simulator, client) = simulator_client(config).await;
+
+                let info = device_info(&client, Local::now()).await.unwrap();
+
+                assert_eq!(info.encoding, encoding, "case: {:?}", markup);
+                assert_eq!(info.rooms, rooms, "case: {:?}", markup);
+                assert_eq!(
+                    info.configured_circuits,
+                    vec![
+                        "EV(kWh)",
+                        "リビングエアコン(kWh)",
+                        "主寝室エアコン(kWh)",
+                        "洋室２エアコン(kWh)"
+                    ],
+                    "case: {:?}",
+                    markup
+                );
+            }
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_device_info_wrong_credentials() {
+            let simulator = simulator::spawn(SimulatorConfig::default()).await.unwrap();
+            let client = Arc::new(Client::new(
+                TestAiseg2ConfigBuilder::new()
+                    .with_url(simulator.url())
+                    .with_user("aiseg")
+                    .with_password("wrong")
+                    .build(),
+            ));
+
+            let result = device_info(&client, Local::now()).await;
+
+            assert!(matches!(
+                result,
+                Err(CollectorError::Source(AisegError::AuthFailed))
+            ));
+        }
+    }
+}
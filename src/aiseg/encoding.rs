@@ -0,0 +1,295 @@
+//! Character-encoding detection for AiSEG2 responses.
+//!
+//! Most AiSEG2 pages are UTF-8, but older firmware serves some pages as
+//! Shift_JIS or EUC-JP without declaring it in the `Content-Type` header.
+//! Decoding those bytes as UTF-8 turns device and room names such as
+//! "リビング" into mojibake, which then ends up in InfluxDB tags.
+//!
+//! The encoding is resolved in this order:
+//! 1. An explicit override from configuration (`AISEG2_ENCODING`)
+//! 2. The `charset` parameter of the `Content-Type` header
+//! 3. A `<meta charset>` / `<meta http-equiv>` declaration in the document
+//! 4. Content sniffing between UTF-8, Shift_JIS and EUC-JP
+//!
+//! A declared encoding (2 or 3) is only trusted if the body decodes without
+//! errors under it, since mislabelled pages are exactly the problem here.
+
+use encoding_rs::{Encoding, EUC_JP, SHIFT_JIS, UTF_8};
+
+/// Number of leading bytes searched for a `<meta>` charset declaration.
+const META_SNIFF_LIMIT: usize = 4096;
+
+/// Resolves an encoding label such as "Shift_JIS", "sjis" or "euc-jp".
+///
+/// Returns `None` if the label is not a known WHATWG encoding label.
+pub fn encoding_for_label(label: &str) -> Option<&'static Encoding> {
+    Encoding::for_label(label.trim().as_bytes())
+}
+
+/// Decodes a response body into a string, detecting its character encoding.
+///
+/// # Arguments
+/// * `body` - Raw response bytes
+/// * `content_type` - Value of the `Content-Type` header, if any
+/// * `override_encoding` - Encoding forced by configuration, if any
+///
+/// # Returns
+/// The decoded body and the encoding it was decoded with. Undecodable
+/// sequences are replaced with U+FFFD only when no candidate encoding
+/// decodes the body cleanly.
+pub fn decode_body(
+    body: &[u8],
+    content_type: Option<&str>,
+    override_encoding: Option<&'static Encoding>,
+) -> (String, &'static Encoding) {
+    if let Some(encoding) = override_encoding {
+        return (
+            encoding.decode_with_bom_removal(body).0.into_owned(),
+            encoding,
+        );
+    }
+
+    let declared = [
+        content_type.and_then(charset_from_content_type),
+        charset_from_meta(body),
+    ];
+    for encoding in declared.into_iter().flatten() {
+        if let Some(text) = decode_strict(encoding, body) {
+            return (text, encoding);
+        }
+        tracing::debug!(
+            encoding = encoding.name(),
+            "Declared encoding does not match response body, sniffing instead"
+        );
+    }
+
+    let encoding = sniff_encoding(body);
+    (
+        encoding.decode_with_bom_removal(body).0.into_owned(),
+        encoding,
+    )
+}
+
+/// Extracts the encoding from a `Content-Type` header value.
+fn charset_from_content_type(content_type: &str) -> Option<&'static Encoding> {
+    content_type
+        .split(';')
+        .skip(1)
+        .filter_map(|param| param.split_once('='))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case("charset"))
+        .and_then(|(_, value)| encoding_for_label(value.trim().trim_matches('"')))
+}
+
+/// Extracts the encoding from a `<meta>` declaration near the start of the document.
+///
+/// Handles both `<meta charset="Shift_JIS">` and
+/// `<meta http-equiv="Content-Type" content="text/html; charset=EUC-JP">`.
+fn charset_from_meta(body: &[u8]) -> Option<&'static Encoding> {
+    let head = &body[..body.len().min(META_SNIFF_LIMIT)];
+    let lower = head.to_ascii_lowercase();
+
+    let mut offset = 0;
+    while let Some(pos) = find(&lower[offset..], b"<meta") {
+        let tag_start = offset + pos;
+        let tag_end = find(&lower[tag_start..], b">").map_or(lower.len(), |end| tag_start + end);
+        let tag = &lower[tag_start..tag_end];
+
+        if let Some(label) = find(tag, b"charset").and_then(|pos| read_charset_value(&tag[pos..])) {
+            if let Some(encoding) = Encoding::for_label(label) {
+                return Some(encoding);
+            }
+        }
+        offset = tag_end;
+    }
+
+    None
+}
+
+/// Reads the value following `charset` in a meta tag, e.g. `charset = "sjis"`.
+fn read_charset_value(attr: &[u8]) -> Option<&[u8]> {
+    let rest = &attr[b"charset".len()..];
+    let rest = rest
+        .trim_ascii_start()
+        .strip_prefix(b"=")?
+        .trim_ascii_start();
+    let rest = rest
+        .strip_prefix(b"\"")
+        .or_else(|| rest.strip_prefix(b"'"))
+        .unwrap_or(rest);
+    let end = rest
+        .iter()
+        .position(|b| !(b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b':' | b'.')))
+        .unwrap_or(rest.len());
+
+    (end > 0).then(|| &rest[..end])
+}
+
+/// Guesses the encoding of an undeclared body.
+///
+/// Valid UTF-8 wins outright. Otherwise Shift_JIS and EUC-JP are tried and the
+/// one that decodes cleanly into the most Japanese text is chosen. Shift_JIS is
+/// the fallback since it is what older AiSEG2 firmware uses.
+fn sniff_encoding(body: &[u8]) -> &'static Encoding {
+    if std::str::from_utf8(body).is_ok() {
+        return UTF_8;
+    }
+
+    [SHIFT_JIS, EUC_JP]
+        .into_iter()
+        .filter_map(|encoding| {
+            decode_strict(encoding, body).map(|text| (encoding, japanese_score(&text)))
+        })
+        .max_by_key(|(_, score)| *score)
+        .map_or(SHIFT_JIS, |(encoding, _)| encoding)
+}
+
+/// Decodes `body` with `encoding`, returning `None` if any byte sequence is malformed.
+fn decode_strict(encoding: &'static Encoding, body: &[u8]) -> Option<String> {
+    encoding
+        .decode_without_bom_handling_and_without_replacement(body)
+        .map(|text| text.into_owned())
+}
+
+/// Scores how plausible a decoded string is as Japanese text.
+///
+/// Hiragana, full-width katakana and common kanji count for, half-width
+/// katakana (a typical artefact of decoding EUC-JP as Shift_JIS) counts against.
+fn japanese_score(text: &str) -> i64 {
+    text.chars()
+        .map(|c| match c {
+            '\u{3040}'..='\u{30FF}' | '\u{4E00}'..='\u{9FFF}' => 2,
+            '\u{FF61}'..='\u{FF9F}' => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Returns the position of `needle` in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(encoding: &'static Encoding, text: &str) -> Vec<u8> {
+        encoding.encode(text).0.into_owned()
+    }
+
+    mod succeeds {
+        use super::*;
+
+        #[test]
+        fn test_decodes_undeclared_bodies() {
+            let text = r#"<div class="txt_name">リビング</div><div>主寝室エアコン</div>"#;
+            let test_cases = vec![
+                ("utf-8", UTF_8),
+                ("shift_jis", SHIFT_JIS),
+                ("euc-jp", EUC_JP),
+            ];
+
+            for (name, encoding) in test_cases {
+                let body = encode(encoding, text);
+                assert_eq!(decode_body(&body, None, None).0, text, "case: {}", name);
+            }
+        }
+
+        #[test]
+        fn test_uses_content_type_charset() {
+            let body = encode(EUC_JP, "洋室２");
+            let decoded = decode_body(&body, Some("text/html; charset=EUC-JP"), None).0;
+            assert_eq!(decoded, "洋室２");
+        }
+
+        #[test]
+        fn test_uses_meta_charset() {
+            let test_cases = vec![
+                (r#"<meta charset="Shift_JIS">"#, SHIFT_JIS),
+                (
+                    r#"<META HTTP-EQUIV="Content-Type" CONTENT="text/html; charset=x-euc-jp">"#,
+                    EUC_JP,
+                ),
+            ];
+
+            for (meta, encoding) in test_cases {
+                let html = format!("<html><head>{}</head><body>太陽光</body></html>", meta);
+                let body = encode(encoding, &html);
+                assert_eq!(decode_body(&body, None, None).0, html, "case: {}", meta);
+            }
+        }
+
+        #[test]
+        fn test_ignores_mislabelled_header() {
+            // Header claims UTF-8 but the body is Shift_JIS
+            let body = encode(SHIFT_JIS, "リビング");
+            let decoded = decode_body(&body, Some("text/html; charset=UTF-8"), None).0;
+            assert_eq!(decoded, "リビング");
+        }
+
+        #[test]
+        fn test_reports_encoding() {
+            let test_cases = vec![
+                ("undeclared", encode(SHIFT_JIS, "リビング"), None, SHIFT_JIS),
+                (
+                    "declared",
+                    encode(EUC_JP, "洋室２"),
+                    Some("text/html; charset=EUC-JP"),
+                    EUC_JP,
+                ),
+                (
+                    "mislabelled",
+                    encode(SHIFT_JIS, "リビング"),
+                    Some("text/html; charset=UTF-8"),
+                    SHIFT_JIS,
+                ),
+            ];
+
+            for (name, body, content_type, expected) in test_cases {
+                let (_, encoding) = decode_body(&body, content_type, None);
+                assert_eq!(encoding, expected, "case: {}", name);
+            }
+        }
+
+        #[test]
+        fn test_override_wins() {
+            let body = encode(EUC_JP, "寝室");
+            let decoded = decode_body(&body, Some("text/html; charset=Shift_JIS"), Some(EUC_JP)).0;
+            assert_eq!(decoded, "寝室");
+        }
+
+        #[test]
+        fn test_encoding_for_label() {
+            let test_cases = vec![
+                ("Shift_JIS", Some(SHIFT_JIS)),
+                ("sjis", Some(SHIFT_JIS)),
+                (" euc-jp ", Some(EUC_JP)),
+                ("utf8", Some(UTF_8)),
+            ];
+
+            for (label, expected) in test_cases {
+                assert_eq!(encoding_for_label(label), expected, "case: {}", label);
+            }
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[test]
+        fn test_unknown_label() {
+            assert_eq!(encoding_for_label("klingon"), None);
+        }
+
+        #[test]
+        fn test_undecodable_body_falls_back_to_shift_jis_with_replacement() {
+            // 0xFF is invalid in UTF-8, Shift_JIS and EUC-JP alike
+            let body = [b'a', 0xFF, 0xFF, b'b'];
+            let decoded = decode_body(&body, None, None).0;
+            assert!(decoded.starts_with('a'));
+            assert!(decoded.contains('\u{FFFD}'));
+        }
+    }
+}
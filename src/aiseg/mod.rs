@@ -6,8 +6,11 @@ mod helper;
 // New modular structure
 mod collector_base;
 mod collectors;
+mod device;
+mod encoding;
 mod html_parsing;
 mod metrics;
+mod page_cache;
 mod pagination;
 mod parser_adapters;
 mod parser_traits;
@@ -21,3 +24,4 @@ pub use collectors::{ClimateMetricCollector, PowerMetricCollector};
 pub use circuit_daily_total_metric_collector::CircuitDailyTotalMetricCollector;
 pub use client::Client;
 pub use daily_total_metric_collector::DailyTotalMetricCollector;
+pub use device::{device_info, DeviceInfo};
@@ -0,0 +1,315 @@
+//! Short-lived response cache for AiSEG2 pages.
+//!
+//! Several collectors read the same page within one collection cycle (for
+//! example `/page/electricflow/111`). This cache keeps successful responses for
+//! a short TTL keyed by request path and coalesces concurrent requests for the
+//! same path into a single device request (single-flight).
+//!
+//! Failed requests are never cached. When the in-flight request fails, the next
+//! waiter retries the fetch itself; without one, the entry is dropped.
+
+use crate::error::{AisegError, Result};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+
+/// Snapshot of the cache hit/miss counters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Requests served from the cache or coalesced onto an in-flight request
+    pub hits: u64,
+    /// Requests that went to the device
+    pub misses: u64,
+}
+
+/// A single cache slot, filled at most once.
+struct CacheEntry {
+    /// The response body and the time it was fetched
+    cell: OnceCell<(Instant, String)>,
+}
+
+impl CacheEntry {
+    fn new() -> Self {
+        Self {
+            cell: OnceCell::new(),
+        }
+    }
+
+    /// Returns true if `entry` holds a response older than `ttl`, or holds
+    /// none and no caller is fetching it, as after a failed or cancelled
+    /// fetch.
+    fn is_expired(entry: &Arc<Self>, ttl: Duration) -> bool {
+        match entry.cell.get() {
+            Some((fetched_at, _)) => fetched_at.elapsed() >= ttl,
+            // Callers holding the entry are fetching it or waiting to join
+            None => Arc::strong_count(entry) == 1,
+        }
+    }
+}
+
+/// Per-path response cache with single-flight de-duplication.
+pub struct PageCache {
+    /// How long a successful response is served from the cache
+    ttl: Duration,
+    /// Cached and in-flight entries keyed by request path
+    entries: Mutex<HashMap<String, Arc<CacheEntry>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl PageCache {
+    /// Creates a new cache. A zero TTL disables caching entirely.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns true if responses are cached at all.
+    pub fn is_enabled(&self) -> bool {
+        !self.ttl.is_zero()
+    }
+
+    /// Returns the current hit/miss counters.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the cached response for `path`, or runs `fetch` to obtain it.
+    ///
+    /// Concurrent calls for the same path share a single `fetch`. Only
+    /// successful responses are stored.
+    pub async fn get_or_fetch<F, Fut>(&self, path: &str, fetch: F) -> Result<String, AisegError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<String, AisegError>>,
+    {
+        if !self.is_enabled() {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return fetch().await;
+        }
+
+        let entry = self.entry_for(path);
+        let mut fetched = false;
+        let result = entry
+            .cell
+            .get_or_try_init(|| {
+                fetched = true;
+                async move { fetch().await.map(|body| (Instant::now(), body)) }
+            })
+            .await;
+
+        if fetched {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let result = result.map(|(_, body)| body.clone());
+        if result.is_err() {
+            drop(entry);
+            self.prune(&mut self.entries.lock().unwrap_or_else(|e| e.into_inner()));
+        }
+        result
+    }
+
+    /// Drops expired entries and those no caller fetches any more.
+    fn prune(&self, entries: &mut HashMap<String, Arc<CacheEntry>>) {
+        entries.retain(|_, entry| !CacheEntry::is_expired(entry, self.ttl));
+    }
+
+    /// Looks up a live entry for `path`, replacing it if it has expired.
+    ///
+    /// Expired entries for other paths are pruned at the same time so the map
+    /// does not grow with every distinct query string (e.g. backfill dates).
+    fn entry_for(&self, path: &str) -> Arc<CacheEntry> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        self.prune(&mut entries);
+
+        Arc::clone(
+            entries
+                .entry(path.to_string())
+                .or_insert_with(|| Arc::new(CacheEntry::new())),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    fn counting_fetch(
+        counter: &Arc<AtomicUsize>,
+        body: &'static str,
+    ) -> impl Future<Output = Result<String, AisegError>> {
+        let counter = Arc::clone(counter);
+        async move {
+            counter.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Ok(body.to_string())
+        }
+    }
+
+    mod succeeds {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_serves_cached_response_within_ttl() {
+            let cache = PageCache::new(Duration::from_secs(60));
+            let counter = Arc::new(AtomicUsize::new(0));
+
+            let first = cache
+                .get_or_fetch("/page/a", || counting_fetch(&counter, "A"))
+                .await
+                .unwrap();
+            let second = cache
+                .get_or_fetch("/page/a", || counting_fetch(&counter, "B"))
+                .await
+                .unwrap();
+
+            assert_eq!(first, "A");
+            assert_eq!(second, "A");
+            assert_eq!(counter.load(Ordering::SeqCst), 1);
+            assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 1 });
+        }
+
+        #[tokio::test]
+        async fn test_coalesces_concurrent_requests() {
+            let cache = PageCache::new(Duration::from_secs(60));
+            let counter = Arc::new(AtomicUsize::new(0));
+
+            let (a, b, c) = tokio::join!(
+                cache.get_or_fetch("/page/a", || counting_fetch(&counter, "A")),
+                cache.get_or_fetch("/page/a", || counting_fetch(&counter, "A")),
+                cache.get_or_fetch("/page/a", || counting_fetch(&counter, "A")),
+            );
+
+            assert_eq!(a.unwrap(), "A");
+            assert_eq!(b.unwrap(), "A");
+            assert_eq!(c.unwrap(), "A");
+            assert_eq!(counter.load(Ordering::SeqCst), 1);
+            assert_eq!(cache.stats(), CacheStats { hits: 2, misses: 1 });
+        }
+
+        #[tokio::test]
+        async fn test_keys_by_path() {
+            let cache = PageCache::new(Duration::from_secs(60));
+            let counter = Arc::new(AtomicUsize::new(0));
+
+            let a = cache
+                .get_or_fetch("/page/a", || counting_fetch(&counter, "A"))
+                .await
+                .unwrap();
+            let b = cache
+                .get_or_fetch("/page/b", || counting_fetch(&counter, "B"))
+                .await
+                .unwrap();
+
+            assert_eq!(a, "A");
+            assert_eq!(b, "B");
+            assert_eq!(counter.load(Ordering::SeqCst), 2);
+        }
+
+        #[tokio::test]
+        async fn test_refetches_after_ttl() {
+            let cache = PageCache::new(Duration::from_millis(30));
+            let counter = Arc::new(AtomicUsize::new(0));
+
+            cache
+                .get_or_fetch("/page/a", || counting_fetch(&counter, "A"))
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let second = cache
+                .get_or_fetch("/page/a", || counting_fetch(&counter, "B"))
+                .await
+                .unwrap();
+
+            assert_eq!(second, "B");
+            assert_eq!(counter.load(Ordering::SeqCst), 2);
+        }
+
+        #[tokio::test]
+        async fn test_zero_ttl_disables_cache() {
+            let cache = PageCache::new(Duration::ZERO);
+            let counter = Arc::new(AtomicUsize::new(0));
+
+            cache
+                .get_or_fetch("/page/a", || counting_fetch(&counter, "A"))
+                .await
+                .unwrap();
+            cache
+                .get_or_fetch("/page/a", || counting_fetch(&counter, "A"))
+                .await
+                .unwrap();
+
+            assert!(!cache.is_enabled());
+            assert_eq!(counter.load(Ordering::SeqCst), 2);
+            assert_eq!(cache.stats(), CacheStats { hits: 0, misses: 2 });
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_errors_are_not_cached() {
+            let cache = PageCache::new(Duration::from_secs(60));
+
+            let entries = |cache: &PageCache| cache.entries.lock().unwrap().len();
+
+            let first = cache
+                .get_or_fetch("/page/a", || async { Err(AisegError::Timeout(10)) })
+                .await;
+            // Failed fetches of distinct paths, e.g. backfill dates, leave nothing
+            for day in 1..=3 {
+                let path = format!("/page/graph/51111?day={}", day);
+                let failed = cache
+                    .get_or_fetch(&path, || async { Err(AisegError::Timeout(10)) })
+                    .await;
+                assert!(failed.is_err());
+            }
+            assert_eq!(entries(&cache), 0);
+
+            let second = cache
+                .get_or_fetch("/page/a", || async { Ok("A".to_string()) })
+                .await;
+
+            assert!(first.is_err());
+            assert_eq!(second.unwrap(), "A");
+            assert_eq!(entries(&cache), 1);
+            assert_eq!(cache.stats(), CacheStats { hits: 0, misses: 5 });
+        }
+
+        #[tokio::test]
+        async fn test_cancelled_fetches_are_pruned() {
+            let cache = PageCache::new(Duration::from_secs(60));
+
+            let cancelled = tokio::time::timeout(
+                Duration::from_millis(10),
+                cache.get_or_fetch("/page/a", std::future::pending),
+            )
+            .await;
+            cache
+                .get_or_fetch("/page/b", || async { Ok("B".to_string()) })
+                .await
+                .unwrap();
+
+            assert!(cancelled.is_err());
+            assert_eq!(
+                cache.entries.lock().unwrap().keys().collect::<Vec<_>>(),
+                vec!["/page/b"]
+            );
+        }
+    }
+}
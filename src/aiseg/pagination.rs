@@ -2,9 +2,14 @@
 //!
 //! This module provides reusable pagination functionality to reduce duplication
 //! across collectors that need to iterate through multiple pages of data.
+//!
+//! Pages are fetched one by one until a page is empty, the device wraps around
+//! to an already-seen page, a page cannot be parsed, or `max_pages` is hit. The
+//! reason pagination stopped is reported in [`Paginated::stop_reason`].
 
 use crate::error::{AisegError, Result};
 use scraper::Html;
+use std::fmt;
 use std::future::Future;
 use std::pin::Pin;
 
@@ -37,6 +42,41 @@ impl Default for PaginationConfig {
     }
 }
 
+/// Why pagination stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// A page contained no items
+    EmptyPage,
+    /// A page repeated the previous page's items (the device wrapped around)
+    WrapAround,
+    /// A page could not be parsed
+    ParseError,
+    /// `max_pages` was reached, so later pages may have been truncated
+    LimitReached,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StopReason::EmptyPage => write!(f, "empty page"),
+            StopReason::WrapAround => write!(f, "wrap-around"),
+            StopReason::ParseError => write!(f, "parse error"),
+            StopReason::LimitReached => write!(f, "page limit reached"),
+        }
+    }
+}
+
+/// Items collected across pages together with how pagination ended.
+#[derive(Debug)]
+pub struct Paginated<T> {
+    /// All items in page order
+    pub items: Vec<T>,
+    /// Number of pages whose items were accepted
+    pub pages: usize,
+    /// Why no further pages were read
+    pub stop_reason: StopReason,
+}
+
 /// Trait for types that can be collected across multiple pages.
 pub trait PageItem: Clone + PartialEq {
     /// Returns a key that identifies this item for duplicate detection.
@@ -44,6 +84,14 @@ pub trait PageItem: Clone + PartialEq {
     fn dedup_key(&self) -> String;
 }
 
+/// Result of inspecting a single fetched page.
+enum PageOutcome<T> {
+    /// The page contributed items and pagination may continue
+    Accepted(Vec<T>),
+    /// Pagination must stop without using this page
+    Stop(StopReason),
+}
+
 /// Generic paginator for collecting items across multiple pages.
 pub struct Paginator<'a, T> {
     config: PaginationConfig,
@@ -53,45 +101,97 @@ pub struct Paginator<'a, T> {
 
 impl<'a, T: PageItem> Paginator<'a, T> {
     /// Collects all items from all pages.
+    ///
+    /// Logs a warning if `max_pages` truncated the result.
     pub async fn collect_all(&self) -> Result<Vec<T>, AisegError> {
-        let mut all_items = Vec::new();
-        let mut last_page_items: Vec<T> = Vec::new();
+        let paginated = self.collect().await?;
+
+        if paginated.stop_reason == StopReason::LimitReached {
+            tracing::warn!(
+                pages = paginated.pages,
+                max_pages = self.config.max_pages,
+                "Pagination hit the page limit, later pages may be missing"
+            );
+        } else {
+            tracing::trace!(
+                pages = paginated.pages,
+                stop_reason = %paginated.stop_reason,
+                "Pagination finished"
+            );
+        }
 
-        for page in self.config.start_page..=self.config.max_pages {
-            let response = (self.fetch_fn)(page).await?;
-            let document = Html::parse_document(&response);
+        Ok(paginated.items)
+    }
 
-            let page_items = match (self.parse_fn)(&document) {
-                Ok(items) => items,
-                Err(_) => break, // Stop on parsing error
-            };
+    /// Collects all items and reports why pagination stopped.
+    ///
+    /// Fetch errors are returned as errors; parse errors end pagination with
+    /// [`StopReason::ParseError`] and keep the items collected so far.
+    pub async fn collect(&self) -> Result<Paginated<T>, AisegError> {
+        let mut result = Paginated {
+            items: Vec::new(),
+            pages: 0,
+            stop_reason: StopReason::LimitReached,
+        };
+        let first = self.config.start_page;
+        if first > self.config.max_pages {
+            return Ok(result);
+        }
 
-            // Check for end of data
-            if page_items.is_empty() {
-                break;
+        let mut last_page_items: Vec<T> = Vec::new();
+        for page in first..=self.config.max_pages {
+            let response = (self.fetch_fn)(page).await?;
+            match self.inspect(&response, &last_page_items) {
+                PageOutcome::Accepted(items) => {
+                    result.pages += 1;
+                    result.items.extend(items.iter().cloned());
+                    last_page_items = items;
+                }
+                PageOutcome::Stop(reason) => {
+                    result.stop_reason = reason;
+                    return Ok(result);
+                }
             }
+        }
 
-            // Check if items indicate end of pagination by comparing page signatures
-            if !last_page_items.is_empty() && !page_items.is_empty() {
-                // Get keys for both pages
-                let last_keys: Vec<String> = last_page_items
-                    .iter()
-                    .map(|item| item.dedup_key())
-                    .collect();
-                let current_keys: Vec<String> =
-                    page_items.iter().map(|item| item.dedup_key()).collect();
+        Ok(result)
+    }
 
-                // If the pages have the same items in the same order, we've wrapped around
-                if last_keys == current_keys {
-                    break;
-                }
-            }
+    /// Parses a page and decides whether its items continue the pagination.
+    ///
+    /// The parsed document is not `Send`, so it never outlives this
+    /// synchronous call.
+    fn inspect(&self, response: &str, last_page_items: &[T]) -> PageOutcome<T> {
+        let document = Html::parse_document(response);
+        self.check_items((self.parse_fn)(&document), last_page_items)
+    }
 
-            last_page_items = page_items.clone();
-            all_items.extend(page_items);
+    /// Decides whether a page's parsed items continue the pagination.
+    fn check_items(
+        &self,
+        parsed: Result<Vec<T>, AisegError>,
+        last_page_items: &[T],
+    ) -> PageOutcome<T> {
+        let page_items = match parsed {
+            Ok(items) => items,
+            Err(_) => return PageOutcome::Stop(StopReason::ParseError),
+        };
+
+        // Check for end of data
+        if page_items.is_empty() {
+            return PageOutcome::Stop(StopReason::EmptyPage);
         }
 
-        Ok(all_items)
+        // If the pages have the same items in the same order, we've wrapped around
+        if !last_page_items.is_empty() {
+            let last_keys = last_page_items.iter().map(|item| item.dedup_key());
+            let current_keys = page_items.iter().map(|item| item.dedup_key());
+            if last_keys.eq(current_keys) {
+                return PageOutcome::Stop(StopReason::WrapAround);
+            }
+        }
+
+        PageOutcome::Accepted(page_items)
     }
 }
 
@@ -190,6 +290,136 @@ mod tests {
         html
     }
 
+    fn parse_test_items(document: &Html) -> Result<Vec<TestItem>, AisegError> {
+        let selector = crate::aiseg::helper::html_selector(".item")?;
+        document
+            .select(&selector)
+            .map(|element| {
+                let id = element
+                    .value()
+                    .attr("data-id")
+                    .and_then(|s| s.parse().ok())
+                    .ok_or_else(|| {
+                        AisegError::Parse(crate::error::ParseError::UnexpectedStructure(
+                            "Missing data-id attribute".to_string(),
+                        ))
+                    })?;
+                let name = element.text().collect::<String>();
+                Ok(TestItem { id, name })
+            })
+            .collect()
+    }
+
+    /// In-memory page contents as (id, name) pairs per page.
+    type TestPages = Vec<Vec<(usize, &'static str)>>;
+
+    /// Builds a paginator over in-memory pages.
+    fn paginator_over<'a>(
+        pages: TestPages,
+        max_pages: usize,
+        fetched: Arc<Mutex<Vec<usize>>>,
+    ) -> Paginator<'a, TestItem> {
+        let pages = Arc::new(pages);
+        PaginatorBuilder::new()
+            .max_pages(max_pages)
+            .fetch_with(move |page| {
+                let pages = Arc::clone(&pages);
+                let fetched = Arc::clone(&fetched);
+                Box::pin(async move {
+                    fetched.lock().unwrap().push(page);
+                    let items = pages.get(page - 1).cloned().unwrap_or_default();
+                    Ok(create_test_html(&items))
+                })
+            })
+            .parse_with(parse_test_items)
+            .build()
+            .unwrap()
+    }
+
+    mod succeeds {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_reports_stop_reasons() {
+            // (name, pages, max_pages, expected reason, expected fetches)
+            let test_cases: Vec<(&str, TestPages, usize, StopReason, usize)> = vec![
+                (
+                    "empty page",
+                    vec![vec![(1, "A")], vec![]],
+                    20,
+                    StopReason::EmptyPage,
+                    2,
+                ),
+                (
+                    "wrap-around",
+                    vec![vec![(1, "A")], vec![(2, "B")], vec![(2, "B")]],
+                    20,
+                    StopReason::WrapAround,
+                    3,
+                ),
+                (
+                    "limit",
+                    vec![vec![(1, "A")], vec![(2, "B")], vec![(3, "C")]],
+                    2,
+                    StopReason::LimitReached,
+                    2,
+                ),
+            ];
+
+            for (name, pages, max_pages, expected, fetches) in test_cases {
+                let fetched = Arc::new(Mutex::new(Vec::new()));
+                let paginator = paginator_over(pages, max_pages, Arc::clone(&fetched));
+                let result = paginator.collect().await.unwrap();
+                assert_eq!(result.stop_reason, expected, "case: {}", name);
+                assert_eq!(fetched.lock().unwrap().len(), fetches, "case: {}", name);
+            }
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_parse_error_keeps_earlier_items() {
+            let paginator = PaginatorBuilder::new()
+                .max_pages(3)
+                .fetch_with(|page| {
+                    Box::pin(async move {
+                        Ok(match page {
+                            1 => create_test_html(&[(1, "A")]),
+                            _ => r#"<div class="item" data-id="x">broken</div>"#.to_string(),
+                        })
+                    })
+                })
+                .parse_with(parse_test_items)
+                .build()
+                .unwrap();
+
+            let result = paginator.collect().await.unwrap();
+
+            assert_eq!(result.items.len(), 1);
+            assert_eq!(result.stop_reason, StopReason::ParseError);
+        }
+
+        #[tokio::test]
+        async fn test_fetch_error_is_returned() {
+            let paginator = PaginatorBuilder::<TestItem>::new()
+                .fetch_with(|page| {
+                    Box::pin(async move {
+                        match page {
+                            1 => Ok(create_test_html(&[(1, "A")])),
+                            _ => Err(AisegError::Timeout(10)),
+                        }
+                    })
+                })
+                .parse_with(parse_test_items)
+                .build()
+                .unwrap();
+
+            assert!(paginator.collect().await.is_err());
+        }
+    }
+
     #[tokio::test]
     async fn test_paginator_collects_all_pages() {
         let pages = vec![
@@ -0,0 +1,966 @@
+//! Startup backfill of daily totals.
+//!
+//! On startup the total collectors run once for every past day whose daily
+//! totals are not stored yet. The days checked begin after the watermark,
+//! the last day up to which an earlier backfill completed, or
+//! `COLLECTOR_TOTAL_INITIAL_DAYS` ago on the first run. They never reach
+//! back further than `COLLECTOR_TOTAL_MAX_LOOKBACK_DAYS`, as AiSEG2 serves
+//! no older daily graphs.
+//!
+//! Totals collected after their day ended are final and carry a
+//! `final=true` field; live readings of the running day do not. Which days
+//! are final already is asked from a [`FinalizedDays`] source, the InfluxDB
+//! v2 sink. Without one, or when the query fails, every day checked is
+//! collected.
+//!
+//! The `backfill` command instead collects an explicit date range, paced by
+//! a delay between days. Its progress is checkpointed to a file, so an
+//! interrupted run of the same range resumes after the last stored day.
+
+use crate::error::StorageError;
+use crate::model::{batch_collect_metrics, MetricCollector};
+use crate::sink::Sink;
+use async_trait::async_trait;
+use chrono::{DateTime, Days, Local, NaiveDate, NaiveTime};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Boolean field marking daily totals collected after their day ended.
+pub const FINAL_FIELD: &str = "final";
+
+/// A store that can tell which days already hold final totals.
+#[async_trait]
+pub trait FinalizedDays: Send + Sync {
+    /// Days from `first` to `last` holding final points, per measurement.
+    async fn finalized_days(
+        &self,
+        measurements: &[String],
+        first: NaiveDate,
+        last: NaiveDate,
+    ) -> Result<BTreeMap<String, BTreeSet<NaiveDate>>, StorageError>;
+}
+
+/// How far back the backfill looks and what a complete day contains.
+#[derive(Debug, Clone)]
+pub struct BackfillOptions {
+    /// Days checked on the first run, before a watermark exists
+    pub initial_days: u64,
+    /// Oldest day checked, in days before today
+    pub max_lookback_days: u64,
+    /// Measurements every complete day has points of
+    pub measurements: Vec<String>,
+}
+
+/// What one backfill run did.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BackfillReport {
+    /// Days in the checked range
+    pub checked: usize,
+    /// Days collected and written
+    pub collected: Vec<NaiveDate>,
+    /// Days that could not be collected or written
+    pub failed: Vec<NaiveDate>,
+    /// Last day up to which every day is stored, after the run
+    pub complete_through: Option<NaiveDate>,
+}
+
+/// The last day up to which the backfill is complete, kept in a file.
+pub struct Watermark {
+    path: PathBuf,
+}
+
+impl Watermark {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Reads the watermark; a missing or unreadable file means none.
+    pub fn load(&self) -> Option<NaiveDate> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+            Err(e) => {
+                tracing::warn!("Failed to read {}: {}", self.path.display(), e);
+                return None;
+            }
+        };
+        match content.trim().parse() {
+            Ok(day) => Some(day),
+            Err(e) => {
+                tracing::warn!(
+                    "Ignoring invalid watermark in {}: {}",
+                    self.path.display(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// Persists `day` atomically (write, then rename).
+    pub fn save(&self, day: NaiveDate) -> io::Result<()> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let temp = self.path.with_extension("tmp");
+        fs::write(&temp, format!("{}\n", day))?;
+        fs::rename(temp, &self.path)
+    }
+}
+
+/// Start of `day` in local time, if the day has a midnight.
+pub(crate) fn local_midnight(day: NaiveDate) -> Option<DateTime<Local>> {
+    day.and_time(NaiveTime::MIN)
+        .and_local_timezone(Local)
+        .earliest()
+}
+
+/// First and last day to check before `today`, or `None` if there is none.
+fn scan_range(
+    today: NaiveDate,
+    watermark: Option<NaiveDate>,
+    options: &BackfillOptions,
+) -> Option<(NaiveDate, NaiveDate)> {
+    let last = today.pred_opt()?;
+    let oldest = today.checked_sub_days(Days::new(options.max_lookback_days))?;
+    let first = match watermark {
+        Some(watermark) => watermark.succ_opt()?,
+        None => today.checked_sub_days(Days::new(options.initial_days))?,
+    };
+    let first = first.max(oldest);
+    (first <= last).then_some((first, last))
+}
+
+/// Days from `first` to `last` lacking final points of any of `measurements`.
+fn missing_days(
+    first: NaiveDate,
+    last: NaiveDate,
+    measurements: &[String],
+    finalized: &BTreeMap<String, BTreeSet<NaiveDate>>,
+) -> Vec<NaiveDate> {
+    first
+        .iter_days()
+        .take_while(|day| *day <= last)
+        .filter(|day| {
+            measurements.iter().any(|measurement| {
+                !finalized
+                    .get(measurement)
+                    .is_some_and(|days| days.contains(day))
+            })
+        })
+        .collect()
+}
+
+/// Collects the totals of `day` and writes them, marked final if `finalize`.
+///
+/// The day counts as collected only if the points cover every expected
+/// measurement, since failing collectors are skipped rather than reported.
+pub(crate) async fn collect_day(
+    collectors: &Vec<Box<dyn MetricCollector>>,
+    sink: &dyn Sink,
+    day: NaiveDate,
+    measurements: &[String],
+    finalize: bool,
+) -> bool {
+    let Some(timestamp) = local_midnight(day) else {
+        tracing::error!("Failed to set timestamp to midnight for {}", day);
+        return false;
+    };
+    let mut points = batch_collect_metrics(collectors, timestamp).await;
+    if finalize {
+        for point in &mut points {
+            point.fields.insert(FINAL_FIELD.to_string(), true.into());
+        }
+    }
+    let collected: BTreeSet<&str> = points
+        .iter()
+        .map(|point| point.measurement.as_str())
+        .collect();
+    let incomplete: Vec<&str> = measurements
+        .iter()
+        .map(String::as_str)
+        .filter(|measurement| !collected.contains(measurement))
+        .collect();
+
+    match sink.write(&points).await {
+        Ok(_) if incomplete.is_empty() => {
+            tracing::info!("Successfully wrote points: day={}", day);
+            true
+        }
+        Ok(_) => {
+            tracing::error!("Failed to collect {} for {}", incomplete.join(", "), day);
+            false
+        }
+        Err(e) => {
+            tracing::error!("Failed to write points for {}: {:?}", day, e);
+            false
+        }
+    }
+}
+
+/// Collects the daily totals missing before `today` and advances the watermark.
+///
+/// The watermark moves to the last day before the first one that failed, so
+/// failed days are checked again on the next start. Once `cancel` fires, the
+/// day being collected is finished and the rest is left for the next start.
+pub async fn backfill(
+    collectors: &Vec<Box<dyn MetricCollector>>,
+    sink: &dyn Sink,
+    history: Option<&dyn FinalizedDays>,
+    watermark: &Watermark,
+    options: &BackfillOptions,
+    today: NaiveDate,
+    cancel: &CancellationToken,
+) -> BackfillReport {
+    let mut report = BackfillReport {
+        complete_through: watermark.load(),
+        ..BackfillReport::default()
+    };
+    let Some((first, last)) = scan_range(today, report.complete_through, options) else {
+        tracing::info!("Daily totals are complete, nothing to backfill.");
+        return report;
+    };
+    let days: Vec<NaiveDate> = first.iter_days().take_while(|day| *day <= last).collect();
+    report.checked = days.len();
+
+    let missing = match history {
+        Some(history) => match history
+            .finalized_days(&options.measurements, first, last)
+            .await
+        {
+            Ok(recorded) => missing_days(first, last, &options.measurements, &recorded),
+            Err(e) => {
+                tracing::warn!("Failed to look up recorded days, collecting all: {}", e);
+                days.clone()
+            }
+        },
+        None => days.clone(),
+    };
+    tracing::info!(
+        "Backfilling {} of {} days from {} to {}...",
+        missing.len(),
+        days.len(),
+        first,
+        last
+    );
+
+    let mut complete = true;
+    for day in days {
+        if cancel.is_cancelled() {
+            tracing::info!("Backfill stopped before {} by shutdown.", day);
+            break;
+        }
+        if missing.contains(&day) {
+            if collect_day(collectors, sink, day, &options.measurements, true).await {
+                report.collected.push(day);
+            } else {
+                report.failed.push(day);
+                complete = false;
+            }
+        }
+        if complete {
+            report.complete_through = Some(day);
+        }
+    }
+
+    if let Some(day) = report.complete_through.filter(|day| *day >= first) {
+        if let Err(e) = watermark.save(day) {
+            tracing::error!("Failed to save backfill watermark: {}", e);
+        }
+    }
+    tracing::info!(
+        "Finished backfill: {} days written, {} failed.",
+        report.collected.len(),
+        report.failed.len()
+    );
+    report
+}
+
+/// A date range to collect with the `backfill` command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeBackfill {
+    /// First day to collect
+    pub from: NaiveDate,
+    /// Last day to collect, inclusive
+    pub to: NaiveDate,
+    /// Measurements the selected collectors produce
+    pub measurements: Vec<String>,
+    /// Pause between two days
+    pub delay: Duration,
+}
+
+/// Progress of a range backfill, reported after every day.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    /// Days handled so far, including resumed ones
+    pub done: usize,
+    /// Days in the range
+    pub total: usize,
+    pub day: NaiveDate,
+    /// Whether the day was stored completely
+    pub stored: bool,
+}
+
+/// Checkpoint of a range backfill, kept in a file.
+///
+/// Stores the range, the measurements and the last day up to which the
+/// range is stored, as one line of text.
+pub struct Checkpoint {
+    path: PathBuf,
+}
+
+impl Checkpoint {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Last stored day of an earlier run of `range`, if there was one.
+    ///
+    /// Checkpoints of another range or other measurements are ignored.
+    pub fn load(&self, range: &RangeBackfill) -> Option<NaiveDate> {
+        let content = fs::read_to_string(&self.path).ok()?;
+        let mut parts = content.split_whitespace();
+        let from: NaiveDate = parts.next()?.parse().ok()?;
+        let to: NaiveDate = parts.next()?.parse().ok()?;
+        let measurements = parts.next()?;
+        let stored: NaiveDate = parts.next()?.parse().ok()?;
+        (from == range.from && to == range.to && measurements == range.measurements.join(","))
+            .then_some(stored)
+    }
+
+    /// Persists `stored` for `range` atomically (write, then rename).
+    pub fn save(&self, range: &RangeBackfill, stored: NaiveDate) -> io::Result<()> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let temp = self.path.with_extension("tmp");
+        fs::write(
+            &temp,
+            format!(
+                "{} {} {} {}\n",
+                range.from,
+                range.to,
+                range.measurements.join(","),
+                stored
+            ),
+        )?;
+        fs::rename(temp, &self.path)
+    }
+
+    /// Removes the checkpoint once the range is complete.
+    pub fn clear(&self) -> io::Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Collects every day of `range`, oldest first, and reports each to `progress`.
+///
+/// With a `checkpoint`, a previous run of the same range resumes after its
+/// last stored day, the checkpoint advances with every stored day and is
+/// removed once the whole range is stored. Failed days are retried by the
+/// next run, which also collects the days after them again.
+pub async fn backfill_range(
+    collectors: &Vec<Box<dyn MetricCollector>>,
+    sink: &dyn Sink,
+    range: &RangeBackfill,
+    checkpoint: Option<&Checkpoint>,
+    progress: &mut dyn FnMut(Progress),
+) -> BackfillReport {
+    let days: Vec<NaiveDate> = range
+        .from
+        .iter_days()
+        .take_while(|day| *day <= range.to)
+        .collect();
+    let mut report = BackfillReport {
+        checked: days.len(),
+        complete_through: checkpoint.and_then(|checkpoint| checkpoint.load(range)),
+        ..BackfillReport::default()
+    };
+    if let Some(stored) = report.complete_through {
+        tracing::info!("Resuming backfill after {}", stored);
+    }
+
+    let mut complete = true;
+    let mut first = true;
+    for (index, day) in days.iter().copied().enumerate() {
+        if report.complete_through.is_some_and(|stored| day <= stored) {
+            continue;
+        }
+        if !first {
+            tokio::time::sleep(range.delay).await;
+        }
+        first = false;
+
+        // Totals of the running day can still grow
+        let finalize = day < Local::now().date_naive();
+        let stored = collect_day(collectors, sink, day, &range.measurements, finalize).await;
+        if stored {
+            report.collected.push(day);
+        } else {
+            report.failed.push(day);
+            complete = false;
+        }
+        if complete {
+            report.complete_through = Some(day);
+            if let Some(checkpoint) = checkpoint {
+                if let Err(e) = checkpoint.save(range, day) {
+                    tracing::error!("Failed to save backfill checkpoint: {}", e);
+                }
+            }
+        }
+        progress(Progress {
+            done: index + 1,
+            total: days.len(),
+            day,
+            stored,
+        });
+    }
+
+    if report.failed.is_empty() {
+        if let Some(checkpoint) = checkpoint {
+            if let Err(e) = checkpoint.clear() {
+                tracing::error!("Failed to remove backfill checkpoint: {}", e);
+            }
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::influxdb;
+    use crate::model::{DataPointBuilder, Measurement, PowerStatusMetric};
+    use crate::model::{FieldValue, Point};
+    use crate::test_utils::fake_influx::FakeInfluxDb;
+    use crate::test_utils::mocks::{
+        MockMetricCollector, RecordingSink, TimeSensitiveMockCollector,
+    };
+
+    fn day(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, day).unwrap()
+    }
+
+    fn options(initial_days: u64, max_lookback_days: u64) -> BackfillOptions {
+        BackfillOptions {
+            initial_days,
+            max_lookback_days,
+            measurements: vec!["power".to_string()],
+        }
+    }
+
+    fn collectors() -> Vec<Box<dyn MetricCollector>> {
+        vec![Box::new(MockMetricCollector::new_success())]
+    }
+
+    /// A stored point of `day`, final unless collected live.
+    fn recorded(day: NaiveDate, finalized: bool) -> Point {
+        let point = Point::builder("power")
+            .tag("summary", "test")
+            .field("value", 100i64);
+        let point = if finalized {
+            point.field(FINAL_FIELD, true)
+        } else {
+            point
+        };
+        point
+            .timestamp(local_midnight(day).unwrap().timestamp_nanos_opt().unwrap())
+            .build()
+            .unwrap()
+    }
+
+    fn range(from: u32, to: u32) -> RangeBackfill {
+        RangeBackfill {
+            from: day(from),
+            to: day(to),
+            measurements: vec!["power".to_string()],
+            delay: Duration::ZERO,
+        }
+    }
+
+    fn power() -> Vec<Box<dyn DataPointBuilder>> {
+        vec![Box::new(PowerStatusMetric {
+            measurement: Measurement::Power,
+            name: "test".to_string(),
+            value: 100,
+        })]
+    }
+
+    mod succeeds {
+        use super::*;
+
+        #[test]
+        fn test_scan_range() {
+            let test_cases = vec![
+                ("first run", None, options(3, 365), Some((day(15), day(17)))),
+                (
+                    "after watermark",
+                    Some(day(10)),
+                    options(3, 365),
+                    Some((day(11), day(17))),
+                ),
+                ("up to date", Some(day(17)), options(3, 365), None),
+                ("no initial days", None, options(0, 365), None),
+                (
+                    "capped by lookback",
+                    Some(day(1)),
+                    options(3, 5),
+                    Some((day(13), day(17))),
+                ),
+            ];
+
+            for (name, watermark, options, expected) in test_cases {
+                assert_eq!(
+                    scan_range(day(18), watermark, &options),
+                    expected,
+                    "case: {}",
+                    name
+                );
+            }
+        }
+
+        #[test]
+        fn test_missing_days() {
+            let measurements = vec!["daily_total".to_string(), "circuit_daily_total".to_string()];
+            let finalized = BTreeMap::from([
+                (
+                    "daily_total".to_string(),
+                    BTreeSet::from([day(10), day(11), day(12), day(13)]),
+                ),
+                (
+                    "circuit_daily_total".to_string(),
+                    BTreeSet::from([day(10), day(12), day(13)]),
+                ),
+            ]);
+
+            assert_eq!(
+                missing_days(day(10), day(14), &measurements, &finalized),
+                vec![day(11), day(14)]
+            );
+            assert_eq!(
+                missing_days(day(12), day(13), &measurements, &finalized),
+                vec![]
+            );
+        }
+
+        #[test]
+        fn test_watermark_round_trip() {
+            let dir = tempfile::tempdir().unwrap();
+            let watermark = Watermark::new(dir.path().join("state").join("watermark"));
+
+            assert_eq!(watermark.load(), None);
+            watermark.save(day(17)).unwrap();
+            assert_eq!(watermark.load(), Some(day(17)));
+        }
+
+        #[tokio::test]
+        async fn test_backfill_collects_only_missing_days() {
+            let fake = FakeInfluxDb::start().await;
+            let client = influxdb::Client::new(fake.config());
+            let today = Local::now().date_naive();
+            let ago = |days| today.checked_sub_days(Days::new(days)).unwrap();
+            client
+                .write(&[
+                    recorded(ago(4), true),
+                    recorded(ago(2), false),
+                    recorded(ago(1), true),
+                ])
+                .await
+                .unwrap();
+            let dir = tempfile::tempdir().unwrap();
+            let watermark = Watermark::new(dir.path().join("watermark"));
+
+            let report = backfill(
+                &collectors(),
+                &client,
+                Some(&client),
+                &watermark,
+                &options(4, 365),
+                today,
+                &CancellationToken::new(),
+            )
+            .await;
+
+            // The live reading of two days ago is not final
+            assert_eq!(report.checked, 4);
+            assert_eq!(report.collected, vec![ago(3), ago(2)]);
+            assert_eq!(report.complete_through, Some(ago(1)));
+            assert_eq!(watermark.load(), Some(ago(1)));
+            assert_eq!(fake.queries().len(), 1);
+            // The seeded points, then one write per collected day
+            let writes = fake.writes();
+            assert_eq!(writes.len(), 3);
+            for write in &writes[1..] {
+                assert_eq!(
+                    write.points[0].to_string(),
+                    "power,summary=test final=true,value=100i"
+                );
+            }
+        }
+
+        #[tokio::test]
+        async fn test_backfill_resumes_after_watermark() {
+            let fake = FakeInfluxDb::start().await;
+            let client = influxdb::Client::new(fake.config());
+            let today = Local::now().date_naive();
+            let ago = |days| today.checked_sub_days(Days::new(days)).unwrap();
+            let dir = tempfile::tempdir().unwrap();
+            let watermark = Watermark::new(dir.path().join("watermark"));
+            watermark.save(ago(3)).unwrap();
+
+            let report = backfill(
+                &collectors(),
+                &client,
+                None,
+                &watermark,
+                &options(30, 365),
+                today,
+                &CancellationToken::new(),
+            )
+            .await;
+
+            assert_eq!(report.collected, vec![ago(2), ago(1)]);
+            assert_eq!(watermark.load(), Some(ago(1)));
+            assert_eq!(
+                fake.lines(),
+                vec!["power,summary=test final=true,value=100i"; 2]
+            );
+
+            let report = backfill(
+                &collectors(),
+                &client,
+                None,
+                &watermark,
+                &options(30, 365),
+                today,
+                &CancellationToken::new(),
+            )
+            .await;
+
+            assert_eq!(report.checked, 0);
+            assert_eq!(fake.writes().len(), 2);
+        }
+
+        #[tokio::test]
+        async fn test_backfill_collects_all_days_when_query_fails() {
+            let fake = FakeInfluxDb::start().await;
+            let client = influxdb::Client::new(fake.config());
+            let history = influxdb::Client::new(crate::config::InfluxConfig {
+                token: "wrong".to_string(),
+                ..fake.config()
+            });
+            let dir = tempfile::tempdir().unwrap();
+
+            let report = backfill(
+                &collectors(),
+                &client,
+                Some(&history),
+                &Watermark::new(dir.path().join("watermark")),
+                &options(3, 365),
+                Local::now().date_naive(),
+                &CancellationToken::new(),
+            )
+            .await;
+
+            assert_eq!(report.collected.len(), 3);
+            assert_eq!(fake.writes().len(), 3);
+        }
+
+        #[test]
+        fn test_checkpoint_round_trip() {
+            let dir = tempfile::tempdir().unwrap();
+            let checkpoint = Checkpoint::new(dir.path().join("state"));
+
+            assert_eq!(checkpoint.load(&range(1, 5)), None);
+            checkpoint.save(&range(1, 5), day(3)).unwrap();
+            assert_eq!(checkpoint.load(&range(1, 5)), Some(day(3)));
+            checkpoint.clear().unwrap();
+            assert_eq!(checkpoint.load(&range(1, 5)), None);
+            checkpoint.clear().unwrap();
+        }
+
+        #[test]
+        fn test_checkpoint_ignores_other_runs() {
+            let dir = tempfile::tempdir().unwrap();
+            let checkpoint = Checkpoint::new(dir.path().join("state"));
+            checkpoint.save(&range(1, 5), day(3)).unwrap();
+
+            let test_cases = vec![
+                ("other start", range(2, 5)),
+                ("other end", range(1, 6)),
+                (
+                    "other measurements",
+                    RangeBackfill {
+                        measurements: vec!["daily_total".to_string()],
+                        ..range(1, 5)
+                    },
+                ),
+            ];
+
+            for (name, other) in test_cases {
+                assert_eq!(checkpoint.load(&other), None, "case: {}", name);
+            }
+        }
+
+        #[tokio::test]
+        async fn test_backfill_range_reports_progress() {
+            let sink = RecordingSink::new("recording");
+            let dir = tempfile::tempdir().unwrap();
+            let checkpoint = Checkpoint::new(dir.path().join("state"));
+            let mut progress = Vec::new();
+
+            let report = backfill_range(
+                &collectors(),
+                &sink,
+                &range(1, 3),
+                Some(&checkpoint),
+                &mut |p| progress.push(p),
+            )
+            .await;
+
+            assert_eq!(report.collected, vec![day(1), day(2), day(3)]);
+            assert_eq!(report.complete_through, Some(day(3)));
+            assert_eq!(sink.batches().len(), 3);
+            assert_eq!(
+                progress,
+                (1..=3)
+                    .map(|n| Progress {
+                        done: n as usize,
+                        total: 3,
+                        day: day(n),
+                        stored: true,
+                    })
+                    .collect::<Vec<_>>()
+            );
+            // Finished runs leave no checkpoint behind
+            assert!(!dir.path().join("state").exists());
+        }
+
+        #[tokio::test]
+        async fn test_backfill_range_resumes_from_checkpoint() {
+            let sink = RecordingSink::new("recording");
+            let dir = tempfile::tempdir().unwrap();
+            let checkpoint = Checkpoint::new(dir.path().join("state"));
+            checkpoint.save(&range(1, 4), day(2)).unwrap();
+            let mut progress = Vec::new();
+
+            let report = backfill_range(
+                &collectors(),
+                &sink,
+                &range(1, 4),
+                Some(&checkpoint),
+                &mut |p: Progress| progress.push(p.done),
+            )
+            .await;
+
+            assert_eq!(report.checked, 4);
+            assert_eq!(report.collected, vec![day(3), day(4)]);
+            assert_eq!(progress, vec![3, 4]);
+            assert_eq!(sink.batches().len(), 2);
+        }
+
+        #[tokio::test]
+        async fn test_backfill_range_marks_ended_days_final() {
+            let sink = RecordingSink::new("recording");
+            let today = Local::now().date_naive();
+
+            backfill_range(
+                &collectors(),
+                &sink,
+                &RangeBackfill {
+                    from: today.pred_opt().unwrap(),
+                    to: today,
+                    ..range(1, 1)
+                },
+                None,
+                &mut |_| {},
+            )
+            .await;
+
+            let finals: Vec<Option<FieldValue>> = sink
+                .batches()
+                .iter()
+                .map(|batch| batch[0].fields.get(FINAL_FIELD).cloned())
+                .collect();
+            assert_eq!(finals, vec![Some(FieldValue::Boolean(true)), None]);
+        }
+
+        #[tokio::test]
+        async fn test_backfill_range_waits_between_days() {
+            let sink = RecordingSink::new("recording");
+            let started = std::time::Instant::now();
+
+            backfill_range(
+                &collectors(),
+                &sink,
+                &RangeBackfill {
+                    delay: Duration::from_millis(50),
+                    ..range(1, 3)
+                },
+                None,
+                &mut |_| {},
+            )
+            .await;
+
+            // Two pauses for three days
+            assert!(started.elapsed() >= Duration::from_millis(100));
+            assert_eq!(sink.batches().len(), 3);
+        }
+
+        #[tokio::test]
+        async fn test_backfill_stops_when_cancelled() {
+            let sink = RecordingSink::new("recording");
+            let dir = tempfile::tempdir().unwrap();
+            let watermark = Watermark::new(dir.path().join("watermark"));
+            let cancel = CancellationToken::new();
+            cancel.cancel();
+
+            let report = backfill(
+                &collectors(),
+                &sink,
+                None,
+                &watermark,
+                &options(3, 365),
+                Local::now().date_naive(),
+                &cancel,
+            )
+            .await;
+
+            assert_eq!(report.checked, 3);
+            assert_eq!(report.collected, vec![]);
+            assert_eq!(report.failed, vec![]);
+            assert_eq!(watermark.load(), None);
+            assert_eq!(sink.batches().len(), 0);
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_backfill_keeps_watermark_before_failed_day() {
+            let fake = FakeInfluxDb::start().await;
+            let client = influxdb::Client::new(fake.config());
+            let today = Local::now().date_naive();
+            let ago = |days| today.checked_sub_days(Days::new(days)).unwrap();
+            // Nothing is collected for the day in between
+            let collectors: Vec<Box<dyn MetricCollector>> = vec![Box::new(
+                TimeSensitiveMockCollector::new()
+                    .add_result(local_midnight(ago(3)).unwrap(), power)
+                    .add_result(local_midnight(ago(1)).unwrap(), power),
+            )];
+            let dir = tempfile::tempdir().unwrap();
+            let watermark = Watermark::new(dir.path().join("watermark"));
+
+            let report = backfill(
+                &collectors,
+                &client,
+                None,
+                &watermark,
+                &options(3, 365),
+                today,
+                &CancellationToken::new(),
+            )
+            .await;
+
+            assert_eq!(report.collected, vec![ago(3), ago(1)]);
+            assert_eq!(report.failed, vec![ago(2)]);
+            assert_eq!(report.complete_through, Some(ago(3)));
+            assert_eq!(watermark.load(), Some(ago(3)));
+        }
+
+        #[tokio::test]
+        async fn test_backfill_counts_failed_collection_as_missing() {
+            let fake = FakeInfluxDb::start().await;
+            let client = influxdb::Client::new(fake.config());
+            let collectors: Vec<Box<dyn MetricCollector>> = vec![Box::new(
+                MockMetricCollector::new_failure("Mock collection failed"),
+            )];
+            let dir = tempfile::tempdir().unwrap();
+            let watermark = Watermark::new(dir.path().join("watermark"));
+
+            let report = backfill(
+                &collectors,
+                &client,
+                None,
+                &watermark,
+                &options(1, 365),
+                Local::now().date_naive(),
+                &CancellationToken::new(),
+            )
+            .await;
+
+            assert_eq!(report.collected, vec![]);
+            assert_eq!(report.failed.len(), 1);
+            assert_eq!(watermark.load(), None);
+        }
+
+        #[tokio::test]
+        async fn test_backfill_range_keeps_checkpoint_before_failed_day() {
+            let sink = RecordingSink::new("recording");
+            let dir = tempfile::tempdir().unwrap();
+            let checkpoint = Checkpoint::new(dir.path().join("state"));
+            // Nothing is collected for the second day
+            let flaky: Vec<Box<dyn MetricCollector>> = vec![Box::new(
+                TimeSensitiveMockCollector::new()
+                    .add_result(local_midnight(day(1)).unwrap(), power)
+                    .add_result(local_midnight(day(3)).unwrap(), power),
+            )];
+
+            let report =
+                backfill_range(&flaky, &sink, &range(1, 3), Some(&checkpoint), &mut |_| {}).await;
+
+            assert_eq!(report.collected, vec![day(1), day(3)]);
+            assert_eq!(report.failed, vec![day(2)]);
+            assert_eq!(checkpoint.load(&range(1, 3)), Some(day(1)));
+
+            // The next run starts over at the failed day
+            let report = backfill_range(
+                &collectors(),
+                &sink,
+                &range(1, 3),
+                Some(&checkpoint),
+                &mut |_| {},
+            )
+            .await;
+
+            assert_eq!(report.collected, vec![day(2), day(3)]);
+            assert_eq!(checkpoint.load(&range(1, 3)), None);
+        }
+
+        #[tokio::test]
+        async fn test_backfill_range_counts_sink_failure() {
+            let sink = RecordingSink::failing("recording");
+            let mut progress = Vec::new();
+
+            let report = backfill_range(
+                &collectors(),
+                &sink,
+                &range(1, 2),
+                None,
+                &mut |p: Progress| progress.push(p.stored),
+            )
+            .await;
+
+            assert_eq!(report.failed, vec![day(1), day(2)]);
+            assert_eq!(report.complete_through, None);
+            assert_eq!(progress, vec![false, false]);
+        }
+
+        #[test]
+        fn test_watermark_ignores_invalid_file() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("watermark");
+            fs::write(&path, "yesterday").unwrap();
+
+            assert_eq!(Watermark::new(path).load(), None);
+        }
+    }
+}
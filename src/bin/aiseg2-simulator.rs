@@ -0,0 +1,141 @@
+//! AiSEG2 simulator
+//!
+//! Serves synthetic AiSEG2 pages so the forwarder can run on a machine
+//! without access to a real device. Point the forwarder at it with
+//! `AISEG2_URL=http://127.0.0.1:8080 AISEG2_USER=aiseg AISEG2_PASSWORD=aiseg`.
+//!
+//! Configured through `SIMULATOR_`-prefixed environment variables; see the
+//! README for the full list.
+
+// Only `serve` is used here; the in-process helpers are for tests
+#[allow(dead_code)]
+#[path = "../simulator/mod.rs"]
+mod simulator;
+
+use axum::http::StatusCode;
+use chrono::{DateTime, Local};
+use serde_derive::Deserialize;
+use simulator::{Circuit, Credentials, Faults, Markup, SimClock, SimulatorConfig};
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+fn default_listen_addr() -> String {
+    "127.0.0.1:8080".to_string()
+}
+
+fn default_credential() -> String {
+    "aiseg".to_string()
+}
+
+fn default_markup() -> String {
+    "standard".to_string()
+}
+
+fn default_error_status() -> u16 {
+    500
+}
+
+/// Simulator settings, loaded from `SIMULATOR_`-prefixed environment variables.
+///
+/// Lists are comma separated. Unset lists fall back to the built-in household.
+#[derive(Deserialize, Debug)]
+struct EnvConfig {
+    #[serde(default = "default_listen_addr")]
+    listen_addr: String,
+    #[serde(default = "default_credential")]
+    user: String,
+    #[serde(default = "default_credential")]
+    password: String,
+    rooms: Option<Vec<String>>,
+    devices: Option<Vec<String>>,
+    generation_sources: Option<Vec<String>>,
+    /// `id:name` pairs, e.g. `30:EV,27:リビングエアコン`
+    circuits: Option<Vec<String>>,
+    /// RFC 3339 instant to freeze the clock at
+    fixed_time: Option<String>,
+    #[serde(default = "default_markup")]
+    markup: String,
+    #[serde(default)]
+    latency_ms: u64,
+    error_every: Option<u64>,
+    #[serde(default = "default_error_status")]
+    error_status: u16,
+}
+
+impl EnvConfig {
+    fn into_simulator_config(self) -> Result<SimulatorConfig, String> {
+        let defaults = SimulatorConfig::default();
+        let circuits = match self.circuits {
+            Some(circuits) => circuits
+                .iter()
+                .map(|circuit| circuit.parse())
+                .collect::<Result<Vec<Circuit>, _>>()?,
+            None => defaults.circuits,
+        };
+        let clock = match self.fixed_time {
+            Some(time) => SimClock::Fixed(
+                DateTime::parse_from_rfc3339(&time)
+                    .map_err(|e| format!("invalid SIMULATOR_FIXED_TIME '{}': {}", time, e))?
+                    .with_timezone(&Local),
+            ),
+            None => SimClock::System,
+        };
+        let error_status = StatusCode::from_u16(self.error_status)
+            .map_err(|e| format!("invalid SIMULATOR_ERROR_STATUS: {}", e))?;
+
+        Ok(SimulatorConfig {
+            credentials: Credentials {
+                user: self.user,
+                password: self.password,
+            },
+            rooms: self.rooms.unwrap_or(defaults.rooms),
+            devices: self.devices.unwrap_or(defaults.devices),
+            generation_sources: self
+                .generation_sources
+                .unwrap_or(defaults.generation_sources),
+            circuits,
+            clock,
+            markup: self.markup.parse::<Markup>()?,
+            faults: Faults {
+                latency: Duration::from_millis(self.latency_ms),
+                error_every: self.error_every,
+                error_status,
+            },
+        })
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt().init();
+
+    let env_config = envy::prefixed("SIMULATOR_")
+        .from_env::<EnvConfig>()
+        .expect("Failed to load simulator config");
+    let listen_addr = env_config.listen_addr.clone();
+    let config = env_config
+        .into_simulator_config()
+        .expect("Invalid simulator config");
+
+    let listener = TcpListener::bind(&listen_addr)
+        .await
+        .expect("Failed to bind listen address");
+    tracing::info!(
+        "AiSEG2 simulator listening on http://{} ({} rooms, {} devices, {:?} markup)",
+        listen_addr,
+        config.rooms.len(),
+        config.devices.len(),
+        config.markup
+    );
+
+    tokio::select! {
+        result = simulator::serve(listener, config) => {
+            if let Err(e) = result {
+                tracing::error!("Simulator stopped: {}", e);
+            }
+        }
+        _ = tokio::signal::ctrl_c() => {
+            tracing::info!("Shutting down simulator");
+        }
+    }
+}
@@ -0,0 +1,413 @@
+//! Command line interface.
+//!
+//! Without a subcommand, or with `run`, the forwarder runs as a service,
+//! configured through environment variables and an optional TOML file. The
+//! other subcommands are one-off tools using the same configuration.
+
+use crate::export::Format;
+use crate::model::Measurement;
+use chrono::NaiveDate;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+/// Forwards AiSEG2 energy metrics to InfluxDB and other sinks.
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Cli {
+    /// TOML configuration file; environment variables override its values.
+    /// Defaults to CONFIG_FILE
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the forwarder as a service; the default without a subcommand
+    Run,
+    /// Collect once from every enabled collector, print the points and exit
+    Once(OnceArgs),
+    /// Run a single collector, print its points and exit
+    Collect(CollectArgs),
+    /// Check the connection and credentials to AiSEG2 and InfluxDB, and show
+    /// the rooms and circuits AiSEG2 reports
+    Probe(ProbeArgs),
+    /// Export readings from the SQLite sink's database to CSV or Parquet
+    Export(ExportArgs),
+    /// Collect the daily totals of a date range from AiSEG2 and write them to
+    /// the sinks that store history; prometheus and mqtt are skipped
+    Backfill(BackfillArgs),
+    /// Inspect the configuration
+    #[command(subcommand)]
+    Config(ConfigCommand),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Validate the configuration and print the effective values, secrets redacted
+    Check,
+}
+
+#[derive(Debug, Args)]
+pub struct OnceArgs {
+    /// Also write the points to the sinks configured by SINKS; prometheus and
+    /// mqtt are skipped
+    #[arg(long)]
+    pub write: bool,
+    /// Print the points as JSON instead of line protocol
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct CollectArgs {
+    /// Collector to run
+    #[arg(value_parser = clap::builder::PossibleValuesParser::new(crate::COLLECTORS))]
+    pub collector: String,
+    /// Day to collect the totals of, in local time (YYYY-MM-DD); only for
+    /// daily_total and circuit_daily_total, which collect today by default
+    #[arg(long)]
+    pub date: Option<NaiveDate>,
+    /// Print the points as JSON instead of line protocol
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ProbeArgs {
+    /// Print the report as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// First day to export, in local time (YYYY-MM-DD)
+    #[arg(long)]
+    pub from: NaiveDate,
+    /// Last day to export, inclusive; defaults to `--from`
+    #[arg(long)]
+    pub to: Option<NaiveDate>,
+    #[arg(long, value_enum, default_value_t = Format::Csv)]
+    pub format: Format,
+    /// Directory to write one file per measurement to
+    #[arg(long, default_value = ".")]
+    pub output: PathBuf,
+    /// Database file; defaults to SQLITE_PATH
+    #[arg(long)]
+    pub database: Option<PathBuf>,
+    /// Measurements to export, comma separated; all by default
+    #[arg(long = "measurement", value_delimiter = ',')]
+    pub measurements: Vec<String>,
+}
+
+/// Total collectors the `backfill` command can run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BackfillCollector {
+    /// Daily totals of generation, consumption, trade, hot water and gas
+    Daily,
+    /// Daily totals per circuit
+    Circuit,
+}
+
+impl BackfillCollector {
+    /// Measurement of the points the collector produces.
+    pub fn measurement(self) -> Measurement {
+        match self {
+            BackfillCollector::Daily => Measurement::DailyTotal,
+            BackfillCollector::Circuit => Measurement::CircuitDailyTotal,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct BackfillArgs {
+    /// First day to collect, in local time (YYYY-MM-DD)
+    #[arg(long)]
+    pub from: NaiveDate,
+    /// Last day to collect, inclusive; defaults to `--from`
+    #[arg(long)]
+    pub to: Option<NaiveDate>,
+    /// Collectors to run, comma separated
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = [BackfillCollector::Daily, BackfillCollector::Circuit]
+    )]
+    pub collectors: Vec<BackfillCollector>,
+    /// Print line protocol to stdout instead of writing to the sinks
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Pause between days in milliseconds, so AiSEG2 stays responsive
+    #[arg(long, default_value_t = 1000)]
+    pub delay_ms: u64,
+    /// Checkpoint file an interrupted run of the same range resumes from
+    #[arg(long, default_value = "aiseg2-backfill.state")]
+    pub state: PathBuf,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod succeeds {
+        use super::*;
+
+        #[test]
+        fn test_parse_without_subcommand() {
+            let cli = Cli::try_parse_from(["aiseg2-influxdb2-forwarder"]).unwrap();
+
+            assert!(cli.command.is_none());
+            assert!(cli.config.is_none());
+        }
+
+        #[test]
+        fn test_parse_config_check() {
+            let test_cases = vec![
+                ("without file", vec!["config", "check"], None),
+                (
+                    "file before subcommand",
+                    vec!["--config", "forwarder.toml", "config", "check"],
+                    Some(PathBuf::from("forwarder.toml")),
+                ),
+                (
+                    "file after subcommand",
+                    vec!["config", "check", "--config", "forwarder.toml"],
+                    Some(PathBuf::from("forwarder.toml")),
+                ),
+            ];
+
+            for (name, args, config) in test_cases {
+                let cli =
+                    Cli::try_parse_from(std::iter::once("aiseg2-influxdb2-forwarder").chain(args))
+                        .unwrap();
+
+                assert!(
+                    matches!(cli.command, Some(Command::Config(ConfigCommand::Check))),
+                    "case: {}",
+                    name
+                );
+                assert_eq!(cli.config, config, "case: {}", name);
+            }
+        }
+
+        #[test]
+        fn test_parse_run_once_and_probe() {
+            // Options of once and probe as (write, json); run has none
+            let test_cases = vec![
+                ("run", vec!["run"], None),
+                ("once", vec!["once"], Some((false, false))),
+                (
+                    "once options",
+                    vec!["once", "--write", "--json"],
+                    Some((true, true)),
+                ),
+                ("probe", vec!["probe"], Some((false, false))),
+                ("probe json", vec!["probe", "--json"], Some((false, true))),
+            ];
+
+            for (name, args, expected) in test_cases {
+                let cli =
+                    Cli::try_parse_from(std::iter::once("aiseg2-influxdb2-forwarder").chain(args))
+                        .unwrap();
+
+                let options = match cli.command {
+                    Some(Command::Run) => None,
+                    Some(Command::Once(args)) => Some((args.write, args.json)),
+                    Some(Command::Probe(args)) => Some((false, args.json)),
+                    command => panic!("case: {}: unexpected {:?}", name, command),
+                };
+                assert_eq!(options, expected, "case: {}", name);
+            }
+        }
+
+        #[test]
+        fn test_parse_collect() {
+            let test_cases = vec![
+                (
+                    "status collector",
+                    vec!["collect", "power"],
+                    "power",
+                    None,
+                    false,
+                ),
+                (
+                    "total collector on a day",
+                    vec![
+                        "collect",
+                        "circuit_daily_total",
+                        "--date",
+                        "2026-10-17",
+                        "--json",
+                    ],
+                    "circuit_daily_total",
+                    NaiveDate::from_ymd_opt(2026, 10, 17),
+                    true,
+                ),
+            ];
+
+            for (name, args, collector, date, json) in test_cases {
+                let cli =
+                    Cli::try_parse_from(std::iter::once("aiseg2-influxdb2-forwarder").chain(args))
+                        .unwrap();
+
+                let Some(Command::Collect(args)) = cli.command else {
+                    panic!("case: {}: expected collect", name);
+                };
+                assert_eq!(args.collector, collector, "case: {}", name);
+                assert_eq!(args.date, date, "case: {}", name);
+                assert_eq!(args.json, json, "case: {}", name);
+            }
+        }
+
+        #[test]
+        fn test_parse_export() {
+            let cli = Cli::try_parse_from([
+                "aiseg2-influxdb2-forwarder",
+                "export",
+                "--from",
+                "2026-10-01",
+                "--to",
+                "2026-10-07",
+                "--format",
+                "parquet",
+                "--measurement",
+                "power,daily_total",
+            ])
+            .unwrap();
+
+            let Some(Command::Export(args)) = cli.command else {
+                panic!("expected export");
+            };
+            assert_eq!(args.from, NaiveDate::from_ymd_opt(2026, 10, 1).unwrap());
+            assert_eq!(args.to, NaiveDate::from_ymd_opt(2026, 10, 7));
+            assert_eq!(args.format, Format::Parquet);
+            assert_eq!(args.output, PathBuf::from("."));
+            assert_eq!(args.database, None);
+            assert_eq!(args.measurements, vec!["power", "daily_total"]);
+        }
+
+        #[test]
+        fn test_parse_backfill() {
+            let test_cases = vec![
+                (
+                    "defaults",
+                    vec!["backfill", "--from", "2024-01-01"],
+                    None,
+                    vec![BackfillCollector::Daily, BackfillCollector::Circuit],
+                    false,
+                    1000,
+                ),
+                (
+                    "all options",
+                    vec![
+                        "backfill",
+                        "--from",
+                        "2024-01-01",
+                        "--to",
+                        "2024-03-31",
+                        "--collectors",
+                        "circuit",
+                        "--dry-run",
+                        "--delay-ms",
+                        "0",
+                    ],
+                    NaiveDate::from_ymd_opt(2024, 3, 31),
+                    vec![BackfillCollector::Circuit],
+                    true,
+                    0,
+                ),
+            ];
+
+            for (name, args, to, collectors, dry_run, delay_ms) in test_cases {
+                let cli =
+                    Cli::try_parse_from(std::iter::once("aiseg2-influxdb2-forwarder").chain(args))
+                        .unwrap();
+
+                let Some(Command::Backfill(args)) = cli.command else {
+                    panic!("case: {}: expected backfill", name);
+                };
+                assert_eq!(
+                    args.from,
+                    NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+                    "case: {}",
+                    name
+                );
+                assert_eq!(args.to, to, "case: {}", name);
+                assert_eq!(args.collectors, collectors, "case: {}", name);
+                assert_eq!(args.dry_run, dry_run, "case: {}", name);
+                assert_eq!(args.delay_ms, delay_ms, "case: {}", name);
+                assert_eq!(
+                    args.state,
+                    PathBuf::from("aiseg2-backfill.state"),
+                    "case: {}",
+                    name
+                );
+            }
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[test]
+        fn test_parse_invalid_export_args() {
+            let test_cases = vec![
+                ("missing from", vec!["export"]),
+                ("invalid date", vec!["export", "--from", "2026-13-01"]),
+                (
+                    "unknown format",
+                    vec!["export", "--from", "2026-10-01", "--format", "xlsx"],
+                ),
+            ];
+
+            for (name, args) in test_cases {
+                let result =
+                    Cli::try_parse_from(std::iter::once("aiseg2-influxdb2-forwarder").chain(args));
+                assert!(result.is_err(), "case: {}", name);
+            }
+        }
+
+        #[test]
+        fn test_parse_invalid_collect_args() {
+            let test_cases = vec![
+                ("missing collector", vec!["collect"]),
+                ("unknown collector", vec!["collect", "water"]),
+                (
+                    "invalid date",
+                    vec!["collect", "daily_total", "--date", "2026-02-30"],
+                ),
+                ("unknown option", vec!["once", "--dry-run"]),
+            ];
+
+            for (name, args) in test_cases {
+                let result =
+                    Cli::try_parse_from(std::iter::once("aiseg2-influxdb2-forwarder").chain(args));
+                assert!(result.is_err(), "case: {}", name);
+            }
+        }
+
+        #[test]
+        fn test_parse_invalid_backfill_args() {
+            let test_cases = vec![
+                ("missing from", vec!["backfill"]),
+                (
+                    "unknown collector",
+                    vec!["backfill", "--from", "2024-01-01", "--collectors", "power"],
+                ),
+                (
+                    "negative delay",
+                    vec!["backfill", "--from", "2024-01-01", "--delay-ms", "-1"],
+                ),
+            ];
+
+            for (name, args) in test_cases {
+                let result =
+                    Cli::try_parse_from(std::iter::once("aiseg2-influxdb2-forwarder").chain(args));
+                assert!(result.is_err(), "case: {}", name);
+            }
+        }
+    }
+}
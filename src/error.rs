@@ -139,10 +139,6 @@ pub enum CollectorError {
 #[derive(Error, Debug)]
 #[allow(dead_code)]
 pub enum StorageError {
-    /// InfluxDB client error
-    #[error("InfluxDB error: {0}")]
-    Client(#[from] influxdb2::RequestError),
-
     /// Authentication failed
     #[error("InfluxDB authentication failed")]
     AuthFailed,
@@ -158,6 +154,42 @@ pub enum StorageError {
     /// Invalid data point
     #[error("invalid data point: {0}")]
     InvalidDataPoint(String),
+
+    /// Querying stored data failed
+    #[error("failed to query InfluxDB: {0}")]
+    QueryFailed(String),
+
+    /// One or more sinks of a fan-out failed to store a batch
+    #[error("failed to write to sinks: {}", .0.join(", "))]
+    SinksFailed(Vec<String>),
+
+    /// The on-disk write buffer could not be read or written
+    #[error("write buffer I/O error: {0}")]
+    Buffer(#[from] std::io::Error),
+}
+
+/// Errors of the `export` command.
+#[derive(Error, Debug)]
+pub enum ExportError {
+    /// The SQLite database could not be read
+    #[error("database error: {0}")]
+    Database(#[from] rusqlite::Error),
+
+    /// An output file could not be written
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// Encoding a Parquet file failed
+    #[error("Parquet error: {0}")]
+    Parquet(#[from] parquet::errors::ParquetError),
+
+    /// Building Arrow columns for a Parquet file failed
+    #[error("Arrow error: {0}")]
+    Arrow(#[from] arrow_schema::ArrowError),
+
+    /// The requested date range is empty or cannot be represented
+    #[error("invalid date range: {0}")]
+    InvalidRange(String),
 }
 
 // Note: Our Error types automatically work with anyhow due to implementing std::error::Error
@@ -183,6 +215,23 @@ impl ConfigError {
     }
 }
 
+impl StorageError {
+    /// Whether writing the same batch again may succeed.
+    ///
+    /// Invalid points and refused credentials fail no matter how often the
+    /// batch is sent, so buffering it would only block the queue.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::InvalidDataPoint(_) | Self::AuthFailed => false,
+            Self::WriteFailed { .. }
+            | Self::ConnectionFailed { .. }
+            | Self::QueryFailed(_)
+            | Self::SinksFailed(_)
+            | Self::Buffer(_) => true,
+        }
+    }
+}
+
 impl AisegError {
     /// Creates a server error from HTTP status and response body.
     pub fn server_error(status: reqwest::StatusCode, body: String) -> Self {
@@ -252,6 +301,15 @@ impl CollectorError {
     pub fn unavailable(name: impl Into<String>) -> Self {
         Self::Unavailable { name: name.into() }
     }
+
+    /// Message for users, naming the AiSEG2 error of a source error, which
+    /// the `Display` message leaves out.
+    pub fn detail(&self) -> String {
+        match self {
+            Self::Source(e) => e.to_string(),
+            e => e.to_string(),
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -336,6 +394,26 @@ mod tests {
             );
         }
 
+        #[test]
+        fn test_detail() {
+            let test_cases = vec![
+                (
+                    "source",
+                    CollectorError::Source(AisegError::AuthFailed),
+                    "authentication failed: invalid credentials",
+                ),
+                (
+                    "other",
+                    CollectorError::timeout("PowerCollector", 30),
+                    "collector 'PowerCollector' timed out after 30 seconds",
+                ),
+            ];
+
+            for (name, err, expected) in test_cases {
+                assert_eq!(err.detail(), expected, "case: {}", name);
+            }
+        }
+
         #[test]
         fn test_circuit_open() {
             let err = CollectorError::circuit_open("ClimateCollector");
@@ -366,6 +444,30 @@ mod tests {
                 "failed to connect to InfluxDB at http://localhost:8086"
             );
         }
+
+        #[test]
+        fn test_is_retryable() {
+            let test_cases = vec![
+                (
+                    StorageError::write_failed(1, "503 Service Unavailable"),
+                    true,
+                ),
+                (
+                    StorageError::connection_failed("http://localhost:8086"),
+                    true,
+                ),
+                (StorageError::SinksFailed(vec!["mqtt".to_string()]), true),
+                (
+                    StorageError::InvalidDataPoint("bad field".to_string()),
+                    false,
+                ),
+                (StorageError::AuthFailed, false),
+            ];
+
+            for (err, expected) in test_cases {
+                assert_eq!(err.is_retryable(), expected, "case: {}", err);
+            }
+        }
     }
 
     mod error_conversion {
@@ -0,0 +1,494 @@
+//! Export of stored readings to CSV or Parquet files.
+//!
+//! Reads the SQLite database written by the `sqlite` sink and writes one
+//! file per measurement. Each file has a `time` column, one column per tag
+//! and one column per field, with a row per time and tag combination, so it
+//! loads directly into pandas or a spreadsheet.
+
+use crate::error::ExportError;
+use crate::sink::SqliteSink;
+use arrow_array::builder::{Float64Builder, StringBuilder, TimestampNanosecondBuilder};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use rusqlite::{params, Connection, OpenFlags};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const TABLE: &str = SqliteSink::TABLE;
+
+/// Output file format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Csv,
+    Parquet,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Parquet => "parquet",
+        }
+    }
+}
+
+/// What to export and where.
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    /// SQLite database written by the `sqlite` sink
+    pub database: PathBuf,
+    /// First day, in local time
+    pub from: NaiveDate,
+    /// Last day, inclusive
+    pub to: NaiveDate,
+    pub format: Format,
+    /// Directory the files are written to, created if missing
+    pub output: PathBuf,
+    /// Measurements to export; all found in the range if empty
+    pub measurements: Vec<String>,
+}
+
+/// One written file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exported {
+    pub measurement: String,
+    pub path: PathBuf,
+    pub rows: usize,
+}
+
+/// A value of a field column.
+#[derive(Debug, Clone, PartialEq)]
+enum Cell {
+    Number(f64),
+    Text(String),
+}
+
+/// Readings of one measurement, pivoted to a row per time and tags.
+#[derive(Debug, Default)]
+struct Table {
+    tag_keys: BTreeSet<String>,
+    /// Field names, mapped to whether any value is text
+    fields: BTreeMap<String, bool>,
+    rows: Vec<TableRow>,
+}
+
+#[derive(Debug)]
+struct TableRow {
+    /// Nanoseconds since the Unix epoch
+    time: i64,
+    tags: BTreeMap<String, String>,
+    values: BTreeMap<String, Cell>,
+}
+
+/// Exports every selected measurement to its own file.
+///
+/// # Returns
+/// - `Ok(Vec<Exported>)` listing the written files
+/// - `Err(ExportError)` if the range is invalid, the database cannot be read
+///   or a file cannot be written
+pub fn export(options: &ExportOptions) -> Result<Vec<Exported>, ExportError> {
+    let (start, end) = time_range(options.from, options.to)?;
+    let conn = Connection::open_with_flags(&options.database, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+    let measurements = if options.measurements.is_empty() {
+        let mut statement = conn.prepare(&format!(
+            "SELECT DISTINCT measurement FROM {TABLE} WHERE time >= ?1 AND time < ?2 ORDER BY measurement"
+        ))?;
+        let names = statement.query_map(params![start, end], |row| row.get(0))?;
+        names.collect::<Result<Vec<String>, _>>()?
+    } else {
+        options.measurements.clone()
+    };
+
+    fs::create_dir_all(&options.output)?;
+    let mut exported = Vec::with_capacity(measurements.len());
+    for measurement in measurements {
+        let table = load(&conn, &measurement, start, end)?;
+        let path = options.output.join(format!(
+            "{}.{}",
+            file_stem(&measurement),
+            options.format.extension()
+        ));
+        match options.format {
+            Format::Csv => write_csv(&table, &path)?,
+            Format::Parquet => write_parquet(&table, &path)?,
+        }
+        exported.push(Exported {
+            measurement,
+            path,
+            rows: table.rows.len(),
+        });
+    }
+    Ok(exported)
+}
+
+/// Converts the inclusive local date range into nanosecond bounds `[start, end)`.
+fn time_range(from: NaiveDate, to: NaiveDate) -> Result<(i64, i64), ExportError> {
+    if from > to {
+        return Err(ExportError::InvalidRange(format!(
+            "{} is after {}",
+            from, to
+        )));
+    }
+    let midnight = |date: NaiveDate| {
+        date.and_hms_opt(0, 0, 0)
+            .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+            .and_then(|time| time.timestamp_nanos_opt())
+            .ok_or_else(|| ExportError::InvalidRange(format!("{} is out of range", date)))
+    };
+    let after = to
+        .succ_opt()
+        .ok_or_else(|| ExportError::InvalidRange(format!("{} is out of range", to)))?;
+    Ok((midnight(from)?, midnight(after)?))
+}
+
+/// Keeps measurement names usable as file names.
+fn file_stem(measurement: &str) -> String {
+    measurement
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Reads one measurement and pivots its fields into columns.
+fn load(conn: &Connection, measurement: &str, start: i64, end: i64) -> Result<Table, ExportError> {
+    let mut statement = conn.prepare(&format!(
+        "SELECT time, tags, field, value, text_value FROM {TABLE}
+         WHERE measurement = ?1 AND time >= ?2 AND time < ?3
+         ORDER BY time, tags, field"
+    ))?;
+    let mut rows = statement.query(params![measurement, start, end])?;
+
+    let mut table = Table::default();
+    let mut last_key: Option<(i64, String)> = None;
+    while let Some(row) = rows.next()? {
+        let time: i64 = row.get(0)?;
+        let tags_json: String = row.get(1)?;
+        let field: String = row.get(2)?;
+        let cell = match (
+            row.get::<_, Option<f64>>(3)?,
+            row.get::<_, Option<String>>(4)?,
+        ) {
+            (_, Some(text)) => Cell::Text(text),
+            (Some(value), None) => Cell::Number(value),
+            (None, None) => continue,
+        };
+
+        let key = (time, tags_json);
+        if last_key.as_ref() != Some(&key) {
+            let tags: BTreeMap<String, String> = serde_json::from_str(&key.1).unwrap_or_default();
+            table.tag_keys.extend(tags.keys().cloned());
+            table.rows.push(TableRow {
+                time,
+                tags,
+                values: BTreeMap::new(),
+            });
+            last_key = Some(key);
+        }
+
+        let is_text = matches!(cell, Cell::Text(_));
+        *table.fields.entry(field.clone()).or_default() |= is_text;
+        if let Some(current) = table.rows.last_mut() {
+            current.values.insert(field, cell);
+        }
+    }
+    Ok(table)
+}
+
+/// Quotes a CSV value if it contains a separator, quote or line break.
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Writes the table as CSV with RFC 3339 local times.
+fn write_csv(table: &Table, path: &Path) -> Result<(), ExportError> {
+    let mut out = BufWriter::new(File::create(path)?);
+
+    let header: Vec<String> = std::iter::once("time")
+        .chain(table.tag_keys.iter().map(String::as_str))
+        .chain(table.fields.keys().map(String::as_str))
+        .map(csv_escape)
+        .collect();
+    writeln!(out, "{}", header.join(","))?;
+
+    for row in &table.rows {
+        let time = DateTime::from_timestamp_nanos(row.time).with_timezone(&Local);
+        let mut record = vec![time.to_rfc3339()];
+        for key in &table.tag_keys {
+            record.push(csv_escape(row.tags.get(key).map_or("", String::as_str)));
+        }
+        for field in table.fields.keys() {
+            record.push(match row.values.get(field) {
+                Some(Cell::Number(value)) => value.to_string(),
+                Some(Cell::Text(text)) => csv_escape(text),
+                None => String::new(),
+            });
+        }
+        writeln!(out, "{}", record.join(","))?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Writes the table as a Snappy-compressed Parquet file with UTC timestamps.
+fn write_parquet(table: &Table, path: &Path) -> Result<(), ExportError> {
+    let mut fields = vec![Field::new(
+        "time",
+        DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into())),
+        false,
+    )];
+    let mut columns: Vec<ArrayRef> = Vec::new();
+
+    let mut times = TimestampNanosecondBuilder::with_capacity(table.rows.len());
+    for row in &table.rows {
+        times.append_value(row.time);
+    }
+    columns.push(Arc::new(times.finish().with_timezone("UTC")));
+
+    for key in &table.tag_keys {
+        fields.push(Field::new(key, DataType::Utf8, true));
+        let mut tags = StringBuilder::new();
+        for row in &table.rows {
+            tags.append_option(row.tags.get(key));
+        }
+        columns.push(Arc::new(tags.finish()));
+    }
+
+    for (name, &is_text) in &table.fields {
+        if is_text {
+            fields.push(Field::new(name, DataType::Utf8, true));
+            let mut values = StringBuilder::new();
+            for row in &table.rows {
+                values.append_option(match row.values.get(name) {
+                    Some(Cell::Text(text)) => Some(text.clone()),
+                    Some(Cell::Number(value)) => Some(value.to_string()),
+                    None => None,
+                });
+            }
+            columns.push(Arc::new(values.finish()));
+        } else {
+            fields.push(Field::new(name, DataType::Float64, true));
+            let mut values = Float64Builder::with_capacity(table.rows.len());
+            for row in &table.rows {
+                values.append_option(match row.values.get(name) {
+                    Some(Cell::Number(value)) => Some(*value),
+                    _ => None,
+                });
+            }
+            columns.push(Arc::new(values.finish()));
+        }
+    }
+
+    let schema = Arc::new(Schema::new(fields));
+    let batch = RecordBatch::try_new(Arc::clone(&schema), columns)?;
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(File::create(path)?, schema, Some(properties))?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Point;
+    use crate::sink::Sink;
+    use arrow_array::{Float64Array, StringArray};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    fn nanos(date: NaiveDate, hour: u32) -> i64 {
+        Local
+            .from_local_datetime(&date.and_hms_opt(hour, 0, 0).unwrap())
+            .unwrap()
+            .timestamp_nanos_opt()
+            .unwrap()
+    }
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, d).unwrap()
+    }
+
+    /// Database with climate readings on the 1st and 2nd and a total on the 3rd.
+    async fn database(dir: &Path) -> PathBuf {
+        let path = dir.join("aiseg2.db");
+        let sink = SqliteSink::open(&path).unwrap();
+        let climate = |section: &str, value: f64, timestamp: i64| {
+            Point::builder("climate")
+                .tag("detail-section", section)
+                .tag("detail-type", "temperature")
+                .field("value", value)
+                .timestamp(timestamp)
+                .build()
+                .unwrap()
+        };
+        sink.write(&[
+            climate("リビング", 21.5, nanos(day(1), 12)),
+            climate("主寝室", 19.0, nanos(day(1), 12)),
+            climate("リビング", 22.0, nanos(day(2), 12)),
+            Point::builder("daily_total")
+                .tag("detail-section", "発電量(kWh)")
+                .field("value", 12.3)
+                .timestamp(nanos(day(3), 0))
+                .build()
+                .unwrap(),
+        ])
+        .await
+        .unwrap();
+        path
+    }
+
+    fn options(database: PathBuf, output: &Path, format: Format) -> ExportOptions {
+        ExportOptions {
+            database,
+            from: day(1),
+            to: day(2),
+            format,
+            output: output.to_path_buf(),
+            measurements: Vec::new(),
+        }
+    }
+
+    mod succeeds {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_export_csv() {
+            let dir = tempfile::tempdir().unwrap();
+            let database = database(dir.path()).await;
+            let output = dir.path().join("out");
+
+            let exported = export(&options(database, &output, Format::Csv)).unwrap();
+
+            assert_eq!(
+                exported,
+                vec![Exported {
+                    measurement: "climate".to_string(),
+                    path: output.join("climate.csv"),
+                    rows: 3,
+                }]
+            );
+            let csv = fs::read_to_string(output.join("climate.csv")).unwrap();
+            let lines: Vec<&str> = csv.lines().collect();
+            assert_eq!(lines[0], "time,detail-section,detail-type,value");
+            let first = DateTime::from_timestamp_nanos(nanos(day(1), 12))
+                .with_timezone(&Local)
+                .to_rfc3339();
+            assert_eq!(lines[1], format!("{},リビング,temperature,21.5", first));
+            assert_eq!(lines[2], format!("{},主寝室,temperature,19", first));
+            assert_eq!(lines.len(), 4);
+        }
+
+        #[tokio::test]
+        async fn test_export_parquet() {
+            let dir = tempfile::tempdir().unwrap();
+            let database = database(dir.path()).await;
+            let output = dir.path().join("out");
+            let options = ExportOptions {
+                to: day(3),
+                ..options(database, &output, Format::Parquet)
+            };
+
+            let exported = export(&options).unwrap();
+
+            let files: Vec<&str> = exported.iter().map(|e| e.measurement.as_str()).collect();
+            assert_eq!(files, vec!["climate", "daily_total"]);
+
+            let file = File::open(output.join("daily_total.parquet")).unwrap();
+            let batch = ParquetRecordBatchReaderBuilder::try_new(file)
+                .unwrap()
+                .build()
+                .unwrap()
+                .next()
+                .unwrap()
+                .unwrap();
+            let schema = batch.schema();
+            let names: Vec<&str> = schema
+                .fields()
+                .iter()
+                .map(|field| field.name().as_str())
+                .collect();
+            assert_eq!(names, vec!["time", "detail-section", "value"]);
+            let section = batch
+                .column(1)
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap();
+            assert_eq!(section.value(0), "発電量(kWh)");
+            let value = batch
+                .column(2)
+                .as_any()
+                .downcast_ref::<Float64Array>()
+                .unwrap();
+            assert_eq!(value.value(0), 12.3);
+        }
+
+        #[tokio::test]
+        async fn test_export_selected_measurements() {
+            let dir = tempfile::tempdir().unwrap();
+            let database = database(dir.path()).await;
+            let output = dir.path().join("out");
+            let options = ExportOptions {
+                measurements: vec!["power".to_string()],
+                ..options(database, &output, Format::Csv)
+            };
+
+            let exported = export(&options).unwrap();
+
+            assert_eq!(exported[0].rows, 0);
+            let csv = fs::read_to_string(output.join("power.csv")).unwrap();
+            assert_eq!(csv, "time\n");
+        }
+
+        #[test]
+        fn test_csv_escape() {
+            let test_cases = vec![
+                ("plain", "リビング", "リビング"),
+                ("comma", "a,b", "\"a,b\""),
+                ("quote", "say \"hi\"", "\"say \"\"hi\"\"\""),
+            ];
+
+            for (name, input, expected) in test_cases {
+                assert_eq!(csv_escape(input), expected, "case: {}", name);
+            }
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[test]
+        fn test_reversed_range() {
+            assert!(matches!(
+                time_range(day(2), day(1)),
+                Err(ExportError::InvalidRange(_))
+            ));
+        }
+
+        #[test]
+        fn test_missing_database() {
+            let dir = tempfile::tempdir().unwrap();
+            let options = options(dir.path().join("missing.db"), dir.path(), Format::Csv);
+
+            assert!(matches!(export(&options), Err(ExportError::Database(_))));
+        }
+    }
+}
@@ -0,0 +1,297 @@
+//! Finalization of daily totals after midnight.
+//!
+//! The total collectors always read the running day, so the last minutes of
+//! each day are never captured by them. After every local midnight the
+//! finalizer collects the day that just ended again, in a few passes spread
+//! over a grace window while AiSEG2 settles the day's figures. The last
+//! pass marks the totals with `final=true`, which the startup backfill
+//! relies on to skip days that are complete.
+
+use crate::backfill::{collect_day, local_midnight};
+use crate::model::MetricCollector;
+use crate::scheduler::sleep_until;
+use crate::sink::Sink;
+use chrono::{DateTime, Local, NaiveDate};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+
+/// When and what the finalizer collects.
+#[derive(Debug, Clone)]
+pub struct FinalizeOptions {
+    /// Collections of the ended day; 0 disables finalization
+    pub passes: u32,
+    /// Time after midnight by which the last pass runs
+    pub grace: Duration,
+    /// Measurements every complete day has points of
+    pub measurements: Vec<String>,
+}
+
+/// Times of the passes for a day ending at `end`, spread evenly over the
+/// grace window with the last one at its end.
+fn pass_times(end: DateTime<Local>, options: &FinalizeOptions) -> Vec<DateTime<Local>> {
+    (1..=options.passes)
+        .map(|pass| end + options.grace * pass / options.passes)
+        .collect()
+}
+
+/// The day that ended last and its end, if its grace window is still open
+/// at `now`.
+///
+/// A finalizer started within the window, e.g. after a reload, would skip
+/// that day otherwise.
+fn unfinished_day(
+    now: DateTime<Local>,
+    options: &FinalizeOptions,
+) -> Option<(NaiveDate, DateTime<Local>)> {
+    let today = now.date_naive();
+    let end = local_midnight(today)?;
+    let day = today.pred_opt()?;
+    (now < end + options.grace).then_some((day, end))
+}
+
+/// Runs the passes for `day`, which ends at `end`, until `cancel` fires.
+///
+/// # Returns
+/// Whether the last pass stored the final totals
+async fn finalize_day(
+    collectors: &Vec<Box<dyn MetricCollector>>,
+    sink: &dyn Sink,
+    day: NaiveDate,
+    end: DateTime<Local>,
+    options: &FinalizeOptions,
+    cancel: &CancellationToken,
+) -> bool {
+    let times = pass_times(end, options);
+    let mut stored = false;
+    for (pass, at) in times.iter().enumerate() {
+        tokio::select! {
+            _ = sleep_until(*at) => {}
+            _ = cancel.cancelled() => return false,
+        }
+        let last = pass + 1 == times.len();
+        stored = collect_day(collectors, sink, day, &options.measurements, last).await;
+    }
+    if stored {
+        tracing::info!("Finalized daily totals of {}", day);
+    } else {
+        tracing::warn!(
+            "Failed to finalize daily totals of {}; the next startup backfills them",
+            day
+        );
+    }
+    stored
+}
+
+/// Finalizes the daily totals of every day once it has ended, until
+/// `cancel` fires.
+///
+/// Starting within the grace window after midnight, the day before is
+/// finalized first; passes whose time has passed run at once.
+pub async fn finalize_totals(
+    collectors: Arc<Vec<Box<dyn MetricCollector>>>,
+    sink: Arc<dyn Sink>,
+    options: FinalizeOptions,
+    cancel: CancellationToken,
+) {
+    if options.passes == 0 {
+        tracing::info!("Finalization of daily totals is disabled.");
+        return;
+    }
+    if let Some((day, end)) = unfinished_day(Local::now(), &options) {
+        finalize_day(&collectors, sink.as_ref(), day, end, &options, &cancel).await;
+    }
+    while !cancel.is_cancelled() {
+        let day = Local::now().date_naive();
+        let Some(end) = day.succ_opt().and_then(local_midnight) else {
+            tracing::error!("Failed to find the end of {}", day);
+            tokio::select! {
+                _ = sleep(Duration::from_secs(60 * 60)) => {}
+                _ = cancel.cancelled() => {}
+            }
+            continue;
+        };
+        finalize_day(&collectors, sink.as_ref(), day, end, &options, &cancel).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backfill::FINAL_FIELD;
+    use crate::model::FieldValue;
+    use crate::test_utils::mocks::{MockMetricCollector, RecordingSink};
+    use chrono::TimeZone;
+
+    fn options(passes: u32, grace_ms: u64) -> FinalizeOptions {
+        FinalizeOptions {
+            passes,
+            grace: Duration::from_millis(grace_ms),
+            measurements: vec!["power".to_string()],
+        }
+    }
+
+    fn collectors() -> Vec<Box<dyn MetricCollector>> {
+        vec![Box::new(MockMetricCollector::new_success())]
+    }
+
+    mod succeeds {
+        use super::*;
+
+        #[test]
+        fn test_pass_times() {
+            let end = Local.with_ymd_and_hms(2026, 10, 18, 0, 0, 0).unwrap();
+            let minutes = |minutes| end + chrono::Duration::minutes(minutes);
+
+            let test_cases = vec![
+                ("single pass", 1, vec![minutes(10)]),
+                (
+                    "three passes",
+                    3,
+                    vec![
+                        minutes(3) + chrono::Duration::seconds(20),
+                        minutes(6) + chrono::Duration::seconds(40),
+                        minutes(10),
+                    ],
+                ),
+                ("disabled", 0, vec![]),
+            ];
+
+            for (name, passes, expected) in test_cases {
+                assert_eq!(
+                    pass_times(end, &options(passes, 600_000)),
+                    expected,
+                    "case: {}",
+                    name
+                );
+            }
+        }
+
+        #[test]
+        fn test_unfinished_day() {
+            let midnight = Local.with_ymd_and_hms(2026, 10, 18, 0, 0, 0).unwrap();
+            let yesterday = NaiveDate::from_ymd_opt(2026, 10, 17).unwrap();
+            let minutes = |minutes| midnight + chrono::Duration::minutes(minutes);
+
+            let test_cases = vec![
+                ("at midnight", minutes(0), Some((yesterday, midnight))),
+                (
+                    "reloaded within grace window",
+                    minutes(5),
+                    Some((yesterday, midnight)),
+                ),
+                ("at end of grace window", minutes(10), None),
+                ("later that day", minutes(600), None),
+            ];
+
+            for (name, now, expected) in test_cases {
+                assert_eq!(
+                    unfinished_day(now, &options(3, 600_000)),
+                    expected,
+                    "case: {}",
+                    name
+                );
+            }
+        }
+
+        #[tokio::test]
+        async fn test_finalize_day_marks_last_pass_final() {
+            let sink = RecordingSink::new("recording");
+            let yesterday = Local::now().date_naive().pred_opt().unwrap();
+
+            let stored = finalize_day(
+                &collectors(),
+                &sink,
+                yesterday,
+                Local::now(),
+                &options(3, 30),
+                &CancellationToken::new(),
+            )
+            .await;
+
+            assert!(stored);
+            let finals: Vec<Option<FieldValue>> = sink
+                .batches()
+                .iter()
+                .map(|batch| batch[0].fields.get(FINAL_FIELD).cloned())
+                .collect();
+            assert_eq!(finals, vec![None, None, Some(FieldValue::Boolean(true))]);
+        }
+
+        #[tokio::test]
+        async fn test_finalize_day_waits_for_grace_window() {
+            let sink = RecordingSink::new("recording");
+            let started = std::time::Instant::now();
+
+            finalize_day(
+                &collectors(),
+                &sink,
+                Local::now().date_naive(),
+                Local::now(),
+                &options(2, 100),
+                &CancellationToken::new(),
+            )
+            .await;
+
+            assert!(started.elapsed() >= Duration::from_millis(100));
+            assert_eq!(sink.batches().len(), 2);
+        }
+
+        #[tokio::test]
+        async fn test_finalize_totals_disabled() {
+            let sink: Arc<dyn Sink> = Arc::new(RecordingSink::new("recording"));
+
+            // Returns at once instead of waiting for midnight
+            finalize_totals(
+                Arc::new(collectors()),
+                sink,
+                options(0, 0),
+                CancellationToken::new(),
+            )
+            .await;
+        }
+
+        #[tokio::test]
+        async fn test_finalize_totals_stops_when_cancelled() {
+            let sink = RecordingSink::new("recording");
+            let cancel = CancellationToken::new();
+            let finalizer = tokio::spawn(finalize_totals(
+                Arc::new(collectors()),
+                Arc::new(sink.clone()),
+                options(3, 600_000),
+                cancel.clone(),
+            ));
+
+            cancel.cancel();
+
+            // Returns instead of waiting for midnight
+            tokio::time::timeout(Duration::from_secs(1), finalizer)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(sink.batches().len(), 0);
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_finalize_day_reports_failed_last_pass() {
+            let sink = RecordingSink::failing("recording");
+
+            let stored = finalize_day(
+                &collectors(),
+                &sink,
+                Local::now().date_naive(),
+                Local::now(),
+                &options(1, 0),
+                &CancellationToken::new(),
+            )
+            .await;
+
+            assert!(!stored);
+        }
+    }
+}
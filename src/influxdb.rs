@@ -1,124 +1,657 @@
-//! InfluxDB2 client wrapper for writing time-series metrics.
+//! InfluxDB write client.
 //!
-//! This module provides a simplified interface to the InfluxDB2 client, handling
-//! connection management and data point writing operations. It wraps the official
-//! influxdb2 Rust client to provide a focused API for the forwarder's needs.
+//! Points are encoded as line protocol and posted to the write endpoint of
+//! the configured API version:
+//! - v1: `/write` with database, optional retention policy and basic auth,
+//!   for InfluxDB 1.x
+//! - v2: `/api/v2/write` with organization, bucket and token
+//! - v3: `/api/v3/write_lp` with database and bearer token, for InfluxDB 3
 //!
-//! # Architecture
-//! The client maintains a persistent connection to InfluxDB2 and streams data points
-//! in batches for efficient network utilization. All write operations are asynchronous
-//! to prevent blocking the metric collection tasks.
+//! Failed requests are classified by status so each failure is handled the
+//! way it can actually be resolved.
 //!
 //! # Error Handling
-//! Network errors, authentication failures, and InfluxDB server errors are propagated
-//! as anyhow::Result errors, allowing the caller to implement retry logic or failover
-//! strategies as needed.
+//! - 401/403: the credentials are wrong or lack write access; the write
+//!   fails at once with `StorageError::AuthFailed` and is logged as an error
+//! - 429/503: InfluxDB is throttling or starting up; the write is retried
+//!   after the `Retry-After` delay, or with exponential backoff without one
+//! - 400: lines InfluxDB could not parse are dropped and the rest of the
+//!   batch is sent again
+//! - NaN and infinite floats, which line protocol cannot express, are
+//!   dropped before the batch is sent
+//! - Connection errors and timeouts: retried with exponential backoff, then
+//!   reported as `StorageError::ConnectionFailed`
+//!
+//! A write, requests and retry delays included, ends within the configured
+//! write timeout, so it finishes inside the collector task that makes it. A
+//! retry that would not end in time is not made.
+//!
+//! Every batch reports its [`WriteOutcome`].
+//!
+//! With the v2 API the client also answers which days already hold final
+//! daily totals, through a Flux query, so the startup backfill only fetches
+//! the missing ones. The `probe` command checks the connection and
+//! credentials without writing a point.
 
-use crate::config::InfluxConfig;
+use crate::backfill::{local_midnight, FinalizedDays, FINAL_FIELD};
+use crate::config::{InfluxApiVersion, InfluxConfig};
 use crate::error::{Result, StorageError};
-use futures::prelude::stream;
+use crate::line_protocol;
+use crate::model::Point;
+use crate::sink::{RetryPolicy, Sink};
+use async_trait::async_trait;
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use reqwest::header::{HeaderMap, ACCEPT, AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::StatusCode;
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+/// Timeout of a query or probe request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
 
-/// InfluxDB2 client wrapper for writing metrics data.
+/// Header of `/ping` responses naming the server version.
+const INFLUXDB_VERSION: &str = "x-influxdb-version";
+
+/// InfluxDB client for writing metrics data.
 ///
-/// This client encapsulates the InfluxDB2 connection and provides a simple interface
-/// for writing data points. It maintains the target bucket configuration and handles
-/// the streaming of data points to the InfluxDB2 write API.
+/// This client implements [`Sink`] for writing points. It maintains the
+/// write target of the configured API version and retries writes that may
+/// succeed later.
 ///
 /// # Example
 /// ```rust
 /// let config = InfluxConfig {
 ///     url: "http://localhost:8086".to_string(),
+///     api_version: InfluxApiVersion::V2,
 ///     org: "my-org".to_string(),
 ///     token: "my-token".to_string(),
 ///     bucket: "metrics".to_string(),
+///     database: String::new(),
+///     retention_policy: None,
+///     username: None,
+///     password: None,
+///     max_retries: 3,
+///     retry_initial_ms: 500,
+///     retry_max_ms: 2_000,
+///     write_timeout_ms: 4_000,
 /// };
 /// let client = Client::new(config);
-/// let points = vec![/* data points */];
-/// client.write(points).await?;
+/// let points = vec![/* points */];
+/// client.write(&points).await?;
 /// ```
 pub struct Client {
-    /// The underlying InfluxDB2 client instance
-    client: influxdb2::Client,
-    /// The target bucket for all write operations
+    /// HTTP client shared by all writes
+    http: reqwest::Client,
+    /// The InfluxDB server URL
+    url: String,
+    /// Write API of the server
+    api_version: InfluxApiVersion,
+    /// Token sent with every write
+    token: String,
+    /// The organization owning the bucket (v2)
+    org: String,
+    /// The target bucket for all write operations (v2)
     bucket: String,
+    /// The target database (v1 and v3)
+    database: String,
+    /// Retention policy of the database (v1)
+    retention_policy: Option<String>,
+    /// Basic auth user name and password (v1)
+    username: Option<String>,
+    password: Option<String>,
+    /// Retries of throttled or unreachable writes
+    max_retries: u32,
+    /// Delays between retries
+    retry: RetryPolicy,
+    /// Time one write may take, retries included
+    write_timeout: Duration,
+}
+
+/// Result of writing one batch.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WriteOutcome {
+    /// Points stored by InfluxDB
+    pub written: usize,
+    /// Points InfluxDB rejected and that were dropped from the batch
+    pub dropped: usize,
+    /// Requests sent, including retries
+    pub attempts: u32,
 }
 
 impl Client {
     /// Creates a new InfluxDB client instance.
     ///
-    /// Initializes the client with the provided configuration, establishing the connection
-    /// parameters for the InfluxDB2 instance. The actual network connection is established
-    /// lazily on the first write operation.
+    /// No network activity occurs during construction. The first write
+    /// operation will establish the connection.
     ///
     /// # Arguments
     /// * `config` - InfluxDB connection configuration containing:
-    ///   - `url`: The InfluxDB2 server URL (e.g., "http://localhost:8086")
-    ///   - `org`: The organization name for authentication
-    ///   - `token`: The API token for authentication
-    ///   - `bucket`: The target bucket for write operations
+    ///   - `url`: The InfluxDB server URL (e.g., "http://localhost:8086")
+    ///   - `api_version`: Which write API to use
+    ///   - `org`, `bucket`: The write target for v2
+    ///   - `database`, `retention_policy`: The write target for v1 and v3
+    ///   - `token`, `username`, `password`: Credentials
+    ///   - `max_retries`, `retry_initial_ms`, `retry_max_ms`: Retry behavior
+    ///   - `write_timeout_ms`: Time one write may take, retries included
     ///
     /// # Returns
     /// A new Client instance configured with the provided settings
-    ///
-    /// # Connection Behavior
-    /// The client uses lazy connection establishment - no network activity occurs
-    /// during construction. The first write operation will establish the connection.
     pub(crate) fn new(config: InfluxConfig) -> Self {
-        let client = influxdb2::Client::new(config.url, config.org, config.token);
+        let http = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("Failed to build InfluxDB HTTP client");
         Self {
-            client,
+            http,
+            url: config.url.trim_end_matches('/').to_string(),
+            api_version: config.api_version,
+            token: config.token,
+            org: config.org,
             bucket: config.bucket,
+            database: config.database,
+            retention_policy: config.retention_policy,
+            username: config.username,
+            password: config.password,
+            max_retries: config.max_retries,
+            retry: RetryPolicy {
+                initial: Duration::from_millis(config.retry_initial_ms),
+                max: Duration::from_millis(config.retry_max_ms),
+            },
+            write_timeout: Duration::from_millis(config.write_timeout_ms),
         }
     }
 
-    /// Writes a batch of data points to InfluxDB.
-    ///
-    /// Streams the provided data points to the configured bucket using the InfluxDB2
-    /// line protocol. The operation is atomic - either all points are written successfully
-    /// or none are written.
+    /// Writes a batch of points and reports what happened to it.
     ///
     /// # Arguments
-    /// * `points` - Vector of DataPoint instances to write. Can be empty.
+    /// * `points` - Points to write. Can be empty.
     ///
     /// # Returns
-    /// * `Ok(())` - All points were written successfully
-    /// * `Err(e)` - Write operation failed due to:
-    ///   - Network connectivity issues
-    ///   - Authentication/authorization failures (401/403)
-    ///   - Invalid data point format (400)
-    ///   - Server errors (500)
-    ///   - Rate limiting (429)
+    /// * `Ok(WriteOutcome)` - The batch was stored, possibly without rejected points
+    /// * `Err(StorageError::AuthFailed)` - The credentials were refused (401/403)
+    /// * `Err(StorageError::ConnectionFailed)` - InfluxDB stayed unreachable
+    /// * `Err(StorageError::InvalidDataPoint)` - No point of the batch was accepted
+    /// * `Err(StorageError::WriteFailed)` - Any other failure, including
+    ///   throttling that outlasted all retries or the write timeout
+    pub async fn write_batch(&self, points: &[Point]) -> Result<WriteOutcome, StorageError> {
+        let mut outcome = WriteOutcome::default();
+        let mut lines = Vec::with_capacity(points.len());
+        let mut invalid = None;
+        for point in points {
+            match line_protocol::encode(point) {
+                Ok(line) => lines.push(line),
+                Err(e) => {
+                    // InfluxDB would refuse the whole batch over this point
+                    tracing::warn!("Dropping point InfluxDB cannot store: {}", e);
+                    outcome.dropped += 1;
+                    invalid = Some(e);
+                }
+            }
+        }
+        if lines.is_empty() {
+            return invalid.map_or(Ok(outcome), Err);
+        }
+
+        let deadline = tokio::time::Instant::now() + self.write_timeout;
+        let mut retries = 0;
+        let mut delay = self.retry.initial;
+        loop {
+            // A retry is only made if it can still start before the deadline
+            let can_retry = |wait: Duration| {
+                retries < self.max_retries && tokio::time::Instant::now() + wait < deadline
+            };
+            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+            outcome.attempts += 1;
+            let wait = match self.send(&lines, remaining).await {
+                Ok(response) if response.status().is_success() => {
+                    outcome.written += lines.len();
+                    return Ok(outcome);
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    let wait = retry_after(response.headers())
+                        .map_or(delay, |wait| wait.min(self.retry.max));
+                    let body = response.text().await.unwrap_or_default();
+                    let message = error_message(&body);
+
+                    match status {
+                        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                            tracing::error!(
+                                "InfluxDB refused the credentials for '{}' ({}): {}",
+                                self.target(),
+                                status,
+                                message
+                            );
+                            return Err(StorageError::AuthFailed);
+                        }
+                        StatusCode::BAD_REQUEST => {
+                            let dropped = partial_write_dropped(&message)
+                                .or_else(|| partial_write_rejected(&body));
+                            if let Some(dropped) = dropped {
+                                // InfluxDB stored everything else already
+                                tracing::warn!("InfluxDB dropped {} points: {}", dropped, message);
+                                outcome.dropped += dropped.min(lines.len());
+                                outcome.written += lines.len().saturating_sub(dropped);
+                                return Ok(outcome);
+                            }
+
+                            let rejected = rejected_lines(&message, &lines);
+                            if rejected.is_empty() || rejected.len() == lines.len() {
+                                return Err(StorageError::InvalidDataPoint(message));
+                            }
+                            for (index, line) in lines.iter().enumerate() {
+                                if rejected.contains(&index) {
+                                    tracing::warn!("Dropping point rejected by InfluxDB: {}", line);
+                                }
+                            }
+                            outcome.dropped += rejected.len();
+                            lines = lines
+                                .into_iter()
+                                .enumerate()
+                                .filter(|(index, _)| !rejected.contains(index))
+                                .map(|(_, line)| line)
+                                .collect();
+                            continue;
+                        }
+                        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+                            if can_retry(wait) =>
+                        {
+                            tracing::warn!("InfluxDB is unavailable ({}): {}", status, message);
+                            wait
+                        }
+                        _ => {
+                            return Err(StorageError::write_failed(
+                                lines.len(),
+                                format!("{}: {}", status, message),
+                            ))
+                        }
+                    }
+                }
+                Err(e) if e.is_connect() || e.is_timeout() => {
+                    if !can_retry(delay) {
+                        tracing::warn!("Giving up on InfluxDB at {}: {}", self.url, e);
+                        return Err(StorageError::connection_failed(&self.url));
+                    }
+                    tracing::warn!("Failed to reach InfluxDB at {}: {}", self.url, e);
+                    delay
+                }
+                Err(e) => return Err(StorageError::write_failed(lines.len(), e)),
+            };
+
+            tracing::info!(
+                "Retrying write of {} points in {:?} ({}/{})",
+                lines.len(),
+                wait,
+                retries + 1,
+                self.max_retries
+            );
+            tokio::time::sleep(wait).await;
+            delay = self.retry.next(delay);
+            retries += 1;
+        }
+    }
+
+    /// Days from `first` to `last` whose points of each of `measurements`
+    /// are marked final.
     ///
-    /// # Performance
-    /// The client streams points efficiently using the line protocol, batching them
-    /// for optimal network utilization. Large batches are automatically handled by
-    /// the underlying client.
+    /// Runs a Flux query over the local days' time range, so it needs the
+    /// v2 API. Days are local dates, like the midnight timestamps of
+    /// daily totals.
     ///
-    /// # Example
-    /// ```rust
-    /// let points = vec![
-    ///     DataPoint::builder("temperature")
-    ///         .tag("location", "room1")
-    ///         .field("value", 23.5)
-    ///         .build()?,
-    /// ];
-    /// client.write(points).await?;
-    /// ```
-    pub async fn write(
+    /// # Returns
+    /// * `Ok(days)` - Finalized days per measurement; measurements without
+    ///   any final point are missing from the map
+    /// * `Err(StorageError::AuthFailed)` - The token lacks read access
+    /// * `Err(StorageError::QueryFailed)` - The query failed or the API
+    ///   version has no Flux
+    pub async fn finalized_days(
         &self,
-        points: Vec<influxdb2::models::DataPoint>,
-    ) -> Result<(), StorageError> {
-        self.client
-            .write(self.bucket.as_str(), stream::iter(points))
+        measurements: &[String],
+        first: NaiveDate,
+        last: NaiveDate,
+    ) -> Result<BTreeMap<String, BTreeSet<NaiveDate>>, StorageError> {
+        if self.api_version != InfluxApiVersion::V2 {
+            return Err(StorageError::QueryFailed(format!(
+                "Flux queries need the v2 API, not {:?}",
+                self.api_version
+            )));
+        }
+        let (Some(start), Some(stop)) = (
+            local_midnight(first),
+            last.succ_opt().and_then(local_midnight),
+        ) else {
+            return Err(StorageError::QueryFailed(format!(
+                "no local midnight for {}..={}",
+                first, last
+            )));
+        };
+        let filter = measurements
+            .iter()
+            .map(|measurement| format!("r._measurement == {}", flux_string(measurement)))
+            .collect::<Vec<_>>()
+            .join(" or ");
+        let query = format!(
+            "from(bucket: {})\n  \
+             |> range(start: {}, stop: {})\n  \
+             |> filter(fn: (r) => {})\n  \
+             |> filter(fn: (r) => r._field == {} and r._value == true)\n  \
+             |> keep(columns: [\"_measurement\", \"_time\"])\n  \
+             |> group(columns: [\"_measurement\"])\n  \
+             |> unique(column: \"_time\")",
+            flux_string(&self.bucket),
+            start.to_rfc3339(),
+            stop.to_rfc3339(),
+            filter,
+            flux_string(FINAL_FIELD)
+        );
+
+        let response = self
+            .http
+            .post(format!("{}/api/v2/query", self.url))
+            .query(&[("org", self.org.as_str())])
+            .header(AUTHORIZATION, format!("Token {}", self.token))
+            .header(ACCEPT, "application/csv")
+            .json(&serde_json::json!({
+                "query": query,
+                "type": "flux",
+                "dialect": { "header": true, "annotations": [] },
+            }))
+            .send()
             .await
-            .map_err(|e| {
-                // Check for specific error types
-                if e.to_string().contains("401") || e.to_string().contains("Unauthorized") {
-                    StorageError::AuthFailed
-                } else {
-                    StorageError::Client(e)
+            .map_err(|e| StorageError::QueryFailed(e.to_string()))?;
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .map_err(|e| StorageError::QueryFailed(e.to_string()))?;
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                tracing::error!(
+                    "InfluxDB refused the credentials for '{}' ({}): {}",
+                    self.target(),
+                    status,
+                    error_message(&body)
+                );
+                Err(StorageError::AuthFailed)
+            }
+            status if status.is_success() => Ok(parse_finalized_days(&body)),
+            status => Err(StorageError::QueryFailed(format!(
+                "{}: {}",
+                status,
+                error_message(&body)
+            ))),
+        }
+    }
+
+    /// Checks that InfluxDB is reachable and accepts the credentials for
+    /// writing, without storing anything.
+    ///
+    /// `GET /ping` answers the server version, then an empty batch is sent
+    /// to the write endpoint, which InfluxDB authenticates before it looks
+    /// at the body.
+    ///
+    /// # Returns
+    /// * `Ok(version)` - The write endpoint accepted the credentials; the
+    ///   version is `None` if the server does not report it
+    /// * `Err(StorageError::ConnectionFailed)` - InfluxDB is unreachable
+    /// * `Err(StorageError::AuthFailed)` - The credentials were refused (401/403)
+    /// * `Err(StorageError::WriteFailed)` - Any other failure, such as a
+    ///   bucket or database that does not exist
+    pub async fn probe(&self) -> Result<Option<String>, StorageError> {
+        let connection_failed = |_| StorageError::ConnectionFailed {
+            url: self.url.clone(),
+        };
+        let ping = self
+            .http
+            .get(format!("{}/ping", self.url))
+            .send()
+            .await
+            .map_err(connection_failed)?;
+        let version = ping
+            .headers()
+            .get(INFLUXDB_VERSION)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+
+        let response = self
+            .send(&[], REQUEST_TIMEOUT)
+            .await
+            .map_err(connection_failed)?;
+        match response.status() {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(StorageError::AuthFailed),
+            // Some versions refuse a batch without points, after authenticating it
+            status if status.is_success() || status == StatusCode::BAD_REQUEST => Ok(version),
+            status => {
+                let body = response.text().await.unwrap_or_default();
+                Err(StorageError::WriteFailed {
+                    count: 0,
+                    message: format!("{}: {}", status, error_message(&body)),
+                })
+            }
+        }
+    }
+
+    /// Bucket or database the points are written to, for logs.
+    pub fn target(&self) -> String {
+        match (self.api_version, &self.retention_policy) {
+            (InfluxApiVersion::V1, Some(rp)) => format!("{}/{}", self.database, rp),
+            (InfluxApiVersion::V1 | InfluxApiVersion::V3, _) => self.database.clone(),
+            (InfluxApiVersion::V2, _) => self.bucket.clone(),
+        }
+    }
+
+    /// Posts `lines` to the write endpoint of the configured API version,
+    /// giving up after `timeout`.
+    async fn send(
+        &self,
+        lines: &[String],
+        timeout: Duration,
+    ) -> reqwest::Result<reqwest::Response> {
+        let request = match self.api_version {
+            InfluxApiVersion::V1 => {
+                let mut query = vec![("db", self.database.as_str()), ("precision", "ns")];
+                if let Some(rp) = &self.retention_policy {
+                    query.push(("rp", rp.as_str()));
+                }
+                let request = self.http.post(format!("{}/write", self.url)).query(&query);
+                match &self.username {
+                    Some(username) => request.basic_auth(username, self.password.as_deref()),
+                    None if !self.token.is_empty() => {
+                        request.header(AUTHORIZATION, format!("Token {}", self.token))
+                    }
+                    None => request,
                 }
-            })
+            }
+            InfluxApiVersion::V2 => self
+                .http
+                .post(format!("{}/api/v2/write", self.url))
+                .query(&[
+                    ("org", self.org.as_str()),
+                    ("bucket", self.bucket.as_str()),
+                    ("precision", "ns"),
+                ])
+                .header(AUTHORIZATION, format!("Token {}", self.token)),
+            InfluxApiVersion::V3 => self
+                .http
+                .post(format!("{}/api/v3/write_lp", self.url))
+                .query(&[("db", self.database.as_str()), ("precision", "nanosecond")])
+                .bearer_auth(&self.token),
+        };
+        request
+            .timeout(timeout)
+            .header(CONTENT_TYPE, "text/plain; charset=utf-8")
+            .body(lines.join("\n"))
+            .send()
+            .await
+    }
+}
+
+/// Reads the `Retry-After` header, given either in seconds or as an HTTP date.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (at.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// Extracts the message from an InfluxDB JSON error body, or returns the body.
+///
+/// InfluxDB 2.x reports it as `message`, 1.x and 3 as `error`.
+fn error_message(body: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|json| {
+            json["message"]
+                .as_str()
+                .or_else(|| json["error"].as_str())
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| body.trim().to_string())
+}
+
+/// Quotes `value` as a Flux string literal.
+fn flux_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Reads the `_measurement` and `_time` columns of a CSV query result
+/// into local days per measurement.
+///
+/// Each table of the result repeats the header, so header rows are
+/// recognized wherever they appear.
+fn parse_finalized_days(csv: &str) -> BTreeMap<String, BTreeSet<NaiveDate>> {
+    let mut days: BTreeMap<String, BTreeSet<NaiveDate>> = BTreeMap::new();
+    let mut columns = None;
+    for line in csv
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty())
+    {
+        let cells: Vec<&str> = line.split(',').collect();
+        let measurement = cells.iter().position(|cell| *cell == "_measurement");
+        let time = cells.iter().position(|cell| *cell == "_time");
+        if let (Some(measurement), Some(time)) = (measurement, time) {
+            columns = Some((measurement, time));
+            continue;
+        }
+        let Some((measurement, time)) = columns else {
+            continue;
+        };
+        let (Some(measurement), Some(time)) = (cells.get(measurement), cells.get(time)) else {
+            continue;
+        };
+        match DateTime::parse_from_rfc3339(time) {
+            Ok(time) => {
+                days.entry(measurement.to_string())
+                    .or_default()
+                    .insert(time.with_timezone(&Local).date_naive());
+            }
+            Err(e) => tracing::warn!("Ignoring query row with time '{}': {}", time, e),
+        }
+    }
+    days
+}
+
+/// Number of points InfluxDB dropped from a partially stored batch.
+///
+/// InfluxDB reports e.g. field type conflicts as
+/// `partial write: field type conflict: ... dropped=1` and stores the rest.
+fn partial_write_dropped(message: &str) -> Option<usize> {
+    if !message.starts_with("partial write") {
+        return None;
+    }
+    let (_, dropped) = message.rsplit_once("dropped=")?;
+    let digits: String = dropped.chars().take_while(char::is_ascii_digit).collect();
+    digits.parse().ok()
+}
+
+/// Number of lines InfluxDB 3 rejected from a partially stored batch.
+///
+/// InfluxDB 3 stores the valid lines and lists the others under `data`,
+/// with `partial write of line protocol occurred` as the error.
+fn partial_write_rejected(body: &str) -> Option<usize> {
+    let json: serde_json::Value = serde_json::from_str(body).ok()?;
+    if !json["error"].as_str()?.starts_with("partial write") {
+        return None;
+    }
+    json["data"].as_array().map(Vec::len)
+}
+
+/// Indices of the lines a 400 response names as unparsable.
+///
+/// InfluxDB lists them as `line 2: ...`, counting from one, or quotes a
+/// single line as `unable to parse '...'`.
+fn rejected_lines(message: &str, lines: &[String]) -> BTreeSet<usize> {
+    let mut rejected: BTreeSet<usize> = message
+        .match_indices("line ")
+        .filter_map(|(start, prefix)| {
+            let rest = &message[start + prefix.len()..];
+            let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+            if !rest[digits.len()..].starts_with(':') {
+                return None;
+            }
+            digits.parse::<usize>().ok()
+        })
+        .filter(|number| (1..=lines.len()).contains(number))
+        .map(|number| number - 1)
+        .collect();
+
+    if rejected.is_empty() {
+        rejected = lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| message.contains(&format!("'{}'", line)))
+            .map(|(index, _)| index)
+            .collect();
+    }
+    rejected
+}
+
+#[async_trait]
+impl Sink for Client {
+    fn name(&self) -> &str {
+        "influxdb"
+    }
+
+    /// Writes a batch of points to InfluxDB.
+    ///
+    /// See [`Client::write_batch`] for how failures are handled. Points
+    /// InfluxDB rejects are dropped, so the rest of the batch is still stored.
+    async fn write(&self, points: &[Point]) -> Result<(), StorageError> {
+        let outcome = self.write_batch(points).await?;
+        if outcome.dropped > 0 || outcome.attempts > 1 {
+            tracing::warn!(
+                written = outcome.written,
+                dropped = outcome.dropped,
+                attempts = outcome.attempts,
+                "Wrote batch to InfluxDB"
+            );
+        } else {
+            tracing::debug!(
+                written = outcome.written,
+                attempts = outcome.attempts,
+                "Wrote batch to InfluxDB"
+            );
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl FinalizedDays for Client {
+    async fn finalized_days(
+        &self,
+        measurements: &[String],
+        first: NaiveDate,
+        last: NaiveDate,
+    ) -> Result<BTreeMap<String, BTreeSet<NaiveDate>>, StorageError> {
+        Client::finalized_days(self, measurements, first, last).await
     }
 }
 
@@ -126,12 +659,32 @@ impl Client {
 mod tests {
     use super::*;
     use crate::test_utils::{
-        builders::TestInfluxDataPointBuilder, config::test_influx_config_with_url,
+        builders::TestInfluxDataPointBuilder,
+        config::{test_influx_config_with_url, TestInfluxConfigBuilder},
+        fake_influx::{self, FakeInfluxDb, BUCKET, DATABASE},
     };
     use wiremock::matchers::{method, path};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
-    fn create_test_point() -> influxdb2::models::DataPoint {
+    fn create_value_point(value: f64) -> Point {
+        Point::builder("power")
+            .tag("summary", "総消費電力(W)")
+            .field("value", value)
+            .build()
+            .unwrap()
+    }
+
+    /// A point InfluxDB refuses to parse, for its time is out of range.
+    fn create_unparsable_point() -> Point {
+        Point::builder("power")
+            .tag("summary", "総消費電力(W)")
+            .field("value", 2.0)
+            .timestamp(i64::MAX)
+            .build()
+            .unwrap()
+    }
+
+    fn create_test_point() -> Point {
         TestInfluxDataPointBuilder::new("test_measurement")
             .add_tag("test_tag", "test_value")
             .add_field("test_field", 123.45)
@@ -150,6 +703,32 @@ mod tests {
             assert_eq!(client.bucket, "test-bucket");
         }
 
+        #[tokio::test]
+        async fn test_write_converts_field_types() {
+            let fake = FakeInfluxDb::start().await;
+            let client = Client::new(fake.config());
+            let point = Point::builder("test_measurement")
+                .tag("test_tag", "test_value")
+                .field("float", 1.5)
+                .field("integer", -2i64)
+                .field("unsigned", u64::MAX)
+                .field("text", "on")
+                .field("flag", true)
+                .timestamp(1_000)
+                .build()
+                .unwrap();
+
+            client.write(&[point]).await.unwrap();
+
+            assert_eq!(
+                fake.lines(),
+                vec![format!(
+                    "test_measurement,test_tag=test_value flag=true,float=1.5,integer=-2i,text=\"on\",unsigned={}i 1000",
+                    i64::MAX
+                )]
+            );
+        }
+
         #[tokio::test]
         async fn test_write_single_point() {
             let mock_server = MockServer::start().await;
@@ -164,7 +743,7 @@ mod tests {
                 .await;
 
             let point = create_test_point();
-            let result = client.write(vec![point]).await;
+            let result = client.write(&[point]).await;
 
             assert!(result.is_ok());
         }
@@ -187,7 +766,7 @@ mod tests {
                 create_test_point(),
                 create_test_point(),
             ];
-            let result = client.write(points).await;
+            let result = client.write(&points).await;
 
             assert!(result.is_ok());
         }
@@ -198,18 +777,416 @@ mod tests {
             let config = test_influx_config_with_url(mock_server.uri());
             let client = Client::new(config);
 
-            // The influxdb2 client handles empty vectors gracefully
-            // It might not even make a request, but if it does, we're ready
+            // An empty batch encodes to no lines and is never posted
             Mock::given(method("POST"))
                 .and(path("/api/v2/write"))
                 .respond_with(ResponseTemplate::new(204))
+                .expect(0)
                 .mount(&mock_server)
                 .await;
 
-            let result = client.write(vec![]).await;
+            let result = client.write(&[]).await;
 
             assert!(result.is_ok());
         }
+
+        #[tokio::test]
+        async fn test_write_retries_throttled_requests() {
+            let test_cases = vec![
+                ("429 with delay in seconds", 429, Some("0".to_string())),
+                (
+                    "503 with past HTTP date",
+                    503,
+                    Some("Wed, 21 Oct 2015 07:28:00 GMT".to_string()),
+                ),
+                ("503 without Retry-After", 503, None),
+            ];
+
+            for (name, status, retry_after) in test_cases {
+                let fake = FakeInfluxDb::start().await;
+                fake.fail_next(status, retry_after.as_deref());
+                let client = Client::new(fake.config());
+
+                let outcome = client.write_batch(&[create_value_point(1.0)]).await;
+
+                assert_eq!(
+                    outcome.unwrap(),
+                    WriteOutcome {
+                        written: 1,
+                        dropped: 0,
+                        attempts: 2,
+                    },
+                    "case: {}",
+                    name
+                );
+                assert_eq!(fake.points().len(), 1, "case: {}", name);
+            }
+        }
+
+        #[tokio::test]
+        async fn test_write_retries_after_connection_error() {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let addr = listener.local_addr().unwrap();
+            drop(listener);
+            let client = Client::new(
+                TestInfluxConfigBuilder::new()
+                    .with_url(format!("http://{}", addr))
+                    .with_max_retries(20)
+                    .build(),
+            );
+
+            // Start the server only after the first attempt was refused
+            let write =
+                tokio::spawn(async move { client.write_batch(&[create_value_point(1.0)]).await });
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            let mock_server = MockServer::builder()
+                .listener(std::net::TcpListener::bind(addr).unwrap())
+                .start()
+                .await;
+            Mock::given(method("POST"))
+                .and(path("/api/v2/write"))
+                .respond_with(ResponseTemplate::new(204))
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+
+            let outcome = write.await.unwrap().unwrap();
+
+            assert_eq!(outcome.written, 1);
+            assert!(outcome.attempts > 1);
+        }
+
+        #[tokio::test]
+        async fn test_write_drops_rejected_points() {
+            let fake = FakeInfluxDb::start().await;
+            let client = Client::new(fake.config());
+            let points = vec![
+                create_value_point(1.0),
+                create_unparsable_point(),
+                create_value_point(3.0),
+            ];
+
+            let outcome = client.write_batch(&points).await.unwrap();
+
+            assert_eq!(
+                outcome,
+                WriteOutcome {
+                    written: 2,
+                    dropped: 1,
+                    attempts: 2,
+                }
+            );
+            assert_eq!(
+                fake.lines(),
+                vec![
+                    "power,summary=総消費電力(W) value=1",
+                    "power,summary=総消費電力(W) value=3",
+                ]
+            );
+        }
+
+        #[tokio::test]
+        async fn test_probe() {
+            for api_version in [
+                InfluxApiVersion::V1,
+                InfluxApiVersion::V2,
+                InfluxApiVersion::V3,
+            ] {
+                let fake = FakeInfluxDb::start().await;
+                let client = Client::new(fake.config_for(api_version));
+
+                let result = client.probe().await;
+
+                assert_eq!(
+                    result.unwrap().as_deref(),
+                    Some(fake_influx::VERSION),
+                    "case: {:?}",
+                    api_version
+                );
+                assert!(fake.points().is_empty(), "case: {:?}", api_version);
+            }
+        }
+
+        #[tokio::test]
+        async fn test_write_api_versions() {
+            let test_cases = vec![
+                (InfluxApiVersion::V1, None, Some("ns"), Some(DATABASE)),
+                (InfluxApiVersion::V2, Some(BUCKET), Some("ns"), None),
+                (
+                    InfluxApiVersion::V3,
+                    None,
+                    Some("nanosecond"),
+                    Some(DATABASE),
+                ),
+            ];
+
+            for (api_version, bucket, precision, database) in test_cases {
+                let fake = FakeInfluxDb::start().await;
+                let client = Client::new(fake.config_for(api_version));
+
+                client.write(&[create_value_point(1.5)]).await.unwrap();
+
+                let writes = fake.writes();
+                assert_eq!(writes.len(), 1, "case: {:?}", api_version);
+                assert_eq!(writes[0].api_version, api_version);
+                assert_eq!(
+                    writes[0].bucket.as_deref(),
+                    bucket,
+                    "case: {:?}",
+                    api_version
+                );
+                assert_eq!(
+                    writes[0].precision.as_deref(),
+                    precision,
+                    "case: {:?}",
+                    api_version
+                );
+                assert_eq!(
+                    writes[0].database.as_deref(),
+                    database,
+                    "case: {:?}",
+                    api_version
+                );
+                assert_eq!(
+                    fake.lines(),
+                    vec!["power,summary=総消費電力(W) value=1.5"],
+                    "case: {:?}",
+                    api_version
+                );
+            }
+        }
+
+        #[tokio::test]
+        async fn test_write_v1_retention_policy() {
+            let fake = FakeInfluxDb::start().await;
+            let client = Client::new(InfluxConfig {
+                retention_policy: Some("one_year".to_string()),
+                ..fake.config_for(InfluxApiVersion::V1)
+            });
+
+            client.write(&[create_value_point(1.0)]).await.unwrap();
+
+            assert_eq!(
+                fake.writes()[0].retention_policy.as_deref(),
+                Some("one_year")
+            );
+        }
+
+        #[tokio::test]
+        async fn test_write_drops_rejected_points_in_every_api_version() {
+            for api_version in [InfluxApiVersion::V1, InfluxApiVersion::V3] {
+                let fake = FakeInfluxDb::start().await;
+                let client = Client::new(fake.config_for(api_version));
+                let points = vec![create_value_point(1.0), create_unparsable_point()];
+
+                let outcome = client.write_batch(&points).await.unwrap();
+
+                // InfluxDB 3 stores the valid line at once, 1.x after a resend
+                let attempts = if api_version == InfluxApiVersion::V3 {
+                    1
+                } else {
+                    2
+                };
+                assert_eq!(
+                    outcome,
+                    WriteOutcome {
+                        written: 1,
+                        dropped: 1,
+                        attempts,
+                    },
+                    "case: {:?}",
+                    api_version
+                );
+                assert_eq!(
+                    fake.lines(),
+                    vec!["power,summary=総消費電力(W) value=1"],
+                    "case: {:?}",
+                    api_version
+                );
+            }
+        }
+
+        #[tokio::test]
+        async fn test_write_partial_write() {
+            let mock_server = MockServer::start().await;
+            let client = Client::new(test_influx_config_with_url(mock_server.uri()));
+
+            Mock::given(method("POST"))
+                .and(path("/api/v2/write"))
+                .respond_with(ResponseTemplate::new(400).set_body_string(
+                    r#"{"code":"invalid","message":"partial write: field type conflict: input field \"value\" on measurement \"power\" is type integer, already exists as type float dropped=1"}"#,
+                ))
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+
+            let outcome = client
+                .write_batch(&[create_value_point(1.0), create_value_point(2.0)])
+                .await
+                .unwrap();
+
+            assert_eq!(
+                outcome,
+                WriteOutcome {
+                    written: 1,
+                    dropped: 1,
+                    attempts: 1,
+                }
+            );
+        }
+
+        #[test]
+        fn test_rejected_lines() {
+            let lines: Vec<String> =
+                vec!["a value=1".into(), "b value=x".into(), "c value=y".into()];
+            let test_cases = vec![
+                (
+                    "numbered lines",
+                    "failed to parse line protocol: errors encountered on line(s):\nline 2: invalid field\nline 3: invalid field",
+                    vec![1, 2],
+                ),
+                ("quoted line", "unable to parse 'b value=x': invalid field format", vec![1]),
+                ("line number out of range", "line 9: invalid field", vec![]),
+                ("no line named", "invalid", vec![]),
+            ];
+
+            for (name, message, expected) in test_cases {
+                assert_eq!(
+                    rejected_lines(message, &lines),
+                    expected.into_iter().collect::<BTreeSet<_>>(),
+                    "case: {}",
+                    name
+                );
+            }
+        }
+
+        #[test]
+        fn test_partial_write_rejected() {
+            let test_cases = vec![
+                (
+                    "InfluxDB 3 partial write",
+                    r#"{"error":"partial write of line protocol occurred","data":[{"original_line":"b value=x","line_number":2,"error_message":"invalid field"}]}"#,
+                    Some(1),
+                ),
+                (
+                    "nothing stored",
+                    r#"{"error":"parsing failed for write_lp endpoint","data":[]}"#,
+                    None,
+                ),
+                ("not JSON", "partial write", None),
+            ];
+
+            for (name, body, expected) in test_cases {
+                assert_eq!(partial_write_rejected(body), expected, "case: {}", name);
+            }
+        }
+
+        #[tokio::test]
+        async fn test_finalized_days() {
+            let fake = FakeInfluxDb::start().await;
+            let client = Client::new(fake.config());
+            let day = |day| NaiveDate::from_ymd_opt(2026, 10, day).unwrap();
+            let point = |measurement: &str, day, finalized: Option<bool>| {
+                let point = Point::builder(measurement)
+                    .tag("detail-section", "発電量(kWh)")
+                    .field("value", 1.5)
+                    .timestamp(local_midnight(day).unwrap().timestamp_nanos_opt().unwrap());
+                match finalized {
+                    Some(finalized) => point.field(FINAL_FIELD, finalized),
+                    None => point,
+                }
+                .build()
+                .unwrap()
+            };
+            client
+                .write(&[
+                    point("daily_total", day(1), Some(true)),
+                    point("daily_total", day(2), Some(true)),
+                    point("daily_total", day(3), None),
+                    point("daily_total", day(4), Some(false)),
+                    point("daily_total", day(5), Some(true)),
+                    point("circuit_daily_total", day(2), Some(true)),
+                    point("power", day(3), Some(true)),
+                ])
+                .await
+                .unwrap();
+
+            let days = client
+                .finalized_days(
+                    &["daily_total".to_string(), "circuit_daily_total".to_string()],
+                    day(2),
+                    day(4),
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(
+                days,
+                BTreeMap::from([
+                    ("circuit_daily_total".to_string(), BTreeSet::from([day(2)])),
+                    ("daily_total".to_string(), BTreeSet::from([day(2)])),
+                ])
+            );
+            let queries = fake.queries();
+            assert_eq!(queries.len(), 1);
+            assert!(queries[0].starts_with(r#"from(bucket: "test-bucket")"#));
+        }
+
+        #[test]
+        fn test_parse_finalized_days() {
+            let csv = ",result,table,_measurement,_time\r\n\
+                       ,_result,0,daily_total,2026-10-01T15:00:00Z\r\n\
+                       ,_result,0,daily_total,2026-10-02T15:00:00Z\r\n\
+                       \r\n\
+                       ,result,table,_measurement,_time\r\n\
+                       ,_result,1,circuit_daily_total,2026-10-01T15:00:00Z\r\n\
+                       ,_result,1,circuit_daily_total,soon\r\n";
+            let day = |time: &str| {
+                DateTime::parse_from_rfc3339(time)
+                    .unwrap()
+                    .with_timezone(&Local)
+                    .date_naive()
+            };
+
+            let days = parse_finalized_days(csv);
+
+            assert_eq!(
+                days["daily_total"],
+                BTreeSet::from([day("2026-10-01T15:00:00Z"), day("2026-10-02T15:00:00Z")])
+            );
+            assert_eq!(
+                days["circuit_daily_total"],
+                BTreeSet::from([day("2026-10-01T15:00:00Z")])
+            );
+            assert!(parse_finalized_days("").is_empty());
+        }
+
+        #[test]
+        fn test_flux_string() {
+            assert_eq!(flux_string("daily_total"), r#""daily_total""#);
+            assert_eq!(flux_string(r#"a"b\c"#), r#""a\"b\\c""#);
+        }
+
+        #[test]
+        fn test_partial_write_dropped() {
+            let test_cases = vec![
+                (
+                    "dropped count",
+                    "partial write: field type conflict dropped=2",
+                    Some(2),
+                ),
+                ("not a partial write", "unable to parse 'x'", None),
+                (
+                    "no count",
+                    "partial write: points beyond retention policy",
+                    None,
+                ),
+            ];
+
+            for (name, message, expected) in test_cases {
+                assert_eq!(partial_write_dropped(message), expected, "case: {}", name);
+            }
+        }
     }
 
     mod fails {
@@ -222,9 +1199,12 @@ mod tests {
             let client = Client::new(config);
 
             let point = create_test_point();
-            let result = client.write(vec![point]).await;
+            let result = client.write(&[point]).await;
 
-            assert!(result.is_err());
+            assert!(matches!(
+                result,
+                Err(StorageError::ConnectionFailed { url }) if url == "http://localhost:1"
+            ));
         }
 
         #[tokio::test]
@@ -241,13 +1221,124 @@ mod tests {
                 .await;
 
             let point = create_test_point();
-            let result = client.write(vec![point]).await;
+            let result = client.write(&[point]).await;
 
             assert!(result.is_err());
             let err_str = result.unwrap_err().to_string();
             assert!(err_str.contains("InfluxDB authentication failed"));
         }
 
+        #[tokio::test]
+        async fn test_finalized_days_fails() {
+            let fake = FakeInfluxDb::start().await;
+            let day = NaiveDate::from_ymd_opt(2026, 10, 1).unwrap();
+            let measurements = ["daily_total".to_string()];
+            let test_cases = vec![
+                (
+                    "wrong token",
+                    InfluxConfig {
+                        token: "wrong".to_string(),
+                        ..fake.config()
+                    },
+                    "AuthFailed",
+                ),
+                (
+                    "unknown bucket",
+                    InfluxConfig {
+                        bucket: "other".to_string(),
+                        ..fake.config()
+                    },
+                    "QueryFailed",
+                ),
+                (
+                    "no Flux in v1",
+                    fake.config_for(InfluxApiVersion::V1),
+                    "QueryFailed",
+                ),
+                (
+                    "no Flux in v3",
+                    fake.config_for(InfluxApiVersion::V3),
+                    "QueryFailed",
+                ),
+            ];
+
+            for (name, config, expected) in test_cases {
+                let result = Client::new(config)
+                    .finalized_days(&measurements, day, day)
+                    .await;
+                let error = format!("{:?}", result.unwrap_err());
+                assert!(error.starts_with(expected), "case: {}: {}", name, error);
+            }
+            // v1 and v3 clients never send a query, the wrong token is refused
+            assert_eq!(fake.queries().len(), 1);
+        }
+
+        #[tokio::test]
+        async fn test_write_wrong_credentials_in_every_api_version() {
+            for api_version in [
+                InfluxApiVersion::V1,
+                InfluxApiVersion::V2,
+                InfluxApiVersion::V3,
+            ] {
+                let fake = FakeInfluxDb::start().await;
+                let client = Client::new(InfluxConfig {
+                    token: "wrong".to_string(),
+                    password: Some("wrong".to_string()),
+                    ..fake.config_for(api_version)
+                });
+
+                let result = client.write_batch(&[create_value_point(1.0)]).await;
+
+                assert!(
+                    matches!(result, Err(StorageError::AuthFailed)),
+                    "case: {:?}",
+                    api_version
+                );
+                assert!(fake.writes().is_empty(), "case: {:?}", api_version);
+            }
+        }
+
+        #[tokio::test]
+        async fn test_probe_fails() {
+            let fake = FakeInfluxDb::start().await;
+            let missing = MockServer::start().await;
+            Mock::given(method("POST"))
+                .and(path("/api/v2/write"))
+                .respond_with(
+                    ResponseTemplate::new(404)
+                        .set_body_string(r#"{"code":"not found","message":"bucket not found"}"#),
+                )
+                .mount(&missing)
+                .await;
+            let test_cases = vec![
+                (
+                    "unreachable",
+                    test_influx_config_with_url("http://localhost:1".to_string()),
+                    "failed to connect to InfluxDB at http://localhost:1",
+                ),
+                (
+                    "wrong token",
+                    InfluxConfig {
+                        token: "wrong".to_string(),
+                        ..fake.config()
+                    },
+                    "InfluxDB authentication failed",
+                ),
+                (
+                    "missing bucket",
+                    test_influx_config_with_url(missing.uri()),
+                    "bucket not found",
+                ),
+            ];
+
+            for (name, config, expected) in test_cases {
+                let result = Client::new(config).probe().await;
+
+                let error = result.unwrap_err().to_string();
+                assert!(error.contains(expected), "case: {}: {}", name, error);
+            }
+        }
+
         #[tokio::test]
         async fn test_write_server_error() {
             let mock_server = MockServer::start().await;
@@ -262,11 +1353,100 @@ mod tests {
                 .await;
 
             let point = create_test_point();
-            let result = client.write(vec![point]).await;
+            let result = client.write(&[point]).await;
 
             assert!(result.is_err());
             let err_str = result.unwrap_err().to_string();
             assert!(err_str.contains("500") || err_str.contains("Internal Server Error"));
         }
+
+        #[tokio::test]
+        async fn test_write_auth_errors_are_not_retried() {
+            for status in [401, 403] {
+                let mock_server = MockServer::start().await;
+                let client = Client::new(test_influx_config_with_url(mock_server.uri()));
+
+                Mock::given(method("POST"))
+                    .and(path("/api/v2/write"))
+                    .respond_with(ResponseTemplate::new(status))
+                    .expect(1)
+                    .mount(&mock_server)
+                    .await;
+
+                let result = client.write_batch(&[create_test_point()]).await;
+
+                assert!(
+                    matches!(result, Err(StorageError::AuthFailed)),
+                    "case: {}",
+                    status
+                );
+            }
+        }
+
+        #[tokio::test]
+        async fn test_write_throttled_until_retries_exhausted() {
+            let mock_server = MockServer::start().await;
+            let client = Client::new(
+                TestInfluxConfigBuilder::new()
+                    .with_url(mock_server.uri())
+                    .with_max_retries(1)
+                    .build(),
+            );
+
+            Mock::given(method("POST"))
+                .and(path("/api/v2/write"))
+                .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+                .expect(2)
+                .mount(&mock_server)
+                .await;
+
+            let result = client.write_batch(&[create_test_point()]).await;
+
+            assert!(matches!(
+                result,
+                Err(StorageError::WriteFailed { count: 1, ref message }) if message.starts_with("429")
+            ));
+        }
+
+        #[tokio::test]
+        async fn test_write_all_points_rejected() {
+            let fake = FakeInfluxDb::start().await;
+            let client = Client::new(fake.config());
+
+            let result = client.write_batch(&[create_unparsable_point()]).await;
+
+            assert!(matches!(result, Err(StorageError::InvalidDataPoint(_))));
+            assert!(fake.writes().is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_write_non_finite_floats() {
+            let fake = FakeInfluxDb::start().await;
+            let client = Client::new(fake.config());
+
+            // Never sent, since InfluxDB would refuse the whole batch
+            let result = client
+                .write_batch(&[
+                    create_value_point(f64::NAN),
+                    create_value_point(f64::INFINITY),
+                ])
+                .await;
+            assert!(matches!(result, Err(StorageError::InvalidDataPoint(_))));
+            assert!(fake.writes().is_empty());
+
+            let outcome = client
+                .write_batch(&[create_value_point(f64::NAN), create_value_point(1.0)])
+                .await
+                .unwrap();
+            assert_eq!(
+                outcome,
+                WriteOutcome {
+                    written: 1,
+                    dropped: 1,
+                    attempts: 1,
+                }
+            );
+            assert_eq!(fake.lines(), vec!["power,summary=総消費電力(W) value=1"]);
+        }
     }
 }
@@ -0,0 +1,231 @@
+//! InfluxDB line protocol encoding of [`Point`]s.
+//!
+//! One point becomes one line:
+//! `measurement[,tag=value...] field=value[,field=value...] [timestamp]`.
+//! Tags and fields are written in key order and timestamps in nanoseconds.
+
+use crate::error::StorageError;
+use crate::model::{FieldValue, Point};
+use std::fmt::Write;
+
+/// Encodes a point as a single line, without a trailing newline.
+///
+/// Unsigned fields are written as signed integers, saturating at
+/// `i64::MAX`, so they never conflict with existing integer fields.
+///
+/// # Returns
+/// - `Ok(String)` with the line
+/// - `Err(StorageError::InvalidDataPoint)` if a float field is NaN or
+///   infinite, which line protocol cannot express
+pub fn encode(point: &Point) -> Result<String, StorageError> {
+    if let Some((key, value)) = point.fields.iter().find_map(|(key, value)| match value {
+        FieldValue::Float(v) if !v.is_finite() => Some((key, v)),
+        _ => None,
+    }) {
+        return Err(StorageError::InvalidDataPoint(format!(
+            "field '{}' of {} is {}",
+            key, point.measurement, value
+        )));
+    }
+
+    let mut line = String::new();
+    escape(&mut line, &point.measurement, &[',', ' ']);
+    for (key, value) in &point.tags {
+        line.push(',');
+        escape(&mut line, key, &[',', '=', ' ']);
+        line.push('=');
+        escape(&mut line, value, &[',', '=', ' ']);
+    }
+
+    let mut separator = ' ';
+    for (key, value) in &point.fields {
+        line.push(separator);
+        separator = ',';
+        escape(&mut line, key, &[',', '=', ' ']);
+        line.push('=');
+        match value {
+            FieldValue::Float(v) => write!(line, "{}", v),
+            FieldValue::Integer(v) => write!(line, "{}i", v),
+            FieldValue::UInteger(v) => write!(line, "{}i", i64::try_from(*v).unwrap_or(i64::MAX)),
+            FieldValue::Boolean(v) => write!(line, "{}", v),
+            FieldValue::String(v) => {
+                line.push('"');
+                escape(&mut line, v, &['"', '\\']);
+                line.push('"');
+                Ok(())
+            }
+        }
+        .expect("writing to a String cannot fail");
+    }
+
+    if let Some(timestamp) = point.timestamp {
+        write!(line, " {}", timestamp).expect("writing to a String cannot fail");
+    }
+    Ok(line)
+}
+
+/// Encodes points as a request body, one line per point.
+///
+/// Points that cannot be encoded are logged and left out.
+pub fn encode_batch(points: &[Point]) -> Vec<String> {
+    points
+        .iter()
+        .filter_map(|point| {
+            encode(point)
+                .inspect_err(|e| tracing::warn!("Dropping point: {}", e))
+                .ok()
+        })
+        .collect()
+}
+
+/// Appends `input`, prefixing every character in `special` with a backslash.
+///
+/// Line breaks would end the line early and are written as `\n` and `\r`.
+fn escape(output: &mut String, input: &str, special: &[char]) {
+    for c in input.chars() {
+        match c {
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            c => {
+                if special.contains(&c) {
+                    output.push('\\');
+                }
+                output.push(c);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::fake_influx::parse_line_protocol;
+
+    mod succeeds {
+        use super::*;
+
+        #[test]
+        fn test_encode() {
+            let test_cases = vec![
+                (
+                    "integer without timestamp",
+                    Point::builder("power")
+                        .tag("summary", "総消費電力(W)")
+                        .field("value", 3800i64)
+                        .build()
+                        .unwrap(),
+                    "power,summary=総消費電力(W) value=3800i",
+                ),
+                (
+                    "float with timestamp",
+                    Point::builder("climate")
+                        .tag("detail-type", "temperature")
+                        .tag("detail-section", "リビング")
+                        .field("value", 23.5)
+                        .timestamp(1_717_815_600_000_000_000)
+                        .build()
+                        .unwrap(),
+                    "climate,detail-section=リビング,detail-type=temperature value=23.5 1717815600000000000",
+                ),
+                (
+                    "all field types",
+                    Point::builder("m")
+                        .field("f", 1.0)
+                        .field("i", -2i64)
+                        .field("u", u64::MAX)
+                        .field("b", false)
+                        .field("s", "on")
+                        .build()
+                        .unwrap(),
+                    "m b=false,f=1,i=-2i,s=\"on\",u=9223372036854775807i",
+                ),
+                (
+                    "escaping",
+                    Point::builder("my measurement,x")
+                        .tag("tag=key", "a,b c")
+                        .field("field key", r#"say "hi" \o/"#)
+                        .build()
+                        .unwrap(),
+                    r#"my\ measurement\,x,tag\=key=a\,b\ c field\ key="say \"hi\" \\o/""#,
+                ),
+                (
+                    "line breaks",
+                    Point::builder("m")
+                        .tag("section", "洋室\n２")
+                        .field("s", "a\r\nb")
+                        .build()
+                        .unwrap(),
+                    r#"m,section=洋室\n２ s="a\r\nb""#,
+                ),
+            ];
+
+            for (name, point, expected) in test_cases {
+                assert_eq!(encode(&point).unwrap(), expected, "case: {}", name);
+            }
+        }
+
+        #[test]
+        fn test_encode_round_trips_through_parser() {
+            let points = vec![
+                Point::builder("my measurement")
+                    .tag("tag=key", "a,b c")
+                    .field("text", r#"say "hi""#)
+                    .field("value", 1.5)
+                    .timestamp(42)
+                    .build()
+                    .unwrap(),
+                Point::builder("power")
+                    .field("value", 7i64)
+                    .build()
+                    .unwrap(),
+            ];
+
+            let parsed = parse_line_protocol(&encode_batch(&points).join("\n")).unwrap();
+
+            assert_eq!(parsed.len(), 2);
+            assert_eq!(parsed[0].measurement, "my measurement");
+            assert_eq!(parsed[0].tag("tag=key"), Some("a,b c"));
+            assert_eq!(parsed[0].timestamp, Some(42));
+            assert_eq!(parsed[1].to_string(), "power value=7i");
+        }
+
+        #[test]
+        fn test_encode_batch_leaves_out_invalid_points() {
+            let points = vec![
+                Point::builder("power")
+                    .field("value", f64::NAN)
+                    .build()
+                    .unwrap(),
+                Point::builder("power")
+                    .field("value", 7i64)
+                    .build()
+                    .unwrap(),
+            ];
+
+            assert_eq!(encode_batch(&points), vec!["power value=7i"]);
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[test]
+        fn test_encode_non_finite_floats() {
+            for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+                let point = Point::builder("climate")
+                    .field("humidity", 40.0)
+                    .field("value", value)
+                    .build()
+                    .unwrap();
+
+                let result = encode(&point);
+
+                assert!(
+                    matches!(result, Err(StorageError::InvalidDataPoint(ref message)) if message.starts_with("field 'value' of climate is")),
+                    "case: {}",
+                    value
+                );
+            }
+        }
+    }
+}
@@ -5,41 +5,99 @@
 //!
 //! # Architecture
 //!
-//! The application runs two parallel collection loops:
-//! - **Status collectors** (5-second interval): Real-time power and climate metrics
-//! - **Total collectors** (60-second interval): Daily aggregated consumption metrics
+//! Every collector runs as its own scheduled job:
+//! - **Status collectors** (power, climate; 5-second interval by default):
+//!   Real-time metrics
+//! - **Total collectors** (daily_total, circuit_daily_total; 60-second interval
+//!   by default): Daily aggregated consumption metrics
+//!
+//! Each job can get its own interval or cron schedule, timeout and enable flag.
 //!
 //! # Features
 //!
-//! - Automatic retry on task failure
-//! - Graceful shutdown on SIGTERM/SIGINT
-//! - Historical data backfill on startup
-//! - Configurable collection intervals
+//! - Restart of crashed tasks with exponential backoff, exiting non-zero on
+//!   a crash loop
+//! - Graceful shutdown on SIGTERM/SIGINT, draining in-flight writes
+//! - Configuration reload on SIGHUP without a restart
+//! - Backfill of missing daily totals on startup
+//! - Final daily totals collected again after midnight
+//! - Configurable per-collector schedules
 //! - Timeout protection for hung tasks
 
 mod aiseg;
+mod backfill;
 mod circuit_breaker;
+mod cli;
 mod collector;
 mod config;
 mod error;
+mod export;
+mod finalize;
 mod influxdb;
+mod line_protocol;
 mod model;
+mod oneshot;
+mod probe;
+mod scheduler;
+mod sink;
+mod supervisor;
 
+// Spawned in-process by tests; also built as the `aiseg2-simulator` binary
+#[cfg(test)]
+#[allow(dead_code)]
+mod simulator;
 #[cfg(test)]
 mod test_utils;
 
+use crate::backfill::{BackfillOptions, Checkpoint, FinalizedDays, RangeBackfill, Watermark};
 use crate::circuit_breaker::{CircuitBreaker, CircuitBreakerConfig as CircuitConfig};
+use crate::cli::{
+    BackfillArgs, BackfillCollector, Cli, CollectArgs, Command, ConfigCommand, ExportArgs,
+    OnceArgs, ProbeArgs,
+};
 use crate::collector::circuit_protected::CircuitProtectedCollector;
-use crate::model::{batch_collect_metrics, MetricCollector};
-use chrono::{Local, NaiveTime};
+use crate::error::{ConfigError, StorageError};
+use crate::finalize::FinalizeOptions;
+use crate::model::{batch_collect_metrics, Measurement, MetricCollector};
+use crate::scheduler::Job;
+use crate::sink::{Handover, ReloadableSink, Sink};
+use crate::supervisor::{Decision, RestartPolicy, RestartStats, Supervisor};
+use chrono::{DateTime, Local};
+use clap::Parser;
+use std::collections::{BTreeMap, HashMap};
 use std::future::IntoFuture;
-use std::ops::Sub;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::signal::ctrl_c;
 use tokio::signal::unix::{signal, SignalKind};
-use tokio::task::JoinError;
+use tokio::task::{self, JoinError, JoinSet};
 use tokio::time;
-use tokio::time::{sleep, Duration};
+use tokio::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::reload;
+
+/// A shared set of collectors run together in one collection task.
+type Collectors = Arc<Vec<Box<dyn MetricCollector>>>;
+
+/// Collectors with the names their settings are configured under.
+type NamedCollectors = Vec<(&'static str, Arc<dyn MetricCollector>)>;
+
+/// Names collectors are scheduled and configured under, e.g.
+/// `COLLECTOR_POWER_INTERVAL_SEC`.
+const POWER: &str = "power";
+const CLIMATE: &str = "climate";
+const DAILY_TOTAL: &str = "daily_total";
+const CIRCUIT_DAILY_TOTAL: &str = "circuit_daily_total";
+
+/// Collectors of daily totals, which run at the total interval by default
+/// and are also used to backfill and finalize past days.
+const TOTAL_COLLECTORS: [&str; 2] = [DAILY_TOTAL, CIRCUIT_DAILY_TOTAL];
+
+/// Every collector, in the order they are created.
+const COLLECTORS: [&str; 4] = [POWER, CLIMATE, DAILY_TOTAL, CIRCUIT_DAILY_TOTAL];
 
 /// Application entry point.
 ///
@@ -47,129 +105,939 @@ use tokio::time::{sleep, Duration};
 /// with signal handling for graceful shutdown.
 #[tokio::main]
 async fn main() {
-    let app_config = config::load_app_config().expect("Failed to load AppConfig");
+    let cli = Cli::parse();
+    let config_file = cli
+        .config
+        .or_else(|| std::env::var_os("CONFIG_FILE").map(PathBuf::from));
+    if let Some(path) = &config_file {
+        config::use_config_vars(config::read_config_file(path).unwrap_or_else(|e| exit_invalid(e)));
+    }
+
+    match cli.command {
+        Some(Command::Config(ConfigCommand::Check)) => {
+            run_config_check();
+            return;
+        }
+        Some(Command::Export(args)) => {
+            run_export(args);
+            return;
+        }
+        Some(Command::Backfill(args)) => {
+            run_backfill(args).await;
+            return;
+        }
+        Some(Command::Once(args)) => {
+            run_once(args).await;
+            return;
+        }
+        Some(Command::Collect(args)) => {
+            run_collect(args).await;
+            return;
+        }
+        Some(Command::Probe(args)) => {
+            run_probe(args).await;
+            return;
+        }
+        Some(Command::Run) | None => {}
+    }
+
+    let app_config = config::load_app_config().unwrap_or_else(|e| exit_invalid(e));
+    let sink_config = config::load_sink_config().unwrap_or_else(|e| exit_invalid(e));
+    // The level can be changed by a reload, the output cannot
+    let (level_filter, log_level) =
+        reload::Layer::new(LevelFilter::from_level(app_config.log_level()));
+    let registry = tracing_subscriber::registry().with(level_filter);
+    // Keep stdout clean for line protocol when it is written there
+    if sink_config.selected().contains(&config::SinkKind::Stdout) {
+        registry
+            .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+            .init();
+    } else {
+        registry.with(tracing_subscriber::fmt::layer()).init();
+    }
+
+    let mut runtime = Runtime::load().unwrap_or_else(|e| exit_invalid(e));
+    let mut supervisor = Supervisor::new(
+        runtime.restart_policy.clone(),
+        runtime.jobs.iter().map(|job| job.name),
+    );
+    let (sinks, mut handover) =
+        sink::create_sinks_after(&sink_config, Some(supervisor.stats()), &Handover::default())
+            .unwrap_or_else(|e| exit_invalid(e));
+    let reloadable_sink = Arc::new(ReloadableSink::new(Box::new(sinks)));
+    let sink: Arc<dyn Sink> = reloadable_sink.clone();
+
+    // Cancelled on SIGTERM/SIGINT, so tasks stop starting new work
+    let cancel = CancellationToken::new();
+    // Cancelled when a reload replaces the jobs, and on shutdown
+    let mut jobs_cancel = cancel.child_token();
+    let mut background = JoinSet::new();
+    // Only enabled total collectors are backfilled and finalized
+    if let Some(total_collectors) = runtime.total_collectors() {
+        // Spawn background task to collect missing historical data
+        let collector_config = &runtime.collector_config;
+        let history = finalized_days_source(&sink_config);
+        let backfill_options = BackfillOptions {
+            initial_days: collector_config.total_initial_days,
+            max_lookback_days: collector_config.total_max_lookback_days,
+            measurements: runtime.total_measurements(),
+        };
+        let watermark = Watermark::new(&collector_config.total_watermark_path);
+        let backfill_sink = Arc::clone(&sink);
+        let backfill_cancel = cancel.clone();
+        background.spawn(async move {
+            backfill::backfill(
+                &total_collectors,
+                backfill_sink.as_ref(),
+                history.as_deref(),
+                &watermark,
+                &backfill_options,
+                Local::now().date_naive(),
+                &backfill_cancel,
+            )
+            .await;
+        });
+    }
+    runtime.spawn_finalize(&mut background, &sink, &jobs_cancel);
+
+    // Jobs of earlier configurations finish, but are not started again
+    let mut generation = 0;
+    let mut tasks = JoinSet::new();
+    let mut running = HashMap::new();
+    for index in 0..runtime.jobs.len() {
+        // Collect once at startup, then on the schedule's ticks
+        let id = runtime.spawn_job(&mut tasks, index, Local::now(), &sink, &jobs_cancel);
+        running.insert(id, (generation, index, runtime.jobs[index].name));
+    }
+
+    let mut sig_term = signal(SignalKind::terminate()).expect("Failed to register SIGTERM handler");
+    let mut sig_hup = signal(SignalKind::hangup()).expect("Failed to register SIGHUP handler");
+    // Set when a task crashed too often, so the process exits non-zero
+    let mut escalated = false;
+    tracing::info!("Running... Press Ctrl-C or send SIGTERM to terminate, send SIGHUP to reload.");
+    // Main event loop with signal handling and task supervision
+    loop {
+        tokio::select! {
+            // Handle SIGTERM for graceful shutdown in containers
+            _ = sig_term.recv() => {
+                tracing::info!("Received SIGTERM. Shutting down...");
+                break;
+            }
+            // Handle Ctrl-C for manual termination
+            _ = ctrl_c() => {
+                tracing::info!("Received SIGINT. Shutting down...");
+                break;
+            }
+            // Re-read the configuration and swap in what it describes
+            _ = sig_hup.recv() => {
+                tracing::info!("Received SIGHUP. Reloading configuration...");
+                let reloaded =
+                    reload_config(
+                        config_file.as_deref(),
+                        &runtime,
+                        &reloadable_sink,
+                        &mut handover,
+                        supervisor.stats(),
+                    )
+                    .await;
+                match reloaded {
+                    Ok(reloaded) => {
+                        runtime = reloaded;
+                        if let Err(e) = log_level.reload(LevelFilter::from_level(runtime.log_level)) {
+                            tracing::warn!("Failed to change the log level: {}", e);
+                        }
+                        supervisor.set_policy(runtime.restart_policy.clone());
+
+                        // Pending collections of the old jobs return, running ones finish
+                        jobs_cancel.cancel();
+                        jobs_cancel = cancel.child_token();
+                        generation += 1;
+                        for (index, job) in runtime.jobs.iter().enumerate() {
+                            match job.schedule.next_tick(Local::now()) {
+                                Some(tick) => {
+                                    let id = runtime.spawn_job(&mut tasks, index, tick, &sink, &jobs_cancel);
+                                    running.insert(id, (generation, index, job.name));
+                                }
+                                None => tracing::warn!("Schedule of {} has no upcoming times.", job.name),
+                            }
+                        }
+                        runtime.spawn_finalize(&mut background, &sink, &jobs_cancel);
+                        tracing::info!("Configuration reloaded.");
+                    }
+                    Err(e) => tracing::error!(
+                        "Configuration reload failed, keeping the running configuration: {}",
+                        e
+                    ),
+                }
+            }
+            // Monitor collection tasks and restart each on completion or failure
+            Some(joined) = tasks.join_next_with_id() => {
+                let (id, result) = match joined {
+                    Ok((id, _)) => (id, Ok(())),
+                    Err(e) => (e.id(), Err(e)),
+                };
+                let (task_generation, index, name) =
+                    running.remove(&id).expect("Finished task belongs to a job");
+                let crashed = result.as_ref().is_err_and(|e| e.is_panic());
+                handle_task_result(name, result);
+                if task_generation != generation {
+                    continue;
+                }
+                let cache_stats = runtime.aiseg_client.cache_stats();
+                tracing::debug!(
+                    hits = cache_stats.hits,
+                    misses = cache_stats.misses,
+                    "AiSEG2 page cache statistics"
+                );
+                let backoff = match supervisor.task_finished(name, crashed, Instant::now()) {
+                    Decision::Restart { backoff } => backoff,
+                    Decision::Escalate => {
+                        escalated = true;
+                        break;
+                    }
+                };
+                // Ticks missed while collecting or backing off are skipped
+                let earliest = Local::now() + backoff;
+                match runtime.jobs[index].schedule.next_tick(earliest) {
+                    Some(tick) => {
+                        let id = runtime.spawn_job(&mut tasks, index, tick, &sink, &jobs_cancel);
+                        running.insert(id, (generation, index, name));
+                    }
+                    None => tracing::warn!("Schedule of {} has no upcoming times.", name),
+                }
+            }
+        }
+    }
+
+    cancel.cancel();
+    let timeout = runtime.shutdown_timeout;
+    tracing::info!(
+        "Draining in-flight collections and writes for up to {:?}...",
+        timeout
+    );
+    let stored = drain(tasks, background, sink.as_ref(), timeout).await;
+    if !stored {
+        tracing::error!("Shutdown complete, but collected points were lost.");
+    } else if escalated {
+        tracing::error!("Shutdown complete after a crash loop.");
+    } else {
+        tracing::info!("Shutdown complete.");
+    }
+    if !stored || escalated {
+        std::process::exit(1);
+    }
+}
+
+/// Settings that sinks are built from; a reload replaces the sinks only when
+/// one of them changed.
+const SINK_SETTINGS: [&str; 10] = [
+    "sinks",
+    "dry_run",
+    "buffer",
+    "influxdb",
+    "prometheus",
+    "mqtt",
+    "line_protocol",
+    "postgres",
+    "sqlite",
+    "otlp",
+];
+
+/// Collectors, schedules and settings built from one configuration.
+///
+/// A reload builds a new `Runtime` and replaces the running one as a whole.
+struct Runtime {
+    aiseg_client: Arc<aiseg::Client>,
+    collectors: Vec<(&'static str, Arc<dyn MetricCollector>)>,
+    collector_config: config::CollectorConfig,
+    jobs: Vec<Job>,
+    restart_policy: RestartPolicy,
+    log_level: tracing::Level,
+    shutdown_timeout: Duration,
+    /// Effective configuration, secrets included, to compare with a reload
+    effective: toml::Table,
+}
+
+impl Runtime {
+    /// Loads and validates the configuration and builds the collectors and
+    /// their schedules, without connecting to anything.
+    ///
+    /// # Returns
+    /// - `Ok(Runtime)` if the whole configuration is valid
+    /// - `Err(ConfigError)` with the first problem found
+    fn load() -> Result<Self, ConfigError> {
+        let effective = config::effective_config(&COLLECTORS, false)?;
+        let app_config = config::load_app_config()?;
+        let collector_config = config::load_collector_config()?;
+        let circuit_breaker_config = config::load_circuit_breaker_config()?;
+        let supervisor_config = config::load_supervisor_config()?;
+        let aiseg_client = Arc::new(aiseg::Client::new(config::load_aiseg_config()?));
+
+        // Convert circuit breaker config to internal format
+        let circuit_config = CircuitConfig {
+            failure_threshold: circuit_breaker_config.failure_threshold,
+            recovery_timeout: Duration::from_secs(circuit_breaker_config.recovery_timeout_seconds),
+            half_open_success_threshold: circuit_breaker_config.half_open_success_threshold,
+            half_open_failure_threshold: circuit_breaker_config.half_open_failure_threshold,
+        };
+        // Convert supervisor config to internal format
+        let restart_policy = RestartPolicy {
+            initial_backoff: Duration::from_millis(supervisor_config.initial_backoff_ms),
+            max_backoff: Duration::from_millis(supervisor_config.max_backoff_ms),
+            max_crashes: supervisor_config.max_crashes,
+            window: Duration::from_secs(supervisor_config.crash_window_sec),
+        };
+
+        let collectors = create_collectors(&aiseg_client, &circuit_config);
+        let jobs = create_jobs(&collectors, &collector_config)?;
+        if config::load_sink_config()?
+            .selected()
+            .contains(&config::SinkKind::Influxdb)
+        {
+            check_write_timeout(&jobs, config::load_influx_config()?.write_timeout_ms)?;
+        }
+        Ok(Self {
+            aiseg_client,
+            collectors,
+            collector_config,
+            jobs,
+            restart_policy,
+            log_level: app_config.log_level(),
+            shutdown_timeout: Duration::from_secs(app_config.shutdown_timeout_sec),
+            effective,
+        })
+    }
+
+    /// Whether `other` needs different sinks than this configuration.
+    fn sinks_differ(&self, other: &Runtime) -> bool {
+        SINK_SETTINGS
+            .iter()
+            .any(|key| self.effective.get(*key) != other.effective.get(*key))
+    }
+
+    /// Names of the enabled total collectors.
+    fn total_names(&self) -> Vec<&'static str> {
+        self.jobs
+            .iter()
+            .map(|job| job.name)
+            .filter(|name| TOTAL_COLLECTORS.contains(name))
+            .collect()
+    }
+
+    /// The enabled total collectors as one group, if any is enabled.
+    fn total_collectors(&self) -> Option<Collectors> {
+        let names = self.total_names();
+        (!names.is_empty()).then(|| collector_group(&self.collectors, &names))
+    }
+
+    /// Measurements of the enabled total collectors.
+    fn total_measurements(&self) -> Vec<String> {
+        self.total_names()
+            .iter()
+            .map(|name| total_measurement(name).to_string())
+            .collect()
+    }
+
+    /// Spawns the collection of job `index` at `tick`.
+    fn spawn_job(
+        &self,
+        tasks: &mut JoinSet<bool>,
+        index: usize,
+        tick: DateTime<Local>,
+        sink: &Arc<dyn Sink>,
+        cancel: &CancellationToken,
+    ) -> task::Id {
+        let job = &self.jobs[index];
+        tasks
+            .spawn(create_collect_task(
+                Arc::clone(sink),
+                Arc::clone(&job.collectors),
+                tick,
+                self.collector_config.stamp_tick_time,
+                job.name,
+                job.timeout_seconds,
+                cancel.clone(),
+            ))
+            .id()
+    }
+
+    /// Spawns the task collecting each day's final totals after midnight,
+    /// if any total collector is enabled.
+    fn spawn_finalize(
+        &self,
+        background: &mut JoinSet<()>,
+        sink: &Arc<dyn Sink>,
+        cancel: &CancellationToken,
+    ) {
+        if let Some(total_collectors) = self.total_collectors() {
+            background.spawn(finalize::finalize_totals(
+                total_collectors,
+                Arc::clone(sink),
+                FinalizeOptions {
+                    passes: self.collector_config.total_finalize_passes,
+                    grace: Duration::from_secs(self.collector_config.total_finalize_grace_sec),
+                    measurements: self.total_measurements(),
+                },
+                cancel.clone(),
+            ));
+        }
+    }
+}
+
+/// Reads the configuration again and builds what it describes.
+///
+/// The configuration file, if any, and secret files are read again;
+/// environment variables cannot change while the process runs. Nothing that
+/// runs is touched unless the whole configuration is valid, and `sink` only
+/// gets new backends if their settings changed. They are created while the
+/// running ones still receive writes, taking over what `handover` describes,
+/// and keep running ones in place if they cannot be created.
+///
+/// # Returns
+/// - `Ok(Runtime)` to replace `running` with
+/// - `Err(ConfigError)` if the configuration is invalid or the new sinks
+///   cannot be created; the previous configuration stays in effect
+async fn reload_config(
+    config_file: Option<&Path>,
+    running: &Runtime,
+    sink: &ReloadableSink,
+    handover: &mut Handover,
+    restarts: Arc<RestartStats>,
+) -> Result<Runtime, ConfigError> {
+    let previous = match config_file {
+        Some(path) => Some(config::use_config_vars(config::read_config_file(path)?)),
+        None => None,
+    };
+    let restore = |previous: Option<BTreeMap<String, String>>| {
+        if let Some(previous) = previous {
+            config::use_config_vars(previous);
+        }
+    };
+
+    let reloaded = match Runtime::load() {
+        Ok(reloaded) => reloaded,
+        Err(e) => {
+            restore(previous);
+            return Err(e);
+        }
+    };
+    if !reloaded.sinks_differ(running) {
+        tracing::info!("Sink settings unchanged, keeping the sinks.");
+        return Ok(reloaded);
+    }
+
+    let built = config::load_sink_config()
+        .and_then(|sink_config| sink::create_sinks_after(&sink_config, Some(restarts), handover));
+    let (sinks, replacing) = match built {
+        Ok(built) => built,
+        Err(e) => {
+            restore(previous);
+            return Err(e);
+        }
+    };
+    sink.replace(Box::new(sinks)).await;
+    *handover = replacing;
+    tracing::info!("Sinks replaced.");
+    Ok(reloaded)
+}
+
+/// Finishes in-flight work after shutdown was requested and flushes `sink`.
+///
+/// Collection tasks waiting for their next tick return at once, running ones
+/// finish their collection and write. Whatever still runs when `timeout`
+/// has passed is aborted; the flush must complete within the same deadline.
+///
+/// # Returns
+/// Whether every collected point was stored or durably buffered
+async fn drain(
+    mut tasks: JoinSet<bool>,
+    mut background: JoinSet<()>,
+    sink: &dyn Sink,
+    timeout: Duration,
+) -> bool {
+    let deadline = time::Instant::now() + timeout;
+    let mut stored = true;
+
+    let finished = time::timeout_at(deadline, async {
+        while let Some(result) = tasks.join_next().await {
+            // A panicked collection lost its points
+            stored &= result.unwrap_or(false);
+        }
+        while background.join_next().await.is_some() {}
+    })
+    .await;
+    if finished.is_err() {
+        if !tasks.is_empty() {
+            tracing::error!(
+                "{} collections did not finish before the shutdown deadline",
+                tasks.len()
+            );
+            stored = false;
+        }
+        // Unfinished background days are collected again on the next start
+        tasks.abort_all();
+        background.abort_all();
+    }
+
+    match time::timeout_at(deadline, sink.flush()).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => {
+            tracing::error!("Failed to flush {}: {}", sink.name(), e);
+            stored = false;
+        }
+        Err(_) => {
+            tracing::error!(
+                "Flushing {} did not finish before the shutdown deadline",
+                sink.name()
+            );
+            stored = false;
+        }
+    }
+    stored
+}
+
+/// Runs the `config check` subcommand and exits with status 1 if the
+/// configuration is invalid.
+///
+/// Collector schedules are also built, which checks cron expressions and
+/// timeouts, but nothing connects to AiSEG2 or the sinks.
+fn run_config_check() {
+    let checked = Runtime::load().and_then(|_| config::effective_config(&COLLECTORS, true));
+
+    match checked {
+        Ok(effective) => print!("{}", effective),
+        Err(e) => exit_invalid(e),
+    }
+}
+
+/// Reports an invalid configuration and exits with status 1.
+fn exit_invalid(e: ConfigError) -> ! {
+    eprintln!("Invalid configuration: {}", e);
+    std::process::exit(1);
+}
+
+/// Loads the sinks of a one-off command, leaving out those that do not store
+/// history.
+///
+/// Prometheus and MQTT belong to the running forwarder: a second Prometheus
+/// endpoint cannot bind its port, and MQTT would publish past values as the
+/// current state.
+///
+/// # Returns
+/// - `Ok(SinkConfig)` with at least one sink, or a dry run with `dry_run`
+/// - `Err(ConfigError)` if `SINKS` is invalid or lists no sink that stores
+///   history
+fn load_storage_sink_config(dry_run: bool) -> Result<config::SinkConfig, ConfigError> {
+    let mut sink_config = config::load_sink_config()?;
+    sink_config.dry_run |= dry_run;
+    let skipped = sink_config.retain_storage();
+    if !skipped.is_empty() && !sink_config.dry_run {
+        let names: Vec<String> = skipped.iter().map(ToString::to_string).collect();
+        eprintln!(
+            "Not writing to {}: only the running forwarder publishes to them",
+            names.join(", ")
+        );
+    }
+    if sink_config.selected().is_empty() {
+        return Err(ConfigError::invalid(
+            "SINKS",
+            "none of the sinks stores history (influxdb, postgres, sqlite, file, stdout)",
+        ));
+    }
+    Ok(sink_config)
+}
+
+/// Logs to stderr at the configured level, so the output of one-off
+/// commands on stdout stays clean.
+fn init_stderr_tracing() {
+    let app_config = config::load_app_config().unwrap_or_else(|e| exit_invalid(e));
     tracing_subscriber::fmt()
         .with_max_level(app_config.log_level())
+        .with_writer(std::io::stderr)
         .init();
+}
+
+/// Builds the collectors of the one-off commands, all or only the enabled
+/// ones, and the timeout of one collection.
+///
+/// Only the AiSEG2 and collector settings are needed, not the sinks. The
+/// collectors have no circuit breakers, which would hide their errors.
+fn load_collectors(enabled_only: bool) -> Result<(NamedCollectors, Duration), ConfigError> {
+    let collector_config = config::load_collector_config()?;
+    let aiseg_client = Arc::new(aiseg::Client::new(config::load_aiseg_config()?));
+    let mut collectors: NamedCollectors = Vec::new();
+    for (name, _, collector) in create_unprotected_collectors(&aiseg_client) {
+        if !enabled_only || config::load_collector_schedule_config(name)?.enabled {
+            collectors.push((name, Arc::from(collector)));
+        }
+    }
+    Ok((
+        collectors,
+        Duration::from_secs(collector_config.task_timeout_seconds),
+    ))
+}
+
+/// Prints collected points to stdout as line protocol or JSON. Failed
+/// collectors go to stderr, unless they are part of the JSON.
+fn print_collection(collection: &oneshot::Collection, json: bool) {
+    if json {
+        println!("{:#}", collection.to_json());
+    } else {
+        print!("{}", collection.to_line_protocol());
+        for failure in &collection.failures {
+            eprintln!("Collector {} failed: {}", failure.collector, failure.error);
+        }
+    }
+}
+
+/// Runs the `once` subcommand and exits with status 1 if a collector failed
+/// or, with `--write`, the points could not be stored.
+async fn run_once(args: OnceArgs) {
+    init_stderr_tracing();
+    let (collectors, timeout) = load_collectors(true).unwrap_or_else(|e| exit_invalid(e));
+
+    let collection = oneshot::collect(&collectors, Local::now(), timeout).await;
+    print_collection(&collection, args.json);
 
-    let collector_config =
-        Arc::new(config::load_collector_config().expect("Failed to load CollectorConfig"));
-    let circuit_breaker_config =
-        config::load_circuit_breaker_config().expect("Failed to load CircuitBreakerConfig");
-    let influx_config = config::load_influx_config().expect("Failed to load InfluxConfig");
-    let influx_client = Arc::new(influxdb::Client::new(influx_config));
+    let mut stored = true;
+    if args.write {
+        let sink_config = load_storage_sink_config(false).unwrap_or_else(|e| exit_invalid(e));
+        let sink = sink::create_sinks(&sink_config, None).unwrap_or_else(|e| exit_invalid(e));
+        if let Err(e) = sink.write(&collection.points).await {
+            eprintln!("Failed to write points: {}", e);
+            stored = false;
+        }
+        if let Err(e) = sink.flush().await {
+            eprintln!("Failed to flush sinks: {}", e);
+            stored = false;
+        }
+    }
+    if !collection.ok() || !stored {
+        std::process::exit(1);
+    }
+}
 
-    let aiseg_config = config::load_aiseg_config().expect("Failed to load AisegConfig");
+/// Runs the `collect` subcommand and exits with status 1 if the collector
+/// failed.
+async fn run_collect(args: CollectArgs) {
+    init_stderr_tracing();
+    let timestamp = match args.date {
+        None => Local::now(),
+        Some(date) if TOTAL_COLLECTORS.contains(&args.collector.as_str()) => {
+            match backfill::local_midnight(date) {
+                Some(timestamp) => timestamp,
+                None => {
+                    eprintln!("Collect failed: {} has no midnight in local time", date);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(_) => {
+            eprintln!(
+                "Collect failed: --date only applies to {}",
+                TOTAL_COLLECTORS.join(" and ")
+            );
+            std::process::exit(1);
+        }
+    };
+    let (collectors, timeout) = load_collectors(false).unwrap_or_else(|e| exit_invalid(e));
+    let collectors: Vec<_> = collectors
+        .into_iter()
+        .filter(|(name, _)| *name == args.collector)
+        .collect();
+
+    let collection = oneshot::collect(&collectors, timestamp, timeout).await;
+    print_collection(&collection, args.json);
+    if !collection.ok() {
+        std::process::exit(1);
+    }
+}
+
+/// Runs the `probe` subcommand and exits with status 1 if AiSEG2 or
+/// InfluxDB could not be reached or refused the credentials.
+///
+/// InfluxDB is only probed if it is one of the configured sinks.
+async fn run_probe(args: ProbeArgs) {
+    init_stderr_tracing();
+    let configs = config::load_aiseg_config().and_then(|aiseg_config| {
+        let sink_config = config::load_sink_config()?;
+        let influx_config = if sink_config.selected().contains(&config::SinkKind::Influxdb) {
+            Some(config::load_influx_config()?)
+        } else {
+            None
+        };
+        Ok((aiseg_config, influx_config))
+    });
+    let (aiseg_config, influx_config) = configs.unwrap_or_else(|e| exit_invalid(e));
+    let url = aiseg_config.url.clone();
+    let aiseg_client = Arc::new(aiseg::Client::new(aiseg_config));
+
+    let report = probe::probe(&url, &aiseg_client, influx_config).await;
+    if args.json {
+        println!("{:#}", serde_json::json!(report));
+    } else {
+        print!("{}", report);
+    }
+    if !report.ok() {
+        std::process::exit(1);
+    }
+}
+
+/// Runs the `export` subcommand and exits with status 1 on failure.
+fn run_export(args: ExportArgs) {
+    let database = match args.database {
+        Some(database) => database,
+        None => config::load_sqlite_config()
+            .unwrap_or_else(|e| exit_invalid(e))
+            .path
+            .into(),
+    };
+    let options = export::ExportOptions {
+        database,
+        from: args.from,
+        to: args.to.unwrap_or(args.from),
+        format: args.format,
+        output: args.output,
+        measurements: args.measurements,
+    };
+
+    match export::export(&options) {
+        Ok(exported) => {
+            for file in exported {
+                println!("{} ({} rows)", file.path.display(), file.rows);
+            }
+        }
+        Err(e) => {
+            eprintln!("Export failed: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Runs the `backfill` subcommand and exits with status 1 if a day failed.
+///
+/// Progress goes to stderr, so a dry run prints only line protocol to stdout.
+async fn run_backfill(args: BackfillArgs) {
+    init_stderr_tracing();
+
+    let to = args.to.unwrap_or(args.from);
+    if to < args.from {
+        eprintln!(
+            "Backfill failed: --to {} is before --from {}",
+            to, args.from
+        );
+        std::process::exit(1);
+    }
+    let sink_config = load_storage_sink_config(args.dry_run).unwrap_or_else(|e| exit_invalid(e));
+    let sink = sink::create_sinks(&sink_config, None).unwrap_or_else(|e| exit_invalid(e));
+    let aiseg_config = config::load_aiseg_config().unwrap_or_else(|e| exit_invalid(e));
     let aiseg_client = Arc::new(aiseg::Client::new(aiseg_config));
 
-    // Convert circuit breaker config to internal format
-    let circuit_config = CircuitConfig {
-        failure_threshold: circuit_breaker_config.failure_threshold,
-        recovery_timeout: Duration::from_secs(circuit_breaker_config.recovery_timeout_seconds),
-        half_open_success_threshold: circuit_breaker_config.half_open_success_threshold,
-        half_open_failure_threshold: circuit_breaker_config.half_open_failure_threshold,
+    let mut kinds: Vec<BackfillCollector> = Vec::new();
+    for kind in args.collectors {
+        if !kinds.contains(&kind) {
+            kinds.push(kind);
+        }
+    }
+    let collectors: Vec<Box<dyn MetricCollector>> = kinds
+        .iter()
+        .map(|kind| -> Box<dyn MetricCollector> {
+            match kind {
+                BackfillCollector::Daily => Box::new(aiseg::DailyTotalMetricCollector::new(
+                    Arc::clone(&aiseg_client),
+                )),
+                BackfillCollector::Circuit => Box::new(
+                    aiseg::CircuitDailyTotalMetricCollector::new(Arc::clone(&aiseg_client)),
+                ),
+            }
+        })
+        .collect();
+    let range = RangeBackfill {
+        from: args.from,
+        to,
+        measurements: kinds
+            .iter()
+            .map(|kind| kind.measurement().to_string())
+            .collect(),
+        delay: Duration::from_millis(args.delay_ms),
     };
+    // A dry run stores nothing, so there is nothing to resume
+    let checkpoint = (!sink_config.dry_run).then(|| Checkpoint::new(args.state));
+
+    let report = backfill::backfill_range(
+        &collectors,
+        &sink,
+        &range,
+        checkpoint.as_ref(),
+        &mut |progress| {
+            eprintln!(
+                "[{}/{}] {} {}",
+                progress.done,
+                progress.total,
+                progress.day,
+                if progress.stored { "stored" } else { "failed" }
+            )
+        },
+    )
+    .await;
+
+    eprintln!(
+        "Backfilled {} of {} days",
+        report.collected.len(),
+        report.checked
+    );
+    if !report.failed.is_empty() {
+        let failed: Vec<String> = report.failed.iter().map(ToString::to_string).collect();
+        eprintln!("Backfill failed for {}", failed.join(", "));
+        if checkpoint.is_some() {
+            eprintln!("Run the same command again to resume");
+        }
+        std::process::exit(1);
+    }
+}
+
+/// Returns the InfluxDB sink's client when it can answer which days are
+/// recorded, which needs the v2 API.
+fn finalized_days_source(sink_config: &config::SinkConfig) -> Option<Arc<dyn FinalizedDays>> {
+    if !sink_config.selected().contains(&config::SinkKind::Influxdb) {
+        return None;
+    }
+    let influx_config = config::load_influx_config().unwrap_or_else(|e| exit_invalid(e));
+    (influx_config.api_version == config::InfluxApiVersion::V2)
+        .then(|| Arc::new(influxdb::Client::new(influx_config)) as Arc<dyn FinalizedDays>)
+}
 
-    // Helper to create circuit-protected collectors
-    let create_protected_collector =
-        |name: &str, collector: Box<dyn MetricCollector>| -> Box<dyn MetricCollector> {
-            let circuit_breaker = CircuitBreaker::new(name.to_string(), circuit_config.clone());
-            Box::new(CircuitProtectedCollector::new(
-                name.to_string(),
+/// Builds every collector, each wrapped in its own circuit breaker.
+///
+/// # Returns
+///
+/// The collectors with the names their schedules are configured under:
+/// - Status collectors (power, climate): real-time metrics
+/// - Total collectors (daily_total, circuit_daily_total): daily aggregated
+///   consumption metrics
+fn create_collectors(
+    aiseg_client: &Arc<aiseg::Client>,
+    circuit_config: &CircuitConfig,
+) -> NamedCollectors {
+    create_unprotected_collectors(aiseg_client)
+        .into_iter()
+        .map(|(name, breaker_name, collector)| {
+            let circuit_breaker =
+                CircuitBreaker::new(breaker_name.to_string(), circuit_config.clone());
+            let protected: Arc<dyn MetricCollector> = Arc::new(CircuitProtectedCollector::new(
+                breaker_name.to_string(),
                 Arc::from(collector),
                 circuit_breaker,
-            ))
-        };
+            ));
+            (name, protected)
+        })
+        .collect()
+}
 
-    // Initialize collectors for daily totals (60-second interval)
-    let total_collectors: Arc<Vec<Box<dyn MetricCollector>>> = Arc::new(vec![
-        create_protected_collector(
+/// Builds every collector without a circuit breaker, with the names their
+/// schedules and circuit breakers go by.
+fn create_unprotected_collectors(
+    aiseg_client: &Arc<aiseg::Client>,
+) -> Vec<(&'static str, &'static str, Box<dyn MetricCollector>)> {
+    vec![
+        (
+            POWER,
+            "PowerMetricCollector",
+            Box::new(aiseg::PowerMetricCollector::new(Arc::clone(aiseg_client))),
+        ),
+        (
+            CLIMATE,
+            "ClimateMetricCollector",
+            Box::new(aiseg::ClimateMetricCollector::new(Arc::clone(aiseg_client))),
+        ),
+        (
+            DAILY_TOTAL,
             "DailyTotalMetricCollector",
             Box::new(aiseg::DailyTotalMetricCollector::new(Arc::clone(
-                &aiseg_client,
+                aiseg_client,
             ))),
         ),
-        create_protected_collector(
+        (
+            CIRCUIT_DAILY_TOTAL,
             "CircuitDailyTotalMetricCollector",
             Box::new(aiseg::CircuitDailyTotalMetricCollector::new(Arc::clone(
-                &aiseg_client,
+                aiseg_client,
             ))),
         ),
-    ]);
+    ]
+}
 
-    // Initialize collectors for real-time status (5-second interval)
-    let status_collectors: Arc<Vec<Box<dyn MetricCollector>>> = Arc::new(vec![
-        create_protected_collector(
-            "PowerMetricCollector",
-            Box::new(aiseg::PowerMetricCollector::new(Arc::clone(&aiseg_client))),
-        ),
-        create_protected_collector(
-            "ClimateMetricCollector",
-            Box::new(aiseg::ClimateMetricCollector::new(Arc::clone(
-                &aiseg_client,
-            ))),
-        ),
-    ]);
-
-    // Spawn background task to collect historical data
-    tokio::spawn(collect_past_total(
-        Arc::clone(&total_collectors),
-        Arc::clone(&influx_client),
-        collector_config.total_initial_days,
-    ));
-
-    // Factory functions for creating collector tasks
-    // These allow easy task recreation after failures
-    let create_collect_status_task = || -> tokio::task::JoinHandle<()> {
-        let config = Arc::clone(&collector_config);
-        tokio::spawn(create_collect_task(
-            Arc::clone(&influx_client),
-            Arc::clone(&status_collectors),
-            Duration::from_secs(config.status_interval_sec),
-            "status_collectors",
-            config.task_timeout_seconds,
-        ))
-    };
-    let create_collect_total_task = || -> tokio::task::JoinHandle<()> {
-        let config = Arc::clone(&collector_config);
-        tokio::spawn(create_collect_task(
-            Arc::clone(&influx_client),
-            Arc::clone(&total_collectors),
-            Duration::from_secs(config.total_interval_sec),
-            "total_collectors",
-            config.task_timeout_seconds,
-        ))
-    };
-    let mut collect_status_task = create_collect_status_task();
-    let mut collect_total_task = create_collect_total_task();
+/// Gathers the collectors called `names`, sharing their circuit breakers with
+/// the scheduled jobs.
+fn collector_group(
+    collectors: &[(&'static str, Arc<dyn MetricCollector>)],
+    names: &[&str],
+) -> Collectors {
+    Arc::new(
+        collectors
+            .iter()
+            .filter(|(name, _)| names.contains(name))
+            .map(|(_, collector)| Box::new(Arc::clone(collector)) as Box<dyn MetricCollector>)
+            .collect(),
+    )
+}
 
-    let mut sig_term = signal(SignalKind::terminate()).expect("Failed to register SIGTERM handler");
-    tracing::info!("Running... Press Ctrl-C or send SIGTERM to terminate.");
-    // Main event loop with signal handling and task supervision
-    loop {
-        tokio::select! {
-            // Handle SIGTERM for graceful shutdown in containers
-            _ = sig_term.recv() => {
-                tracing::info!("Received SIGTERM. Exiting...");
-                break;
-            }
-            // Handle Ctrl-C for manual termination
-            _ = ctrl_c() => {
-                tracing::info!("Received SIGINT. Exiting...");
-                break;
-            }
-            // Monitor status collector task and restart on failure
-            result = &mut collect_status_task => {
-                handle_task_result("status_collectors", result);
-                collect_status_task = create_collect_status_task();
-            }
-            // Monitor total collector task and restart on failure
-            result = &mut collect_total_task => {
-                handle_task_result("total_collectors", result);
-                collect_total_task = create_collect_total_task();
+/// Measurement written by the total collector called `name`.
+fn total_measurement(name: &str) -> Measurement {
+    if name == CIRCUIT_DAILY_TOTAL {
+        Measurement::CircuitDailyTotal
+    } else {
+        Measurement::DailyTotal
+    }
+}
+
+/// Builds a job for every enabled collector from its `COLLECTOR_<NAME>_`
+/// schedule settings.
+///
+/// Status collectors default to the status interval and total collectors to
+/// the total interval; all default to the task timeout.
+fn create_jobs(
+    collectors: &[(&'static str, Arc<dyn MetricCollector>)],
+    collector_config: &config::CollectorConfig,
+) -> Result<Vec<Job>, ConfigError> {
+    let mut jobs = Vec::new();
+    for (name, collector) in collectors {
+        let default_interval_sec = if TOTAL_COLLECTORS.contains(name) {
+            collector_config.total_interval_sec
+        } else {
+            collector_config.status_interval_sec
+        };
+        let schedule_config = config::load_collector_schedule_config(name)?;
+        match Job::configure(
+            name,
+            Arc::clone(collector),
+            &schedule_config,
+            default_interval_sec,
+            collector_config.task_timeout_seconds,
+        )? {
+            Some(job) => {
+                tracing::info!("Collector {} runs {}", name, job.schedule);
+                jobs.push(job);
             }
+            None => tracing::info!("Collector {} is disabled.", name),
         }
     }
+    Ok(jobs)
+}
+
+/// Rejects an InfluxDB write timeout that is not shorter than the timeout of
+/// every job, since each job writes its points within its own timeout.
+fn check_write_timeout(jobs: &[Job], write_timeout_ms: u64) -> Result<(), ConfigError> {
+    match jobs.iter().min_by_key(|job| job.timeout_seconds) {
+        Some(job) if write_timeout_ms >= job.timeout_seconds * 1000 => Err(ConfigError::invalid(
+            "INFLUXDB_WRITE_TIMEOUT_MS",
+            format!(
+                "{} is not shorter than the {} second timeout of collector '{}'",
+                write_timeout_ms, job.timeout_seconds, job.name
+            ),
+        )),
+        _ => Ok(()),
+    }
 }
 
 /// Wraps a future with a timeout to prevent tasks from hanging indefinitely.
@@ -182,68 +1050,115 @@ async fn main() {
 /// # Behavior
 ///
 /// - Timeout duration is configurable via the timeout_seconds parameter
-/// - Logs an error if the task times out but doesn't propagate the error
+/// - Logs an error if the task times out and returns `None` instead of
+///   propagating the error
 /// - Used to prevent collector tasks from blocking the main loop
-async fn with_timeout<F>(task_name: &'static str, future: F, timeout_seconds: u64)
+async fn with_timeout<F>(
+    task_name: &'static str,
+    future: F,
+    timeout_seconds: u64,
+) -> Option<F::Output>
 where
     F: IntoFuture,
 {
     let timeout_duration = Duration::from_secs(timeout_seconds);
 
     match time::timeout(timeout_duration, future).await {
-        Ok(_) => {}
-        Err(_) => tracing::error!("Task {} timed out.", task_name),
+        Ok(output) => Some(output),
+        Err(_) => {
+            tracing::error!("Task {} timed out.", task_name);
+            None
+        }
     }
 }
 
 /// Creates and executes a single metric collection cycle.
 ///
 /// This function:
-/// 1. Collects metrics from all provided collectors
-/// 2. Writes the metrics to InfluxDB
-/// 3. Sleeps for the specified interval
+/// 1. Waits until the scheduled tick
+/// 2. Collects metrics from all provided collectors
+/// 3. Writes the metrics to the configured sinks
 ///
 /// # Arguments
 ///
-/// * `influx_client` - Shared InfluxDB client for writing metrics
+/// * `sink` - Shared sink for writing metrics
 /// * `collectors` - List of metric collectors to execute
-/// * `interval` - Duration to sleep after collection completes
+/// * `tick` - When to collect
+/// * `stamp_tick_time` - Timestamp the points with `tick` instead of the
+///   collection time, including points that would have none
 /// * `task_name` - Name of the task for logging purposes
+/// * `cancel` - Shutdown signal; a task still waiting for its tick returns
+///   without collecting
+///
+/// # Returns
+///
+/// Whether the collected points were stored, or nothing was collected
 ///
 /// # Error Handling
 ///
 /// - Collection errors from individual collectors are logged but don't stop other collectors
-/// - InfluxDB write errors are logged but don't crash the task
+/// - Sink write errors are logged but don't crash the task
 /// - The entire operation is wrapped in a timeout to prevent hanging
 async fn create_collect_task(
-    influx_client: Arc<influxdb::Client>,
+    sink: Arc<dyn Sink>,
     collectors: Arc<Vec<Box<dyn MetricCollector>>>,
-    interval: Duration,
+    tick: DateTime<Local>,
+    stamp_tick_time: bool,
     task_name: &'static str,
     timeout_seconds: u64,
-) {
+    cancel: CancellationToken,
+) -> bool {
+    tokio::select! {
+        _ = scheduler::sleep_until(tick) => {}
+        _ = cancel.cancelled() => return true,
+    }
+    let timestamp = if stamp_tick_time { tick } else { Local::now() };
     with_timeout(
         task_name,
         async {
-            let points = batch_collect_metrics(&collectors, Local::now()).await;
-
-            for point in &points {
-                tracing::debug!("{:?}", point);
-            }
-
-            match influx_client.write(points).await {
-                Ok(_) => tracing::info!("Successfully wrote points to InfluxDB ({})", task_name),
-                Err(e) => tracing::error!(
-                    "Failed to write points to InfluxDB ({}): {:?}",
-                    task_name,
-                    e
-                ),
+            match collect_and_write(sink.as_ref(), &collectors, timestamp, stamp_tick_time).await {
+                Ok(_) => {
+                    tracing::info!("Successfully wrote points ({})", task_name);
+                    true
+                }
+                Err(e) => {
+                    tracing::error!("Failed to write points ({}): {:?}", task_name, e);
+                    false
+                }
             }
         },
         timeout_seconds,
     )
-    .await;
-    sleep(interval).await;
+    .await
+    .unwrap_or(false)
+}
+
+/// Runs all collectors once at `timestamp` and writes the resulting points.
+///
+/// With `stamp_untimed`, points without a timestamp get `timestamp` instead
+/// of the time the sink stores them at.
+///
+/// Collector failures are logged and skipped by `batch_collect_metrics`, so
+/// only the write outcome is returned.
+async fn collect_and_write(
+    sink: &dyn Sink,
+    collectors: &Vec<Box<dyn MetricCollector>>,
+    timestamp: DateTime<Local>,
+    stamp_untimed: bool,
+) -> Result<(), StorageError> {
+    let mut points = batch_collect_metrics(collectors, timestamp).await;
+    if stamp_untimed {
+        let nanos = timestamp.timestamp_nanos_opt();
+        for point in points.iter_mut().filter(|point| point.timestamp.is_none()) {
+            point.timestamp = nanos;
+        }
+    }
+
+    for point in &points {
+        tracing::debug!("{:?}", point);
+    }
+
+    sink.write(&points).await
 }
 
 /// Handles the result of a tokio task, logging success or failure.
@@ -269,63 +1184,12 @@ fn handle_task_result(task_name: &str, result: Result<(), JoinError>) {
     }
 }
 
-/// Collects and stores historical daily total metrics.
-///
-/// This function runs once at startup to backfill historical data for the
-/// specified number of days. It's useful for populating graphs when the
-/// forwarder is first deployed or after downtime.
-///
-/// # Arguments
-///
-/// * `collectors` - Total metric collectors (daily aggregates)
-/// * `influx_client` - InfluxDB client for writing historical data
-/// * `days` - Number of past days to collect (1 = yesterday only)
-///
-/// # Behavior
-///
-/// - Iterates from `days` ago to yesterday (excludes today)
-/// - Each day's timestamp is normalized to midnight
-/// - Continues even if individual days fail
-/// - Logs progress for each day processed
-async fn collect_past_total(
-    collectors: Arc<Vec<Box<dyn MetricCollector>>>,
-    influx_client: Arc<influxdb::Client>,
-    days: u64,
-) {
-    tracing::info!("Inserting last {} days...", days);
-    for i in 1..=days {
-        let timestamp = match Local::now()
-            .sub(Duration::from_secs(i * 24 * 60 * 60))
-            .with_time(NaiveTime::default())
-            .single()
-        {
-            Some(ts) => ts,
-            None => {
-                tracing::error!("Failed to set timestamp to midnight for day {} days ago", i);
-                continue;
-            }
-        };
-        let points = batch_collect_metrics(&collectors, timestamp).await;
-
-        for point in &points {
-            tracing::debug!("{:?}", point);
-        }
-
-        match influx_client.write(points).await {
-            Ok(_) => tracing::info!(
-                "Successfully wrote points to InfluxDB: day={}",
-                timestamp.format("%Y-%m-%d")
-            ),
-            Err(e) => tracing::error!("Failed to write points to InfluxDB: {:?}", e),
-        }
-    }
-    tracing::info!("Finished inserting last {} days.", days);
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_utils::{config::test_influx_config, mocks::MockMetricCollector};
+    use crate::test_utils::config::test_influx_config;
+    use crate::test_utils::fake_influx::FakeInfluxDb;
+    use crate::test_utils::mocks::{MockMetricCollector, RecordingSink};
     use std::sync::atomic::{AtomicBool, Ordering};
     use std::sync::Arc;
     use tokio::time::Duration;
@@ -402,7 +1266,7 @@ mod tests {
         }
     }
 
-    mod collect_past_total {
+    mod create_collect_task {
         use super::*;
 
         #[tokio::test]
@@ -411,50 +1275,131 @@ mod tests {
             let collectors: Arc<Vec<Box<dyn MetricCollector>>> =
                 Arc::new(vec![Box::new(MockMetricCollector::new_success())]);
 
-            let influx_config = test_influx_config();
-            let influx_client = Arc::new(influxdb::Client::new(influx_config));
-
-            // We can't easily test the actual write without a real InfluxDB instance,
-            // so we'll just verify the function completes without panic
-            collect_past_total(collectors, influx_client, 1).await;
-        }
+            let fake_influx = FakeInfluxDb::start().await;
+            let influx_client = Arc::new(influxdb::Client::new(fake_influx.config()));
 
-        #[tokio::test]
-        async fn fails() {
-            // Mock failed collection
-            let collectors: Arc<Vec<Box<dyn MetricCollector>>> = Arc::new(vec![Box::new(
-                MockMetricCollector::new_failure("Mock collection failed"),
-            )]);
-
-            let influx_config = test_influx_config();
-            let influx_client = Arc::new(influxdb::Client::new(influx_config));
+            // Run once with minimal interval
+            create_collect_task(
+                influx_client,
+                collectors,
+                Local::now(),
+                false,
+                "test_task",
+                10,
+                CancellationToken::new(),
+            )
+            .await;
 
-            // Function should handle collection failures gracefully
-            collect_past_total(collectors, influx_client, 1).await;
+            assert_eq!(fake_influx.lines(), vec!["power,summary=test value=100i"]);
         }
-    }
-
-    mod create_collect_task {
-        use super::*;
 
         #[tokio::test]
-        async fn succeeds() {
-            // Mock successful collection and write
+        async fn test_stamps_points_with_tick() {
             let collectors: Arc<Vec<Box<dyn MetricCollector>>> =
                 Arc::new(vec![Box::new(MockMetricCollector::new_success())]);
+            let fake_influx = FakeInfluxDb::start().await;
+            let influx_client = Arc::new(influxdb::Client::new(fake_influx.config()));
+            let tick = Local::now() + chrono::Duration::milliseconds(100);
 
-            let influx_config = test_influx_config();
-            let influx_client = Arc::new(influxdb::Client::new(influx_config));
-
-            // Run once with minimal interval
-            create_collect_task(
+            let stored = create_collect_task(
                 influx_client,
                 collectors,
-                Duration::from_millis(1),
+                tick,
+                true,
                 "test_task",
                 10,
+                CancellationToken::new(),
             )
             .await;
+
+            assert!(stored);
+            // Waited for the tick before collecting
+            assert!(Local::now() >= tick);
+            assert_eq!(
+                fake_influx.lines(),
+                vec![format!(
+                    "power,summary=test value=100i {}",
+                    tick.timestamp_nanos_opt().unwrap()
+                )]
+            );
+        }
+
+        #[tokio::test]
+        async fn test_throttled_write_fits_task_timeout() {
+            // (Retry-After, maximum retry delay, whether the retry fits the
+            // default 4 second write timeout)
+            let test_cases = vec![("1", 2_000, true), ("10", 10_000, false)];
+
+            for (retry_after, retry_max_ms, stored) in test_cases {
+                let collectors: Arc<Vec<Box<dyn MetricCollector>>> =
+                    Arc::new(vec![Box::new(MockMetricCollector::new_success())]);
+                let fake_influx = FakeInfluxDb::start().await;
+                fake_influx.fail_next(429, Some(retry_after));
+                let influx_client = Arc::new(influxdb::Client::new(config::InfluxConfig {
+                    max_retries: 3,
+                    retry_initial_ms: 500,
+                    retry_max_ms,
+                    write_timeout_ms: 4_000,
+                    ..fake_influx.config()
+                }));
+                let started = std::time::Instant::now();
+
+                // The 5 second timeout of the status collectors
+                let result = create_collect_task(
+                    influx_client,
+                    collectors,
+                    Local::now(),
+                    false,
+                    "test_task",
+                    5,
+                    CancellationToken::new(),
+                )
+                .await;
+
+                assert_eq!(result, stored, "case: {}", retry_after);
+                assert_eq!(
+                    fake_influx.lines().len(),
+                    usize::from(stored),
+                    "case: {}",
+                    retry_after
+                );
+                if !stored {
+                    // Gave up at once instead of waiting into the task timeout
+                    assert!(
+                        started.elapsed() < Duration::from_secs(1),
+                        "case: {}",
+                        retry_after
+                    );
+                }
+            }
+        }
+
+        #[tokio::test]
+        async fn test_cancelled_before_tick() {
+            let collectors: Arc<Vec<Box<dyn MetricCollector>>> =
+                Arc::new(vec![Box::new(MockMetricCollector::new_success())]);
+            let sink = RecordingSink::new("recording");
+            let cancel = CancellationToken::new();
+            cancel.cancel();
+
+            let stored = tokio::time::timeout(
+                Duration::from_secs(1),
+                create_collect_task(
+                    Arc::new(sink.clone()),
+                    collectors,
+                    Local::now() + chrono::Duration::hours(1),
+                    false,
+                    "test_task",
+                    10,
+                    cancel,
+                ),
+            )
+            .await
+            .unwrap();
+
+            // Nothing was collected, so nothing was lost
+            assert!(stored);
+            assert_eq!(sink.batches().len(), 0);
         }
 
         #[tokio::test]
@@ -471,11 +1416,483 @@ mod tests {
             create_collect_task(
                 influx_client,
                 collectors,
-                Duration::from_millis(1),
+                Local::now(),
+                false,
                 "test_task_fails",
                 10,
+                CancellationToken::new(),
+            )
+            .await;
+        }
+    }
+
+    mod drain {
+        use super::*;
+
+        /// A collection task that finishes after `ms` with `stored`.
+        fn task(tasks: &mut JoinSet<bool>, ms: u64, stored: bool) {
+            tasks.spawn(async move {
+                tokio::time::sleep(Duration::from_millis(ms)).await;
+                stored
+            });
+        }
+
+        #[tokio::test]
+        async fn succeeds() {
+            let sink = RecordingSink::new("recording");
+            let mut tasks = JoinSet::new();
+            task(&mut tasks, 50, true);
+            task(&mut tasks, 0, true);
+            let mut background = JoinSet::new();
+            background.spawn(tokio::time::sleep(Duration::from_millis(50)));
+
+            let stored = drain(tasks, background, &sink, Duration::from_secs(5)).await;
+
+            assert!(stored);
+            assert_eq!(sink.flushes(), 1);
+        }
+
+        #[tokio::test]
+        async fn test_background_past_deadline_loses_nothing() {
+            let sink = RecordingSink::new("recording");
+            let mut background = JoinSet::new();
+            background.spawn(tokio::time::sleep(Duration::from_secs(60)));
+
+            let stored = drain(JoinSet::new(), background, &sink, Duration::from_millis(50)).await;
+
+            assert!(stored);
+            assert_eq!(sink.flushes(), 1);
+        }
+
+        #[tokio::test]
+        async fn fails() {
+            let test_cases = vec![
+                ("failed write", 0, false, RecordingSink::new("recording")),
+                (
+                    "past deadline",
+                    60_000,
+                    true,
+                    RecordingSink::new("recording"),
+                ),
+                ("failed flush", 0, true, RecordingSink::failing("recording")),
+            ];
+
+            for (name, ms, task_stored, sink) in test_cases {
+                let mut tasks = JoinSet::new();
+                task(&mut tasks, ms, task_stored);
+
+                let started = std::time::Instant::now();
+                let stored = drain(tasks, JoinSet::new(), &sink, Duration::from_millis(100)).await;
+
+                assert!(!stored, "case: {}", name);
+                assert!(started.elapsed() < Duration::from_secs(5), "case: {}", name);
+            }
+        }
+    }
+
+    mod reload_config {
+        use super::*;
+        use crate::supervisor::RestartStats;
+        use serial_test::serial;
+
+        const BASE: &str = r#"
+sinks = ["stdout"]
+
+[aiseg2]
+url = "http://127.0.0.1:8080"
+user = "aiseg"
+password = "aiseg"
+"#;
+
+        /// Writes a configuration file with `extra` appended to the base.
+        fn config_file(dir: &tempfile::TempDir, name: &str, extra: &str) -> PathBuf {
+            let path = dir.path().join(name);
+            std::fs::write(&path, format!("{}\n{}", BASE, extra)).unwrap();
+            path
+        }
+
+        /// Loads `extra` as the running configuration and reloads `reloaded`.
+        async fn reload(
+            extra: &str,
+            reloaded: &str,
+        ) -> (Result<Runtime, ConfigError>, RecordingSink) {
+            let dir = tempfile::tempdir().unwrap();
+            let running = config_file(&dir, "running.toml", extra);
+            config::use_config_vars(config::read_config_file(&running).unwrap());
+            let runtime = Runtime::load().unwrap();
+            let recording = RecordingSink::new("recording");
+            let sink = ReloadableSink::new(Box::new(recording.clone()));
+
+            let path = config_file(&dir, "reloaded.toml", reloaded);
+            let result = reload_config(
+                Some(&path),
+                &runtime,
+                &sink,
+                &mut Handover::default(),
+                Arc::new(RestartStats::default()),
+            )
+            .await;
+            (result, recording)
+        }
+
+        fn schedule(runtime: &Runtime, name: &str) -> String {
+            let job = runtime.jobs.iter().find(|job| job.name == name).unwrap();
+            job.schedule.to_string()
+        }
+
+        #[tokio::test]
+        #[serial]
+        async fn succeeds() {
+            let test_cases = vec![
+                (
+                    "schedule changed",
+                    "[collector.climate]\ninterval_sec = 30",
+                    0,
+                ),
+                ("sink settings changed", "[buffer]\nmax_bytes = 1024", 1),
+            ];
+
+            for (name, reloaded, flushes) in test_cases {
+                let (result, recording) = reload("", reloaded).await;
+                let reloaded_config = config::load_collector_config();
+                config::use_config_vars(BTreeMap::new());
+
+                let runtime = result.unwrap();
+                let expected = if flushes == 0 {
+                    "every 30s"
+                } else {
+                    "every 5s"
+                };
+                assert_eq!(schedule(&runtime, CLIMATE), expected, "case: {}", name);
+                assert!(reloaded_config.is_ok(), "case: {}", name);
+                assert_eq!(recording.flushes(), flushes, "case: {}", name);
+            }
+        }
+
+        #[tokio::test]
+        #[serial]
+        async fn fails() {
+            let test_cases = vec![
+                ("zero interval", "[collector]\nstatus_interval_sec = 0"),
+                (
+                    "timeout above interval",
+                    "[collector.power]\ntimeout_seconds = 9",
+                ),
+                ("unparsable", "[collector\n"),
+            ];
+
+            for (name, reloaded) in test_cases {
+                let (result, recording) =
+                    reload("[collector]\nstatus_interval_sec = 10", reloaded).await;
+                let running_config = config::load_collector_config();
+                config::use_config_vars(BTreeMap::new());
+
+                assert!(
+                    matches!(result, Err(ConfigError::Invalid { .. })),
+                    "case: {}",
+                    name
+                );
+                // The running configuration file stays in effect
+                assert_eq!(
+                    running_config.unwrap().status_interval_sec,
+                    10,
+                    "case: {}",
+                    name
+                );
+                assert_eq!(recording.flushes(), 0, "case: {}", name);
+            }
+        }
+    }
+
+    mod create_jobs {
+        use super::*;
+        use crate::config::CollectorConfig;
+        use crate::test_utils::config::test_aiseg2_config;
+        use serial_test::serial;
+
+        fn collector_config() -> CollectorConfig {
+            CollectorConfig {
+                status_interval_sec: 5,
+                total_interval_sec: 60,
+                total_initial_days: 30,
+                total_max_lookback_days: 365,
+                total_watermark_path: "aiseg2-backfill.watermark".to_string(),
+                total_finalize_passes: 3,
+                total_finalize_grace_sec: 600,
+                task_timeout_seconds: 10,
+                stamp_tick_time: false,
+            }
+        }
+
+        fn collectors() -> Vec<(&'static str, Arc<dyn MetricCollector>)> {
+            let aiseg_client = Arc::new(aiseg::Client::new(test_aiseg2_config()));
+            create_collectors(&aiseg_client, &CircuitConfig::default())
+        }
+
+        #[test]
+        #[serial]
+        fn succeeds() {
+            let collectors = collectors();
+
+            std::env::set_var("COLLECTOR_CLIMATE_INTERVAL_SEC", "60");
+            std::env::set_var("COLLECTOR_CIRCUIT_DAILY_TOTAL_ENABLED", "false");
+            let jobs = create_jobs(&collectors, &collector_config());
+            std::env::remove_var("COLLECTOR_CLIMATE_INTERVAL_SEC");
+            std::env::remove_var("COLLECTOR_CIRCUIT_DAILY_TOTAL_ENABLED");
+
+            let schedules: Vec<(&str, String)> = jobs
+                .unwrap()
+                .iter()
+                .map(|job| (job.name, job.schedule.to_string()))
+                .collect();
+            assert_eq!(
+                schedules,
+                vec![
+                    (POWER, "every 5s".to_string()),
+                    (CLIMATE, "every 60s".to_string()),
+                    (DAILY_TOTAL, "every 60s".to_string()),
+                ]
+            );
+        }
+
+        #[test]
+        #[serial]
+        fn fails() {
+            let collectors = collectors();
+
+            std::env::set_var("COLLECTOR_POWER_SCHEDULE", "every second");
+            let jobs = create_jobs(&collectors, &collector_config());
+            std::env::remove_var("COLLECTOR_POWER_SCHEDULE");
+
+            assert!(matches!(jobs, Err(ConfigError::Invalid { .. })));
+        }
+
+        #[test]
+        #[serial]
+        fn test_check_write_timeout() {
+            let jobs = create_jobs(&collectors(), &collector_config()).unwrap();
+            // The status collectors' timeout is cut to their 5 second interval
+            let test_cases = vec![(4_000, true), (5_000, false)];
+
+            for (write_timeout_ms, fits) in test_cases {
+                let result = check_write_timeout(&jobs, write_timeout_ms);
+
+                assert_eq!(result.is_ok(), fits, "case: {}", write_timeout_ms);
+            }
+        }
+    }
+
+    /// Runs the production collector wiring against the AiSEG2 simulator and
+    /// checks what lands in a fake InfluxDB, so schema changes show up here.
+    mod end_to_end {
+        use super::*;
+        use crate::simulator::{RunningSimulator, SimClock, SimulatorConfig};
+        use crate::test_utils::mocks::simulator_client;
+        use chrono::{NaiveTime, TimeZone};
+
+        struct Harness {
+            // Kept alive for the duration of the test
+            _simulator: RunningSimulator,
+            fake_influx: FakeInfluxDb,
+            influx_client: Arc<dyn Sink>,
+            status_collectors: Collectors,
+            total_collectors: Collectors,
+        }
+
+        async fn harness(clock: SimClock) -> Harness {
+            let (simulator, aiseg_client) = simulator_client(SimulatorConfig {
+                clock,
+                ..SimulatorConfig::default()
+            })
+            .await;
+            let fake_influx = FakeInfluxDb::start().await;
+            let influx_client = Arc::new(influxdb::Client::new(fake_influx.config()));
+            let collectors = create_collectors(&aiseg_client, &CircuitConfig::default());
+            let status_collectors = collector_group(&collectors, &[POWER, CLIMATE]);
+            let total_collectors = collector_group(&collectors, &TOTAL_COLLECTORS);
+
+            Harness {
+                _simulator: simulator,
+                fake_influx,
+                influx_client,
+                status_collectors,
+                total_collectors,
+            }
+        }
+
+        fn fixed_now() -> DateTime<Local> {
+            Local.with_ymd_and_hms(2024, 6, 8, 12, 0, 0).unwrap()
+        }
+
+        fn nanos(timestamp: DateTime<Local>) -> i64 {
+            timestamp.timestamp_nanos_opt().unwrap()
+        }
+
+        /// Expands `{now}` and `{midnight}` so expectations hold in any time zone.
+        fn expected_lines(template: &[&str], now: DateTime<Local>) -> Vec<String> {
+            let midnight = now.with_time(NaiveTime::default()).unwrap();
+            template
+                .iter()
+                .map(|line| {
+                    line.replace("{now}", &nanos(now).to_string())
+                        .replace("{midnight}", &nanos(midnight).to_string())
+                })
+                .collect()
+        }
+
+        #[tokio::test]
+        async fn test_status_collectors_store_expected_points() {
+            let now = fixed_now();
+            let harness = harness(SimClock::Fixed(now)).await;
+
+            collect_and_write(
+                harness.influx_client.as_ref(),
+                &harness.status_collectors,
+                now,
+                false,
+            )
+            .await
+            .unwrap();
+
+            let expected = expected_lines(
+                &[
+                    "climate,detail-section=リビング,detail-type=humidity value=47.1 {now}",
+                    "climate,detail-section=リビング,detail-type=temperature value=26.5 {now}",
+                    "climate,detail-section=主寝室,detail-type=humidity value=44.4 {now}",
+                    "climate,detail-section=主寝室,detail-type=temperature value=26.4 {now}",
+                    "climate,detail-section=和室,detail-type=humidity value=44.8 {now}",
+                    "climate,detail-section=和室,detail-type=temperature value=26.3 {now}",
+                    "climate,detail-section=洋室１,detail-type=humidity value=47.3 {now}",
+                    "climate,detail-section=洋室１,detail-type=temperature value=24.4 {now}",
+                    "climate,detail-section=洋室２,detail-type=humidity value=45.4 {now}",
+                    "climate,detail-section=洋室２,detail-type=temperature value=26.8 {now}",
+                    "power,detail-section=EV(W),detail-type=consumption value=237i",
+                    "power,detail-section=IHクッキングヒーター(W),detail-type=consumption value=165i",
+                    "power,detail-section=エコキュート(W),detail-type=consumption value=574i",
+                    "power,detail-section=コンセント(W),detail-type=consumption value=401i",
+                    "power,detail-section=リビングエアコン(W),detail-type=consumption value=48i",
+                    "power,detail-section=主寝室エアコン(W),detail-type=consumption value=288i",
+                    "power,detail-section=冷蔵庫(W),detail-type=consumption value=163i",
+                    "power,detail-section=太陽光(W),detail-type=generation value=4200i",
+                    "power,detail-section=洋室２エアコン(W),detail-type=consumption value=180i",
+                    "power,detail-section=洗濯機(W),detail-type=consumption value=327i",
+                    "power,detail-section=浴室換気乾燥機(W),detail-type=consumption value=299i",
+                    "power,detail-section=照明(W),detail-type=consumption value=348i",
+                    "power,detail-section=食器洗い乾燥機(W),detail-type=consumption value=35i",
+                    "power,summary=売買電力(W) value=1140i",
+                    "power,summary=総消費電力(W) value=3060i",
+                    "power,summary=総発電電力(W) value=4200i",
+                ],
+                now,
+            );
+            assert_eq!(harness.fake_influx.lines(), expected);
+        }
+
+        #[tokio::test]
+        async fn test_total_collectors_store_expected_points() {
+            let now = fixed_now();
+            let harness = harness(SimClock::Fixed(now)).await;
+
+            collect_and_write(
+                harness.influx_client.as_ref(),
+                &harness.total_collectors,
+                now,
+                false,
+            )
+            .await
+            .unwrap();
+
+            let expected = expected_lines(
+                &[
+                    "circuit_daily_total,detail-section=EV(kWh) value=1.8 {midnight}",
+                    "circuit_daily_total,detail-section=リビングエアコン(kWh) value=1.6 {midnight}",
+                    "circuit_daily_total,detail-section=主寝室エアコン(kWh) value=2.5 {midnight}",
+                    "circuit_daily_total,detail-section=洋室２エアコン(kWh) value=2.7 {midnight}",
+                    "daily_total,detail-section=ガス量(㎥) value=0.5 {midnight}",
+                    "daily_total,detail-section=売電量(kWh) value=8.5 {midnight}",
+                    "daily_total,detail-section=消費量(kWh) value=7.9 {midnight}",
+                    "daily_total,detail-section=発電量(kWh) value=17.1 {midnight}",
+                    "daily_total,detail-section=給湯量(L) value=182.4 {midnight}",
+                    "daily_total,detail-section=買電量(kWh) value=3.4 {midnight}",
+                ],
+                now,
+            );
+            assert_eq!(harness.fake_influx.lines(), expected);
+        }
+
+        #[tokio::test]
+        async fn test_collect_task_stamps_climate_with_collection_time() {
+            let harness = harness(SimClock::System).await;
+            let before = nanos(Local::now());
+
+            create_collect_task(
+                Arc::clone(&harness.influx_client),
+                Arc::clone(&harness.status_collectors),
+                Local::now(),
+                false,
+                "status_collectors",
+                10,
+                CancellationToken::new(),
             )
             .await;
+
+            let after = nanos(Local::now());
+            let writes = harness.fake_influx.writes();
+            assert_eq!(writes.len(), 1);
+            assert_eq!(writes[0].precision.as_deref(), Some("ns"));
+
+            let points = harness.fake_influx.points();
+            assert_eq!(points.len(), 26);
+            for point in points {
+                match point.measurement.as_str() {
+                    "climate" => {
+                        let timestamp = point.timestamp.unwrap();
+                        assert!((before..=after).contains(&timestamp), "{}", point);
+                    }
+                    "power" => assert_eq!(point.timestamp, None, "{}", point),
+                    other => panic!("unexpected measurement {}", other),
+                }
+            }
+        }
+
+        #[tokio::test]
+        async fn test_backfill_writes_each_missing_day_at_midnight() {
+            let harness = harness(SimClock::System).await;
+            let history = influxdb::Client::new(harness.fake_influx.config());
+            let dir = tempfile::tempdir().unwrap();
+
+            let report = backfill::backfill(
+                &harness.total_collectors,
+                harness.influx_client.as_ref(),
+                Some(&history),
+                &Watermark::new(dir.path().join("watermark")),
+                &BackfillOptions {
+                    initial_days: 2,
+                    max_lookback_days: 365,
+                    measurements: vec![
+                        Measurement::DailyTotal.to_string(),
+                        Measurement::CircuitDailyTotal.to_string(),
+                    ],
+                },
+                Local::now().date_naive(),
+                &CancellationToken::new(),
+            )
+            .await;
+            assert_eq!(report.failed, vec![]);
+
+            let today = Local::now().with_time(NaiveTime::default()).unwrap();
+            let writes = harness.fake_influx.writes();
+            assert_eq!(writes.len(), 2);
+            // Oldest day first
+            for (write, days_ago) in writes.iter().zip([2, 1]) {
+                let midnight = nanos(today - chrono::Duration::days(days_ago));
+                assert_eq!(write.points.len(), 10);
+                assert!(
+                    write.points.iter().all(|p| p.timestamp == Some(midnight)),
+                    "day {}",
+                    days_ago
+                );
+            }
         }
     }
 }
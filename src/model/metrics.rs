@@ -1,7 +1,7 @@
 use crate::error::{Result, StorageError};
 use chrono::{DateTime, Local};
-use influxdb2::models::DataPoint;
 
+use super::point::Point;
 use super::traits::DataPointBuilder;
 use super::types::{ClimateStatusMetricCategory, Measurement, PowerStatusBreakdownMetricCategory};
 
@@ -20,8 +20,8 @@ pub struct PowerStatusMetric {
 }
 
 impl DataPointBuilder for PowerStatusMetric {
-    fn to_point(&self) -> Result<DataPoint, StorageError> {
-        DataPoint::builder(self.measurement.to_string().as_str())
+    fn to_point(&self) -> Result<Point, StorageError> {
+        Point::builder(self.measurement.to_string())
             .tag("summary", self.name.clone())
             .field("value", self.value)
             .build()
@@ -49,8 +49,8 @@ pub struct PowerStatusBreakdownMetric {
 }
 
 impl DataPointBuilder for PowerStatusBreakdownMetric {
-    fn to_point(&self) -> Result<DataPoint, StorageError> {
-        DataPoint::builder(self.measurement.to_string().as_str())
+    fn to_point(&self) -> Result<Point, StorageError> {
+        Point::builder(self.measurement.to_string())
             .tag("detail-type", self.category.to_string())
             .tag("detail-section", self.name.clone())
             .field("value", self.value)
@@ -82,13 +82,13 @@ pub struct PowerTotalMetric {
 }
 
 impl DataPointBuilder for PowerTotalMetric {
-    fn to_point(&self) -> Result<DataPoint, StorageError> {
+    fn to_point(&self) -> Result<Point, StorageError> {
         let timestamp = self
             .date
             .timestamp_nanos_opt()
             .ok_or_else(|| StorageError::InvalidDataPoint("Timestamp overflow".to_string()))?;
 
-        DataPoint::builder(self.measurement.to_string().as_str())
+        Point::builder(self.measurement.to_string())
             .tag("detail-section", self.name.clone())
             .field("value", self.value)
             .timestamp(timestamp)
@@ -118,13 +118,13 @@ pub struct ClimateStatusMetric {
 }
 
 impl DataPointBuilder for ClimateStatusMetric {
-    fn to_point(&self) -> Result<DataPoint, StorageError> {
+    fn to_point(&self) -> Result<Point, StorageError> {
         let timestamp = self
             .timestamp
             .timestamp_nanos_opt()
             .ok_or_else(|| StorageError::InvalidDataPoint("Timestamp overflow".to_string()))?;
 
-        DataPoint::builder(self.measurement.to_string().as_str())
+        Point::builder(self.measurement.to_string())
             .tag("detail-type", self.category.to_string())
             .tag("detail-section", self.name.clone())
             .field("value", self.value)
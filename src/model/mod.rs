@@ -1,10 +1,11 @@
-//! Model definitions for AiSEG2 metrics and InfluxDB data points.
+//! Model definitions for AiSEG2 metrics and backend-neutral points.
 //!
 //! This module provides the core data structures and traits for representing
-//! metrics collected from the AiSEG2 system and converting them to InfluxDB
-//! data points.
+//! metrics collected from the AiSEG2 system and converting them to points
+//! that any sink can store.
 
 pub mod metrics;
+pub mod point;
 pub mod traits;
 pub mod types;
 pub mod utilities;
@@ -13,6 +14,7 @@ pub mod utilities;
 pub use metrics::{
     ClimateStatusMetric, PowerStatusBreakdownMetric, PowerStatusMetric, PowerTotalMetric,
 };
+pub use point::{FieldValue, Point};
 pub use traits::{DataPointBuilder, MetricCollector};
 pub use types::{
     ClimateStatusMetricCategory, Measurement, PowerStatusBreakdownMetricCategory, Unit,
@@ -87,7 +89,7 @@ mod tests {
 
             let result = metric.to_point();
             assert!(result.is_ok());
-            // DataPoint is successfully created
+            // Point is successfully created
         }
 
         #[test]
@@ -101,7 +103,7 @@ mod tests {
 
             let result = metric.to_point();
             assert!(result.is_ok());
-            // DataPoint is successfully created
+            // Point is successfully created
         }
 
         #[test]
@@ -115,7 +117,7 @@ mod tests {
 
             let result = metric.to_point();
             assert!(result.is_ok());
-            // DataPoint is successfully created
+            // Point is successfully created
         }
 
         #[test]
@@ -130,7 +132,7 @@ mod tests {
 
             let result = metric.to_point();
             assert!(result.is_ok());
-            // DataPoint is successfully created
+            // Point is successfully created
         }
 
         #[tokio::test]
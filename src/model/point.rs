@@ -0,0 +1,203 @@
+//! Backend-neutral representation of a stored metric.
+//!
+//! Metrics are converted into [`Point`]s once, and every sink translates
+//! points into its own wire format. Nothing here depends on a storage client.
+
+use crate::error::StorageError;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A field value of a point.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FieldValue {
+    Float(f64),
+    Integer(i64),
+    UInteger(u64),
+    String(String),
+    Boolean(bool),
+}
+
+impl FieldValue {
+    /// Returns the value as a float, if it is numeric.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            FieldValue::Float(v) => Some(*v),
+            FieldValue::Integer(v) => Some(*v as f64),
+            FieldValue::UInteger(v) => Some(*v as f64),
+            FieldValue::String(_) | FieldValue::Boolean(_) => None,
+        }
+    }
+}
+
+impl From<f64> for FieldValue {
+    fn from(value: f64) -> Self {
+        FieldValue::Float(value)
+    }
+}
+
+impl From<i64> for FieldValue {
+    fn from(value: i64) -> Self {
+        FieldValue::Integer(value)
+    }
+}
+
+impl From<u64> for FieldValue {
+    fn from(value: u64) -> Self {
+        FieldValue::UInteger(value)
+    }
+}
+
+impl From<bool> for FieldValue {
+    fn from(value: bool) -> Self {
+        FieldValue::Boolean(value)
+    }
+}
+
+impl From<String> for FieldValue {
+    fn from(value: String) -> Self {
+        FieldValue::String(value)
+    }
+}
+
+impl From<&str> for FieldValue {
+    fn from(value: &str) -> Self {
+        FieldValue::String(value.to_string())
+    }
+}
+
+/// A single measurement with tags, fields and an optional timestamp.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    pub measurement: String,
+    pub tags: BTreeMap<String, String>,
+    pub fields: BTreeMap<String, FieldValue>,
+    /// Nanoseconds since the Unix epoch; `None` lets the backend assign the time
+    pub timestamp: Option<i64>,
+}
+
+impl Point {
+    /// Starts building a point for `measurement`.
+    pub fn builder(measurement: impl Into<String>) -> PointBuilder {
+        PointBuilder {
+            measurement: measurement.into(),
+            tags: BTreeMap::new(),
+            fields: BTreeMap::new(),
+            timestamp: None,
+        }
+    }
+}
+
+/// Builder for [`Point`].
+#[derive(Debug)]
+pub struct PointBuilder {
+    measurement: String,
+    tags: BTreeMap<String, String>,
+    fields: BTreeMap<String, FieldValue>,
+    timestamp: Option<i64>,
+}
+
+impl PointBuilder {
+    /// Adds a tag, replacing any previous value for the key.
+    pub fn tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.insert(key.into(), value.into());
+        self
+    }
+
+    /// Adds a field, replacing any previous value for the key.
+    pub fn field(mut self, key: impl Into<String>, value: impl Into<FieldValue>) -> Self {
+        self.fields.insert(key.into(), value.into());
+        self
+    }
+
+    /// Sets the timestamp in nanoseconds since the Unix epoch.
+    pub fn timestamp(mut self, timestamp: i64) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// Builds the point.
+    ///
+    /// # Errors
+    /// Returns `StorageError::InvalidDataPoint` if the measurement is empty or
+    /// no fields were added, since no backend can store such a point.
+    pub fn build(self) -> Result<Point, StorageError> {
+        if self.measurement.is_empty() {
+            return Err(StorageError::InvalidDataPoint(
+                "measurement is empty".to_string(),
+            ));
+        }
+        if self.fields.is_empty() {
+            return Err(StorageError::InvalidDataPoint(format!(
+                "point '{}' has no fields",
+                self.measurement
+            )));
+        }
+
+        Ok(Point {
+            measurement: self.measurement,
+            tags: self.tags,
+            fields: self.fields,
+            timestamp: self.timestamp,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod succeeds {
+        use super::*;
+
+        #[test]
+        fn test_build_point() {
+            let point = Point::builder("climate")
+                .tag("detail-type", "temperature")
+                .tag("detail-section", "リビング")
+                .field("value", 23.5)
+                .timestamp(1_717_804_800_000_000_000)
+                .build()
+                .unwrap();
+
+            assert_eq!(point.measurement, "climate");
+            assert_eq!(point.tags["detail-section"], "リビング");
+            assert_eq!(point.fields["value"], FieldValue::Float(23.5));
+            assert_eq!(point.timestamp, Some(1_717_804_800_000_000_000));
+        }
+
+        #[test]
+        fn test_field_value_as_f64() {
+            let test_cases = vec![
+                (FieldValue::Float(1.5), Some(1.5)),
+                (FieldValue::Integer(-2), Some(-2.0)),
+                (FieldValue::UInteger(3), Some(3.0)),
+                (FieldValue::Boolean(true), None),
+                (FieldValue::String("x".to_string()), None),
+            ];
+
+            for (value, expected) in test_cases {
+                assert_eq!(value.as_f64(), expected, "case: {:?}", value);
+            }
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[test]
+        fn test_build_invalid_points() {
+            let test_cases = vec![
+                ("no fields", Point::builder("power").tag("summary", "x")),
+                ("empty measurement", Point::builder("").field("value", 1i64)),
+            ];
+
+            for (name, builder) in test_cases {
+                assert!(
+                    matches!(builder.build(), Err(StorageError::InvalidDataPoint(_))),
+                    "case: {}",
+                    name
+                );
+            }
+        }
+    }
+}
@@ -1,20 +1,21 @@
+use super::point::Point;
 use crate::error::{CollectorError, Result, StorageError};
 use async_trait::async_trait;
 use chrono::{DateTime, Local};
-use influxdb2::models::DataPoint;
+use std::sync::Arc;
 
-/// Trait for types that can be converted to InfluxDB data points.
+/// Trait for types that can be converted to backend-neutral points.
 ///
-/// This trait enables metric types to be transformed into InfluxDB-compatible
-/// data points for storage. Implementors must be thread-safe (Send + Sync)
-/// to support concurrent metric collection.
+/// This trait enables metric types to be transformed into [`Point`]s, which
+/// every sink then translates into its own storage format. Implementors must
+/// be thread-safe (Send + Sync) to support concurrent metric collection.
 pub trait DataPointBuilder: Send + Sync {
-    /// Converts the metric into an InfluxDB DataPoint.
+    /// Converts the metric into a Point.
     ///
     /// # Returns
-    /// - `Ok(DataPoint)` if conversion succeeds
-    /// - `Err` if the metric data cannot be converted to a valid DataPoint
-    fn to_point(&self) -> Result<DataPoint, StorageError>;
+    /// - `Ok(Point)` if conversion succeeds
+    /// - `Err` if the metric data cannot be converted to a valid Point
+    fn to_point(&self) -> Result<Point, StorageError>;
 }
 
 /// Trait for types that can collect metrics from AiSEG2.
@@ -36,3 +37,15 @@ pub trait MetricCollector: Send + Sync {
         timestamp: DateTime<Local>,
     ) -> Result<Vec<Box<dyn DataPointBuilder>>, CollectorError>;
 }
+
+/// Shares one collector, and its state such as a circuit breaker, between
+/// several collector lists.
+#[async_trait]
+impl<T: MetricCollector + ?Sized> MetricCollector for Arc<T> {
+    async fn collect(
+        &self,
+        timestamp: DateTime<Local>,
+    ) -> Result<Vec<Box<dyn DataPointBuilder>>, CollectorError> {
+        self.as_ref().collect(timestamp).await
+    }
+}
@@ -1,13 +1,13 @@
 use chrono::{DateTime, Local};
 use futures::future::join_all;
-use influxdb2::models::DataPoint;
 
+use super::point::Point;
 use super::traits::MetricCollector;
 
 /// Collects metrics from multiple collectors concurrently.
 ///
 /// This function runs all collectors in parallel, handles errors gracefully,
-/// and converts successful results to InfluxDB data points. Failed collections
+/// and converts successful results to points. Failed collections
 /// or conversions are logged but don't stop other collectors.
 ///
 /// # Arguments
@@ -19,7 +19,7 @@ use super::traits::MetricCollector;
 pub async fn batch_collect_metrics<'a>(
     clients: &Vec<Box<dyn MetricCollector + 'a>>,
     timestamp: DateTime<Local>,
-) -> Vec<DataPoint> {
+) -> Vec<Point> {
     let results = join_all(clients.iter().map(|client| client.collect(timestamp))).await;
 
     results
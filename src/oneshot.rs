@@ -0,0 +1,239 @@
+//! One-off collections of the `once` and `collect` commands.
+//!
+//! Unlike the scheduled jobs, which log a failing collector and carry on,
+//! a one-off collection reports every failure, so a cron job or a person
+//! debugging a setup sees why points are missing.
+
+use crate::error::CollectorError;
+use crate::line_protocol;
+use crate::model::{FieldValue, MetricCollector, Point};
+use chrono::{DateTime, Local};
+use futures::future::join_all;
+use serde_derive::Serialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Points collected by a one-off collection and the collectors that failed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Collection {
+    pub points: Vec<Point>,
+    pub failures: Vec<Failure>,
+}
+
+/// A collector that returned no points.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Failure {
+    pub collector: String,
+    pub error: String,
+}
+
+/// Runs every collector once at `timestamp`, concurrently, each within
+/// `timeout`.
+///
+/// Points without their own time get `timestamp`, so the output is complete
+/// wherever it ends up.
+pub async fn collect(
+    collectors: &[(&str, Arc<dyn MetricCollector>)],
+    timestamp: DateTime<Local>,
+    timeout: Duration,
+) -> Collection {
+    let results = join_all(collectors.iter().map(|(name, collector)| async move {
+        let result = match tokio::time::timeout(timeout, collector.collect(timestamp)).await {
+            Ok(result) => result,
+            Err(_) => Err(CollectorError::timeout(*name, timeout.as_secs())),
+        };
+        (*name, result)
+    }))
+    .await;
+
+    let mut collection = Collection::default();
+    let nanos = timestamp.timestamp_nanos_opt();
+    for (name, result) in results {
+        let failure = |error: String| Failure {
+            collector: name.to_string(),
+            error,
+        };
+        let builders = match result {
+            Ok(builders) => builders,
+            Err(e) => {
+                collection.failures.push(failure(e.detail()));
+                continue;
+            }
+        };
+        for builder in builders {
+            match builder.to_point() {
+                Ok(mut point) => {
+                    point.timestamp = point.timestamp.or(nanos);
+                    collection.points.push(point);
+                }
+                Err(e) => collection.failures.push(failure(e.to_string())),
+            }
+        }
+    }
+    collection
+}
+
+impl Collection {
+    /// Whether every collector returned its points.
+    pub fn ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// The points as line protocol, one line each.
+    pub fn to_line_protocol(&self) -> String {
+        line_protocol::encode_batch(&self.points)
+            .into_iter()
+            .map(|line| line + "\n")
+            .collect()
+    }
+
+    /// The points and failures as one JSON document.
+    ///
+    /// Field values are plain JSON numbers, strings and booleans, and
+    /// timestamps are nanoseconds since the Unix epoch, like in line protocol.
+    pub fn to_json(&self) -> Value {
+        let points: Vec<Value> = self
+            .points
+            .iter()
+            .map(|point| {
+                let fields: serde_json::Map<String, Value> = point
+                    .fields
+                    .iter()
+                    .map(|(key, value)| (key.clone(), field_json(value)))
+                    .collect();
+                json!({
+                    "measurement": point.measurement,
+                    "tags": point.tags,
+                    "fields": fields,
+                    "timestamp": point.timestamp,
+                })
+            })
+            .collect();
+        json!({ "points": points, "failures": self.failures })
+    }
+}
+
+/// A field value as plain JSON.
+fn field_json(value: &FieldValue) -> Value {
+    match value {
+        FieldValue::Float(v) => json!(v),
+        FieldValue::Integer(v) => json!(v),
+        FieldValue::UInteger(v) => json!(v),
+        FieldValue::String(v) => json!(v),
+        FieldValue::Boolean(v) => json!(v),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Measurement, PowerStatusMetric, PowerTotalMetric};
+    use crate::test_utils::mocks::MockMetricCollector;
+    use chrono::TimeZone;
+
+    fn test_timestamp() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 10, 18, 12, 0, 0).unwrap()
+    }
+
+    fn power_collector() -> Arc<dyn MetricCollector> {
+        Arc::new(MockMetricCollector::new_with_data(|| {
+            vec![Box::new(PowerStatusMetric {
+                measurement: Measurement::Power,
+                name: "総発電電力(W)".to_string(),
+                value: 2500,
+            })]
+        }))
+    }
+
+    fn total_collector() -> Arc<dyn MetricCollector> {
+        Arc::new(MockMetricCollector::new_with_data(|| {
+            vec![Box::new(PowerTotalMetric {
+                measurement: Measurement::DailyTotal,
+                name: "総発電量(kWh)".to_string(),
+                value: 12.5,
+                date: Local.with_ymd_and_hms(2026, 10, 18, 0, 0, 0).unwrap(),
+            })]
+        }))
+    }
+
+    mod succeeds {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_collect() {
+            let collectors = vec![
+                ("power", power_collector()),
+                ("daily_total", total_collector()),
+            ];
+
+            let collection = collect(&collectors, test_timestamp(), Duration::from_secs(1)).await;
+
+            assert!(collection.ok());
+            assert_eq!(collection.points.len(), 2);
+            // Status points get the collection time, totals keep their day
+            assert_eq!(
+                collection.points[0].timestamp,
+                test_timestamp().timestamp_nanos_opt()
+            );
+            assert_eq!(
+                collection.points[1].timestamp,
+                Local
+                    .with_ymd_and_hms(2026, 10, 18, 0, 0, 0)
+                    .unwrap()
+                    .timestamp_nanos_opt()
+            );
+        }
+
+        #[tokio::test]
+        async fn test_output() {
+            let collectors = vec![("power", power_collector())];
+            let collection = collect(&collectors, test_timestamp(), Duration::from_secs(1)).await;
+            let nanos = test_timestamp().timestamp_nanos_opt().unwrap();
+
+            assert_eq!(
+                collection.to_line_protocol(),
+                format!("power,summary=総発電電力(W) value=2500i {}\n", nanos)
+            );
+            assert_eq!(
+                collection.to_json(),
+                json!({
+                    "points": [{
+                        "measurement": "power",
+                        "tags": { "summary": "総発電電力(W)" },
+                        "fields": { "value": 2500 },
+                        "timestamp": nanos,
+                    }],
+                    "failures": [],
+                })
+            );
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_collect_reports_failures() {
+            let failing: Arc<dyn MetricCollector> =
+                Arc::new(MockMetricCollector::new_failure("no rooms"));
+            let collectors = vec![("power", power_collector()), ("climate", failing)];
+
+            let collection = collect(&collectors, test_timestamp(), Duration::from_secs(1)).await;
+
+            assert!(!collection.ok());
+            assert_eq!(collection.points.len(), 1);
+            assert_eq!(
+                collection.failures,
+                vec![Failure {
+                    collector: "climate".to_string(),
+                    error: "invalid metric data: no rooms".to_string(),
+                }]
+            );
+            assert_eq!(
+                collection.to_json()["failures"][0]["collector"],
+                json!("climate")
+            );
+        }
+    }
+}
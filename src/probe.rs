@@ -0,0 +1,241 @@
+//! Connectivity checks of the `probe` command.
+//!
+//! Connects to AiSEG2 and, if it is a configured sink, to InfluxDB with the
+//! configured credentials and reports what each answered, so a new setup can
+//! be checked before the forwarder runs. Nothing is written.
+
+use crate::aiseg::{self, DeviceInfo};
+use crate::config::{InfluxApiVersion, InfluxConfig};
+use crate::error::CollectorError;
+use crate::influxdb;
+use chrono::Local;
+use serde_derive::Serialize;
+use std::fmt;
+use std::sync::Arc;
+
+/// Outcome of probing every configured backend.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProbeReport {
+    pub aiseg2: AisegReport,
+    /// `None` if InfluxDB is not among the configured sinks
+    pub influxdb: Option<InfluxReport>,
+}
+
+impl ProbeReport {
+    /// Whether every probed backend was reachable and accepted the credentials.
+    pub fn ok(&self) -> bool {
+        self.aiseg2.ok && self.influxdb.as_ref().is_none_or(|influxdb| influxdb.ok)
+    }
+}
+
+/// Outcome of probing AiSEG2.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AisegReport {
+    pub url: String,
+    pub ok: bool,
+    pub error: Option<String>,
+    /// Encoding, rooms and configured circuits; missing if AiSEG2 failed
+    #[serde(flatten)]
+    pub device: Option<DeviceInfo>,
+}
+
+/// Outcome of probing InfluxDB.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InfluxReport {
+    pub url: String,
+    pub api_version: InfluxApiVersion,
+    /// Bucket or database the forwarder writes to
+    pub target: String,
+    pub ok: bool,
+    pub error: Option<String>,
+    /// Server version, if InfluxDB reports it
+    pub version: Option<String>,
+}
+
+/// Probes AiSEG2 at `url` through `client`, and InfluxDB if `influx_config`
+/// is given. Both are probed concurrently.
+pub async fn probe(
+    url: &str,
+    client: &Arc<aiseg::Client>,
+    influx_config: Option<InfluxConfig>,
+) -> ProbeReport {
+    let (aiseg2, influxdb) = tokio::join!(probe_aiseg(url, client), async {
+        match influx_config {
+            Some(config) => Some(probe_influx(config).await),
+            None => None,
+        }
+    });
+    ProbeReport { aiseg2, influxdb }
+}
+
+/// Reads the device information from AiSEG2.
+async fn probe_aiseg(url: &str, client: &Arc<aiseg::Client>) -> AisegReport {
+    let result = aiseg::device_info(client, Local::now()).await;
+    AisegReport {
+        url: url.to_string(),
+        ok: result.is_ok(),
+        error: result.as_ref().err().map(CollectorError::detail),
+        device: result.ok(),
+    }
+}
+
+/// Checks the InfluxDB write endpoint with the configured credentials.
+async fn probe_influx(config: InfluxConfig) -> InfluxReport {
+    let url = config.url.clone();
+    let api_version = config.api_version;
+    let client = influxdb::Client::new(config);
+    let result = client.probe().await;
+    InfluxReport {
+        url,
+        api_version,
+        target: client.target(),
+        ok: result.is_ok(),
+        error: result.as_ref().err().map(ToString::to_string),
+        version: result.ok().flatten(),
+    }
+}
+
+impl fmt::Display for ProbeReport {
+    /// One section per backend, e.g.
+    ///
+    /// ```text
+    /// AiSEG2 http://192.168.0.216: ok
+    ///   Encoding: UTF-8
+    ///   Rooms: リビング, 主寝室
+    ///   Configured circuits: EV(kWh)
+    /// InfluxDB http://localhost:8086 (v2, aiseg2): ok, version v2.7.12
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let aiseg2 = &self.aiseg2;
+        match (&aiseg2.device, &aiseg2.error) {
+            (Some(device), _) => {
+                writeln!(f, "AiSEG2 {}: ok", aiseg2.url)?;
+                writeln!(f, "  Encoding: {}", device.encoding)?;
+                writeln!(f, "  Rooms: {}", device.rooms.join(", "))?;
+                writeln!(
+                    f,
+                    "  Configured circuits: {}",
+                    device.configured_circuits.join(", ")
+                )?;
+            }
+            (None, error) => writeln!(
+                f,
+                "AiSEG2 {}: failed: {}",
+                aiseg2.url,
+                error.as_deref().unwrap_or_default()
+            )?,
+        }
+
+        match &self.influxdb {
+            Some(influxdb) => {
+                let api_version = format!("{:?}", influxdb.api_version).to_lowercase();
+                write!(
+                    f,
+                    "InfluxDB {} ({}, {}): ",
+                    influxdb.url, api_version, influxdb.target
+                )?;
+                match (&influxdb.error, &influxdb.version) {
+                    (Some(error), _) => writeln!(f, "failed: {}", error),
+                    (None, Some(version)) => writeln!(f, "ok, version {}", version),
+                    (None, None) => writeln!(f, "ok"),
+                }
+            }
+            None => writeln!(f, "InfluxDB: not probed, it is not in SINKS"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::SimulatorConfig;
+    use crate::test_utils::fake_influx::{self, FakeInfluxDb};
+    use crate::test_utils::mocks::simulator_client;
+
+    mod succeeds {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_probe() {
+            let (simulator, client) = simulator_client(SimulatorConfig::default()).await;
+            let fake = FakeInfluxDb::start().await;
+
+            let report = probe(&simulator.url(), &client, Some(fake.config())).await;
+
+            assert!(report.ok());
+            let device = report.aiseg2.device.as_ref().unwrap();
+            assert_eq!(device.rooms, SimulatorConfig::default().rooms);
+            let influxdb = report.influxdb.as_ref().unwrap();
+            assert_eq!(influxdb.target, fake_influx::BUCKET);
+            assert_eq!(influxdb.version.as_deref(), Some(fake_influx::VERSION));
+
+            let text = report.to_string();
+            assert!(text.contains(": ok\n  Encoding: UTF-8\n"));
+            assert!(text.contains("  Rooms: リビング, 主寝室, 洋室１, 洋室２, 和室\n"));
+            assert!(text.contains("  Configured circuits: EV(kWh), "));
+            assert!(text.contains(&format!("(v2, {}): ok, version", fake_influx::BUCKET)));
+
+            let json = serde_json::to_value(&report).unwrap();
+            assert_eq!(json["aiseg2"]["ok"], true);
+            assert_eq!(json["aiseg2"]["encoding"], "UTF-8");
+            assert_eq!(json["aiseg2"]["configured_circuits"][0], "EV(kWh)");
+            assert_eq!(json["influxdb"]["api_version"], "v2");
+        }
+
+        #[tokio::test]
+        async fn test_probe_without_influxdb() {
+            let (simulator, client) = simulator_client(SimulatorConfig::default()).await;
+
+            let report = probe(&simulator.url(), &client, None).await;
+
+            assert!(report.ok());
+            assert!(report.to_string().contains("not in SINKS"));
+            assert!(serde_json::to_value(&report).unwrap()["influxdb"].is_null());
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_probe_fails() {
+            let (simulator, client) = simulator_client(SimulatorConfig::default()).await;
+            let fake = FakeInfluxDb::start().await;
+            let unreachable = Arc::new(aiseg::Client::new(
+                crate::test_utils::config::test_aiseg2_config_with_url("http://localhost:1"),
+            ));
+            let test_cases = vec![
+                (
+                    "wrong token",
+                    simulator.url().to_string(),
+                    &client,
+                    InfluxConfig {
+                        token: "wrong".to_string(),
+                        ..fake.config()
+                    },
+                    true,
+                    false,
+                ),
+                (
+                    "aiseg2 unreachable",
+                    "http://localhost:1".to_string(),
+                    &unreachable,
+                    fake.config(),
+                    false,
+                    true,
+                ),
+            ];
+
+            for (name, url, client, influx_config, aiseg_ok, influx_ok) in test_cases {
+                let report = probe(&url, client, Some(influx_config)).await;
+
+                assert!(!report.ok(), "case: {}", name);
+                assert_eq!(report.aiseg2.ok, aiseg_ok, "case: {}", name);
+                assert_eq!(report.aiseg2.device.is_some(), aiseg_ok, "case: {}", name);
+                let influxdb = report.influxdb.as_ref().unwrap();
+                assert_eq!(influxdb.ok, influx_ok, "case: {}", name);
+                assert!(report.to_string().contains("failed: "), "case: {}", name);
+            }
+        }
+    }
+}
@@ -0,0 +1,370 @@
+//! Per-collector schedules.
+//!
+//! Every collector runs as its own job with an interval or a cron schedule,
+//! a timeout and an enable flag, so slowly changing metrics are not polled
+//! as often as power readings. Jobs collect once at startup and then
+//! follow their schedule.
+//!
+//! Interval ticks are aligned to the local wall clock, e.g. a 5-second job
+//! ticks at :00, :05, :10 seconds, so collectors line up with each other and
+//! collection time does not add up to drift. A tick missed because the
+//! previous collection overran is skipped rather than run late.
+
+use crate::config::CollectorScheduleConfig;
+use crate::error::ConfigError;
+use crate::model::MetricCollector;
+use chrono::{DateTime, Local};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// When a job collects.
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    /// Every multiple of an interval on the local wall clock
+    Every(Duration),
+    /// The next time matching a cron expression
+    Cron(Box<cron::Schedule>),
+}
+
+impl Schedule {
+    /// Parses a cron expression with five fields, or with a leading seconds
+    /// field and an optional trailing year field.
+    pub fn cron(expression: &str) -> Result<Self, cron::error::Error> {
+        let expression = expression.trim();
+        let expression = if expression.split_whitespace().count() == 5 {
+            format!("0 {}", expression)
+        } else {
+            expression.to_string()
+        };
+        cron::Schedule::from_str(&expression).map(|schedule| Self::Cron(Box::new(schedule)))
+    }
+
+    /// First tick strictly after `now`.
+    ///
+    /// Interval ticks fall on multiples of the interval counted from the
+    /// local midnight of 1970-01-01, so hourly ticks are on the hour and
+    /// daily ticks at local midnight.
+    ///
+    /// # Returns
+    /// `None` when a cron schedule has no upcoming times left
+    pub fn next_tick(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        match self {
+            Self::Every(interval) => {
+                let step = i64::try_from(interval.as_nanos()).ok()?;
+                if step == 0 {
+                    return Some(now);
+                }
+                let offset = i64::from(now.offset().local_minus_utc()) * 1_000_000_000;
+                let local = now.timestamp_nanos_opt()? + offset;
+                let next = (local.div_euclid(step) + 1).checked_mul(step)?;
+                Some(now + chrono::Duration::nanoseconds(next - local))
+            }
+            Self::Cron(schedule) => schedule.after(&now).next(),
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Every(interval) => write!(f, "every {:?}", interval),
+            Self::Cron(schedule) => write!(f, "cron '{}'", schedule),
+        }
+    }
+}
+
+/// Waits until the wall clock reaches `at`.
+pub async fn sleep_until(at: DateTime<Local>) {
+    sleep((at - Local::now()).to_std().unwrap_or(Duration::ZERO)).await;
+}
+
+/// A collector together with when and how long it may run.
+pub struct Job {
+    /// Name the schedule is configured under, e.g. `climate`
+    pub name: &'static str,
+    pub collectors: Arc<Vec<Box<dyn MetricCollector>>>,
+    pub schedule: Schedule,
+    pub timeout_seconds: u64,
+}
+
+impl Job {
+    /// Builds the job running `collector` as configured by `config`.
+    ///
+    /// # Arguments
+    /// * `default_interval_sec` - Interval used when neither an interval nor
+    ///   a cron schedule is configured
+    /// * `default_timeout_seconds` - Timeout used when none is configured,
+    ///   cut to the interval so a collection ends before the next tick
+    ///
+    /// # Returns
+    /// - `Ok(None)` if the collector is disabled
+    /// - `Err(ConfigError::Invalid)` if the cron expression cannot be parsed
+    ///   or the configured timeout exceeds the interval
+    pub fn configure(
+        name: &'static str,
+        collector: Arc<dyn MetricCollector>,
+        config: &CollectorScheduleConfig,
+        default_interval_sec: u64,
+        default_timeout_seconds: u64,
+    ) -> Result<Option<Self>, ConfigError> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let schedule = match &config.schedule {
+            Some(expression) => Schedule::cron(expression).map_err(|e| {
+                ConfigError::invalid(
+                    format!("COLLECTOR_{}_SCHEDULE", name.to_uppercase()),
+                    e.to_string(),
+                )
+            })?,
+            None => Schedule::Every(Duration::from_secs(
+                config.interval_sec.unwrap_or(default_interval_sec),
+            )),
+        };
+        let timeout_seconds = match (&schedule, config.timeout_seconds) {
+            (Schedule::Every(interval), Some(timeout)) if timeout > interval.as_secs() => {
+                return Err(ConfigError::invalid(
+                    format!("COLLECTOR_{}_TIMEOUT_SECONDS", name.to_uppercase()),
+                    format!(
+                        "{} exceeds the collector's interval of {} seconds",
+                        timeout,
+                        interval.as_secs()
+                    ),
+                ));
+            }
+            (_, Some(timeout)) => timeout,
+            (Schedule::Every(interval), None) => default_timeout_seconds.min(interval.as_secs()),
+            (Schedule::Cron(_), None) => default_timeout_seconds,
+        };
+        Ok(Some(Self {
+            name,
+            collectors: Arc::new(vec![Box::new(collector)]),
+            schedule,
+            timeout_seconds,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::mocks::MockMetricCollector;
+    use chrono::TimeZone;
+
+    fn collector() -> Arc<dyn MetricCollector> {
+        Arc::new(MockMetricCollector::new_success())
+    }
+
+    fn enabled() -> CollectorScheduleConfig {
+        CollectorScheduleConfig {
+            enabled: true,
+            ..CollectorScheduleConfig::default()
+        }
+    }
+
+    mod succeeds {
+        use super::*;
+
+        #[test]
+        fn test_schedule_next_tick() {
+            let at = |h, m, s| Local.with_ymd_and_hms(2026, 10, 18, h, m, s).unwrap();
+            let now = at(9, 59, 32) + chrono::Duration::milliseconds(250);
+            let every = |secs| Schedule::Every(Duration::from_secs(secs));
+
+            let test_cases = vec![
+                ("five seconds", every(5), now, Some(at(9, 59, 35))),
+                (
+                    "on a boundary",
+                    every(5),
+                    at(9, 59, 35),
+                    Some(at(9, 59, 40)),
+                ),
+                ("minute", every(60), now, Some(at(10, 0, 0))),
+                ("hour", every(3600), now, Some(at(10, 0, 0))),
+                (
+                    "day",
+                    every(86400),
+                    now,
+                    Some(at(0, 0, 0) + chrono::Duration::days(1)),
+                ),
+                (
+                    "sub-second",
+                    Schedule::Every(Duration::from_millis(100)),
+                    now,
+                    Some(now + chrono::Duration::milliseconds(50)),
+                ),
+                ("zero interval", every(0), now, Some(now)),
+                (
+                    "hourly with five fields",
+                    Schedule::cron("0 * * * *").unwrap(),
+                    now,
+                    Some(at(10, 0, 0)),
+                ),
+                (
+                    "every minute with seconds",
+                    Schedule::cron("15 * * * * *").unwrap(),
+                    now,
+                    Some(at(10, 0, 15)),
+                ),
+                (
+                    "no upcoming time",
+                    Schedule::cron("0 0 0 1 1 * 2020").unwrap(),
+                    now,
+                    None,
+                ),
+            ];
+
+            for (name, schedule, now, expected) in test_cases {
+                assert_eq!(schedule.next_tick(now), expected, "case: {}", name);
+            }
+        }
+
+        #[tokio::test]
+        async fn test_sleep_until() {
+            let started = std::time::Instant::now();
+
+            sleep_until(Local::now() + chrono::Duration::milliseconds(50)).await;
+            // A time in the past returns at once
+            sleep_until(Local::now() - chrono::Duration::hours(1)).await;
+
+            let elapsed = started.elapsed();
+            assert!(elapsed >= Duration::from_millis(50));
+            assert!(elapsed < Duration::from_secs(1));
+        }
+
+        #[test]
+        fn test_schedule_display() {
+            assert_eq!(
+                Schedule::Every(Duration::from_secs(5)).to_string(),
+                "every 5s"
+            );
+            assert_eq!(
+                Schedule::cron("0 * * * *").unwrap().to_string(),
+                "cron '0 0 * * * *'"
+            );
+        }
+
+        #[test]
+        fn test_configure() {
+            let test_cases = vec![
+                ("defaults", enabled(), "every 60s", 10),
+                (
+                    "interval and timeout",
+                    CollectorScheduleConfig {
+                        interval_sec: Some(5),
+                        timeout_seconds: Some(3),
+                        ..enabled()
+                    },
+                    "every 5s",
+                    3,
+                ),
+                (
+                    "default timeout cut to interval",
+                    CollectorScheduleConfig {
+                        interval_sec: Some(5),
+                        ..enabled()
+                    },
+                    "every 5s",
+                    5,
+                ),
+                (
+                    "schedule overrides interval",
+                    CollectorScheduleConfig {
+                        interval_sec: Some(5),
+                        schedule: Some("0 * * * *".to_string()),
+                        ..enabled()
+                    },
+                    "cron '0 0 * * * *'",
+                    10,
+                ),
+            ];
+
+            for (name, config, schedule, timeout_seconds) in test_cases {
+                let job = Job::configure("climate", collector(), &config, 60, 10)
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(job.name, "climate", "case: {}", name);
+                assert_eq!(job.schedule.to_string(), schedule, "case: {}", name);
+                assert_eq!(job.timeout_seconds, timeout_seconds, "case: {}", name);
+                assert_eq!(job.collectors.len(), 1, "case: {}", name);
+            }
+        }
+
+        #[test]
+        fn test_configure_disabled() {
+            let config = CollectorScheduleConfig {
+                enabled: false,
+                ..CollectorScheduleConfig::default()
+            };
+
+            let job = Job::configure("climate", collector(), &config, 60, 10).unwrap();
+
+            assert!(job.is_none());
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[test]
+        fn test_configure_invalid_schedule() {
+            let test_cases = vec![
+                ("garbage", "every hour"),
+                ("out of range", "0 25 * * *"),
+                ("too few fields", "* * *"),
+            ];
+
+            for (name, expression) in test_cases {
+                let config = CollectorScheduleConfig {
+                    schedule: Some(expression.to_string()),
+                    ..enabled()
+                };
+
+                let result = Job::configure("daily_total", collector(), &config, 60, 10);
+
+                assert!(
+                    matches!(
+                        result,
+                        Err(ConfigError::Invalid { ref field, .. })
+                            if field == "COLLECTOR_DAILY_TOTAL_SCHEDULE"
+                    ),
+                    "case: {}",
+                    name
+                );
+            }
+        }
+
+        #[test]
+        fn test_configure_timeout_exceeds_interval() {
+            let test_cases = vec![("own interval", Some(5), 5), ("default interval", None, 60)];
+
+            for (name, interval_sec, interval) in test_cases {
+                let config = CollectorScheduleConfig {
+                    interval_sec,
+                    timeout_seconds: Some(interval + 1),
+                    ..enabled()
+                };
+
+                let result = Job::configure("power", collector(), &config, 60, 10);
+
+                let Err(ConfigError::Invalid { field, message }) = result else {
+                    panic!("case: {}: expected an invalid timeout", name);
+                };
+                assert_eq!(field, "COLLECTOR_POWER_TIMEOUT_SECONDS", "case: {}", name);
+                assert_eq!(
+                    message,
+                    format!(
+                        "{} exceeds the collector's interval of {} seconds",
+                        interval + 1,
+                        interval
+                    ),
+                    "case: {}",
+                    name
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! Server side of HTTP digest authentication (RFC 2617, MD5).
+//!
+//! Only what the forwarder's client needs is implemented: `qop=auth` and the
+//! legacy no-qop form. Nonces are not tracked, so replayed requests are
+//! accepted; this is a simulator, not a security boundary.
+
+use md5::{Digest, Md5};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Realm announced in the `WWW-Authenticate` challenge.
+pub const REALM: &str = "AiSEG2";
+
+/// Credentials the simulator accepts.
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    pub user: String,
+    pub password: String,
+}
+
+/// Issues challenges and verifies digest responses.
+pub struct DigestAuth {
+    credentials: Credentials,
+    nonce_counter: AtomicU64,
+}
+
+impl DigestAuth {
+    /// Creates a verifier for the given credentials.
+    pub fn new(credentials: Credentials) -> Self {
+        Self {
+            credentials,
+            nonce_counter: AtomicU64::new(0),
+        }
+    }
+
+    /// Builds a fresh `WWW-Authenticate` header value.
+    pub fn challenge(&self) -> String {
+        let counter = self.nonce_counter.fetch_add(1, Ordering::Relaxed);
+        let nonce = md5_hex(&format!("{}:{}", REALM, counter));
+        format!(
+            r#"Digest realm="{}", nonce="{}", qop="auth", algorithm=MD5"#,
+            REALM, nonce
+        )
+    }
+
+    /// Returns true if the `Authorization` header carries a valid digest response.
+    pub fn verify(&self, method: &str, authorization: Option<&str>) -> bool {
+        let Some(params) = authorization.and_then(parse_digest_header) else {
+            return false;
+        };
+        let field = |name: &str| params.get(name).map(String::as_str).unwrap_or_default();
+
+        if field("username") != self.credentials.user {
+            return false;
+        }
+
+        let ha1 = md5_hex(&format!(
+            "{}:{}:{}",
+            self.credentials.user,
+            field("realm"),
+            self.credentials.password
+        ));
+        let ha2 = md5_hex(&format!("{}:{}", method, field("uri")));
+        let expected = match params.get("qop") {
+            Some(qop) => md5_hex(&format!(
+                "{}:{}:{}:{}:{}:{}",
+                ha1,
+                field("nonce"),
+                field("nc"),
+                field("cnonce"),
+                qop,
+                ha2
+            )),
+            None => md5_hex(&format!("{}:{}:{}", ha1, field("nonce"), ha2)),
+        };
+
+        field("response").eq_ignore_ascii_case(&expected)
+    }
+}
+
+/// Parses `Digest key="value", key=value, ...` into a map.
+fn parse_digest_header(header: &str) -> Option<HashMap<String, String>> {
+    let rest = header.trim().strip_prefix("Digest")?;
+    let mut params = HashMap::new();
+    let mut chars = rest.chars().peekable();
+
+    loop {
+        // Skip separators
+        while chars.peek().is_some_and(|c| *c == ',' || c.is_whitespace()) {
+            chars.next();
+        }
+        let key: String = chars.by_ref().take_while(|c| *c != '=').collect();
+        if key.is_empty() {
+            break;
+        }
+
+        let value = if chars.peek() == Some(&'"') {
+            chars.next();
+            let mut value = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => value.extend(chars.next()),
+                    '"' => break,
+                    _ => value.push(c),
+                }
+            }
+            value
+        } else {
+            chars.by_ref().take_while(|c| *c != ',').collect()
+        };
+
+        params.insert(key.trim().to_ascii_lowercase(), value.trim().to_string());
+    }
+
+    Some(params)
+}
+
+/// Returns the lowercase hex MD5 digest of `input`.
+fn md5_hex(input: &str) -> String {
+    format!("{:x}", Md5::digest(input.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auth() -> DigestAuth {
+        DigestAuth::new(Credentials {
+            user: "aiseg".to_string(),
+            password: "secret".to_string(),
+        })
+    }
+
+    /// Builds an Authorization header the way a digest client would.
+    fn authorization(user: &str, password: &str, uri: &str) -> String {
+        let ha1 = md5_hex(&format!("{}:{}:{}", user, REALM, password));
+        let ha2 = md5_hex(&format!("GET:{}", uri));
+        let response = md5_hex(&format!("{}:abc:00000001:xyz:auth:{}", ha1, ha2));
+        format!(
+            r#"Digest username="{}", realm="{}", nonce="abc", uri="{}", qop=auth, nc=00000001, cnonce="xyz", response="{}""#,
+            user, REALM, uri, response
+        )
+    }
+
+    mod succeeds {
+        use super::*;
+
+        #[test]
+        fn test_verifies_valid_response() {
+            let header = authorization("aiseg", "secret", "/page/electricflow/111");
+            assert!(auth().verify("GET", Some(&header)));
+        }
+
+        #[test]
+        fn test_challenge_format() {
+            let challenge = auth().challenge();
+            assert!(challenge.starts_with(r#"Digest realm="AiSEG2", nonce=""#));
+            assert!(challenge.contains(r#"qop="auth""#));
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[test]
+        fn test_rejects_invalid_responses() {
+            let test_cases = vec![
+                ("missing header", None),
+                (
+                    "wrong password",
+                    Some(authorization("aiseg", "wrong", "/page/electricflow/111")),
+                ),
+                (
+                    "wrong user",
+                    Some(authorization("other", "secret", "/page/electricflow/111")),
+                ),
+                ("basic auth", Some("Basic YWlzZWc6c2VjcmV0".to_string())),
+            ];
+
+            for (name, header) in test_cases {
+                assert!(!auth().verify("GET", header.as_deref()), "case: {}", name);
+            }
+        }
+    }
+}
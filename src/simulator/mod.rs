@@ -0,0 +1,662 @@
+//! AiSEG2 device simulator.
+//!
+//! Serves the pages the forwarder scrapes — the electricity flow, per-device
+//! consumption, room climate and daily total graphs — behind HTTP digest
+//! authentication, with values that change over the day. It backs the
+//! `aiseg2-simulator` binary for running the whole pipeline without a real
+//! AiSEG2, and is spawned in-process by tests.
+//!
+//! The simulator only depends on external crates so the binary can include
+//! this module directly.
+//!
+//! # Faults
+//! [`Faults`] injects slow responses and periodic error statuses, and
+//! [`Markup`] switches between page encodings seen on different firmware
+//! versions (undeclared Shift_JIS/EUC-JP bodies).
+
+mod digest;
+mod pages;
+mod values;
+
+pub use digest::Credentials;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{header, HeaderMap, Method, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chrono::{DateTime, Local, NaiveDate};
+use digest::DigestAuth;
+use pages::{DEVICES_PER_PAGE, ROOMS_PER_PAGE};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+/// Daily total graphs: graph ID, title, seed and full-day scale.
+const DAILY_GRAPHS: [(&str, &str, u64, f64); 6] = [
+    ("51111", "発電量", 1, 25.0),
+    ("52111", "消費量", 2, 18.0),
+    ("53111", "買電量", 3, 8.0),
+    ("54111", "売電量", 4, 14.0),
+    ("55111", "給湯量", 5, 300.0),
+    ("57111", "ガス量", 7, 1.5),
+];
+
+/// Graph ID of the per-circuit daily total page.
+const CIRCUIT_GRAPH_ID: &str = "584";
+
+/// A metered circuit served on the circuit daily total graph.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Circuit {
+    pub id: String,
+    pub name: String,
+}
+
+impl Circuit {
+    pub fn new(id: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            name: name.into(),
+        }
+    }
+}
+
+impl FromStr for Circuit {
+    type Err = String;
+
+    /// Parses `id:name`, e.g. `30:EV`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some((id, name)) if !id.trim().is_empty() && !name.trim().is_empty() => {
+                Ok(Self::new(id.trim(), name.trim()))
+            }
+            _ => Err(format!("invalid circuit '{}', expected id:name", s)),
+        }
+    }
+}
+
+/// Source of the simulated current time.
+#[derive(Debug, Clone, Default)]
+pub enum SimClock {
+    /// Follow the system clock
+    #[default]
+    System,
+    /// Always report the given instant, for reproducible values in tests
+    Fixed(DateTime<Local>),
+}
+
+impl SimClock {
+    fn now(&self) -> DateTime<Local> {
+        match self {
+            SimClock::System => Local::now(),
+            SimClock::Fixed(instant) => *instant,
+        }
+    }
+}
+
+/// Page markup variant.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Markup {
+    /// UTF-8 pages with a declared charset
+    #[default]
+    Standard,
+    /// Standard pages encoded as Shift_JIS without a declared charset
+    ShiftJis,
+    /// Standard pages encoded as EUC-JP without a declared charset
+    EucJp,
+}
+
+impl FromStr for Markup {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace(['-', '_'], "").as_str() {
+            "standard" => Ok(Markup::Standard),
+            "shiftjis" | "sjis" => Ok(Markup::ShiftJis),
+            "eucjp" => Ok(Markup::EucJp),
+            _ => Err(format!(
+                "unknown markup '{}', expected standard, shift_jis or euc-jp",
+                s
+            )),
+        }
+    }
+}
+
+/// Injected faults, applied to authenticated requests only.
+#[derive(Debug, Clone)]
+pub struct Faults {
+    /// Delay added before every response
+    pub latency: Duration,
+    /// Answer every Nth request with `error_status` instead of the page
+    pub error_every: Option<u64>,
+    /// Status code used for injected errors
+    pub error_status: StatusCode,
+}
+
+impl Default for Faults {
+    fn default() -> Self {
+        Self {
+            latency: Duration::ZERO,
+            error_every: None,
+            error_status: StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Simulator configuration.
+#[derive(Debug, Clone)]
+pub struct SimulatorConfig {
+    pub credentials: Credentials,
+    /// Room names shown on the climate pages
+    pub rooms: Vec<String>,
+    /// Device names shown on the consumption pages
+    pub devices: Vec<String>,
+    /// Generation source names shown on the electricity flow page
+    pub generation_sources: Vec<String>,
+    pub circuits: Vec<Circuit>,
+    pub clock: SimClock,
+    pub markup: Markup,
+    pub faults: Faults,
+}
+
+impl Default for SimulatorConfig {
+    /// A typical household: two pages of devices, two pages of rooms and
+    /// the circuits the forwarder collects.
+    fn default() -> Self {
+        let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        Self {
+            credentials: Credentials {
+                user: "aiseg".to_string(),
+                password: "aiseg".to_string(),
+            },
+            rooms: names(&["リビング", "主寝室", "洋室１", "洋室２", "和室"]),
+            devices: names(&[
+                "エコキュート",
+                "IHクッキングヒーター",
+                "冷蔵庫",
+                "洗濯機",
+                "食器洗い乾燥機",
+                "リビングエアコン",
+                "主寝室エアコン",
+                "洋室２エアコン",
+                "EV",
+                "照明",
+                "浴室換気乾燥機",
+                "コンセント",
+            ]),
+            generation_sources: names(&["太陽光"]),
+            circuits: vec![
+                Circuit::new("30", "EV"),
+                Circuit::new("27", "リビングエアコン"),
+                Circuit::new("26", "主寝室エアコン"),
+                Circuit::new("25", "洋室２エアコン"),
+            ],
+            clock: SimClock::default(),
+            markup: Markup::default(),
+            faults: Faults::default(),
+        }
+    }
+}
+
+/// Shared state of a running simulator.
+struct SimState {
+    config: SimulatorConfig,
+    auth: DigestAuth,
+    requests: AtomicU64,
+}
+
+/// A simulator serving on a local port, stopped when dropped.
+pub struct RunningSimulator {
+    addr: SocketAddr,
+    state: Arc<SimState>,
+    handle: JoinHandle<()>,
+}
+
+impl RunningSimulator {
+    /// Base URL to use as `AISEG2_URL`.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Number of authenticated requests served so far.
+    pub fn requests(&self) -> u64 {
+        self.state.requests.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for RunningSimulator {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// Starts a simulator on an ephemeral localhost port.
+pub async fn spawn(config: SimulatorConfig) -> std::io::Result<RunningSimulator> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let state = new_state(config);
+    let router = router(Arc::clone(&state));
+    let handle = tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, router).await {
+            tracing::error!("Simulator stopped: {}", e);
+        }
+    });
+
+    Ok(RunningSimulator {
+        addr,
+        state,
+        handle,
+    })
+}
+
+/// Serves the simulator on `listener` until the process exits.
+pub async fn serve(listener: TcpListener, config: SimulatorConfig) -> std::io::Result<()> {
+    axum::serve(listener, router(new_state(config))).await
+}
+
+fn new_state(config: SimulatorConfig) -> Arc<SimState> {
+    Arc::new(SimState {
+        auth: DigestAuth::new(config.credentials.clone()),
+        config,
+        requests: AtomicU64::new(0),
+    })
+}
+
+fn router(state: Arc<SimState>) -> Router {
+    Router::new().fallback(handle).with_state(state)
+}
+
+/// Authenticates, applies faults and renders the requested page.
+async fn handle(
+    State(state): State<Arc<SimState>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Response {
+    let authorization = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+    if !state.auth.verify(method.as_str(), authorization) {
+        return (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, state.auth.challenge())],
+        )
+            .into_response();
+    }
+
+    let count = state.requests.fetch_add(1, Ordering::Relaxed) + 1;
+    let faults = &state.config.faults;
+    if !faults.latency.is_zero() {
+        tokio::time::sleep(faults.latency).await;
+    }
+    if faults.error_every.is_some_and(|n| n > 0 && count % n == 0) {
+        tracing::debug!(path = %uri, "Injecting {}", faults.error_status);
+        return faults.error_status.into_response();
+    }
+
+    let now = state.config.clock.now();
+    match render(&state.config, uri.path(), uri.query(), now) {
+        Some(html) => respond(state.config.markup, html),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Encodes a rendered page according to the markup variant.
+fn respond(markup: Markup, html: String) -> Response {
+    let (content_type, body) = match markup {
+        Markup::Standard => ("text/html; charset=UTF-8", html.into_bytes()),
+        Markup::ShiftJis => ("text/html", encoding_rs::SHIFT_JIS.encode(&html).0.into()),
+        Markup::EucJp => ("text/html", encoding_rs::EUC_JP.encode(&html).0.into()),
+    };
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .body(Body::from(body))
+        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+/// Renders the page at `path`, or `None` if the simulator does not serve it.
+fn render(
+    config: &SimulatorConfig,
+    path: &str,
+    query: Option<&str>,
+    now: DateTime<Local>,
+) -> Option<String> {
+    match path {
+        "/page/electricflow/111" => {
+            let sources: Vec<(&str, f64)> = config
+                .generation_sources
+                .iter()
+                .enumerate()
+                .map(|(i, name)| (name.as_str(), values::generation_kw(i, now)))
+                .collect();
+            let generation_kw = sources.iter().map(|(_, kw)| kw).sum();
+            let consumption_w: u32 = (0..config.devices.len())
+                .map(|i| values::device_watts(i, now))
+                .sum();
+            Some(pages::electricflow(
+                generation_kw,
+                consumption_w as f64 / 1000.0,
+                &sources,
+            ))
+        }
+        "/page/electricflow/1113" => {
+            let requested = query_param(query, "id")?.parse().ok()?;
+            let offset = page_offset(config.devices.len(), DEVICES_PER_PAGE, requested);
+            let devices: Vec<(&str, u32)> = config
+                .devices
+                .iter()
+                .enumerate()
+                .skip(offset)
+                .take(DEVICES_PER_PAGE)
+                .map(|(i, name)| (name.as_str(), values::device_watts(i, now)))
+                .collect();
+            Some(pages::consumption(&devices))
+        }
+        "/page/airenvironment/41" => {
+            let requested = query_param(query, "page")?.parse().ok()?;
+            let offset = page_offset(config.rooms.len(), ROOMS_PER_PAGE, requested);
+            let rooms: Vec<(&str, f64, f64)> = config
+                .rooms
+                .iter()
+                .enumerate()
+                .skip(offset)
+                .take(ROOMS_PER_PAGE)
+                .map(|(i, name)| {
+                    (
+                        name.as_str(),
+                        values::temperature(i, now),
+                        values::humidity(i, now),
+                    )
+                })
+                .collect();
+            Some(pages::climate(&rooms))
+        }
+        _ => {
+            let graph_id = path.strip_prefix("/page/graph/")?;
+            let query = GraphQuery::decode(query_param(query, "data")?)?;
+            render_graph(config, graph_id, &query, now)
+        }
+    }
+}
+
+/// Renders a daily total graph page.
+fn render_graph(
+    config: &SimulatorConfig,
+    graph_id: &str,
+    query: &GraphQuery,
+    now: DateTime<Local>,
+) -> Option<String> {
+    if graph_id == CIRCUIT_GRAPH_ID {
+        let circuit_id = query.circuit_id.as_deref()?;
+        let circuit = config.circuits.iter().find(|c| c.id == circuit_id)?;
+        let seed = circuit_id.parse::<u64>().unwrap_or_default() + 100;
+        let value = values::daily_total(seed, 4.0, query.day, now);
+        return Some(pages::circuit(&circuit.name, value));
+    }
+
+    let (_, title, seed, scale) = DAILY_GRAPHS.iter().find(|(id, ..)| *id == graph_id)?;
+    Some(pages::graph(
+        title,
+        values::daily_total(*seed, *scale, query.day, now),
+    ))
+}
+
+/// Decoded `data` parameter of a graph page request.
+#[derive(Debug, PartialEq)]
+struct GraphQuery {
+    day: NaiveDate,
+    circuit_id: Option<String>,
+}
+
+impl GraphQuery {
+    /// Decodes base64 JSON such as `{"day":[2024,6,8],...,"circuitid":"30"}`.
+    fn decode(data: &str) -> Option<Self> {
+        let json: serde_json::Value = serde_json::from_slice(&STANDARD.decode(data).ok()?).ok()?;
+        let day = json.get("day")?.as_array()?;
+        let part = |i: usize| day.get(i).and_then(serde_json::Value::as_u64);
+        let day = NaiveDate::from_ymd_opt(part(0)? as i32, part(1)? as u32, part(2)? as u32)?;
+        let circuit_id = json
+            .get("circuitid")
+            .and_then(serde_json::Value::as_str)
+            .map(str::to_string);
+
+        Some(Self { day, circuit_id })
+    }
+}
+
+/// Maps a requested page number to the offset of its first item.
+///
+/// Requests past the end are answered with the last page, which is how the
+/// device behaves and what the forwarder's wrap-around detection relies on.
+fn page_offset(items: usize, per_page: usize, requested: usize) -> usize {
+    let total = items.div_ceil(per_page).max(1);
+    (requested.clamp(1, total) - 1) * per_page
+}
+
+/// Returns the raw value of a query parameter.
+///
+/// Values are not percent-decoded: the graph `data` parameter is sent as
+/// unescaped base64, where `+` must not turn into a space.
+fn query_param<'a>(query: Option<&'a str>, key: &str) -> Option<&'a str> {
+    query?
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn fixed_config() -> SimulatorConfig {
+        SimulatorConfig {
+            clock: SimClock::Fixed(Local.with_ymd_and_hms(2024, 6, 8, 12, 0, 0).unwrap()),
+            ..SimulatorConfig::default()
+        }
+    }
+
+    async fn get(
+        simulator: &RunningSimulator,
+        user: &str,
+        password: &str,
+        path: &str,
+    ) -> reqwest::Response {
+        use diqwest::WithDigestAuth;
+
+        reqwest::Client::new()
+            .get(format!("{}{}", simulator.url(), path))
+            .send_with_digest_auth(user, password)
+            .await
+            .unwrap()
+    }
+
+    fn graph_data(json: &str) -> String {
+        STANDARD.encode(json)
+    }
+
+    mod succeeds {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_serves_pages_with_digest_auth() {
+            let simulator = spawn(fixed_config()).await.unwrap();
+            let circuit = graph_data(
+                r#"{"day":[2024,6,7],"term":"2024/06/07","termStr":"day","id":"1","circuitid":"30"}"#,
+            );
+            let daily =
+                graph_data(r#"{"day":[2024,6,7],"month_compare":"mon","day_compare":"day"}"#);
+
+            let test_cases = vec![
+                ("/page/electricflow/111".to_string(), "g_capacity"),
+                ("/page/electricflow/1113?id=1".to_string(), "stage_10"),
+                ("/page/airenvironment/41?page=2".to_string(), "base2_1"),
+                (format!("/page/graph/51111?data={}", daily), "発電量"),
+                (format!("/page/graph/584?data={}", circuit), "EV"),
+            ];
+
+            for (path, expected) in test_cases {
+                let response = get(&simulator, "aiseg", "aiseg", &path).await;
+                assert_eq!(response.status(), StatusCode::OK, "case: {}", path);
+                let body = response.text().await.unwrap();
+                assert!(body.contains(expected), "case: {} body: {}", path, body);
+            }
+            assert_eq!(simulator.requests(), 5);
+        }
+
+        #[tokio::test]
+        async fn test_out_of_range_page_repeats_last_page() {
+            let simulator = spawn(fixed_config()).await.unwrap();
+
+            let last = get(
+                &simulator,
+                "aiseg",
+                "aiseg",
+                "/page/airenvironment/41?page=2",
+            )
+            .await;
+            let beyond = get(
+                &simulator,
+                "aiseg",
+                "aiseg",
+                "/page/airenvironment/41?page=9",
+            )
+            .await;
+            assert_eq!(last.text().await.unwrap(), beyond.text().await.unwrap());
+        }
+
+        #[tokio::test]
+        async fn test_encodes_japanese_markup_variants() {
+            let test_cases = vec![
+                (Markup::ShiftJis, encoding_rs::SHIFT_JIS),
+                (Markup::EucJp, encoding_rs::EUC_JP),
+            ];
+
+            for (markup, encoding) in test_cases {
+                let simulator = spawn(SimulatorConfig {
+                    markup,
+                    ..fixed_config()
+                })
+                .await
+                .unwrap();
+
+                let response = get(
+                    &simulator,
+                    "aiseg",
+                    "aiseg",
+                    "/page/airenvironment/41?page=1",
+                )
+                .await;
+                assert_eq!(response.headers()[header::CONTENT_TYPE], "text/html");
+                let bytes = response.bytes().await.unwrap();
+                let (text, had_errors) = encoding.decode_without_bom_handling(&bytes);
+                assert!(!had_errors, "case: {:?}", markup);
+                assert!(text.contains("リビング"), "case: {:?}", markup);
+            }
+        }
+
+        #[test]
+        fn test_parse_config_values() {
+            assert_eq!("30:EV".parse(), Ok(Circuit::new("30", "EV")));
+            assert_eq!("Shift_JIS".parse(), Ok(Markup::ShiftJis));
+            assert_eq!("euc-jp".parse(), Ok(Markup::EucJp));
+            assert_eq!("STANDARD".parse(), Ok(Markup::Standard));
+        }
+
+        #[test]
+        fn test_page_offset() {
+            let test_cases = vec![
+                // (items, requested, expected offset)
+                (12, 1, 0),
+                (12, 2, 10),
+                (12, 5, 10),
+                (0, 1, 0),
+            ];
+
+            for (items, requested, offset) in test_cases {
+                assert_eq!(
+                    page_offset(items, 10, requested),
+                    offset,
+                    "case: {} items, page {}",
+                    items,
+                    requested
+                );
+            }
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_rejects_wrong_credentials() {
+            let simulator = spawn(fixed_config()).await.unwrap();
+            let response = get(&simulator, "aiseg", "wrong", "/page/electricflow/111").await;
+
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            assert_eq!(simulator.requests(), 0);
+        }
+
+        #[tokio::test]
+        async fn test_injects_errors_every_nth_request() {
+            let simulator = spawn(SimulatorConfig {
+                faults: Faults {
+                    error_every: Some(2),
+                    error_status: StatusCode::SERVICE_UNAVAILABLE,
+                    ..Faults::default()
+                },
+                ..fixed_config()
+            })
+            .await
+            .unwrap();
+
+            let mut statuses = Vec::new();
+            for _ in 0..4 {
+                let response = get(&simulator, "aiseg", "aiseg", "/page/electricflow/111").await;
+                statuses.push(response.status());
+            }
+
+            assert_eq!(
+                statuses,
+                vec![
+                    StatusCode::OK,
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    StatusCode::OK,
+                    StatusCode::SERVICE_UNAVAILABLE
+                ]
+            );
+        }
+
+        #[tokio::test]
+        async fn test_unknown_pages() {
+            let simulator = spawn(fixed_config()).await.unwrap();
+            let unknown_circuit = graph_data(r#"{"day":[2024,6,8],"circuitid":"99"}"#);
+
+            let test_cases = vec![
+                "/page/unknown".to_string(),
+                "/page/electricflow/1113".to_string(),
+                "/page/graph/51111?data=not-base64".to_string(),
+                format!("/page/graph/584?data={}", unknown_circuit),
+            ];
+
+            for path in test_cases {
+                let response = get(&simulator, "aiseg", "aiseg", &path).await;
+                assert_eq!(response.status(), StatusCode::NOT_FOUND, "case: {}", path);
+            }
+        }
+
+        #[test]
+        fn test_parse_invalid_config_values() {
+            assert!("EV".parse::<Circuit>().is_err());
+            assert!(":EV".parse::<Circuit>().is_err());
+            assert!("xml".parse::<Markup>().is_err());
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! HTML renderers mimicking the AiSEG2 web UI.
+//!
+//! Only the elements the forwarder's parsers read are reproduced, wrapped in
+//! enough surrounding markup to keep the selectors honest.
+
+use std::fmt::Write;
+
+/// Number of consumption devices shown per `/page/electricflow/1113` page.
+pub const DEVICES_PER_PAGE: usize = 10;
+
+/// Number of rooms shown per `/page/airenvironment/41` page.
+pub const ROOMS_PER_PAGE: usize = 3;
+
+/// Wraps page content in a minimal document.
+fn document(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html><head><title>{}</title></head>\n<body><div id=\"main\">\n{}</div></body></html>\n",
+        title, body
+    )
+}
+
+/// Renders `/page/electricflow/111`: totals in kW and generation sources.
+pub fn electricflow(generation_kw: f64, consumption_kw: f64, sources: &[(&str, f64)]) -> String {
+    let mut body = format!(
+        "<div id=\"g_capacity\">{:.2}</div><span class=\"unit\">kW</span>\n\
+         <div id=\"u_capacity\">{:.2}</div><span class=\"unit\">kW</span>\n",
+        generation_kw, consumption_kw
+    );
+    for (i, (name, kw)) in sources.iter().enumerate() {
+        let index = i + 1;
+        let _ = writeln!(
+            body,
+            "<div id=\"g_d_{index}_title\">{name}</div><div id=\"g_d_{index}_capacity\">{kw:.2}</div>"
+        );
+    }
+    document("電気の流れ", &body)
+}
+
+/// Renders one `/page/electricflow/1113` page of consumption devices in W.
+pub fn consumption(devices: &[(&str, u32)]) -> String {
+    let mut body = String::new();
+    for (i, (name, watts)) in devices.iter().enumerate() {
+        let _ = writeln!(
+            body,
+            "<div id=\"stage_{}\" class=\"stage\"><div class=\"c_device\">{}</div><div class=\"c_value\">{}</div></div>",
+            i + 1,
+            name,
+            watts
+        );
+    }
+    document("消費電力", &body)
+}
+
+/// Renders one `/page/airenvironment/41` page of rooms.
+///
+/// Temperatures and humidities are drawn as three digit sprites (`XX.X`)
+/// whose digit is carried in the `noN` class, as on the real device.
+pub fn climate(rooms: &[(&str, f64, f64)]) -> String {
+    let mut body = String::new();
+    for (i, (name, temperature, humidity)) in rooms.iter().enumerate() {
+        let base = i + 1;
+        let _ = write!(
+            body,
+            "<div id=\"base{base}_1\" class=\"base\"><div class=\"txt_name\">{name}</div><div class=\"num_wrapper\">"
+        );
+        body.push_str(&digit_spans("num_ond", base, *temperature));
+        body.push_str(&digit_spans("num_shitudo", base, *humidity));
+        body.push_str("</div></div>\n");
+    }
+    document("室内環境", &body)
+}
+
+/// Renders a value between 0.0 and 99.9 as three digit sprites.
+fn digit_spans(prefix: &str, base: usize, value: f64) -> String {
+    let tenths = (value * 10.0).round().clamp(0.0, 999.0) as u32;
+    let digits = [tenths / 100, tenths / 10 % 10, tenths % 10];
+    digits
+        .iter()
+        .enumerate()
+        .map(|(k, digit)| {
+            format!(
+                "<span id=\"{}_{}_{}\" class=\"num no{}\"></span>",
+                prefix,
+                base,
+                k + 1,
+                digit
+            )
+        })
+        .collect()
+}
+
+/// Renders a `/page/graph/5xxxx` daily total page.
+pub fn graph(title: &str, value: f64) -> String {
+    let body = format!(
+        "<div id=\"h_title\">{}</div>\n<div id=\"val_kwh\">{:.1}</div>\n",
+        title, value
+    );
+    document(title, &body)
+}
+
+/// Renders a `/page/graph/584` circuit daily total page.
+pub fn circuit(name: &str, value: f64) -> String {
+    let body = format!(
+        "<div id=\"h_title\">{}</div>\n<div id=\"val_kwh\">{:.1}</div>\n",
+        name, value
+    );
+    document("回路別", &body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod succeeds {
+        use super::*;
+
+        #[test]
+        fn test_digit_spans() {
+            let test_cases = vec![
+                (23.5, ["no2", "no3", "no5"]),
+                (5.0, ["no0", "no5", "no0"]),
+                (99.9, ["no9", "no9", "no9"]),
+            ];
+
+            for (value, expected) in test_cases {
+                let html = digit_spans("num_ond", 1, value);
+                for (k, class) in expected.iter().enumerate() {
+                    let span = format!("id=\"num_ond_1_{}\" class=\"num {}\"", k + 1, class);
+                    assert!(html.contains(&span), "case: {} missing {}", value, span);
+                }
+            }
+        }
+    }
+}
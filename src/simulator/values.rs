@@ -0,0 +1,158 @@
+//! Deterministic, time-varying synthetic readings.
+//!
+//! Every value is a pure function of the simulated time and a stable seed
+//! (device index, room index, circuit id), so two requests at the same instant
+//! always agree and tests with a fixed clock get reproducible numbers.
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, Timelike};
+use std::f64::consts::PI;
+
+/// Peak output of the simulated solar array in kW.
+const SOLAR_PEAK_KW: f64 = 4.5;
+
+/// Returns a pseudo-random number in `[0, 1)` derived from `seed` (splitmix64).
+pub fn noise(seed: u64) -> f64 {
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Hour of day as a fraction, e.g. 13:30 -> 13.5.
+fn fractional_hour(now: DateTime<Local>) -> f64 {
+    now.hour() as f64 + now.minute() as f64 / 60.0 + now.second() as f64 / 3600.0
+}
+
+/// Minutes since midnight, used as a slowly changing noise seed.
+fn minute_of_day(now: DateTime<Local>) -> u64 {
+    (now.hour() * 60 + now.minute()) as u64
+}
+
+/// Output of generation source `index` in kW.
+///
+/// The first source follows a daylight curve peaking at noon; additional
+/// sources (fuel cells, batteries) produce a small constant output.
+pub fn generation_kw(index: usize, now: DateTime<Local>) -> f64 {
+    if index > 0 {
+        return round_to(0.3 + 0.4 * noise(index as u64), 2);
+    }
+
+    let daylight = (PI * (fractional_hour(now) - 6.0) / 12.0).sin().max(0.0);
+    let cloud = 0.85 + 0.15 * noise(minute_of_day(now));
+    round_to(SOLAR_PEAK_KW * daylight * cloud, 2)
+}
+
+/// Power drawn by consumption device `index` in W.
+pub fn device_watts(index: usize, now: DateTime<Local>) -> u32 {
+    let base = 20.0 + 600.0 * noise(index as u64 * 7919);
+    let cycle = (2.0 * PI * (fractional_hour(now) + index as f64) / 24.0).sin();
+    let jitter = noise(index as u64 ^ (minute_of_day(now) << 8));
+    (base * (1.0 + 0.5 * cycle) + 30.0 * jitter)
+        .round()
+        .max(0.0) as u32
+}
+
+/// Temperature of room `index` in °C, between 0.0 and 99.9.
+pub fn temperature(index: usize, now: DateTime<Local>) -> f64 {
+    let daily = (2.0 * PI * (fractional_hour(now) - 9.0) / 24.0).sin();
+    let value = 21.0 + 4.0 * daily + 3.0 * noise(index as u64 * 31);
+    round_to(value.clamp(0.0, 99.9), 1)
+}
+
+/// Relative humidity of room `index` in %, between 0.0 and 99.9.
+pub fn humidity(index: usize, now: DateTime<Local>) -> f64 {
+    let daily = (2.0 * PI * fractional_hour(now) / 24.0).cos();
+    let value = 50.0 + 10.0 * daily + 8.0 * noise(index as u64 * 131);
+    round_to(value.clamp(0.0, 99.9), 1)
+}
+
+/// Fraction of `date` that has elapsed at `now`.
+///
+/// Past days are complete (1.0) and future days have not started (0.0), so
+/// daily totals grow during the day and stay fixed once it is over.
+fn day_progress(date: NaiveDate, now: DateTime<Local>) -> f64 {
+    let today = now.date_naive();
+    if date < today {
+        1.0
+    } else if date > today {
+        0.0
+    } else {
+        fractional_hour(now) / 24.0
+    }
+}
+
+/// Seed that differs per day so every day gets its own totals.
+fn day_seed(date: NaiveDate) -> u64 {
+    date.num_days_from_ce() as u64
+}
+
+/// Accumulated total of a daily graph for `date`, as of `now`.
+///
+/// `scale` is the full-day total on an average day.
+pub fn daily_total(graph_seed: u64, scale: f64, date: NaiveDate, now: DateTime<Local>) -> f64 {
+    let full_day = scale * (0.6 + 0.8 * noise(day_seed(date) ^ (graph_seed << 20)));
+    round_to(full_day * day_progress(date, now), 1)
+}
+
+/// Rounds `value` to `digits` decimal places.
+fn round_to(value: f64, digits: i32) -> f64 {
+    let factor = 10f64.powi(digits);
+    (value * factor).round() / factor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 6, 8, hour, minute, 0).unwrap()
+    }
+
+    mod succeeds {
+        use super::*;
+
+        #[test]
+        fn test_values_are_deterministic() {
+            let now = at(12, 34);
+            assert_eq!(generation_kw(0, now), generation_kw(0, now));
+            assert_eq!(device_watts(3, now), device_watts(3, now));
+            assert_eq!(temperature(1, now), temperature(1, now));
+        }
+
+        #[test]
+        fn test_solar_follows_daylight() {
+            assert_eq!(generation_kw(0, at(2, 0)), 0.0);
+            assert!(generation_kw(0, at(12, 0)) > generation_kw(0, at(8, 0)));
+        }
+
+        #[test]
+        fn test_climate_values_in_display_range() {
+            for hour in 0..24 {
+                for room in 0..8 {
+                    let t = temperature(room, at(hour, 0));
+                    let h = humidity(room, at(hour, 0));
+                    assert!((0.0..=99.9).contains(&t), "temperature {}", t);
+                    assert!((0.0..=99.9).contains(&h), "humidity {}", h);
+                }
+            }
+        }
+
+        #[test]
+        fn test_daily_total_grows_during_the_day() {
+            let today = at(0, 0).date_naive();
+            let yesterday = today.pred_opt().unwrap();
+            let tomorrow = today.succ_opt().unwrap();
+
+            let morning = daily_total(1, 20.0, today, at(8, 0));
+            let evening = daily_total(1, 20.0, today, at(20, 0));
+            assert!(evening > morning);
+            assert_eq!(daily_total(1, 20.0, tomorrow, at(20, 0)), 0.0);
+            assert_eq!(
+                daily_total(1, 20.0, yesterday, at(8, 0)),
+                daily_total(1, 20.0, yesterday, at(20, 0))
+            );
+        }
+    }
+}
@@ -0,0 +1,587 @@
+//! Disk-backed write buffer in front of a sink.
+//!
+//! While the wrapped sink accepts writes and nothing is queued, batches go
+//! straight through. Once a write fails or takes longer than
+//! [`INLINE_WRITE_TIMEOUT`], the batch and every later one are appended to a
+//! [`DiskQueue`] and a background task replays them in order, backing off
+//! between attempts, until the sink is reachable again.
+//!
+//! A configuration reload hands the [`SharedQueue`] of a buffer to the sink
+//! replacing it, so its directory is opened once. The replacement replays
+//! the queue once the replaced sink has stopped replaying it.
+
+use super::disk_queue::{BufferStats, DiskQueue, QueueLimits};
+use super::{stamp, Sink};
+use crate::error::StorageError;
+use crate::model::Point;
+use async_trait::async_trait;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, Notify};
+use tokio::task::JoinHandle;
+
+/// Longest a write goes straight to the wrapped sink before it is queued.
+///
+/// Kept well below the collector task timeout, which is 5 seconds for the
+/// status collectors, so a slow sink ends in the queue rather than losing the
+/// batch when the task is cancelled.
+const INLINE_WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Delays between replay attempts while the wrapped sink keeps failing.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Delay after the first failure
+    pub initial: Duration,
+    /// Upper bound of the doubling delay
+    pub max: Duration,
+}
+
+impl RetryPolicy {
+    /// Doubles `delay`, capped at the maximum.
+    pub(crate) fn next(&self, delay: Duration) -> Duration {
+        (delay * 2).min(self.max)
+    }
+}
+
+/// Queue of a buffered sink, shared with the sink replacing it.
+#[derive(Clone)]
+pub struct SharedQueue {
+    queue: Arc<Mutex<DiskQueue>>,
+    stats: Arc<BufferStats>,
+    wake: Arc<Notify>,
+    /// Held by the sink that replays the queue
+    replaying: Arc<Mutex<()>>,
+}
+
+/// Sink that queues batches on disk while the wrapped sink is failing.
+pub struct BufferedSink {
+    inner: Arc<dyn Sink>,
+    shared: SharedQueue,
+    /// Longest a write goes straight to the wrapped sink
+    inline_timeout: Duration,
+    /// Background replay, taken over by `flush` at shutdown
+    replay: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl BufferedSink {
+    /// Opens the queue in `dir` and starts replaying anything left in it.
+    ///
+    /// Must be called from within a tokio runtime.
+    ///
+    /// # Returns
+    /// - `Ok(BufferedSink)` wrapping `inner`
+    /// - `Err(StorageError::Buffer)` if the directory cannot be used
+    pub fn open(
+        inner: Box<dyn Sink>,
+        dir: impl Into<PathBuf>,
+        limits: QueueLimits,
+        retry: RetryPolicy,
+    ) -> Result<Self, StorageError> {
+        let queue = DiskQueue::open(dir, limits)?;
+        let stats = queue.stats();
+        let depth = stats.snapshot().depth_batches;
+        if depth > 0 {
+            tracing::info!("Replaying {} buffered batches to {}", depth, inner.name());
+        }
+
+        let shared = SharedQueue {
+            queue: Arc::new(Mutex::new(queue)),
+            stats,
+            wake: Arc::new(Notify::new()),
+            replaying: Arc::new(Mutex::new(())),
+        };
+        Ok(Self::take_over(inner, shared, limits, retry))
+    }
+
+    /// Buffers writes to `inner` in the queue of another buffered sink.
+    ///
+    /// Replaying starts once that sink has stopped replaying, i.e. has been
+    /// flushed or dropped; `limits` apply from then on. Must be called from
+    /// within a tokio runtime.
+    pub fn take_over(
+        inner: Box<dyn Sink>,
+        shared: SharedQueue,
+        limits: QueueLimits,
+        retry: RetryPolicy,
+    ) -> Self {
+        let inner: Arc<dyn Sink> = Arc::from(inner);
+        let replay = tokio::spawn(replay(Arc::clone(&inner), shared.clone(), limits, retry));
+        shared.wake.notify_one();
+
+        Self {
+            inner,
+            shared,
+            inline_timeout: INLINE_WRITE_TIMEOUT,
+            replay: Mutex::new(Some(replay)),
+        }
+    }
+
+    /// Queue depth and drop counters.
+    pub fn stats(&self) -> Arc<BufferStats> {
+        Arc::clone(&self.shared.stats)
+    }
+
+    /// The queue, to hand to a sink replacing this one.
+    pub fn queue(&self) -> SharedQueue {
+        self.shared.clone()
+    }
+}
+
+impl Drop for BufferedSink {
+    fn drop(&mut self) {
+        if let Some(replay) = self.replay.get_mut().take() {
+            replay.abort();
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for BufferedSink {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    /// Writes through to the wrapped sink, or queues the batch.
+    ///
+    /// The attempt runs on its own task, so a caller that is cancelled, e.g.
+    /// by the collector task timeout, still gets the batch queued.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the batch was written or durably queued
+    /// - `Err(StorageError)` if the batch is invalid, the credentials were
+    ///   refused, or the batch could not be queued
+    async fn write(&self, points: &[Point]) -> Result<(), StorageError> {
+        tokio::spawn(write_or_queue(
+            Arc::clone(&self.inner),
+            self.shared.clone(),
+            points.to_vec(),
+            self.inline_timeout,
+        ))
+        .await
+        .map_err(|e| StorageError::write_failed(points.len(), e))?
+    }
+
+    /// Stops the background replay and replays the queue once more, without
+    /// waiting for the retry delay.
+    ///
+    /// Batches the wrapped sink still refuses stay queued on disk and are
+    /// replayed on the next start, so they are not lost. If a replacement
+    /// has taken over the queue, it replays them instead.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the queue was replayed or is kept for the next start
+    /// - `Err(StorageError::Buffer)` if the queue cannot be read or updated
+    async fn flush(&self) -> Result<(), StorageError> {
+        if let Some(replay) = self.replay.lock().await.take() {
+            replay.abort();
+            let _ = replay.await;
+        }
+        let Ok(_replaying) = self.shared.replaying.try_lock() else {
+            tracing::debug!(
+                "Buffer of {} replayed by its replacement",
+                self.inner.name()
+            );
+            return Ok(());
+        };
+
+        let mut queue = self.shared.queue.lock().await;
+        while let Some(batch) = queue.front()?.map(<[Point]>::to_vec) {
+            match self.inner.write(&batch).await {
+                Ok(()) => queue.pop_front()?,
+                Err(e) if !e.is_retryable() => {
+                    tracing::error!(
+                        "Dropping {} buffered points rejected by {}: {}",
+                        batch.len(),
+                        self.inner.name(),
+                        e
+                    );
+                    queue.drop_front()?
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        "{} batches stay buffered for {} until the next start: {}",
+                        self.shared.stats.snapshot().depth_batches,
+                        self.inner.name(),
+                        e
+                    );
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes `points` to `inner` while nothing is queued.
+///
+/// Queues them instead if others are queued already, or if the write fails
+/// with a retryable error or does not finish within `inline_timeout`.
+async fn write_or_queue(
+    inner: Arc<dyn Sink>,
+    shared: SharedQueue,
+    points: Vec<Point>,
+    inline_timeout: Duration,
+) -> Result<(), StorageError> {
+    // Stamped now, so a queued point keeps the time it was collected
+    let stamped = stamp(&points);
+    let mut queue = shared.queue.lock().await;
+
+    if queue.is_empty() {
+        match tokio::time::timeout(inline_timeout, inner.write(&points)).await {
+            Ok(Ok(())) => return Ok(()),
+            Ok(Err(e)) if !e.is_retryable() => return Err(e),
+            Ok(Err(e)) => tracing::warn!(
+                "Failed to write {} points to {}, buffering: {}",
+                points.len(),
+                inner.name(),
+                e
+            ),
+            Err(_) => tracing::warn!(
+                "Writing {} points to {} took longer than {:?}, buffering",
+                points.len(),
+                inner.name(),
+                inline_timeout
+            ),
+        }
+    }
+
+    queue.push(stamped)?;
+    drop(queue);
+    shared.wake.notify_one();
+
+    let stats = shared.stats.snapshot();
+    tracing::debug!(
+        depth_batches = stats.depth_batches,
+        depth_points = stats.depth_points,
+        dropped_batches = stats.dropped_batches,
+        "Buffered batch for {}",
+        inner.name()
+    );
+    Ok(())
+}
+
+/// Replays queued batches in order until the queue is empty, then waits.
+///
+/// Starts once no other sink replays the queue, and applies `limits`.
+async fn replay(
+    inner: Arc<dyn Sink>,
+    shared: SharedQueue,
+    limits: QueueLimits,
+    retry: RetryPolicy,
+) {
+    let SharedQueue {
+        queue,
+        wake,
+        replaying,
+        ..
+    } = shared;
+    let _replaying = replaying.lock_owned().await;
+    queue.lock().await.set_limits(limits);
+
+    let mut delay = retry.initial;
+    loop {
+        let batch = match queue.lock().await.front() {
+            Ok(batch) => batch.map(<[Point]>::to_vec),
+            Err(e) => {
+                // No write may come to wake us, so try again on our own
+                tracing::error!(
+                    "Failed to read write buffer, retrying in {:?}: {}",
+                    delay,
+                    e
+                );
+                tokio::time::sleep(delay).await;
+                delay = retry.next(delay);
+                continue;
+            }
+        };
+        let Some(batch) = batch else {
+            wake.notified().await;
+            continue;
+        };
+
+        let result = inner.write(&batch).await;
+        let mut queue = queue.lock().await;
+        let removed = match result {
+            Ok(()) => {
+                delay = retry.initial;
+                queue.pop_front()
+            }
+            Err(e) if !e.is_retryable() => {
+                tracing::error!(
+                    "Dropping {} buffered points rejected by {}: {}",
+                    batch.len(),
+                    inner.name(),
+                    e
+                );
+                queue.drop_front()
+            }
+            Err(e) => {
+                drop(queue);
+                tracing::warn!(
+                    "Failed to replay buffered points to {}, retrying in {:?}: {}",
+                    inner.name(),
+                    delay,
+                    e
+                );
+                tokio::time::sleep(delay).await;
+                delay = retry.next(delay);
+                continue;
+            }
+        };
+        if let Err(e) = removed {
+            tracing::error!("Failed to update write buffer: {}", e);
+        }
+        if queue.is_empty() {
+            tracing::info!("Write buffer for {} drained", inner.name());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::mocks::RecordingSink;
+
+    fn limits() -> QueueLimits {
+        QueueLimits {
+            max_bytes: 1024 * 1024,
+            segment_bytes: 64 * 1024,
+            max_age: Duration::from_secs(3600),
+        }
+    }
+
+    fn retry() -> RetryPolicy {
+        RetryPolicy {
+            initial: Duration::from_millis(10),
+            max: Duration::from_millis(40),
+        }
+    }
+
+    fn batch(value: i64) -> Vec<Point> {
+        vec![Point::builder("power")
+            .tag("summary", "総消費電力(W)")
+            .field("value", value)
+            .build()
+            .unwrap()]
+    }
+
+    fn values(points: &[Point]) -> Vec<i64> {
+        points
+            .iter()
+            .map(|point| match point.fields["value"] {
+                crate::model::FieldValue::Integer(v) => v,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    /// Retries so rarely that only `flush` replays within a test.
+    fn no_retry() -> RetryPolicy {
+        RetryPolicy {
+            initial: Duration::from_secs(3600),
+            max: Duration::from_secs(3600),
+        }
+    }
+
+    async fn wait_for_drain(sink: &BufferedSink) {
+        for _ in 0..500 {
+            if sink.stats().snapshot().depth_batches == 0 {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("write buffer did not drain");
+    }
+
+    mod succeeds {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_writes_through_when_healthy() {
+            let dir = tempfile::tempdir().unwrap();
+            let inner = RecordingSink::new("influxdb");
+            let sink =
+                BufferedSink::open(Box::new(inner.clone()), dir.path(), limits(), retry()).unwrap();
+
+            sink.write(&batch(1)).await.unwrap();
+
+            assert_eq!(sink.name(), "influxdb");
+            assert_eq!(inner.points(), batch(1));
+            assert_eq!(sink.stats().snapshot().depth_batches, 0);
+        }
+
+        #[tokio::test]
+        async fn test_replays_in_order_after_outage() {
+            let dir = tempfile::tempdir().unwrap();
+            let inner = RecordingSink::new("influxdb");
+            inner.set_failing(true);
+            let sink =
+                BufferedSink::open(Box::new(inner.clone()), dir.path(), limits(), retry()).unwrap();
+
+            for value in 1..=3 {
+                sink.write(&batch(value)).await.unwrap();
+            }
+            assert_eq!(sink.stats().snapshot().depth_batches, 3);
+
+            inner.set_failing(false);
+            wait_for_drain(&sink).await;
+            sink.write(&batch(4)).await.unwrap();
+
+            assert_eq!(values(&inner.points()), vec![1, 2, 3, 4]);
+            assert!(inner.points()[..3].iter().all(|p| p.timestamp.is_some()));
+            assert_eq!(inner.points()[3].timestamp, None);
+        }
+
+        #[tokio::test]
+        async fn test_replays_batches_left_by_previous_run() {
+            let dir = tempfile::tempdir().unwrap();
+            {
+                let failing = RecordingSink::failing("influxdb");
+                let sink =
+                    BufferedSink::open(Box::new(failing), dir.path(), limits(), retry()).unwrap();
+                sink.write(&batch(1)).await.unwrap();
+                sink.write(&batch(2)).await.unwrap();
+            }
+
+            let inner = RecordingSink::new("influxdb");
+            let sink =
+                BufferedSink::open(Box::new(inner.clone()), dir.path(), limits(), retry()).unwrap();
+            wait_for_drain(&sink).await;
+
+            assert_eq!(values(&inner.points()), vec![1, 2]);
+        }
+
+        #[tokio::test]
+        async fn test_flush_replays_queue_at_once() {
+            let dir = tempfile::tempdir().unwrap();
+            let inner = RecordingSink::failing("influxdb");
+            let sink =
+                BufferedSink::open(Box::new(inner.clone()), dir.path(), limits(), no_retry())
+                    .unwrap();
+            sink.write(&batch(1)).await.unwrap();
+            sink.write(&batch(2)).await.unwrap();
+
+            inner.set_failing(false);
+            sink.flush().await.unwrap();
+
+            assert_eq!(values(&inner.points()), vec![1, 2]);
+            assert_eq!(sink.stats().snapshot().depth_batches, 0);
+        }
+
+        #[tokio::test]
+        async fn test_take_over_replays_once_replaced_sink_is_flushed() {
+            let dir = tempfile::tempdir().unwrap();
+            let failing = RecordingSink::failing("influxdb");
+            let replaced =
+                BufferedSink::open(Box::new(failing.clone()), dir.path(), limits(), retry())
+                    .unwrap();
+            replaced.write(&batch(1)).await.unwrap();
+
+            let inner = RecordingSink::new("influxdb");
+            let sink = BufferedSink::take_over(
+                Box::new(inner.clone()),
+                replaced.queue(),
+                limits(),
+                retry(),
+            );
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert_eq!(inner.points(), vec![]);
+
+            replaced.flush().await.unwrap();
+            wait_for_drain(&sink).await;
+
+            assert_eq!(values(&inner.points()), vec![1]);
+            assert_eq!(failing.points(), vec![]);
+        }
+
+        #[tokio::test]
+        async fn test_slow_write_is_queued_despite_task_timeout() {
+            // (task timeout, whether the task times out before the write is queued)
+            let test_cases = vec![
+                (Duration::from_millis(500), false),
+                (Duration::from_millis(20), true),
+            ];
+
+            for (task_timeout, cancelled) in test_cases {
+                let dir = tempfile::tempdir().unwrap();
+                let inner = RecordingSink::hanging("influxdb");
+                let mut sink =
+                    BufferedSink::open(Box::new(inner), dir.path(), limits(), no_retry()).unwrap();
+                sink.inline_timeout = Duration::from_millis(100);
+
+                let result = tokio::time::timeout(task_timeout, sink.write(&batch(1))).await;
+                tokio::time::sleep(Duration::from_millis(200)).await;
+
+                assert_eq!(result.is_err(), cancelled, "case: {:?}", task_timeout);
+                assert_eq!(
+                    sink.stats().snapshot().depth_batches,
+                    1,
+                    "case: {:?}",
+                    task_timeout
+                );
+            }
+        }
+
+        #[tokio::test]
+        async fn test_flush_keeps_queue_while_sink_is_down() {
+            let dir = tempfile::tempdir().unwrap();
+            let inner = RecordingSink::failing("influxdb");
+            let sink =
+                BufferedSink::open(Box::new(inner.clone()), dir.path(), limits(), no_retry())
+                    .unwrap();
+            sink.write(&batch(1)).await.unwrap();
+
+            sink.flush().await.unwrap();
+
+            assert_eq!(inner.points(), vec![]);
+            assert_eq!(sink.stats().snapshot().depth_batches, 1);
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_invalid_points_are_not_buffered() {
+            let dir = tempfile::tempdir().unwrap();
+            let inner = RecordingSink::rejecting("influxdb");
+            let sink = BufferedSink::open(Box::new(inner), dir.path(), limits(), retry()).unwrap();
+
+            let result = sink.write(&batch(1)).await;
+
+            assert!(matches!(result, Err(StorageError::InvalidDataPoint(_))));
+            assert_eq!(sink.stats().snapshot().depth_batches, 0);
+        }
+
+        #[tokio::test]
+        async fn test_auth_failures_are_not_buffered() {
+            let dir = tempfile::tempdir().unwrap();
+            let inner = RecordingSink::unauthorized("influxdb");
+            let sink = BufferedSink::open(Box::new(inner), dir.path(), limits(), retry()).unwrap();
+
+            for value in 1..=2 {
+                let result = sink.write(&batch(value)).await;
+
+                assert!(matches!(result, Err(StorageError::AuthFailed)));
+            }
+            assert_eq!(sink.stats().snapshot().depth_batches, 0);
+        }
+
+        #[tokio::test]
+        async fn test_open_unusable_directory() {
+            let dir = tempfile::tempdir().unwrap();
+            let file = dir.path().join("file");
+            std::fs::write(&file, "x").unwrap();
+
+            let result = BufferedSink::open(
+                Box::new(RecordingSink::new("influxdb")),
+                &file,
+                limits(),
+                retry(),
+            );
+
+            assert!(matches!(result, Err(StorageError::Buffer(_))));
+        }
+    }
+}
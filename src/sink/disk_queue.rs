@@ -0,0 +1,614 @@
+//! Segmented on-disk queue of point batches.
+//!
+//! Batches are stored as one JSON record per line in numbered segment files
+//! (`00000000000000000001.seg`, ...). New batches are appended to the newest
+//! segment until it reaches the segment size, then a new segment is started.
+//! The number of consumed records of the oldest segment is kept in a small
+//! `cursor` file, and a segment is deleted once all its records are consumed.
+//!
+//! The queue is bounded by total size and record age. When a bound is
+//! exceeded the oldest batches are dropped and counted in [`BufferStats`].
+
+use crate::model::Point;
+use chrono::Utc;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// File extension of segment files.
+const SEGMENT_EXTENSION: &str = "seg";
+
+/// Name of the file holding the read position.
+const CURSOR_FILE: &str = "cursor";
+
+/// Bounds of the queue.
+#[derive(Debug, Clone, Copy)]
+pub struct QueueLimits {
+    /// Total size of all segments before the oldest are dropped
+    pub max_bytes: u64,
+    /// Size at which a new segment is started
+    pub segment_bytes: u64,
+    /// Age after which a queued batch is dropped instead of retried
+    pub max_age: Duration,
+}
+
+/// Counters describing the queue, shared with whoever reports them.
+#[derive(Debug, Default)]
+pub struct BufferStats {
+    depth_batches: AtomicU64,
+    depth_points: AtomicU64,
+    depth_bytes: AtomicU64,
+    dropped_batches: AtomicU64,
+    dropped_points: AtomicU64,
+}
+
+/// Point-in-time copy of [`BufferStats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BufferSnapshot {
+    /// Batches waiting to be written
+    pub depth_batches: u64,
+    /// Points waiting to be written
+    pub depth_points: u64,
+    /// Bytes used by segment files
+    pub depth_bytes: u64,
+    /// Batches dropped because of the size or age cap, or rejected as invalid
+    pub dropped_batches: u64,
+    /// Points in the dropped batches
+    pub dropped_points: u64,
+}
+
+impl BufferStats {
+    /// Returns the current values.
+    pub fn snapshot(&self) -> BufferSnapshot {
+        BufferSnapshot {
+            depth_batches: self.depth_batches.load(Ordering::Relaxed),
+            depth_points: self.depth_points.load(Ordering::Relaxed),
+            depth_bytes: self.depth_bytes.load(Ordering::Relaxed),
+            dropped_batches: self.dropped_batches.load(Ordering::Relaxed),
+            dropped_points: self.dropped_points.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// One queued batch as stored on disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Record {
+    /// Unix time in seconds when the batch was queued
+    queued_at: i64,
+    points: Vec<Point>,
+}
+
+/// Bookkeeping for one segment file.
+#[derive(Debug)]
+struct Segment {
+    seq: u64,
+    bytes: u64,
+    batches: u64,
+    points: u64,
+}
+
+/// A bounded, persistent FIFO of point batches.
+pub struct DiskQueue {
+    dir: PathBuf,
+    limits: QueueLimits,
+    segments: VecDeque<Segment>,
+    /// Unconsumed records of the oldest segment
+    head: VecDeque<Record>,
+    /// Records consumed from the oldest segment
+    consumed_batches: u64,
+    consumed_points: u64,
+    next_seq: u64,
+    /// Start a new segment on the next push, e.g. after a torn write
+    roll_segment: bool,
+    stats: Arc<BufferStats>,
+}
+
+impl DiskQueue {
+    /// Opens the queue in `dir`, creating the directory if needed.
+    ///
+    /// Batches left by a previous run are picked up where it stopped. A
+    /// truncated last line, as left by a crash during a write, is skipped.
+    pub fn open(dir: impl Into<PathBuf>, limits: QueueLimits) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        let mut seqs: Vec<u64> = fs::read_dir(&dir)?
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != SEGMENT_EXTENSION {
+                    return None;
+                }
+                path.file_stem()?.to_str()?.parse().ok()
+            })
+            .collect();
+        seqs.sort_unstable();
+
+        let mut segments = VecDeque::with_capacity(seqs.len());
+        let mut head = VecDeque::new();
+        for seq in seqs {
+            let path = segment_path(&dir, seq);
+            let records = read_segment(&path)?;
+            if segments.is_empty() {
+                head = records.iter().cloned().collect();
+            }
+            segments.push_back(Segment {
+                seq,
+                bytes: fs::metadata(&path)?.len(),
+                batches: records.len() as u64,
+                points: records.iter().map(|r| r.points.len() as u64).sum(),
+            });
+        }
+
+        let next_seq = segments.back().map_or(1, |segment| segment.seq + 1);
+        let roll_segment = match segments.back() {
+            Some(segment) => !ends_with_newline(&segment_path(&dir, segment.seq))?,
+            None => false,
+        };
+        let mut queue = Self {
+            dir,
+            limits,
+            segments,
+            head,
+            consumed_batches: 0,
+            consumed_points: 0,
+            next_seq,
+            roll_segment,
+            stats: Arc::new(BufferStats::default()),
+        };
+
+        if let Some((seq, consumed)) = queue.read_cursor() {
+            if queue.segments.front().map(|segment| segment.seq) == Some(seq) {
+                for _ in 0..consumed {
+                    let Some(record) = queue.head.pop_front() else {
+                        break;
+                    };
+                    queue.consumed_batches += 1;
+                    queue.consumed_points += record.points.len() as u64;
+                }
+            }
+        }
+        if queue.head.is_empty() && !queue.segments.is_empty() {
+            queue.advance_segment()?;
+        }
+        queue.enforce_size()?;
+        queue.publish_stats();
+        Ok(queue)
+    }
+
+    /// Counters of this queue.
+    pub fn stats(&self) -> Arc<BufferStats> {
+        Arc::clone(&self.stats)
+    }
+
+    /// Applies new bounds, from the next push on.
+    pub fn set_limits(&mut self, limits: QueueLimits) {
+        self.limits = limits;
+    }
+
+    /// Whether no batches are waiting.
+    pub fn is_empty(&self) -> bool {
+        self.head.is_empty()
+    }
+
+    /// Appends a batch and syncs it to disk.
+    ///
+    /// Drops the oldest segments if the queue grows beyond its size cap.
+    pub fn push(&mut self, points: Vec<Point>) -> io::Result<()> {
+        let record = Record {
+            queued_at: Utc::now().timestamp(),
+            points,
+        };
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+        let length = line.len() as u64;
+
+        let needs_segment = match self.segments.back() {
+            None => true,
+            Some(segment) => {
+                segment.bytes > 0 && segment.bytes + length > self.limits.segment_bytes
+            }
+        };
+        if needs_segment || self.roll_segment {
+            self.roll_segment = false;
+            self.segments.push_back(Segment {
+                seq: self.next_seq,
+                bytes: 0,
+                batches: 0,
+                points: 0,
+            });
+            self.next_seq += 1;
+        }
+
+        let segment = self.segments.back_mut().expect("segment was just ensured");
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(segment_path(&self.dir, segment.seq))?;
+        file.write_all(&line)?;
+        file.sync_data()?;
+
+        segment.bytes += length;
+        segment.batches += 1;
+        segment.points += record.points.len() as u64;
+        if self.segments.len() == 1 {
+            self.head.push_back(record);
+        }
+
+        self.enforce_size()?;
+        self.publish_stats();
+        Ok(())
+    }
+
+    /// Returns the oldest batch, dropping batches older than the age cap first.
+    pub fn front(&mut self) -> io::Result<Option<&[Point]>> {
+        let oldest_allowed = Utc::now().timestamp() - self.limits.max_age.as_secs() as i64;
+        while self
+            .head
+            .front()
+            .is_some_and(|record| record.queued_at < oldest_allowed)
+        {
+            self.pop(true)?;
+        }
+        Ok(self.head.front().map(|record| record.points.as_slice()))
+    }
+
+    /// Removes the oldest batch after it was written.
+    pub fn pop_front(&mut self) -> io::Result<()> {
+        self.pop(false)
+    }
+
+    /// Removes the oldest batch without writing it, counting it as dropped.
+    pub fn drop_front(&mut self) -> io::Result<()> {
+        self.pop(true)
+    }
+
+    fn pop(&mut self, dropped: bool) -> io::Result<()> {
+        let Some(record) = self.head.pop_front() else {
+            return Ok(());
+        };
+        let points = record.points.len() as u64;
+        self.consumed_batches += 1;
+        self.consumed_points += points;
+        if dropped {
+            self.stats.dropped_batches.fetch_add(1, Ordering::Relaxed);
+            self.stats
+                .dropped_points
+                .fetch_add(points, Ordering::Relaxed);
+        }
+
+        if self.head.is_empty() {
+            self.advance_segment()?;
+        } else {
+            self.write_cursor()?;
+        }
+        self.publish_stats();
+        Ok(())
+    }
+
+    /// Deletes the oldest segment, counting unconsumed records as dropped,
+    /// and loads the next segment that still holds records.
+    fn advance_segment(&mut self) -> io::Result<()> {
+        while let Some(segment) = self.segments.pop_front() {
+            let remaining_points: u64 = self.head.iter().map(|r| r.points.len() as u64).sum();
+            self.stats
+                .dropped_batches
+                .fetch_add(self.head.len() as u64, Ordering::Relaxed);
+            self.stats
+                .dropped_points
+                .fetch_add(remaining_points, Ordering::Relaxed);
+
+            remove_if_exists(&segment_path(&self.dir, segment.seq))?;
+            self.consumed_batches = 0;
+            self.consumed_points = 0;
+            self.head = match self.segments.front() {
+                Some(next) => read_segment(&segment_path(&self.dir, next.seq))?.into(),
+                None => VecDeque::new(),
+            };
+            if !self.head.is_empty() {
+                break;
+            }
+        }
+        self.write_cursor()
+    }
+
+    /// Drops whole segments, oldest first, until the size cap is met.
+    ///
+    /// The newest segment is never dropped, so the latest batch survives.
+    fn enforce_size(&mut self) -> io::Result<()> {
+        while self.segments.len() > 1 && self.total_bytes() > self.limits.max_bytes {
+            tracing::warn!(
+                "Write buffer exceeds {} bytes, dropping {} oldest batches",
+                self.limits.max_bytes,
+                self.head.len()
+            );
+            self.advance_segment()?;
+        }
+        Ok(())
+    }
+
+    fn total_bytes(&self) -> u64 {
+        self.segments.iter().map(|segment| segment.bytes).sum()
+    }
+
+    fn publish_stats(&self) {
+        let batches: u64 = self.segments.iter().map(|s| s.batches).sum();
+        let points: u64 = self.segments.iter().map(|s| s.points).sum();
+        self.stats.depth_batches.store(
+            batches.saturating_sub(self.consumed_batches),
+            Ordering::Relaxed,
+        );
+        self.stats.depth_points.store(
+            points.saturating_sub(self.consumed_points),
+            Ordering::Relaxed,
+        );
+        self.stats
+            .depth_bytes
+            .store(self.total_bytes(), Ordering::Relaxed);
+    }
+
+    fn read_cursor(&self) -> Option<(u64, u64)> {
+        let content = fs::read_to_string(self.dir.join(CURSOR_FILE)).ok()?;
+        let (seq, consumed) = content.trim().split_once(' ')?;
+        Some((seq.parse().ok()?, consumed.parse().ok()?))
+    }
+
+    /// Persists the read position atomically (write, then rename).
+    fn write_cursor(&self) -> io::Result<()> {
+        let seq = self.segments.front().map_or(0, |segment| segment.seq);
+        let temp = self.dir.join(format!("{}.tmp", CURSOR_FILE));
+        fs::write(&temp, format!("{} {}\n", seq, self.consumed_batches))?;
+        fs::rename(temp, self.dir.join(CURSOR_FILE))
+    }
+}
+
+fn segment_path(dir: &Path, seq: u64) -> PathBuf {
+    dir.join(format!("{:020}.{}", seq, SEGMENT_EXTENSION))
+}
+
+fn read_segment(path: &Path) -> io::Result<Vec<Record>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut records = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        match serde_json::from_str(&line) {
+            Ok(record) => records.push(record),
+            Err(e) => tracing::warn!("Skipping unreadable record in {}: {}", path.display(), e),
+        }
+    }
+    Ok(records)
+}
+
+/// Whether a file is empty or ends with a complete line.
+fn ends_with_newline(path: &Path) -> io::Result<bool> {
+    let content = fs::read(path)?;
+    Ok(content.last().is_none_or(|byte| *byte == b'\n'))
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> QueueLimits {
+        QueueLimits {
+            max_bytes: 1024 * 1024,
+            segment_bytes: 64 * 1024,
+            max_age: Duration::from_secs(3600),
+        }
+    }
+
+    fn batch(value: i64) -> Vec<Point> {
+        vec![Point::builder("power")
+            .tag("summary", "総消費電力(W)")
+            .field("value", value)
+            .timestamp(value)
+            .build()
+            .unwrap()]
+    }
+
+    fn front_value(queue: &mut DiskQueue) -> Option<i64> {
+        queue
+            .front()
+            .unwrap()
+            .map(|points| points[0].timestamp.unwrap())
+    }
+
+    fn segment_count(dir: &Path) -> usize {
+        fs::read_dir(dir)
+            .unwrap()
+            .filter(|entry| {
+                entry.as_ref().unwrap().path().extension() == Some(SEGMENT_EXTENSION.as_ref())
+            })
+            .count()
+    }
+
+    mod succeeds {
+        use super::*;
+
+        #[test]
+        fn test_fifo_order() {
+            let dir = tempfile::tempdir().unwrap();
+            let mut queue = DiskQueue::open(dir.path(), limits()).unwrap();
+
+            for value in 1..=3 {
+                queue.push(batch(value)).unwrap();
+            }
+
+            let mut values = Vec::new();
+            while let Some(value) = front_value(&mut queue) {
+                values.push(value);
+                queue.pop_front().unwrap();
+            }
+            assert_eq!(values, vec![1, 2, 3]);
+            assert!(queue.is_empty());
+            assert_eq!(queue.stats().snapshot(), BufferSnapshot::default());
+        }
+
+        #[test]
+        fn test_resumes_after_reopen() {
+            let dir = tempfile::tempdir().unwrap();
+            let small = QueueLimits {
+                segment_bytes: 1,
+                ..limits()
+            };
+            {
+                let mut queue = DiskQueue::open(dir.path(), small).unwrap();
+                for value in 1..=4 {
+                    queue.push(batch(value)).unwrap();
+                }
+                queue.pop_front().unwrap();
+            }
+
+            let mut queue = DiskQueue::open(dir.path(), small).unwrap();
+
+            assert_eq!(queue.stats().snapshot().depth_batches, 3);
+            assert_eq!(front_value(&mut queue), Some(2));
+        }
+
+        #[test]
+        fn test_resumes_within_segment() {
+            let dir = tempfile::tempdir().unwrap();
+            {
+                let mut queue = DiskQueue::open(dir.path(), limits()).unwrap();
+                for value in 1..=3 {
+                    queue.push(batch(value)).unwrap();
+                }
+                queue.pop_front().unwrap();
+            }
+
+            let mut queue = DiskQueue::open(dir.path(), limits()).unwrap();
+
+            assert_eq!(front_value(&mut queue), Some(2));
+            assert_eq!(queue.stats().snapshot().depth_points, 2);
+        }
+
+        #[test]
+        fn test_rolls_and_deletes_segments() {
+            let dir = tempfile::tempdir().unwrap();
+            let mut queue = DiskQueue::open(
+                dir.path(),
+                QueueLimits {
+                    segment_bytes: 1,
+                    ..limits()
+                },
+            )
+            .unwrap();
+
+            for value in 1..=3 {
+                queue.push(batch(value)).unwrap();
+            }
+            assert_eq!(segment_count(dir.path()), 3);
+
+            queue.pop_front().unwrap();
+            queue.pop_front().unwrap();
+            assert_eq!(segment_count(dir.path()), 1);
+        }
+
+        #[test]
+        fn test_size_cap_drops_oldest() {
+            let dir = tempfile::tempdir().unwrap();
+            let line_bytes = {
+                let mut queue = DiskQueue::open(dir.path().join("probe"), limits()).unwrap();
+                queue.push(batch(1)).unwrap();
+                queue.stats().snapshot().depth_bytes
+            };
+            let mut queue = DiskQueue::open(
+                dir.path().join("queue"),
+                QueueLimits {
+                    max_bytes: line_bytes * 2,
+                    segment_bytes: 1,
+                    ..limits()
+                },
+            )
+            .unwrap();
+
+            for value in 1..=4 {
+                queue.push(batch(value)).unwrap();
+            }
+
+            let stats = queue.stats().snapshot();
+            assert_eq!(stats.depth_batches, 2);
+            assert_eq!(stats.dropped_batches, 2);
+            assert_eq!(stats.dropped_points, 2);
+            assert_eq!(front_value(&mut queue), Some(3));
+        }
+
+        #[test]
+        fn test_age_cap_drops_expired() {
+            let dir = tempfile::tempdir().unwrap();
+            let mut queue = DiskQueue::open(dir.path(), limits()).unwrap();
+            queue.push(batch(1)).unwrap();
+            queue.head[0].queued_at -= 7200;
+            queue.push(batch(2)).unwrap();
+
+            assert_eq!(front_value(&mut queue), Some(2));
+            assert_eq!(queue.stats().snapshot().dropped_batches, 1);
+        }
+
+        #[test]
+        fn test_skips_truncated_record() {
+            let dir = tempfile::tempdir().unwrap();
+            {
+                let mut queue = DiskQueue::open(dir.path(), limits()).unwrap();
+                queue.push(batch(1)).unwrap();
+            }
+            let mut file = OpenOptions::new()
+                .append(true)
+                .open(segment_path(dir.path(), 1))
+                .unwrap();
+            file.write_all(b"{\"queued_at\":1,\"poi").unwrap();
+
+            let mut queue = DiskQueue::open(dir.path(), limits()).unwrap();
+            queue.push(batch(2)).unwrap();
+
+            assert_eq!(front_value(&mut queue), Some(1));
+            queue.pop_front().unwrap();
+            assert_eq!(front_value(&mut queue), Some(2));
+            queue.pop_front().unwrap();
+            assert!(queue.is_empty());
+        }
+
+        #[test]
+        fn test_drop_front_counts() {
+            let dir = tempfile::tempdir().unwrap();
+            let mut queue = DiskQueue::open(dir.path(), limits()).unwrap();
+            queue.push(batch(1)).unwrap();
+
+            queue.drop_front().unwrap();
+
+            let stats = queue.stats().snapshot();
+            assert_eq!(stats.depth_batches, 0);
+            assert_eq!(stats.dropped_batches, 1);
+            assert_eq!(stats.dropped_points, 1);
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[test]
+        fn test_open_on_file() {
+            let dir = tempfile::tempdir().unwrap();
+            let file = dir.path().join("not-a-dir");
+            fs::write(&file, "x").unwrap();
+
+            assert!(DiskQueue::open(&file, limits()).is_err());
+        }
+    }
+}
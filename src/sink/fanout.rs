@@ -0,0 +1,171 @@
+//! Sink that forwards every batch to several sinks.
+
+use super::Sink;
+use crate::error::StorageError;
+use crate::model::Point;
+use async_trait::async_trait;
+use futures::future::join_all;
+
+/// Writes each batch to all wrapped sinks concurrently.
+///
+/// Sinks are isolated from each other: a failing or slow backend does not
+/// keep the batch from reaching the others. The write only reports success
+/// if every sink accepted the batch.
+pub struct FanoutSink {
+    sinks: Vec<Box<dyn Sink>>,
+}
+
+impl FanoutSink {
+    /// Creates a fan-out over the given sinks.
+    pub fn new(sinks: Vec<Box<dyn Sink>>) -> Self {
+        Self { sinks }
+    }
+}
+
+#[async_trait]
+impl Sink for FanoutSink {
+    fn name(&self) -> &str {
+        "fanout"
+    }
+
+    /// Writes `points` to every sink.
+    ///
+    /// # Returns
+    /// - `Ok(())` if all sinks succeeded
+    /// - `Err(StorageError::SinksFailed)` naming the sinks that failed; the
+    ///   other sinks have still stored the batch
+    async fn write(&self, points: &[Point]) -> Result<(), StorageError> {
+        let results = join_all(self.sinks.iter().map(|sink| sink.write(points))).await;
+
+        let mut failed = Vec::new();
+        for (sink, result) in self.sinks.iter().zip(results) {
+            match result {
+                Ok(()) => tracing::debug!("Wrote {} points to {}", points.len(), sink.name()),
+                Err(e) => {
+                    tracing::error!(
+                        "Failed to write {} points to {}: {}",
+                        points.len(),
+                        sink.name(),
+                        e
+                    );
+                    failed.push(sink.name().to_string());
+                }
+            }
+        }
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(StorageError::SinksFailed(failed))
+        }
+    }
+
+    /// Flushes every sink.
+    ///
+    /// # Returns
+    /// - `Ok(())` if all sinks flushed
+    /// - `Err(StorageError::SinksFailed)` naming the sinks that failed
+    async fn flush(&self) -> Result<(), StorageError> {
+        let results = join_all(self.sinks.iter().map(|sink| sink.flush())).await;
+
+        let mut failed = Vec::new();
+        for (sink, result) in self.sinks.iter().zip(results) {
+            if let Err(e) = result {
+                tracing::error!("Failed to flush {}: {}", sink.name(), e);
+                failed.push(sink.name().to_string());
+            }
+        }
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(StorageError::SinksFailed(failed))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::mocks::RecordingSink;
+
+    fn test_points() -> Vec<Point> {
+        vec![Point::builder("power")
+            .tag("summary", "総発電電力(W)")
+            .field("value", 2500i64)
+            .build()
+            .unwrap()]
+    }
+
+    mod succeeds {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_writes_to_every_sink() {
+            let first = RecordingSink::new("first");
+            let second = RecordingSink::new("second");
+            let fanout = FanoutSink::new(vec![Box::new(first.clone()), Box::new(second.clone())]);
+
+            fanout.write(&test_points()).await.unwrap();
+
+            assert_eq!(first.points(), test_points());
+            assert_eq!(second.points(), test_points());
+        }
+
+        #[tokio::test]
+        async fn test_flushes_every_sink() {
+            let first = RecordingSink::new("first");
+            let second = RecordingSink::new("second");
+            let fanout = FanoutSink::new(vec![Box::new(first.clone()), Box::new(second.clone())]);
+
+            fanout.flush().await.unwrap();
+
+            assert_eq!(first.flushes(), 1);
+            assert_eq!(second.flushes(), 1);
+        }
+
+        #[tokio::test]
+        async fn test_no_sinks() {
+            let fanout = FanoutSink::new(Vec::new());
+            assert!(fanout.write(&test_points()).await.is_ok());
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_failing_sink_does_not_affect_others() {
+            let healthy = RecordingSink::new("healthy");
+            let fanout = FanoutSink::new(vec![
+                Box::new(RecordingSink::failing("broken")),
+                Box::new(healthy.clone()),
+            ]);
+
+            let result = fanout.write(&test_points()).await;
+
+            match result {
+                Err(StorageError::SinksFailed(failed)) => assert_eq!(failed, vec!["broken"]),
+                other => panic!("expected SinksFailed, got {:?}", other),
+            }
+            assert_eq!(healthy.points(), test_points());
+        }
+
+        #[tokio::test]
+        async fn test_failing_flush_does_not_affect_others() {
+            let healthy = RecordingSink::new("healthy");
+            let fanout = FanoutSink::new(vec![
+                Box::new(RecordingSink::failing("broken")),
+                Box::new(healthy.clone()),
+            ]);
+
+            let result = fanout.flush().await;
+
+            match result {
+                Err(StorageError::SinksFailed(failed)) => assert_eq!(failed, vec!["broken"]),
+                other => panic!("expected SinksFailed, got {:?}", other),
+            }
+            assert_eq!(healthy.flushes(), 1);
+        }
+    }
+}
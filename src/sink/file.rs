@@ -0,0 +1,227 @@
+//! Sink appending points as InfluxDB line protocol to rotating files.
+//!
+//! Files are named `aiseg2-<sequence>.lp` and can be bulk-imported with
+//! `influx write --file`. A new file is started once the current one would
+//! exceed the size limit, and the oldest files beyond the configured count
+//! are removed. After a restart, writing continues in the newest file.
+
+use super::{stamp, Sink};
+use crate::config::LineProtocolFileConfig;
+use crate::error::StorageError;
+use crate::line_protocol;
+use crate::model::Point;
+use async_trait::async_trait;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const PREFIX: &str = "aiseg2-";
+const EXTENSION: &str = ".lp";
+
+/// The file currently written to.
+struct Current {
+    seq: u64,
+    file: File,
+    size: u64,
+}
+
+/// Sink that writes line protocol to size-rotated files.
+pub struct FileSink {
+    dir: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    current: Mutex<Current>,
+}
+
+impl FileSink {
+    /// Creates the directory if needed and opens the newest file in it.
+    ///
+    /// # Returns
+    /// - `Ok(FileSink)` ready to append
+    /// - `Err(io::Error)` if the directory or file cannot be used
+    pub fn open(config: &LineProtocolFileConfig) -> io::Result<Self> {
+        let dir = PathBuf::from(&config.dir);
+        fs::create_dir_all(&dir)?;
+        let seq = sequences(&dir)?.last().copied().unwrap_or(1);
+        let file = open_append(&dir, seq)?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            dir,
+            max_bytes: config.max_bytes,
+            max_files: config.max_files,
+            current: Mutex::new(Current { seq, file, size }),
+        })
+    }
+
+    /// Appends `chunk` to the current file, rotating first if it does not fit.
+    fn append(&self, current: &mut Current, chunk: &[u8]) -> io::Result<()> {
+        if current.size > 0 && current.size + chunk.len() as u64 > self.max_bytes {
+            self.rotate(current)?;
+        }
+        current.file.write_all(chunk)?;
+        current.size += chunk.len() as u64;
+        Ok(())
+    }
+
+    /// Starts the next file and removes the oldest ones beyond `max_files`.
+    fn rotate(&self, current: &mut Current) -> io::Result<()> {
+        let seq = current.seq + 1;
+        *current = Current {
+            seq,
+            file: open_append(&self.dir, seq)?,
+            size: 0,
+        };
+
+        if self.max_files > 0 {
+            let seqs = sequences(&self.dir)?;
+            let excess = seqs.len().saturating_sub(self.max_files);
+            for old in seqs.into_iter().take(excess).filter(|&old| old != seq) {
+                fs::remove_file(path(&self.dir, old))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Path of the file with sequence number `seq`.
+fn path(dir: &Path, seq: u64) -> PathBuf {
+    dir.join(format!("{}{:06}{}", PREFIX, seq, EXTENSION))
+}
+
+fn open_append(dir: &Path, seq: u64) -> io::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path(dir, seq))
+}
+
+/// Sequence numbers of the line protocol files in `dir`, ascending.
+fn sequences(dir: &Path) -> io::Result<Vec<u64>> {
+    let mut seqs = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name();
+        let seq = name
+            .to_str()
+            .and_then(|name| name.strip_prefix(PREFIX))
+            .and_then(|name| name.strip_suffix(EXTENSION))
+            .and_then(|seq| seq.parse::<u64>().ok());
+        seqs.extend(seq);
+    }
+    seqs.sort_unstable();
+    Ok(seqs)
+}
+
+#[async_trait]
+impl Sink for FileSink {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    /// Appends one line per point.
+    async fn write(&self, points: &[Point]) -> Result<(), StorageError> {
+        let mut chunk = String::new();
+        for line in line_protocol::encode_batch(&stamp(points)) {
+            chunk.push_str(&line);
+            chunk.push('\n');
+        }
+
+        let mut current = self.current.lock().unwrap();
+        self.append(&mut current, chunk.as_bytes())
+            .map_err(|e| StorageError::write_failed(points.len(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::fake_influx::parse_line_protocol;
+
+    fn config(dir: &Path, max_bytes: u64, max_files: usize) -> LineProtocolFileConfig {
+        LineProtocolFileConfig {
+            dir: dir.to_string_lossy().into_owned(),
+            max_bytes,
+            max_files,
+        }
+    }
+
+    fn batch(value: i64) -> Vec<Point> {
+        vec![Point::builder("power")
+            .tag("summary", "総消費電力(W)")
+            .field("value", value)
+            .timestamp(value)
+            .build()
+            .unwrap()]
+    }
+
+    fn read(dir: &Path, seq: u64) -> String {
+        fs::read_to_string(path(dir, seq)).unwrap()
+    }
+
+    mod succeeds {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_appends_line_protocol() {
+            let dir = tempfile::tempdir().unwrap();
+            let sink = FileSink::open(&config(dir.path(), 1024, 10)).unwrap();
+
+            sink.write(&batch(1)).await.unwrap();
+            sink.write(&batch(2)).await.unwrap();
+
+            let content = read(dir.path(), 1);
+            assert_eq!(
+                content,
+                "power,summary=総消費電力(W) value=1i 1\npower,summary=総消費電力(W) value=2i 2\n"
+            );
+            assert_eq!(parse_line_protocol(&content).unwrap().len(), 2);
+        }
+
+        #[tokio::test]
+        async fn test_rotates_and_removes_oldest_files() {
+            let dir = tempfile::tempdir().unwrap();
+            let line_len = line_protocol::encode(&batch(1)[0]).unwrap().len() as u64 + 1;
+            let sink = FileSink::open(&config(dir.path(), line_len * 2, 2)).unwrap();
+
+            for value in 1..=5 {
+                sink.write(&batch(value)).await.unwrap();
+            }
+
+            assert_eq!(sequences(dir.path()).unwrap(), vec![2, 3]);
+            assert_eq!(parse_line_protocol(&read(dir.path(), 2)).unwrap().len(), 2);
+            assert_eq!(parse_line_protocol(&read(dir.path(), 3)).unwrap().len(), 1);
+        }
+
+        #[tokio::test]
+        async fn test_continues_newest_file_after_restart() {
+            let dir = tempfile::tempdir().unwrap();
+            let line_len = line_protocol::encode(&batch(1)[0]).unwrap().len() as u64 + 1;
+            {
+                let sink = FileSink::open(&config(dir.path(), line_len * 2, 0)).unwrap();
+                for value in 1..=3 {
+                    sink.write(&batch(value)).await.unwrap();
+                }
+            }
+
+            let sink = FileSink::open(&config(dir.path(), line_len * 2, 0)).unwrap();
+            sink.write(&batch(4)).await.unwrap();
+
+            assert_eq!(sequences(dir.path()).unwrap(), vec![1, 2]);
+            assert_eq!(parse_line_protocol(&read(dir.path(), 2)).unwrap().len(), 2);
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[test]
+        fn test_open_unusable_directory() {
+            let dir = tempfile::tempdir().unwrap();
+            let file = dir.path().join("file");
+            fs::write(&file, "x").unwrap();
+
+            assert!(FileSink::open(&config(&file, 1024, 10)).is_err());
+        }
+    }
+}
@@ -0,0 +1,293 @@
+//! Output sinks for collected points.
+//!
+//! A [`Sink`] stores a batch of backend-neutral [`Point`]s. The collection
+//! loop writes each cycle to a single sink, normally a [`FanoutSink`] that
+//! forwards it to every backend listed in `SINKS`. Adding a backend means
+//! implementing `Sink` and registering it in [`create_sinks_after`].
+
+mod buffer;
+mod disk_queue;
+mod fanout;
+mod file;
+mod mqtt;
+mod otlp;
+mod postgres;
+mod prometheus;
+mod reloadable;
+mod rows;
+mod sqlite;
+mod stdout;
+
+pub use buffer::{BufferedSink, RetryPolicy, SharedQueue};
+pub use disk_queue::{BufferStats, QueueLimits};
+pub use fanout::FanoutSink;
+pub use file::FileSink;
+pub use mqtt::MqttSink;
+pub use otlp::OtlpSink;
+pub use postgres::PostgresSink;
+pub use prometheus::{PrometheusSink, SharedEndpoint};
+pub use reloadable::ReloadableSink;
+pub use sqlite::SqliteSink;
+pub use stdout::StdoutSink;
+
+use crate::config::{self, BufferConfig, SinkConfig, SinkKind};
+use crate::error::{ConfigError, StorageError};
+use crate::influxdb;
+use crate::model::Point;
+use crate::supervisor::RestartStats;
+use async_trait::async_trait;
+use chrono::Utc;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A storage backend for collected points.
+#[async_trait]
+pub trait Sink: Send + Sync {
+    /// Short name used in logs, e.g. "influxdb".
+    fn name(&self) -> &str;
+
+    /// Writes a batch of points.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the backend accepted the batch
+    /// - `Err(StorageError)` if the batch could not be stored
+    async fn write(&self, points: &[Point]) -> Result<(), StorageError>;
+
+    /// Stores whatever the sink still holds before the process exits or the
+    /// sink is replaced by a configuration reload.
+    ///
+    /// Called once, at shutdown or once the replacement receives writes; the
+    /// sink may stop background work and is not written to afterwards. Sinks that
+    /// write synchronously have nothing to do.
+    ///
+    /// # Returns
+    /// - `Ok(())` if no accepted point is lost
+    /// - `Err(StorageError)` if held points could not be stored
+    async fn flush(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+}
+
+/// Gives points without a timestamp the current time.
+///
+/// Used by sinks that store points for later, so a point keeps the time it
+/// was collected rather than the time it is finally imported.
+pub(crate) fn stamp(points: &[Point]) -> Vec<Point> {
+    let now = Utc::now().timestamp_nanos_opt();
+    points
+        .iter()
+        .cloned()
+        .map(|mut point| {
+            if point.timestamp.is_none() {
+                point.timestamp = now;
+            }
+            point
+        })
+        .collect()
+}
+
+/// The tag naming a series: `summary` for house totals, else `detail-section`.
+pub(crate) fn label(point: &Point) -> &str {
+    point
+        .tags
+        .get("summary")
+        .or_else(|| point.tags.get("detail-section"))
+        .map(String::as_str)
+        .unwrap_or(point.measurement.as_str())
+}
+
+/// Returns the unit in a trailing parenthesis, e.g. "発電量(kWh)" -> "kWh".
+pub(crate) fn unit_suffix(name: &str) -> Option<&str> {
+    let inner = name.strip_suffix(')')?;
+    inner.rfind('(').map(|start| &inner[start + 1..])
+}
+
+/// Endpoint and write buffers of running sinks, handed to the sinks
+/// replacing them on a configuration reload.
+///
+/// The replacements share them rather than binding the Prometheus address or
+/// opening a buffer directory again, so they can be created while the
+/// running sinks still receive writes.
+#[derive(Default)]
+pub struct Handover {
+    /// Listen address and endpoint of the Prometheus sink
+    prometheus: Option<(String, SharedEndpoint)>,
+    /// Queues of the write buffers by directory
+    buffers: BTreeMap<PathBuf, SharedQueue>,
+}
+
+/// Builds the sinks selected in the configuration.
+///
+/// See [`create_sinks_after`] for what is created.
+///
+/// # Returns
+/// - `Ok(FanoutSink)` writing to every selected backend
+/// - `Err(ConfigError)` if a selected backend is misconfigured
+pub fn create_sinks(
+    config: &SinkConfig,
+    restarts: Option<Arc<RestartStats>>,
+) -> Result<FanoutSink, ConfigError> {
+    create_sinks_after(config, restarts, &Handover::default()).map(|(sinks, _)| sinks)
+}
+
+/// Builds the sinks selected in the configuration, taking over the
+/// Prometheus endpoint and write buffers of `running` where their settings
+/// allow.
+///
+/// Each backend loads its own configuration, so only the variables of the
+/// selected sinks are required. A sink listed twice is created once, and a
+/// dry run creates only the stdout sink. With `BUFFER_DIR` set, InfluxDB and
+/// PostgreSQL writes go through a disk-backed buffer whose depth is exposed
+/// on the Prometheus endpoint, if enabled, as are the task `restarts`.
+///
+/// The endpoint is taken over if its listen address is unchanged, keeping
+/// the latest values, and a buffer if its directory is.
+///
+/// # Returns
+/// - `Ok((FanoutSink, Handover))` writing to every selected backend, with
+///   what to hand to the sinks replacing them
+/// - `Err(ConfigError)` if a selected backend is misconfigured; `running`
+///   is not affected
+pub fn create_sinks_after(
+    config: &SinkConfig,
+    restarts: Option<Arc<RestartStats>>,
+    running: &Handover,
+) -> Result<(FanoutSink, Handover), ConfigError> {
+    let kinds = config.selected();
+    if config.dry_run {
+        tracing::info!("Dry run: printing line protocol instead of writing to sinks");
+    }
+
+    let buffer_config = config::load_buffer_config()?;
+    let mut buffers: Vec<(String, Arc<BufferStats>)> = Vec::new();
+    let mut prometheus: Option<PrometheusSink> = None;
+    let mut handover = Handover::default();
+
+    let mut sinks: Vec<Box<dyn Sink>> = Vec::with_capacity(kinds.len());
+    for kind in kinds {
+        let sink: Box<dyn Sink> = match kind {
+            SinkKind::Influxdb => {
+                let client = Box::new(influxdb::Client::new(config::load_influx_config()?));
+                buffered(client, &buffer_config, &mut buffers, running, &mut handover)?
+            }
+            SinkKind::Prometheus => {
+                let listen_addr = config::load_prometheus_config()?.listen_addr;
+                let taken_over = running
+                    .prometheus
+                    .as_ref()
+                    .filter(|(addr, _)| *addr == listen_addr)
+                    .and_then(|(_, endpoint)| PrometheusSink::take_over(endpoint));
+                let sink = match taken_over {
+                    Some(sink) => sink,
+                    None => PrometheusSink::bind(&listen_addr).map_err(|e| {
+                        ConfigError::invalid("PROMETHEUS_LISTEN_ADDR", e.to_string())
+                    })?,
+                };
+                tracing::info!(
+                    "Serving Prometheus metrics on http://{}/metrics",
+                    sink.local_addr()
+                );
+                handover.prometheus = Some((listen_addr, sink.endpoint()));
+                // Added last, once every buffer it should expose exists
+                prometheus = Some(sink);
+                continue;
+            }
+            SinkKind::Mqtt => {
+                let mqtt_config = config::load_mqtt_config()?;
+                tracing::info!(
+                    "Publishing metrics to MQTT broker {}:{} under {}/",
+                    mqtt_config.host,
+                    mqtt_config.port,
+                    mqtt_config.topic_prefix
+                );
+                Box::new(MqttSink::connect(mqtt_config))
+            }
+            SinkKind::Postgres => {
+                let sink = Box::new(PostgresSink::new(config::load_postgres_config()?)?);
+                buffered(sink, &buffer_config, &mut buffers, running, &mut handover)?
+            }
+            SinkKind::Sqlite => {
+                let path = config::load_sqlite_config()?.path;
+                let sink = SqliteSink::open(&path)
+                    .map_err(|e| ConfigError::invalid("SQLITE_PATH", e.to_string()))?;
+                tracing::info!("Storing metrics in SQLite database {}", path);
+                Box::new(sink)
+            }
+            SinkKind::Otlp => {
+                let otlp_config = config::load_otlp_config()?;
+                tracing::info!(
+                    "Exporting metrics over OTLP ({:?}) to {}",
+                    otlp_config.protocol,
+                    otlp_config.endpoint()
+                );
+                Box::new(OtlpSink::new(&otlp_config)?)
+            }
+            SinkKind::Stdout => Box::new(StdoutSink::new()),
+            SinkKind::File => {
+                let file_config = config::load_line_protocol_file_config()?;
+                let sink = FileSink::open(&file_config)
+                    .map_err(|e| ConfigError::invalid("LINE_PROTOCOL_DIR", e.to_string()))?;
+                tracing::info!("Writing line protocol files to {}", file_config.dir);
+                Box::new(sink)
+            }
+        };
+        tracing::info!("Writing metrics to {}", sink.name());
+        sinks.push(sink);
+    }
+
+    if let Some(prometheus) = prometheus {
+        // A taken over endpoint still exposes the buffers of the running sinks
+        prometheus.clear_buffers();
+        for (name, stats) in buffers {
+            prometheus.expose_buffer(&name, stats);
+        }
+        if let Some(stats) = restarts {
+            prometheus.expose_restarts(stats);
+        }
+        tracing::info!("Writing metrics to {}", prometheus.name());
+        sinks.push(Box::new(prometheus));
+    }
+
+    Ok((FanoutSink::new(sinks), handover))
+}
+
+/// Wraps `sink` in a disk-backed buffer if `BUFFER_DIR` is set.
+///
+/// Each sink gets its own subdirectory named after it, whose queue is taken
+/// over from `running` if it has one there. The buffer's stats are added to
+/// `buffers` so they can be exposed, and its queue to `handover`.
+fn buffered(
+    sink: Box<dyn Sink>,
+    config: &BufferConfig,
+    buffers: &mut Vec<(String, Arc<BufferStats>)>,
+    running: &Handover,
+    handover: &mut Handover,
+) -> Result<Box<dyn Sink>, ConfigError> {
+    let Some(dir) = &config.dir else {
+        return Ok(sink);
+    };
+
+    let name = sink.name().to_string();
+    let dir = Path::new(dir).join(&name);
+    let limits = QueueLimits {
+        max_bytes: config.max_bytes,
+        segment_bytes: config.segment_bytes,
+        max_age: Duration::from_secs(config.max_age_hours * 60 * 60),
+    };
+    let retry = RetryPolicy {
+        initial: Duration::from_millis(config.retry_initial_ms),
+        max: Duration::from_millis(config.retry_max_ms),
+    };
+    let sink = match running.buffers.get(&dir) {
+        Some(queue) => BufferedSink::take_over(sink, queue.clone(), limits, retry),
+        None => BufferedSink::open(sink, &dir, limits, retry)
+            .map_err(|e| ConfigError::invalid("BUFFER_DIR", e.to_string()))?,
+    };
+    tracing::info!("Buffering {} writes in {}", name, dir.display());
+
+    buffers.push((name, sink.stats()));
+    handover.buffers.insert(dir, sink.queue());
+    Ok(Box::new(sink))
+}
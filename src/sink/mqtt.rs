@@ -0,0 +1,570 @@
+//! MQTT sink with Home Assistant discovery.
+//!
+//! Every series is published as a retained state message under
+//! `{prefix}/{measurement}/{tag values...}`, e.g.
+//! `aiseg2/climate/リビング/temperature`. The first time a series is seen, a
+//! retained Home Assistant discovery config is published for it, so power,
+//! energy, temperature and humidity sensors appear without manual setup.
+
+use super::{label, unit_suffix, Sink};
+use crate::config::MqttConfig;
+use crate::error::StorageError;
+use crate::model::Point;
+use async_trait::async_trait;
+use rumqttc::{AsyncClient, Event, EventLoop, LastWill, MqttOptions, Packet, QoS};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Number of outgoing messages buffered while the broker is unreachable.
+const QUEUE_CAPACITY: usize = 1024;
+
+/// Delay before polling again after a connection error.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Keep-alive interval announced to the broker.
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+/// Availability payloads, matching Home Assistant's defaults.
+const ONLINE: &str = "online";
+const OFFLINE: &str = "offline";
+
+/// How Home Assistant should present a sensor.
+#[derive(Debug, Clone, PartialEq)]
+struct SensorClass {
+    device_class: Option<&'static str>,
+    state_class: &'static str,
+    unit: Option<&'static str>,
+}
+
+impl SensorClass {
+    const fn new(
+        device_class: &'static str,
+        state_class: &'static str,
+        unit: &'static str,
+    ) -> Self {
+        Self {
+            device_class: Some(device_class),
+            state_class,
+            unit: Some(unit),
+        }
+    }
+}
+
+/// Classifies a point by its climate category or the unit in its name.
+///
+/// Daily totals reset at midnight, which `total_increasing` treats as a new
+/// cycle, so they can feed the Home Assistant energy dashboard directly.
+fn sensor_class(point: &Point) -> SensorClass {
+    match point.tags.get("detail-type").map(String::as_str) {
+        Some("temperature") if point.measurement == "climate" => {
+            return SensorClass::new("temperature", "measurement", "°C")
+        }
+        Some("humidity") if point.measurement == "climate" => {
+            return SensorClass::new("humidity", "measurement", "%")
+        }
+        _ => {}
+    }
+
+    match unit_suffix(label(point)) {
+        Some("W") => SensorClass::new("power", "measurement", "W"),
+        Some("kWh") => SensorClass::new("energy", "total_increasing", "kWh"),
+        Some("L") => SensorClass::new("water", "total_increasing", "L"),
+        Some("㎥") => SensorClass::new("gas", "total_increasing", "m³"),
+        _ => SensorClass {
+            device_class: None,
+            state_class: "measurement",
+            unit: None,
+        },
+    }
+}
+
+/// Human-readable sensor name, e.g. "リビング 温度" or "EV 電力量".
+fn sensor_name(point: &Point) -> String {
+    let label = label(point);
+    let base = match unit_suffix(label) {
+        Some(unit) => &label[..label.len() - unit.len() - 2],
+        None => label,
+    };
+    match (
+        point.measurement.as_str(),
+        point.tags.get("detail-type").map(String::as_str),
+    ) {
+        ("climate", Some("temperature")) => format!("{} 温度", base),
+        ("climate", Some("humidity")) => format!("{} 湿度", base),
+        ("circuit_daily_total", _) => format!("{} 電力量", base),
+        _ => base.to_string(),
+    }
+}
+
+/// Replaces characters with a special meaning in MQTT topics.
+fn topic_segment(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            '/' | '+' | '#' | '\0' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// State topic of a field, built from the measurement and all tag values.
+fn state_topic(prefix: &str, point: &Point, field: &str) -> String {
+    let mut segments = vec![prefix.to_string(), topic_segment(&point.measurement)];
+    segments.extend(point.tags.values().map(|value| topic_segment(value)));
+    if field != "value" {
+        segments.push(topic_segment(field));
+    }
+    segments.join("/")
+}
+
+/// Stable, ASCII-only id derived from the state topic (FNV-1a).
+///
+/// Home Assistant object ids cannot contain Japanese names, and the id must
+/// not change between releases, so the std hasher is not an option.
+fn object_id(measurement: &str, state_topic: &str) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in state_topic.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    format!("aiseg2_{}_{:016x}", topic_segment(measurement), hash)
+}
+
+/// Sink that publishes points to an MQTT broker.
+///
+/// Publishing only enqueues messages; a background task owns the connection
+/// and reconnects when the broker goes away.
+pub struct MqttSink {
+    client: AsyncClient,
+    config: MqttConfig,
+    /// Timestamp of the last published value per state topic
+    published: Mutex<HashMap<String, Option<i64>>>,
+    event_loop: JoinHandle<()>,
+}
+
+impl MqttSink {
+    /// Creates the client and starts connecting in the background.
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn connect(config: MqttConfig) -> Self {
+        let availability_topic = availability_topic(&config);
+
+        let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+        options.set_keep_alive(KEEP_ALIVE);
+        options.set_last_will(LastWill::new(
+            &availability_topic,
+            OFFLINE,
+            QoS::AtLeastOnce,
+            true,
+        ));
+        if let Some(username) = &config.username {
+            options.set_credentials(username, config.password.clone().unwrap_or_default());
+        }
+
+        let (client, event_loop) = AsyncClient::new(options, QUEUE_CAPACITY);
+        let event_loop = tokio::spawn(run_event_loop(
+            event_loop,
+            client.clone(),
+            availability_topic,
+        ));
+
+        Self {
+            client,
+            config,
+            published: Mutex::new(HashMap::new()),
+            event_loop,
+        }
+    }
+
+    /// Publishes the Home Assistant discovery config of a field.
+    fn publish_discovery(
+        &self,
+        point: &Point,
+        state_topic: &str,
+        field: &str,
+    ) -> Result<(), rumqttc::ClientError> {
+        let object_id = object_id(&point.measurement, state_topic);
+        let class = sensor_class(point);
+        let mut name = sensor_name(point);
+        if field != "value" {
+            name = format!("{} {}", name, field);
+        }
+
+        let mut payload = json!({
+            "name": name,
+            "unique_id": object_id,
+            "object_id": object_id,
+            "state_topic": state_topic,
+            "state_class": class.state_class,
+            "availability_topic": availability_topic(&self.config),
+            "device": {
+                "identifiers": [self.config.topic_prefix],
+                "name": "AiSEG2",
+                "manufacturer": "Panasonic",
+                "model": "AiSEG2",
+            },
+        });
+        if let Some(device_class) = class.device_class {
+            payload["device_class"] = json!(device_class);
+        }
+        if let Some(unit) = class.unit {
+            payload["unit_of_measurement"] = json!(unit);
+        }
+
+        let topic = format!(
+            "{}/sensor/{}/config",
+            self.config.discovery_prefix, object_id
+        );
+        self.client
+            .try_publish(topic, QoS::AtLeastOnce, true, payload.to_string())
+    }
+}
+
+/// Topic carrying the forwarder's online/offline state.
+fn availability_topic(config: &MqttConfig) -> String {
+    format!("{}/status", config.topic_prefix)
+}
+
+/// Drives the connection, announcing availability after every (re)connect.
+async fn run_event_loop(
+    mut event_loop: EventLoop,
+    client: AsyncClient,
+    availability_topic: String,
+) {
+    loop {
+        match event_loop.poll().await {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                tracing::info!("Connected to MQTT broker");
+                if let Err(e) =
+                    client.try_publish(&availability_topic, QoS::AtLeastOnce, true, ONLINE)
+                {
+                    tracing::warn!("Failed to publish MQTT availability: {}", e);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => {
+                tracing::warn!("MQTT connection error: {}", e);
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        }
+    }
+}
+
+impl Drop for MqttSink {
+    fn drop(&mut self) {
+        self.event_loop.abort();
+    }
+}
+
+#[async_trait]
+impl Sink for MqttSink {
+    fn name(&self) -> &str {
+        "mqtt"
+    }
+
+    /// Publishes the numeric fields of `points` as retained state messages.
+    ///
+    /// A value older than the last one published for the same topic, such as
+    /// a backfilled daily total, is skipped so the state stays current.
+    ///
+    /// # Returns
+    /// - `Ok(())` if every message was queued
+    /// - `Err(StorageError::WriteFailed)` if the outgoing queue is full
+    async fn write(&self, points: &[Point]) -> Result<(), StorageError> {
+        let mut published = self.published.lock().unwrap();
+        let mut failed = 0;
+        let mut last_error = None;
+
+        for point in points {
+            for (field, value) in &point.fields {
+                let Some(value) = value.as_f64() else {
+                    continue;
+                };
+                let topic = state_topic(&self.config.topic_prefix, point, field);
+                let previous = published.get(&topic).copied();
+                if let (Some(Some(previous)), Some(timestamp)) = (previous, point.timestamp) {
+                    if timestamp < previous {
+                        continue;
+                    }
+                }
+
+                let mut result = Ok(());
+                if previous.is_none() && self.config.discovery {
+                    result = self.publish_discovery(point, &topic, field);
+                }
+                if result.is_ok() {
+                    result = self.client.try_publish(
+                        topic.as_str(),
+                        QoS::AtLeastOnce,
+                        true,
+                        value.to_string(),
+                    );
+                }
+
+                match result {
+                    Ok(()) => {
+                        published.insert(topic, point.timestamp);
+                    }
+                    Err(e) => {
+                        failed += 1;
+                        last_error = Some(e);
+                    }
+                }
+            }
+        }
+
+        match last_error {
+            None => Ok(()),
+            Some(e) => Err(StorageError::WriteFailed {
+                count: failed,
+                message: e.to_string(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::fake_mqtt::FakeMqttBroker;
+
+    fn point(measurement: &str, tags: &[(&str, &str)], value: f64) -> Point {
+        let mut builder = Point::builder(measurement).field("value", value);
+        for (key, tag) in tags {
+            builder = builder.tag(*key, *tag);
+        }
+        builder.build().unwrap()
+    }
+
+    mod succeeds {
+        use super::*;
+
+        #[test]
+        fn test_sensor_class_and_name() {
+            let test_cases = vec![
+                (
+                    point("power", &[("summary", "総発電電力(W)")], 1.0),
+                    Some("power"),
+                    "measurement",
+                    Some("W"),
+                    "総発電電力",
+                ),
+                (
+                    point(
+                        "climate",
+                        &[
+                            ("detail-type", "temperature"),
+                            ("detail-section", "リビング"),
+                        ],
+                        1.0,
+                    ),
+                    Some("temperature"),
+                    "measurement",
+                    Some("°C"),
+                    "リビング 温度",
+                ),
+                (
+                    point(
+                        "climate",
+                        &[("detail-type", "humidity"), ("detail-section", "和室")],
+                        1.0,
+                    ),
+                    Some("humidity"),
+                    "measurement",
+                    Some("%"),
+                    "和室 湿度",
+                ),
+                (
+                    point("daily_total", &[("detail-section", "発電量(kWh)")], 1.0),
+                    Some("energy"),
+                    "total_increasing",
+                    Some("kWh"),
+                    "発電量",
+                ),
+                (
+                    point("daily_total", &[("detail-section", "給湯量(L)")], 1.0),
+                    Some("water"),
+                    "total_increasing",
+                    Some("L"),
+                    "給湯量",
+                ),
+                (
+                    point("daily_total", &[("detail-section", "ガス量(㎥)")], 1.0),
+                    Some("gas"),
+                    "total_increasing",
+                    Some("m³"),
+                    "ガス量",
+                ),
+                (
+                    point("circuit_daily_total", &[("detail-section", "EV(kWh)")], 1.0),
+                    Some("energy"),
+                    "total_increasing",
+                    Some("kWh"),
+                    "EV 電力量",
+                ),
+                (
+                    point("other", &[("detail-section", "謎")], 1.0),
+                    None,
+                    "measurement",
+                    None,
+                    "謎",
+                ),
+            ];
+
+            for (point, device_class, state_class, unit, name) in test_cases {
+                let class = sensor_class(&point);
+                assert_eq!(class.device_class, device_class, "case: {}", name);
+                assert_eq!(class.state_class, state_class, "case: {}", name);
+                assert_eq!(class.unit, unit, "case: {}", name);
+                assert_eq!(sensor_name(&point), name);
+            }
+        }
+
+        #[test]
+        fn test_state_topic_and_object_id() {
+            let climate = point(
+                "climate",
+                &[
+                    ("detail-type", "temperature"),
+                    ("detail-section", "洋室1/2"),
+                ],
+                1.0,
+            );
+            let topic = state_topic("aiseg2", &climate, "value");
+
+            assert_eq!(topic, "aiseg2/climate/洋室1_2/temperature");
+            assert_eq!(
+                state_topic("aiseg2", &climate, "peak"),
+                "aiseg2/climate/洋室1_2/temperature/peak"
+            );
+            let id = object_id("climate", &topic);
+            assert_eq!(id, object_id("climate", &topic));
+            assert!(id.starts_with("aiseg2_climate_"));
+            assert!(id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'));
+        }
+
+        #[tokio::test]
+        async fn test_publishes_state_and_discovery() {
+            let broker = FakeMqttBroker::start().await;
+            let sink = MqttSink::connect(broker.config());
+
+            sink.write(&[
+                point("power", &[("summary", "総発電電力(W)")], 2500.0),
+                point("daily_total", &[("detail-section", "発電量(kWh)")], 17.1),
+            ])
+            .await
+            .unwrap();
+
+            let messages = broker.wait_until(|messages| messages.len() >= 5).await;
+            let retained = broker.retained();
+            assert!(messages.iter().all(|m| m.retain));
+            assert_eq!(retained["aiseg2/status"], "online");
+            assert_eq!(retained["aiseg2/power/総発電電力(W)"], "2500");
+            assert_eq!(retained["aiseg2/daily_total/発電量(kWh)"], "17.1");
+
+            let object_id = object_id("daily_total", "aiseg2/daily_total/発電量(kWh)");
+            let config: serde_json::Value = serde_json::from_str(
+                &retained[&format!("homeassistant/sensor/{}/config", object_id)],
+            )
+            .unwrap();
+            assert_eq!(config["name"], "発電量");
+            assert_eq!(config["unique_id"], object_id.as_str());
+            assert_eq!(config["state_topic"], "aiseg2/daily_total/発電量(kWh)");
+            assert_eq!(config["device_class"], "energy");
+            assert_eq!(config["state_class"], "total_increasing");
+            assert_eq!(config["unit_of_measurement"], "kWh");
+            assert_eq!(config["availability_topic"], "aiseg2/status");
+
+            let connect = &broker.connects()[0];
+            assert_eq!(connect.client_id, "test-forwarder");
+            assert_eq!(connect.will_topic.as_deref(), Some("aiseg2/status"));
+            assert_eq!(connect.will_payload.as_deref(), Some("offline"));
+        }
+
+        #[tokio::test]
+        async fn test_discovery_once_and_skips_older_values() {
+            let broker = FakeMqttBroker::start().await;
+            let sink = MqttSink::connect(broker.config());
+            let total = |value: f64, timestamp: i64| {
+                Point::builder("daily_total")
+                    .tag("detail-section", "発電量(kWh)")
+                    .field("value", value)
+                    .timestamp(timestamp)
+                    .build()
+                    .unwrap()
+            };
+
+            sink.write(&[total(5.0, 2_000)]).await.unwrap();
+            sink.write(&[total(9.9, 1_000)]).await.unwrap();
+            sink.write(&[total(6.0, 2_000)]).await.unwrap();
+
+            let messages = broker
+                .wait_until(|messages| messages.iter().any(|m| m.payload == "6"))
+                .await;
+            let discovery = messages
+                .iter()
+                .filter(|m| m.topic.starts_with("homeassistant/"))
+                .count();
+            let states: Vec<&str> = messages
+                .iter()
+                .filter(|m| m.topic == "aiseg2/daily_total/発電量(kWh)")
+                .map(|m| m.payload.as_str())
+                .collect();
+            assert_eq!(discovery, 1);
+            assert_eq!(states, vec!["5", "6"]);
+        }
+
+        #[tokio::test]
+        async fn test_discovery_disabled() {
+            let broker = FakeMqttBroker::start().await;
+            let sink = MqttSink::connect(MqttConfig {
+                discovery: false,
+                topic_prefix: "home/energy".to_string(),
+                ..broker.config()
+            });
+
+            sink.write(&[point("power", &[("summary", "総消費電力(W)")], 3800.0)])
+                .await
+                .unwrap();
+
+            let messages = broker
+                .wait_until(|messages| {
+                    messages
+                        .iter()
+                        .any(|m| m.topic == "home/energy/power/総消費電力(W)")
+                })
+                .await;
+            assert!(messages
+                .iter()
+                .all(|m| !m.topic.starts_with("homeassistant/")));
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_queue_full_while_broker_unreachable() {
+            let sink = MqttSink::connect(MqttConfig {
+                host: "127.0.0.1".to_string(),
+                port: 1,
+                client_id: "test-forwarder".to_string(),
+                username: None,
+                password: None,
+                topic_prefix: "aiseg2".to_string(),
+                discovery: false,
+                discovery_prefix: "homeassistant".to_string(),
+            });
+            let points: Vec<Point> = (0..QUEUE_CAPACITY + 10)
+                .map(|i| point("power", &[("summary", &format!("機器{}(W)", i))], 1.0))
+                .collect();
+
+            let result = sink.write(&points).await;
+
+            assert!(matches!(result, Err(StorageError::WriteFailed { .. })));
+        }
+    }
+}
@@ -0,0 +1,637 @@
+//! Sink exporting points as OpenTelemetry metrics over OTLP.
+//!
+//! Every batch becomes one `ExportMetricsServiceRequest`, sent over gRPC or
+//! as protobuf over HTTP. Instantaneous readings (power, climate) are
+//! gauges; daily totals are cumulative monotonic sums that start at the
+//! day's midnight, so a collector can compute rates or reset per day. Tags
+//! become data point attributes, and the resource names the site and the
+//! device model.
+
+use super::{label, unit_suffix, Sink};
+use crate::config::{OtlpConfig, OtlpProtocol};
+use crate::error::{ConfigError, StorageError};
+use crate::model::Point;
+use async_trait::async_trait;
+use chrono::{DateTime, Local, TimeZone, Utc};
+use opentelemetry_proto::tonic::collector::metrics::v1::metrics_service_client::MetricsServiceClient;
+use opentelemetry_proto::tonic::collector::metrics::v1::{
+    ExportMetricsServiceRequest, ExportMetricsServiceResponse,
+};
+use opentelemetry_proto::tonic::common::v1::{any_value, AnyValue, InstrumentationScope, KeyValue};
+use opentelemetry_proto::tonic::metrics::v1::{
+    metric, number_data_point, AggregationTemporality, Gauge, Metric, NumberDataPoint,
+    ResourceMetrics, ScopeMetrics, Sum,
+};
+use opentelemetry_proto::tonic::resource::v1::Resource;
+use prost::Message;
+use reqwest::header;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tonic::transport::{Channel, Endpoint};
+
+/// Prefix of every exported metric name.
+const METRIC_PREFIX: &str = "aiseg2";
+
+/// Path of the metrics endpoint of an OTLP/HTTP receiver.
+const HTTP_PATH: &str = "/v1/metrics";
+
+/// How a metric is aggregated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    /// A reading at one instant
+    Gauge,
+    /// A total accumulated since the start of the day
+    DailySum,
+}
+
+/// Name, unit and kind of the metric a field is exported as.
+#[derive(Debug, Clone, PartialEq)]
+struct Descriptor {
+    name: String,
+    /// UCUM unit, empty if unknown
+    unit: &'static str,
+    kind: Kind,
+}
+
+/// Maps a field of a point to its metric.
+///
+/// Climate readings get one metric per category, since temperature and
+/// humidity have different units. Other units come from the series label,
+/// e.g. "発電量(kWh)".
+fn descriptor(point: &Point, field: &str) -> Descriptor {
+    let mut name = format!("{}.{}", METRIC_PREFIX, point.measurement);
+    let category = point.tags.get("detail-type").map(String::as_str);
+    let unit = match category {
+        Some("temperature") if point.measurement == "climate" => {
+            name.push_str(".temperature");
+            "Cel"
+        }
+        Some("humidity") if point.measurement == "climate" => {
+            name.push_str(".humidity");
+            "%"
+        }
+        _ => match unit_suffix(label(point)) {
+            Some("W") => "W",
+            Some("kWh") => "kW.h",
+            Some("L") => "L",
+            Some("㎥") => "m3",
+            _ => "",
+        },
+    };
+    if field != "value" {
+        name.push('.');
+        name.push_str(field);
+    }
+    let kind = if point.measurement.ends_with("daily_total") {
+        Kind::DailySum
+    } else {
+        Kind::Gauge
+    };
+    Descriptor { name, unit, kind }
+}
+
+/// End of the local day starting at `start`, both in nanoseconds.
+fn day_end(start: i64) -> Option<i64> {
+    let next = DateTime::from_timestamp_nanos(start)
+        .with_timezone(&Local)
+        .date_naive()
+        .succ_opt()?;
+    Local
+        .from_local_datetime(&next.and_hms_opt(0, 0, 0)?)
+        .earliest()?
+        .timestamp_nanos_opt()
+}
+
+fn string_attribute(key: &str, value: &str) -> KeyValue {
+    KeyValue {
+        key: key.to_string(),
+        value: Some(AnyValue {
+            value: Some(any_value::Value::StringValue(value.to_string())),
+        }),
+        ..Default::default()
+    }
+}
+
+/// Resource describing the forwarder and the AiSEG2 it reads.
+fn resource(config: &OtlpConfig) -> Resource {
+    let mut attributes = vec![
+        string_attribute("service.name", env!("CARGO_PKG_NAME")),
+        string_attribute("service.version", env!("CARGO_PKG_VERSION")),
+        string_attribute("device.manufacturer", "Panasonic"),
+        string_attribute("device.model.name", &config.device_model),
+    ];
+    if let Some(site) = &config.site {
+        attributes.push(string_attribute("site", site));
+    }
+    Resource {
+        attributes,
+        ..Default::default()
+    }
+}
+
+/// Groups the numeric fields of `points` into metrics.
+///
+/// Points without a timestamp are stamped with `now`. A daily total is
+/// reported at `now` while its day is running and at the day's end after.
+fn metrics(points: &[Point], now: i64) -> Vec<Metric> {
+    let mut grouped: BTreeMap<(String, &'static str), (Kind, Vec<NumberDataPoint>)> =
+        BTreeMap::new();
+
+    for point in points {
+        let timestamp = point.timestamp.unwrap_or(now);
+        let attributes: Vec<KeyValue> = point
+            .tags
+            .iter()
+            .map(|(key, value)| string_attribute(key, value))
+            .collect();
+
+        for (field, value) in &point.fields {
+            let Some(value) = value.as_f64() else {
+                continue;
+            };
+            let descriptor = descriptor(point, field);
+            let (start, time) = match descriptor.kind {
+                Kind::Gauge => (0, timestamp),
+                Kind::DailySum => {
+                    let end = day_end(timestamp).unwrap_or(timestamp);
+                    (timestamp, now.clamp(timestamp, end))
+                }
+            };
+            grouped
+                .entry((descriptor.name, descriptor.unit))
+                .or_insert_with(|| (descriptor.kind, Vec::new()))
+                .1
+                .push(NumberDataPoint {
+                    attributes: attributes.clone(),
+                    start_time_unix_nano: start.max(0) as u64,
+                    time_unix_nano: time.max(0) as u64,
+                    value: Some(number_data_point::Value::AsDouble(value)),
+                    ..Default::default()
+                });
+        }
+    }
+
+    grouped
+        .into_iter()
+        .map(|((name, unit), (kind, data_points))| {
+            let (description, data) = match kind {
+                Kind::Gauge => (
+                    "Latest value collected from AiSEG2.",
+                    metric::Data::Gauge(Gauge { data_points }),
+                ),
+                Kind::DailySum => (
+                    "Total since midnight collected from AiSEG2.",
+                    metric::Data::Sum(Sum {
+                        data_points,
+                        aggregation_temporality: AggregationTemporality::Cumulative as i32,
+                        is_monotonic: true,
+                    }),
+                ),
+            };
+            Metric {
+                name,
+                description: description.to_string(),
+                unit: unit.to_string(),
+                data: Some(data),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// Connection to the collector.
+enum Transport {
+    Grpc(MetricsServiceClient<Channel>),
+    Http {
+        client: reqwest::Client,
+        url: String,
+    },
+}
+
+/// Sink that exports points to an OpenTelemetry collector.
+pub struct OtlpSink {
+    transport: Transport,
+    resource: Resource,
+}
+
+impl OtlpSink {
+    /// Creates the sink; gRPC connects lazily on the first export.
+    ///
+    /// # Returns
+    /// - `Ok(OtlpSink)` ready to export
+    /// - `Err(ConfigError)` if the endpoint is not a valid URL
+    pub fn new(config: &OtlpConfig) -> Result<Self, ConfigError> {
+        let endpoint = config.endpoint();
+        let timeout = Duration::from_millis(config.timeout_ms);
+        let transport = match config.protocol {
+            OtlpProtocol::Grpc => {
+                let channel = Endpoint::from_shared(endpoint)
+                    .map_err(|e| ConfigError::invalid("OTLP_ENDPOINT", e.to_string()))?
+                    .timeout(timeout)
+                    .connect_lazy();
+                Transport::Grpc(MetricsServiceClient::new(channel))
+            }
+            OtlpProtocol::HttpProtobuf => {
+                let base = endpoint.trim_end_matches('/');
+                let url = if base.ends_with(HTTP_PATH) {
+                    base.to_string()
+                } else {
+                    format!("{}{}", base, HTTP_PATH)
+                };
+                reqwest::Url::parse(&url)
+                    .map_err(|e| ConfigError::invalid("OTLP_ENDPOINT", e.to_string()))?;
+                let client = reqwest::Client::builder()
+                    .timeout(timeout)
+                    .build()
+                    .map_err(|e| ConfigError::invalid("OTLP_ENDPOINT", e.to_string()))?;
+                Transport::Http { client, url }
+            }
+        };
+        Ok(Self {
+            transport,
+            resource: resource(config),
+        })
+    }
+
+    fn request(&self, metrics: Vec<Metric>) -> ExportMetricsServiceRequest {
+        ExportMetricsServiceRequest {
+            resource_metrics: vec![ResourceMetrics {
+                resource: Some(self.resource.clone()),
+                scope_metrics: vec![ScopeMetrics {
+                    scope: Some(InstrumentationScope {
+                        name: env!("CARGO_PKG_NAME").to_string(),
+                        version: env!("CARGO_PKG_VERSION").to_string(),
+                        ..Default::default()
+                    }),
+                    metrics,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        }
+    }
+
+    /// Sends one request over the configured transport.
+    async fn export(
+        &self,
+        request: ExportMetricsServiceRequest,
+        count: usize,
+    ) -> Result<ExportMetricsServiceResponse, StorageError> {
+        match &self.transport {
+            Transport::Grpc(client) => {
+                let mut client = client.clone();
+                match client.export(request).await {
+                    Ok(response) => Ok(response.into_inner()),
+                    Err(status) if status.code() == tonic::Code::InvalidArgument => {
+                        Err(StorageError::InvalidDataPoint(status.message().to_string()))
+                    }
+                    Err(status) => Err(StorageError::write_failed(
+                        count,
+                        format!("{:?}: {}", status.code(), status.message()),
+                    )),
+                }
+            }
+            Transport::Http { client, url } => {
+                let response = client
+                    .post(url)
+                    .header(header::CONTENT_TYPE, "application/x-protobuf")
+                    .body(request.encode_to_vec())
+                    .send()
+                    .await
+                    .map_err(|e| StorageError::write_failed(count, e))?;
+                let status = response.status();
+                let body = response
+                    .bytes()
+                    .await
+                    .map_err(|e| StorageError::write_failed(count, e))?;
+                if status == reqwest::StatusCode::BAD_REQUEST {
+                    return Err(StorageError::InvalidDataPoint(format!(
+                        "{} rejected by OTLP receiver",
+                        status
+                    )));
+                }
+                if !status.is_success() {
+                    return Err(StorageError::write_failed(count, status));
+                }
+                // An empty body is a full success
+                Ok(ExportMetricsServiceResponse::decode(body).unwrap_or_default())
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for OtlpSink {
+    fn name(&self) -> &str {
+        "otlp"
+    }
+
+    /// Exports the numeric fields of the batch in one request.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the receiver accepted the request, even partially
+    /// - `Err(StorageError::InvalidDataPoint)` if it rejected the data
+    /// - `Err(StorageError::WriteFailed)` if it is unreachable or failed
+    async fn write(&self, points: &[Point]) -> Result<(), StorageError> {
+        let now = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+        let metrics = metrics(points, now);
+        if metrics.is_empty() {
+            return Ok(());
+        }
+
+        let response = self.export(self.request(metrics), points.len()).await?;
+        if let Some(partial) = response.partial_success {
+            if partial.rejected_data_points > 0 {
+                tracing::warn!(
+                    "OTLP receiver rejected {} data points: {}",
+                    partial.rejected_data_points,
+                    partial.error_message
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::fake_otlp::FakeOtlpReceiver;
+
+    fn power(section: &str, value: i64) -> Point {
+        Point::builder("power")
+            .tag("detail-section", section)
+            .tag("detail-type", "consumption")
+            .field("value", value)
+            .build()
+            .unwrap()
+    }
+
+    fn daily_total(section: &str, value: f64, midnight: i64) -> Point {
+        Point::builder("daily_total")
+            .tag("detail-section", section)
+            .field("value", value)
+            .timestamp(midnight)
+            .build()
+            .unwrap()
+    }
+
+    fn midnight(days_ago: i64) -> i64 {
+        (Local::now() - chrono::Duration::days(days_ago))
+            .with_time(chrono::NaiveTime::default())
+            .unwrap()
+            .timestamp_nanos_opt()
+            .unwrap()
+    }
+
+    fn value(point: &NumberDataPoint) -> f64 {
+        match point.value {
+            Some(number_data_point::Value::AsDouble(value)) => value,
+            other => panic!("unexpected value {:?}", other),
+        }
+    }
+
+    mod succeeds {
+        use super::*;
+
+        #[test]
+        fn test_descriptor() {
+            let climate = |category: &str| {
+                Point::builder("climate")
+                    .tag("detail-section", "リビング")
+                    .tag("detail-type", category)
+                    .field("value", 21.5)
+                    .build()
+                    .unwrap()
+            };
+            let test_cases = vec![
+                (
+                    "power",
+                    power("EV(W)", 237),
+                    "aiseg2.power",
+                    "W",
+                    Kind::Gauge,
+                ),
+                (
+                    "temperature",
+                    climate("temperature"),
+                    "aiseg2.climate.temperature",
+                    "Cel",
+                    Kind::Gauge,
+                ),
+                (
+                    "humidity",
+                    climate("humidity"),
+                    "aiseg2.climate.humidity",
+                    "%",
+                    Kind::Gauge,
+                ),
+                (
+                    "energy",
+                    daily_total("発電量(kWh)", 17.1, 0),
+                    "aiseg2.daily_total",
+                    "kW.h",
+                    Kind::DailySum,
+                ),
+                (
+                    "gas",
+                    daily_total("ガス量(㎥)", 0.5, 0),
+                    "aiseg2.daily_total",
+                    "m3",
+                    Kind::DailySum,
+                ),
+                (
+                    "unknown unit",
+                    Point::builder("status")
+                        .field("value", 1i64)
+                        .build()
+                        .unwrap(),
+                    "aiseg2.status",
+                    "",
+                    Kind::Gauge,
+                ),
+            ];
+
+            for (name, point, metric, unit, kind) in test_cases {
+                assert_eq!(
+                    descriptor(&point, "value"),
+                    Descriptor {
+                        name: metric.to_string(),
+                        unit,
+                        kind
+                    },
+                    "case: {}",
+                    name
+                );
+            }
+        }
+
+        #[test]
+        fn test_metrics_group_gauges_and_daily_sums() {
+            let now = Utc::now().timestamp_nanos_opt().unwrap();
+            let today = midnight(0);
+            let yesterday = midnight(1);
+
+            let metrics = metrics(
+                &[
+                    power("EV(W)", 237),
+                    power("照明(W)", 348),
+                    daily_total("発電量(kWh)", 17.1, today),
+                    daily_total("発電量(kWh)", 21.4, yesterday),
+                    daily_total("給湯量(L)", 182.4, today),
+                ],
+                now,
+            );
+
+            let names: Vec<(&str, &str)> = metrics
+                .iter()
+                .map(|metric| (metric.name.as_str(), metric.unit.as_str()))
+                .collect();
+            assert_eq!(
+                names,
+                vec![
+                    ("aiseg2.daily_total", "L"),
+                    ("aiseg2.daily_total", "kW.h"),
+                    ("aiseg2.power", "W"),
+                ]
+            );
+
+            let Some(metric::Data::Gauge(gauge)) = &metrics[2].data else {
+                panic!("power should be a gauge");
+            };
+            assert_eq!(gauge.data_points.len(), 2);
+            assert_eq!(gauge.data_points[0].time_unix_nano, now as u64);
+            assert_eq!(
+                gauge.data_points[0].attributes,
+                vec![
+                    string_attribute("detail-section", "EV(W)"),
+                    string_attribute("detail-type", "consumption"),
+                ]
+            );
+
+            let Some(metric::Data::Sum(sum)) = &metrics[1].data else {
+                panic!("energy should be a sum");
+            };
+            assert!(sum.is_monotonic);
+            assert_eq!(
+                sum.aggregation_temporality,
+                AggregationTemporality::Cumulative as i32
+            );
+            let running = &sum.data_points[0];
+            assert_eq!(value(running), 17.1);
+            assert_eq!(running.start_time_unix_nano, today as u64);
+            assert_eq!(running.time_unix_nano, now as u64);
+            let finished = &sum.data_points[1];
+            assert_eq!(finished.start_time_unix_nano, yesterday as u64);
+            assert_eq!(finished.time_unix_nano, today as u64);
+        }
+
+        #[tokio::test]
+        async fn test_exports_over_grpc() {
+            let receiver = FakeOtlpReceiver::start(OtlpProtocol::Grpc).await;
+            let config = OtlpConfig {
+                site: Some("home".to_string()),
+                ..receiver.config()
+            };
+            let sink = OtlpSink::new(&config).unwrap();
+
+            sink.write(&[power("EV(W)", 237)]).await.unwrap();
+
+            let requests = receiver.requests();
+            assert_eq!(requests.len(), 1);
+            let resource_metrics = &requests[0].resource_metrics[0];
+            let attributes = &resource_metrics.resource.as_ref().unwrap().attributes;
+            assert!(attributes.contains(&string_attribute("site", "home")));
+            assert!(attributes.contains(&string_attribute("device.model.name", "AiSEG2")));
+            let metric = &resource_metrics.scope_metrics[0].metrics[0];
+            assert_eq!(metric.name, "aiseg2.power");
+        }
+
+        #[tokio::test]
+        async fn test_exports_over_http() {
+            let receiver = FakeOtlpReceiver::start(OtlpProtocol::HttpProtobuf).await;
+            let sink = OtlpSink::new(&receiver.config()).unwrap();
+
+            sink.write(&[daily_total("消費量(kWh)", 7.9, midnight(0))])
+                .await
+                .unwrap();
+
+            let requests = receiver.requests();
+            assert_eq!(requests.len(), 1);
+            let metric = &requests[0].resource_metrics[0].scope_metrics[0].metrics[0];
+            assert_eq!(metric.name, "aiseg2.daily_total");
+            assert!(matches!(metric.data, Some(metric::Data::Sum(_))));
+        }
+
+        #[tokio::test]
+        async fn test_skips_batches_without_numbers() {
+            let receiver = FakeOtlpReceiver::start(OtlpProtocol::HttpProtobuf).await;
+            let sink = OtlpSink::new(&receiver.config()).unwrap();
+            let status = Point::builder("status")
+                .field("mode", "eco")
+                .build()
+                .unwrap();
+
+            sink.write(&[status]).await.unwrap();
+
+            assert!(receiver.requests().is_empty());
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[test]
+        fn test_invalid_endpoint() {
+            for protocol in [OtlpProtocol::Grpc, OtlpProtocol::HttpProtobuf] {
+                let config = OtlpConfig {
+                    endpoint: Some("not a url".to_string()),
+                    protocol,
+                    site: None,
+                    device_model: "AiSEG2".to_string(),
+                    timeout_ms: 1000,
+                };
+                assert!(OtlpSink::new(&config).is_err(), "case: {:?}", protocol);
+            }
+        }
+
+        #[tokio::test]
+        async fn test_rejected_and_unavailable() {
+            for protocol in [OtlpProtocol::Grpc, OtlpProtocol::HttpProtobuf] {
+                let receiver = FakeOtlpReceiver::start(protocol).await;
+                let sink = OtlpSink::new(&receiver.config()).unwrap();
+                receiver.fail_next(400);
+                receiver.fail_next(503);
+
+                let rejected = sink.write(&[power("EV(W)", 237)]).await;
+                let unavailable = sink.write(&[power("EV(W)", 237)]).await;
+
+                assert!(
+                    matches!(rejected, Err(StorageError::InvalidDataPoint(_))),
+                    "case: {:?}",
+                    protocol
+                );
+                assert!(
+                    matches!(unavailable, Err(StorageError::WriteFailed { count: 1, .. })),
+                    "case: {:?}",
+                    protocol
+                );
+                assert!(receiver.requests().is_empty(), "case: {:?}", protocol);
+            }
+        }
+
+        #[tokio::test]
+        async fn test_unreachable_receiver() {
+            let receiver = FakeOtlpReceiver::start(OtlpProtocol::Grpc).await;
+            let config = receiver.config();
+            drop(receiver);
+
+            let result = OtlpSink::new(&config)
+                .unwrap()
+                .write(&[power("EV(W)", 237)])
+                .await;
+
+            assert!(matches!(result, Err(StorageError::WriteFailed { .. })));
+        }
+    }
+}
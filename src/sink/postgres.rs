@@ -0,0 +1,456 @@
+//! Sink storing points in a PostgreSQL or TimescaleDB table.
+//!
+//! Every field of a point becomes one row of
+//! `(time, measurement, field, tags, value, text_value)`, with the tags as a
+//! jsonb object. The table is created on first connect and turned into a
+//! hypertable when the TimescaleDB extension is installed. A batch is sent
+//! as a single `INSERT ... SELECT FROM UNNEST(...)` that upserts on
+//! `(measurement, field, tags, time)`, so daily totals rewritten every
+//! minute keep one row per day.
+//!
+//! Connecting gives up after [`CONNECT_TIMEOUT`] unless the connection
+//! string sets `connect_timeout`. Refused credentials (SQLSTATE class 28)
+//! fail with `StorageError::AuthFailed`, so they are not retried.
+
+use super::rows::rows;
+use super::Sink;
+use crate::config::PostgresConfig;
+use crate::error::{ConfigError, StorageError};
+use crate::model::Point;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio_postgres::error::SqlState;
+use tokio_postgres::{Client, NoTls};
+
+/// Time allowed to open a connection when the URL does not set one.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Column-wise rows of one batch, bound as arrays to the upsert.
+#[derive(Debug, Default)]
+struct Columns {
+    times: Vec<DateTime<Utc>>,
+    measurements: Vec<String>,
+    fields: Vec<String>,
+    tags: Vec<serde_json::Value>,
+    values: Vec<Option<f64>>,
+    text_values: Vec<Option<String>>,
+}
+
+impl Columns {
+    fn from_points(points: &[Point]) -> Self {
+        let mut columns = Columns::default();
+        for row in rows(points) {
+            columns.times.push(DateTime::from_timestamp_nanos(row.time));
+            columns.measurements.push(row.measurement);
+            columns.fields.push(row.field);
+            columns
+                .tags
+                .push(serde_json::to_value(&row.tags).unwrap_or_default());
+            columns.values.push(row.value);
+            columns.text_values.push(row.text_value);
+        }
+        columns
+    }
+}
+
+/// Sink that upserts points into a PostgreSQL table.
+pub struct PostgresSink {
+    config: PostgresConfig,
+    upsert: String,
+    /// Connection, opened on the first write and reopened after it closes
+    client: Mutex<Option<Client>>,
+}
+
+impl PostgresSink {
+    /// Creates the sink without connecting.
+    ///
+    /// # Returns
+    /// - `Ok(PostgresSink)` connecting on the first write
+    /// - `Err(ConfigError)` if the table name is not a plain identifier
+    pub fn new(config: PostgresConfig) -> Result<Self, ConfigError> {
+        validate_table(&config.table)?;
+        let upsert = upsert_statement(&config.table);
+        Ok(Self {
+            config,
+            upsert,
+            client: Mutex::new(None),
+        })
+    }
+
+    /// Connects and makes sure the table exists.
+    async fn connect(&self) -> Result<Client, tokio_postgres::Error> {
+        let mut config: tokio_postgres::Config = self.config.url.parse()?;
+        if config.get_connect_timeout().is_none() {
+            config.connect_timeout(CONNECT_TIMEOUT);
+        }
+        let (client, connection) = config.connect(NoTls).await?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                tracing::warn!("PostgreSQL connection closed: {}", describe(&e));
+            }
+        });
+        create_table(&client, &self.config.table).await?;
+        Ok(client)
+    }
+}
+
+/// Accepts `table` or `schema.table` made of letters, digits and underscores.
+fn validate_table(table: &str) -> Result<(), ConfigError> {
+    let parts: Vec<&str> = table.split('.').collect();
+    let valid = parts.len() <= 2
+        && parts.iter().all(|part| {
+            part.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        });
+    if valid {
+        Ok(())
+    } else {
+        Err(ConfigError::invalid(
+            "POSTGRES_TABLE",
+            format!("'{}' is not a valid table name", table),
+        ))
+    }
+}
+
+/// Upserts the arrays of [`Columns`], in field order, into `table`.
+fn upsert_statement(table: &str) -> String {
+    format!(
+        "INSERT INTO {} (time, measurement, field, tags, value, text_value) \
+         SELECT * FROM UNNEST($1::timestamptz[], $2::text[], $3::text[], $4::jsonb[], $5::float8[], $6::text[]) \
+         ON CONFLICT (measurement, field, tags, time) \
+         DO UPDATE SET value = EXCLUDED.value, text_value = EXCLUDED.text_value",
+        table
+    )
+}
+
+/// Creates the table and its tag index, and a hypertable on TimescaleDB.
+async fn create_table(client: &Client, table: &str) -> Result<(), tokio_postgres::Error> {
+    let name = table.rsplit('.').next().unwrap_or(table);
+    client
+        .batch_execute(&format!(
+            "CREATE TABLE IF NOT EXISTS {table} (
+                time timestamptz NOT NULL,
+                measurement text NOT NULL,
+                field text NOT NULL,
+                tags jsonb NOT NULL DEFAULT '{{}}',
+                value double precision,
+                text_value text,
+                UNIQUE (measurement, field, tags, time)
+            );
+            CREATE INDEX IF NOT EXISTS {name}_tags_idx ON {table} USING gin (tags);"
+        ))
+        .await?;
+
+    let timescale = client
+        .query_opt(
+            "SELECT 1 FROM pg_extension WHERE extname = 'timescaledb'",
+            &[],
+        )
+        .await?
+        .is_some();
+    if timescale {
+        client
+            .execute(
+                "SELECT create_hypertable($1::text::regclass, 'time', if_not_exists => TRUE, migrate_data => TRUE)",
+                &[&table],
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+/// Maps a failed connect or statement to a [`StorageError`] by its SQLSTATE.
+///
+/// Class 28 is "invalid authorization specification" and class 22 "data
+/// exception": resending the batch cannot help with either.
+fn storage_error(code: Option<&SqlState>, count: usize, message: String) -> StorageError {
+    match code.map(|code| code.code().get(..2)) {
+        Some(Some("28")) => StorageError::AuthFailed,
+        Some(Some("22")) => StorageError::InvalidDataPoint(message),
+        _ => StorageError::write_failed(count, message),
+    }
+}
+
+/// Formats an error with the server's message, if there is one.
+fn describe(e: &tokio_postgres::Error) -> String {
+    match e.as_db_error() {
+        Some(db) => db.to_string(),
+        None => e.to_string(),
+    }
+}
+
+#[async_trait]
+impl Sink for PostgresSink {
+    fn name(&self) -> &str {
+        "postgres"
+    }
+
+    /// Upserts the batch in one statement, connecting first if needed.
+    ///
+    /// # Returns
+    /// - `Ok(())` if every row was stored
+    /// - `Err(StorageError::AuthFailed)` if PostgreSQL refused the credentials
+    /// - `Err(StorageError::InvalidDataPoint)` if PostgreSQL rejected the data
+    /// - `Err(StorageError::WriteFailed)` if the database is unreachable or
+    ///   the statement failed otherwise
+    async fn write(&self, points: &[Point]) -> Result<(), StorageError> {
+        let columns = Columns::from_points(points);
+        if columns.times.is_empty() {
+            return Ok(());
+        }
+
+        let mut client = self.client.lock().await;
+        if client.as_ref().is_none_or(Client::is_closed) {
+            let connected = self.connect().await.map_err(|e| {
+                storage_error(
+                    e.code(),
+                    points.len(),
+                    format!("cannot connect to PostgreSQL: {}", describe(&e)),
+                )
+            })?;
+            tracing::info!("Connected to PostgreSQL, writing to {}", self.config.table);
+            *client = Some(connected);
+        }
+        let Some(connected) = client.as_ref() else {
+            unreachable!("connected above");
+        };
+
+        let result = connected
+            .execute(
+                &self.upsert,
+                &[
+                    &columns.times,
+                    &columns.measurements,
+                    &columns.fields,
+                    &columns.tags,
+                    &columns.values,
+                    &columns.text_values,
+                ],
+            )
+            .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                if e.is_closed() {
+                    *client = None;
+                }
+                Err(storage_error(e.code(), points.len(), describe(&e)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::FieldValue;
+
+    fn point(value: impl Into<FieldValue>, timestamp: i64) -> Point {
+        Point::builder("circuit_daily_total")
+            .tag("detail-section", "EV(kWh)")
+            .field("value", value)
+            .timestamp(timestamp)
+            .build()
+            .unwrap()
+    }
+
+    mod succeeds {
+        use super::*;
+
+        #[test]
+        fn test_valid_table_names() {
+            for table in ["aiseg2_readings", "energy.readings", "_t1"] {
+                assert!(validate_table(table).is_ok(), "case: {}", table);
+            }
+        }
+
+        #[test]
+        fn test_columns_from_points() {
+            let status = Point::builder("power")
+                .tag("summary", "売買電力(W)")
+                .field("value", -1080i64)
+                .field("state", "selling")
+                .field("online", true)
+                .timestamp(3_000_000_000)
+                .build()
+                .unwrap();
+            let points = [
+                point(1.5, 1_000_000_000),
+                point(2.0, 2_000_000_000),
+                // Same key as the point before: the later value wins
+                point(4.5, 2_000_000_000),
+                status,
+            ];
+
+            let columns = Columns::from_points(&points);
+
+            assert_eq!(
+                columns.times,
+                vec![
+                    DateTime::from_timestamp(1, 0).unwrap(),
+                    DateTime::from_timestamp(2, 0).unwrap(),
+                    DateTime::from_timestamp(3, 0).unwrap(),
+                    DateTime::from_timestamp(3, 0).unwrap(),
+                    DateTime::from_timestamp(3, 0).unwrap(),
+                ]
+            );
+            assert_eq!(
+                columns.measurements,
+                vec![
+                    "circuit_daily_total",
+                    "circuit_daily_total",
+                    "power",
+                    "power",
+                    "power"
+                ]
+            );
+            assert_eq!(
+                columns.fields,
+                vec!["value", "value", "online", "state", "value"]
+            );
+            assert_eq!(
+                columns.tags,
+                vec![
+                    serde_json::json!({ "detail-section": "EV(kWh)" }),
+                    serde_json::json!({ "detail-section": "EV(kWh)" }),
+                    serde_json::json!({ "summary": "売買電力(W)" }),
+                    serde_json::json!({ "summary": "売買電力(W)" }),
+                    serde_json::json!({ "summary": "売買電力(W)" }),
+                ]
+            );
+            assert_eq!(
+                columns.values,
+                vec![Some(1.5), Some(4.5), Some(1.0), None, Some(-1080.0)]
+            );
+            assert_eq!(
+                columns.text_values,
+                vec![None, None, None, Some("selling".to_string()), None]
+            );
+        }
+
+        #[test]
+        fn test_upsert_statement() {
+            let statement = upsert_statement("energy.readings");
+
+            assert!(statement.starts_with(
+                "INSERT INTO energy.readings (time, measurement, field, tags, value, text_value) "
+            ));
+            // Bound in the order of the columns above
+            assert!(statement.contains(
+                "UNNEST($1::timestamptz[], $2::text[], $3::text[], $4::jsonb[], $5::float8[], $6::text[])"
+            ));
+            // Matches the unique constraint of the table, so rewrites update
+            assert!(statement.contains("ON CONFLICT (measurement, field, tags, time)"));
+            assert!(statement.ends_with(
+                "DO UPDATE SET value = EXCLUDED.value, text_value = EXCLUDED.text_value"
+            ));
+        }
+
+        /// Runs against the database in `POSTGRES_TEST_URL`, e.g.
+        /// `postgres://postgres@localhost/postgres`, with
+        /// `cargo test -- --ignored`.
+        #[tokio::test]
+        #[ignore = "needs POSTGRES_TEST_URL"]
+        async fn test_upserts_into_database() {
+            let url = std::env::var("POSTGRES_TEST_URL").expect("POSTGRES_TEST_URL not set");
+            let table = format!("aiseg2_test_{}", std::process::id());
+            let sink = PostgresSink::new(PostgresConfig {
+                url: url.clone(),
+                table: table.clone(),
+            })
+            .unwrap();
+
+            sink.write(&[point(1.5, 1_000_000_000), point(2.0, 2_000_000_000)])
+                .await
+                .unwrap();
+            sink.write(&[point(4.5, 2_000_000_000)]).await.unwrap();
+
+            let (client, connection) = tokio_postgres::connect(&url, NoTls).await.unwrap();
+            tokio::spawn(connection);
+            let rows = client
+                .query(
+                    &format!(
+                        "SELECT value, tags->>'detail-section' FROM {} ORDER BY time",
+                        table
+                    ),
+                    &[],
+                )
+                .await
+                .unwrap();
+            client
+                .batch_execute(&format!("DROP TABLE {}", table))
+                .await
+                .unwrap();
+
+            let stored: Vec<(Option<f64>, Option<String>)> =
+                rows.iter().map(|row| (row.get(0), row.get(1))).collect();
+            assert_eq!(
+                stored,
+                vec![
+                    (Some(1.5), Some("EV(kWh)".to_string())),
+                    (Some(4.5), Some("EV(kWh)".to_string())),
+                ]
+            );
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[test]
+        fn test_invalid_table_names() {
+            for table in ["", "readings; DROP TABLE x", "a.b.c", "1st", "my-table"] {
+                assert!(validate_table(table).is_err(), "case: {}", table);
+            }
+        }
+
+        #[test]
+        fn test_storage_error_by_sqlstate() {
+            let test_cases = vec![
+                ("invalid password", Some(SqlState::INVALID_PASSWORD), "auth"),
+                (
+                    "invalid authorization",
+                    Some(SqlState::INVALID_AUTHORIZATION_SPECIFICATION),
+                    "auth",
+                ),
+                (
+                    "numeric overflow",
+                    Some(SqlState::NUMERIC_VALUE_OUT_OF_RANGE),
+                    "invalid",
+                ),
+                ("undefined table", Some(SqlState::UNDEFINED_TABLE), "write"),
+                ("no sqlstate", None, "write"),
+            ];
+
+            for (name, code, expected) in test_cases {
+                let error = storage_error(code.as_ref(), 3, "refused".to_string());
+                let kind = match error {
+                    StorageError::AuthFailed => "auth",
+                    StorageError::InvalidDataPoint(_) => "invalid",
+                    StorageError::WriteFailed { .. } => "write",
+                    _ => "other",
+                };
+                assert_eq!(kind, expected, "case: {}", name);
+            }
+        }
+
+        #[tokio::test]
+        async fn test_write_unreachable_database() {
+            let sink = PostgresSink::new(PostgresConfig {
+                url: "postgres://forwarder@127.0.0.1:1/metrics".to_string(),
+                table: "aiseg2_readings".to_string(),
+            })
+            .unwrap();
+
+            let result = sink.write(&[point(1.0, 1_000)]).await;
+
+            assert!(matches!(
+                result,
+                Err(StorageError::WriteFailed { ref message, .. }) if message.starts_with("cannot connect to PostgreSQL")
+            ));
+        }
+    }
+}
@@ -0,0 +1,674 @@
+//! Prometheus exporter sink.
+//!
+//! Keeps the latest value of every series in memory and serves them as
+//! gauges on `/metrics`. Scrapes only read this store, so they never reach
+//! the AiSEG2 and values change at the collection intervals.
+
+use super::disk_queue::{BufferSnapshot, BufferStats};
+use super::Sink;
+use crate::error::StorageError;
+use crate::model::Point;
+use crate::supervisor::{RestartStats, TaskCounters};
+use async_trait::async_trait;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use tokio::task::JoinHandle;
+
+/// Prefix of every exported metric name.
+const METRIC_PREFIX: &str = "aiseg2";
+
+/// Content type of the text exposition format.
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Labels of a series, sanitized and sorted by name.
+type Labels = Vec<(String, String)>;
+
+/// Latest value of a series.
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    value: f64,
+    /// Timestamp of the point in nanoseconds, if it carried one
+    timestamp: Option<i64>,
+}
+
+/// In-memory store of the latest value per series.
+///
+/// Series are keyed by metric name and labels. A point older than the stored
+/// sample is ignored, so backfilled daily totals never replace today's.
+/// Write buffers registered with [`LatestValues::expose_buffer`] and task
+/// restarts registered with [`LatestValues::expose_restarts`] are rendered
+/// alongside, read at scrape time.
+#[derive(Debug, Default)]
+pub struct LatestValues {
+    families: RwLock<BTreeMap<String, BTreeMap<Labels, Sample>>>,
+    buffers: RwLock<Vec<(String, Arc<BufferStats>)>>,
+    restarts: RwLock<Option<Arc<RestartStats>>>,
+}
+
+impl LatestValues {
+    /// Records the numeric fields of `points`; other field types are skipped.
+    pub fn update(&self, points: &[Point]) {
+        let mut families = self.families.write().unwrap();
+        for point in points {
+            let labels: Labels = point
+                .tags
+                .iter()
+                .map(|(key, value)| (label_name(key), value.clone()))
+                .collect();
+
+            for (field, value) in &point.fields {
+                let Some(value) = value.as_f64() else {
+                    continue;
+                };
+                let sample = Sample {
+                    value,
+                    timestamp: point.timestamp,
+                };
+                let series = families
+                    .entry(metric_name(&point.measurement, field))
+                    .or_default();
+                match series.get(&labels) {
+                    Some(Sample {
+                        timestamp: Some(stored),
+                        ..
+                    }) if matches!(point.timestamp, Some(ts) if ts < *stored) => {}
+                    _ => {
+                        series.insert(labels.clone(), sample);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Adds the write buffer of `sink` to the rendered metrics.
+    pub fn expose_buffer(&self, sink: &str, stats: Arc<BufferStats>) {
+        self.buffers
+            .write()
+            .unwrap()
+            .push((sink.to_string(), stats));
+    }
+
+    /// Removes the write buffers added so far.
+    pub fn clear_buffers(&self) {
+        self.buffers.write().unwrap().clear();
+    }
+
+    /// Adds the crash and restart counters of the collection tasks to the
+    /// rendered metrics.
+    pub fn expose_restarts(&self, stats: Arc<RestartStats>) {
+        *self.restarts.write().unwrap() = Some(stats);
+    }
+
+    /// Renders all series in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let families = self.families.read().unwrap();
+        let mut out = String::new();
+        for (name, series) in families.iter() {
+            let _ = writeln!(out, "# HELP {} Latest value collected from AiSEG2.", name);
+            let _ = writeln!(out, "# TYPE {} gauge", name);
+            for (labels, sample) in series {
+                out.push_str(name);
+                if !labels.is_empty() {
+                    let labels: Vec<String> = labels
+                        .iter()
+                        .map(|(key, value)| format!("{}=\"{}\"", key, escape_label_value(value)))
+                        .collect();
+                    let _ = write!(out, "{{{}}}", labels.join(","));
+                }
+                let _ = writeln!(out, " {}", format_value(sample.value));
+            }
+        }
+        self.render_buffers(&mut out);
+        self.render_restarts(&mut out);
+        out
+    }
+
+    /// Renders queue depth gauges and drop counters of the write buffers.
+    fn render_buffers(&self, out: &mut String) {
+        let buffers = self.buffers.read().unwrap();
+        if buffers.is_empty() {
+            return;
+        }
+        let snapshots: Vec<_> = buffers
+            .iter()
+            .map(|(sink, stats)| (escape_label_value(sink), stats.snapshot()))
+            .collect();
+
+        type Metric = (
+            &'static str,
+            &'static str,
+            &'static str,
+            fn(&BufferSnapshot) -> u64,
+        );
+        let metrics: [Metric; 5] = [
+            (
+                "buffer_depth_batches",
+                "gauge",
+                "Batches waiting in the write buffer.",
+                |s| s.depth_batches,
+            ),
+            (
+                "buffer_depth_points",
+                "gauge",
+                "Points waiting in the write buffer.",
+                |s| s.depth_points,
+            ),
+            (
+                "buffer_depth_bytes",
+                "gauge",
+                "Size of the write buffer on disk.",
+                |s| s.depth_bytes,
+            ),
+            (
+                "buffer_dropped_batches_total",
+                "counter",
+                "Batches dropped from the write buffer.",
+                |s| s.dropped_batches,
+            ),
+            (
+                "buffer_dropped_points_total",
+                "counter",
+                "Points dropped from the write buffer.",
+                |s| s.dropped_points,
+            ),
+        ];
+        for (suffix, kind, help, value) in metrics {
+            let name = format!("{}_forwarder_{}", METRIC_PREFIX, suffix);
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            for (sink, snapshot) in &snapshots {
+                let _ = writeln!(out, "{}{{sink=\"{}\"}} {}", name, sink, value(snapshot));
+            }
+        }
+    }
+
+    /// Renders crash and restart counters and the backoff of every task.
+    fn render_restarts(&self, out: &mut String) {
+        let Some(stats) = self.restarts.read().unwrap().clone() else {
+            return;
+        };
+        let tasks: Vec<_> = stats
+            .snapshot()
+            .into_iter()
+            .map(|(task, counters)| (escape_label_value(&task), counters))
+            .collect();
+
+        type Metric = (
+            &'static str,
+            &'static str,
+            &'static str,
+            fn(&TaskCounters) -> String,
+        );
+        let metrics: [Metric; 3] = [
+            (
+                "task_crashes_total",
+                "counter",
+                "Times a collection task panicked.",
+                |c| c.crashes.to_string(),
+            ),
+            (
+                "task_restarts_total",
+                "counter",
+                "Times a collection task was restarted after a crash.",
+                |c| c.restarts.to_string(),
+            ),
+            (
+                "task_backoff_seconds",
+                "gauge",
+                "Delay before a crashed collection task is restarted.",
+                |c| format_value(c.backoff.as_secs_f64()),
+            ),
+        ];
+        for (suffix, kind, help, value) in metrics {
+            let name = format!("{}_forwarder_{}", METRIC_PREFIX, suffix);
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            for (task, counters) in &tasks {
+                let _ = writeln!(out, "{}{{task=\"{}\"}} {}", name, task, value(counters));
+            }
+        }
+    }
+}
+
+/// Builds the metric name for a field, e.g. `aiseg2_daily_total`.
+///
+/// The conventional `value` field maps to the measurement itself; other
+/// fields are appended to it.
+fn metric_name(measurement: &str, field: &str) -> String {
+    let name = if field == "value" {
+        format!("{}_{}", METRIC_PREFIX, measurement)
+    } else {
+        format!("{}_{}_{}", METRIC_PREFIX, measurement, field)
+    };
+    sanitize(&name, true)
+}
+
+/// Converts a tag key into a valid label name, e.g. `detail-type` -> `detail_type`.
+fn label_name(key: &str) -> String {
+    sanitize(key, false)
+}
+
+/// Replaces characters Prometheus does not allow in names with `_`.
+///
+/// Metric names may also contain `:`. Names cannot start with a digit.
+fn sanitize(name: &str, allow_colon: bool) -> String {
+    let mut out: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || (allow_colon && c == ':') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if out.is_empty() || out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert(0, '_');
+    }
+    out
+}
+
+/// Escapes a label value; UTF-8 such as Japanese room names is kept as is.
+fn escape_label_value(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Formats a sample value, spelling out non-finite values as Prometheus does.
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Handler for `GET /metrics`.
+async fn metrics(State(store): State<Arc<LatestValues>>) -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, CONTENT_TYPE)], store.render())
+}
+
+/// HTTP server task, stopped once no sink serves from it.
+struct Server(Option<JoinHandle<()>>);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        if let Some(task) = self.0.take() {
+            task.abort();
+        }
+    }
+}
+
+/// Endpoint of a running sink, to hand to the sink replacing it.
+#[derive(Clone)]
+pub struct SharedEndpoint {
+    store: Arc<LatestValues>,
+    local_addr: SocketAddr,
+    server: Weak<Server>,
+}
+
+/// Sink that exposes the latest points on a Prometheus `/metrics` endpoint.
+///
+/// The HTTP server runs in a background task until the sink, and every sink
+/// sharing its endpoint, is flushed or dropped.
+pub struct PrometheusSink {
+    store: Arc<LatestValues>,
+    local_addr: SocketAddr,
+    server: Mutex<Option<Arc<Server>>>,
+}
+
+impl PrometheusSink {
+    /// Binds the endpoint to `addr` and starts serving.
+    ///
+    /// Must be called from within a tokio runtime.
+    ///
+    /// # Returns
+    /// - `Ok(PrometheusSink)` once the address is bound
+    /// - `Err` if the address is invalid or already in use
+    pub fn bind(addr: &str) -> std::io::Result<Self> {
+        let listener = std::net::TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+        let listener = tokio::net::TcpListener::from_std(listener)?;
+
+        let store = Arc::new(LatestValues::default());
+        let app = Router::new()
+            .route("/metrics", get(metrics))
+            .with_state(Arc::clone(&store));
+        let server = tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                tracing::error!("Prometheus endpoint stopped: {}", e);
+            }
+        });
+
+        Ok(Self {
+            store,
+            local_addr,
+            server: Mutex::new(Some(Arc::new(Server(Some(server))))),
+        })
+    }
+
+    /// Creates a sink serving from `endpoint` and keeping its latest values,
+    /// to replace a sink on a configuration reload without binding the
+    /// address again.
+    ///
+    /// # Returns
+    /// - `Some(PrometheusSink)` sharing the endpoint
+    /// - `None` if every sink serving it has been flushed or dropped
+    pub fn take_over(endpoint: &SharedEndpoint) -> Option<Self> {
+        let server = endpoint.server.upgrade()?;
+        Some(Self {
+            store: Arc::clone(&endpoint.store),
+            local_addr: endpoint.local_addr,
+            server: Mutex::new(Some(server)),
+        })
+    }
+
+    /// The endpoint, to hand to a sink replacing this one.
+    pub fn endpoint(&self) -> SharedEndpoint {
+        let server = self.server.lock().unwrap();
+        SharedEndpoint {
+            store: Arc::clone(&self.store),
+            local_addr: self.local_addr,
+            server: server.as_ref().map(Arc::downgrade).unwrap_or_default(),
+        }
+    }
+
+    /// Address the endpoint is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Exposes queue depth and drop counts of the write buffer of `sink`.
+    pub fn expose_buffer(&self, sink: &str, stats: Arc<BufferStats>) {
+        self.store.expose_buffer(sink, stats);
+    }
+
+    /// Stops exposing the write buffers, e.g. before the sinks replacing
+    /// those of a shared endpoint expose theirs.
+    pub fn clear_buffers(&self) {
+        self.store.clear_buffers();
+    }
+
+    /// Exposes crash and restart counts of the collection tasks.
+    pub fn expose_restarts(&self, stats: Arc<RestartStats>) {
+        self.store.expose_restarts(stats);
+    }
+}
+
+#[async_trait]
+impl Sink for PrometheusSink {
+    fn name(&self) -> &str {
+        "prometheus"
+    }
+
+    /// Stores the points as the latest values; never fails.
+    async fn write(&self, points: &[Point]) -> Result<(), StorageError> {
+        self.store.update(points);
+        Ok(())
+    }
+
+    /// Stops serving, so the address can be bound again, unless a sink
+    /// created by [`PrometheusSink::take_over`] still serves; never fails.
+    async fn flush(&self) -> Result<(), StorageError> {
+        let server = self.server.lock().unwrap().take();
+        if let Some(task) = server
+            .and_then(Arc::into_inner)
+            .and_then(|mut server| server.0.take())
+        {
+            task.abort();
+            let _ = task.await;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::disk_queue::{DiskQueue, QueueLimits};
+    use crate::supervisor::{RestartPolicy, Supervisor};
+    use std::time::{Duration, Instant};
+
+    fn power(name: &str, value: i64) -> Point {
+        Point::builder("power")
+            .tag("summary", name)
+            .field("value", value)
+            .build()
+            .unwrap()
+    }
+
+    fn climate(room: &str, value: f64, timestamp: i64) -> Point {
+        Point::builder("climate")
+            .tag("detail-type", "temperature")
+            .tag("detail-section", room)
+            .field("value", value)
+            .timestamp(timestamp)
+            .build()
+            .unwrap()
+    }
+
+    mod succeeds {
+        use super::*;
+
+        #[test]
+        fn test_render_gauges() {
+            let store = LatestValues::default();
+            store.update(&[
+                power("総発電電力(W)", 2500),
+                climate("リビング", 23.5, 1_000),
+                power("総消費電力(W)", 3800),
+            ]);
+
+            assert_eq!(
+                store.render(),
+                "# HELP aiseg2_climate Latest value collected from AiSEG2.\n\
+                 # TYPE aiseg2_climate gauge\n\
+                 aiseg2_climate{detail_section=\"リビング\",detail_type=\"temperature\"} 23.5\n\
+                 # HELP aiseg2_power Latest value collected from AiSEG2.\n\
+                 # TYPE aiseg2_power gauge\n\
+                 aiseg2_power{summary=\"総消費電力(W)\"} 3800\n\
+                 aiseg2_power{summary=\"総発電電力(W)\"} 2500\n"
+            );
+        }
+
+        #[test]
+        fn test_keeps_latest_value() {
+            let test_cases = vec![
+                ("newer replaces", vec![(20.0, 1_000), (21.0, 2_000)], "21"),
+                ("older is ignored", vec![(21.0, 2_000), (20.0, 1_000)], "21"),
+                (
+                    "same time replaces",
+                    vec![(20.0, 1_000), (22.0, 1_000)],
+                    "22",
+                ),
+            ];
+
+            for (name, samples, expected) in test_cases {
+                let store = LatestValues::default();
+                for (value, timestamp) in samples {
+                    store.update(&[climate("和室", value, timestamp)]);
+                }
+                let line = format!(
+                    "aiseg2_climate{{detail_section=\"和室\",detail_type=\"temperature\"}} {}\n",
+                    expected
+                );
+                assert!(store.render().contains(&line), "case: {}", name);
+            }
+        }
+
+        #[test]
+        fn test_names_and_label_values() {
+            let test_cases = vec![
+                ("detail-type", false, "detail_type"),
+                ("1st", false, "_1st"),
+                ("daily:total", false, "daily_total"),
+                ("daily:total", true, "daily:total"),
+                ("", false, "_"),
+            ];
+            for (name, allow_colon, expected) in test_cases {
+                assert_eq!(sanitize(name, allow_colon), expected, "case: {}", name);
+            }
+
+            assert_eq!(metric_name("power", "value"), "aiseg2_power");
+            assert_eq!(metric_name("power", "peak"), "aiseg2_power_peak");
+            assert_eq!(escape_label_value("洋室\"1\"\\\n"), "洋室\\\"1\\\"\\\\\\n");
+            assert_eq!(format_value(f64::INFINITY), "+Inf");
+            assert_eq!(format_value(f64::NAN), "NaN");
+        }
+
+        #[test]
+        fn test_render_buffer_stats() {
+            let dir = tempfile::tempdir().unwrap();
+            let mut queue = DiskQueue::open(
+                dir.path(),
+                QueueLimits {
+                    max_bytes: 1024,
+                    segment_bytes: 1024,
+                    max_age: Duration::from_secs(60),
+                },
+            )
+            .unwrap();
+            queue.push(vec![power("総発電電力(W)", 2500)]).unwrap();
+            queue.push(vec![power("総消費電力(W)", 3800)]).unwrap();
+            queue.drop_front().unwrap();
+
+            let store = LatestValues::default();
+            store.expose_buffer("influxdb", queue.stats());
+            let rendered = store.render();
+
+            assert!(rendered.contains("# TYPE aiseg2_forwarder_buffer_depth_batches gauge\n"));
+            assert!(
+                rendered.contains("aiseg2_forwarder_buffer_depth_batches{sink=\"influxdb\"} 1\n")
+            );
+            assert!(
+                rendered.contains("aiseg2_forwarder_buffer_depth_points{sink=\"influxdb\"} 1\n")
+            );
+            assert!(
+                rendered.contains("# TYPE aiseg2_forwarder_buffer_dropped_batches_total counter\n")
+            );
+            assert!(rendered
+                .contains("aiseg2_forwarder_buffer_dropped_points_total{sink=\"influxdb\"} 1\n"));
+        }
+
+        #[test]
+        fn test_render_restart_stats() {
+            let mut supervisor = Supervisor::new(
+                RestartPolicy {
+                    initial_backoff: Duration::from_millis(1500),
+                    max_backoff: Duration::from_secs(60),
+                    max_crashes: 0,
+                    window: Duration::from_secs(60),
+                },
+                ["power", "climate"],
+            );
+            supervisor.task_finished("power", true, Instant::now());
+
+            let store = LatestValues::default();
+            store.expose_restarts(supervisor.stats());
+            let rendered = store.render();
+
+            assert!(rendered.contains("# TYPE aiseg2_forwarder_task_crashes_total counter\n"));
+            assert!(rendered.contains("aiseg2_forwarder_task_crashes_total{task=\"power\"} 1\n"));
+            assert!(rendered.contains("aiseg2_forwarder_task_restarts_total{task=\"climate\"} 0\n"));
+            assert!(rendered.contains("# TYPE aiseg2_forwarder_task_backoff_seconds gauge\n"));
+            assert!(
+                rendered.contains("aiseg2_forwarder_task_backoff_seconds{task=\"power\"} 1.5\n")
+            );
+        }
+
+        #[test]
+        fn test_skips_non_numeric_fields() {
+            let store = LatestValues::default();
+            store.update(&[Point::builder("status")
+                .field("state", "on")
+                .build()
+                .unwrap()]);
+
+            assert_eq!(store.render(), "");
+        }
+
+        #[tokio::test]
+        async fn test_scrape_metrics_endpoint() {
+            let sink = PrometheusSink::bind("127.0.0.1:0").unwrap();
+            sink.write(&[power("総発電電力(W)", 2500)]).await.unwrap();
+
+            let response = reqwest::get(format!("http://{}/metrics", sink.local_addr()))
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), 200);
+            assert_eq!(
+                response.headers()[reqwest::header::CONTENT_TYPE],
+                CONTENT_TYPE
+            );
+            let body = response.text().await.unwrap();
+            assert!(body.contains("aiseg2_power{summary=\"総発電電力(W)\"} 2500\n"));
+        }
+
+        #[tokio::test]
+        async fn test_flush_releases_address() {
+            let sink = PrometheusSink::bind("127.0.0.1:0").unwrap();
+
+            sink.flush().await.unwrap();
+
+            assert!(PrometheusSink::bind(&sink.local_addr().to_string()).is_ok());
+        }
+
+        #[tokio::test]
+        async fn test_take_over_keeps_serving_after_flush() {
+            let sink = PrometheusSink::bind("127.0.0.1:0").unwrap();
+            sink.write(&[power("総発電電力(W)", 2500)]).await.unwrap();
+
+            let replacement = PrometheusSink::take_over(&sink.endpoint()).unwrap();
+            sink.flush().await.unwrap();
+            let body = reqwest::get(format!("http://{}/metrics", replacement.local_addr()))
+                .await
+                .unwrap()
+                .text()
+                .await
+                .unwrap();
+
+            assert!(body.contains("aiseg2_power{summary=\"総発電電力(W)\"} 2500\n"));
+            replacement.flush().await.unwrap();
+            assert!(PrometheusSink::bind(&sink.local_addr().to_string()).is_ok());
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_bind_address_in_use() {
+            let sink = PrometheusSink::bind("127.0.0.1:0").unwrap();
+            assert!(PrometheusSink::bind(&sink.local_addr().to_string()).is_err());
+        }
+
+        #[tokio::test]
+        async fn test_take_over_after_flush() {
+            let sink = PrometheusSink::bind("127.0.0.1:0").unwrap();
+            let endpoint = sink.endpoint();
+
+            sink.flush().await.unwrap();
+
+            assert!(PrometheusSink::take_over(&endpoint).is_none());
+        }
+    }
+}
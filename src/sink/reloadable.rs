@@ -0,0 +1,118 @@
+//! Sink whose backends can be replaced while the forwarder runs.
+
+use super::Sink;
+use crate::error::StorageError;
+use crate::model::Point;
+use async_trait::async_trait;
+use tokio::sync::RwLock;
+
+/// Forwards every batch to the current sink, which a configuration reload
+/// can replace.
+///
+/// Collection tasks keep writing to the same `ReloadableSink`, so they never
+/// see a half-replaced set of backends: a replacement waits for writes in
+/// flight and holds back new ones only while the sinks are swapped.
+pub struct ReloadableSink {
+    current: RwLock<Box<dyn Sink>>,
+}
+
+impl ReloadableSink {
+    /// Starts out writing to `sink`.
+    pub fn new(sink: Box<dyn Sink>) -> Self {
+        Self {
+            current: RwLock::new(sink),
+        }
+    }
+
+    /// Replaces the current sink with `sink` and flushes the replaced one.
+    ///
+    /// `sink` is built beforehand, sharing what the current sink holds, such
+    /// as a listening port or a buffer directory, rather than claiming it
+    /// again. The flush runs once new writes go to `sink`, so they are not
+    /// held back by a slow backend of the replaced sink.
+    pub async fn replace(&self, sink: Box<dyn Sink>) {
+        let replaced = std::mem::replace(&mut *self.current.write().await, sink);
+        if let Err(e) = replaced.flush().await {
+            tracing::warn!(
+                "Failed to flush {} after replacing it: {}",
+                replaced.name(),
+                e
+            );
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for ReloadableSink {
+    fn name(&self) -> &str {
+        "reloadable"
+    }
+
+    async fn write(&self, points: &[Point]) -> Result<(), StorageError> {
+        self.current.read().await.write(points).await
+    }
+
+    async fn flush(&self) -> Result<(), StorageError> {
+        self.current.read().await.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::mocks::RecordingSink;
+
+    fn test_points() -> Vec<Point> {
+        vec![Point::builder("power")
+            .tag("summary", "総発電電力(W)")
+            .field("value", 2500i64)
+            .build()
+            .unwrap()]
+    }
+
+    mod succeeds {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_replace() {
+            let first = RecordingSink::new("first");
+            let second = RecordingSink::new("second");
+            let sink = ReloadableSink::new(Box::new(first.clone()));
+
+            sink.write(&test_points()).await.unwrap();
+            sink.replace(Box::new(second.clone())).await;
+            sink.write(&test_points()).await.unwrap();
+
+            assert_eq!(first.points(), test_points());
+            assert_eq!(first.flushes(), 1);
+            assert_eq!(second.points(), test_points());
+            assert_eq!(second.flushes(), 0);
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_replace_when_flush_fails() {
+            let first = RecordingSink::failing("first");
+            let second = RecordingSink::new("second");
+            let sink = ReloadableSink::new(Box::new(first.clone()));
+
+            sink.replace(Box::new(second.clone())).await;
+            sink.write(&test_points()).await.unwrap();
+
+            assert_eq!(first.flushes(), 0);
+            assert_eq!(second.points(), test_points());
+        }
+
+        #[tokio::test]
+        async fn test_write_failure() {
+            let sink = ReloadableSink::new(Box::new(RecordingSink::failing("first")));
+
+            let result = sink.write(&test_points()).await;
+
+            assert!(result.is_err());
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! One-row-per-field layout shared by the SQL sinks.
+//!
+//! A point with several fields becomes several rows keyed by
+//! `(measurement, field, tags, time)`. Numbers and booleans are stored in
+//! `value`, strings in `text_value`.
+
+use crate::model::{FieldValue, Point};
+use chrono::Utc;
+use std::collections::{BTreeMap, HashMap};
+
+/// A single stored field value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    /// Nanoseconds since the Unix epoch
+    pub time: i64,
+    pub measurement: String,
+    pub field: String,
+    pub tags: BTreeMap<String, String>,
+    pub value: Option<f64>,
+    pub text_value: Option<String>,
+}
+
+/// Flattens points into one row per field.
+///
+/// Points without a timestamp get the current time. Of rows sharing a key,
+/// the last one wins, since an upsert cannot touch the same row twice.
+pub fn rows(points: &[Point]) -> Vec<Row> {
+    let now = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+    let mut rows: Vec<Row> = Vec::new();
+    let mut positions: HashMap<(String, String, BTreeMap<String, String>, i64), usize> =
+        HashMap::new();
+
+    for point in points {
+        let time = point.timestamp.unwrap_or(now);
+        for (field, value) in &point.fields {
+            let (value, text_value) = match value {
+                FieldValue::String(text) => (None, Some(text.clone())),
+                FieldValue::Boolean(flag) => (Some(if *flag { 1.0 } else { 0.0 }), None),
+                numeric => (numeric.as_f64(), None),
+            };
+
+            let key = (
+                point.measurement.clone(),
+                field.clone(),
+                point.tags.clone(),
+                time,
+            );
+            if let Some(&position) = positions.get(&key) {
+                rows[position].value = value;
+                rows[position].text_value = text_value;
+                continue;
+            }
+            positions.insert(key, rows.len());
+            rows.push(Row {
+                time,
+                measurement: point.measurement.clone(),
+                field: field.clone(),
+                tags: point.tags.clone(),
+                value,
+                text_value,
+            });
+        }
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(value: impl Into<FieldValue>, timestamp: i64) -> Point {
+        Point::builder("circuit_daily_total")
+            .tag("detail-section", "EV(kWh)")
+            .field("value", value)
+            .timestamp(timestamp)
+            .build()
+            .unwrap()
+    }
+
+    mod succeeds {
+        use super::*;
+
+        #[test]
+        fn test_rows() {
+            let points = vec![
+                point(12.5, 1_000_000_000),
+                Point::builder("status")
+                    .field("mode", "eco")
+                    .field("on", true)
+                    .field("count", 3u64)
+                    .timestamp(2_000_000_000)
+                    .build()
+                    .unwrap(),
+            ];
+
+            let rows = rows(&points);
+
+            let fields: Vec<&str> = rows.iter().map(|row| row.field.as_str()).collect();
+            assert_eq!(fields, vec!["value", "count", "mode", "on"]);
+            let values: Vec<Option<f64>> = rows.iter().map(|row| row.value).collect();
+            assert_eq!(values, vec![Some(12.5), Some(3.0), None, Some(1.0)]);
+            assert_eq!(rows[2].text_value.as_deref(), Some("eco"));
+            assert_eq!(rows[0].tags["detail-section"], "EV(kWh)");
+            assert!(rows[1].tags.is_empty());
+            assert_eq!(rows[0].time, 1_000_000_000);
+        }
+
+        #[test]
+        fn test_rows_keep_last_value_per_key() {
+            let rows = rows(&[point(1.0, 1_000), point(2.0, 1_000), point(3.0, 2_000)]);
+
+            let values: Vec<Option<f64>> = rows.iter().map(|row| row.value).collect();
+            assert_eq!(values, vec![Some(2.0), Some(3.0)]);
+        }
+    }
+}
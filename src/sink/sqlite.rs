@@ -0,0 +1,187 @@
+//! Sink storing points in a local SQLite database.
+//!
+//! Uses the same one-row-per-field layout as the PostgreSQL sink, in a
+//! `readings` table with the time in nanoseconds and the tags as a JSON
+//! object. Rows are upserted, so daily totals keep one row per day. The
+//! database runs in WAL mode, which keeps it readable by the `export`
+//! command and other tools while the forwarder writes.
+
+use super::rows::rows;
+use super::Sink;
+use crate::error::StorageError;
+use crate::model::Point;
+use async_trait::async_trait;
+use rusqlite::{params, Connection};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+const TABLE: &str = SqliteSink::TABLE;
+
+/// Sink that upserts points into a SQLite database file.
+pub struct SqliteSink {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteSink {
+    /// Name of the table holding all readings.
+    pub const TABLE: &'static str = "readings";
+
+    /// Opens or creates the database and its table.
+    ///
+    /// # Returns
+    /// - `Ok(SqliteSink)` ready to write
+    /// - `Err(rusqlite::Error)` if the file cannot be opened or initialized
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        create_table(&conn)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+}
+
+/// Creates the readings table and its time index.
+fn create_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {TABLE} (
+            time INTEGER NOT NULL,
+            measurement TEXT NOT NULL,
+            field TEXT NOT NULL,
+            tags TEXT NOT NULL DEFAULT '{{}}',
+            value REAL,
+            text_value TEXT,
+            PRIMARY KEY (measurement, field, tags, time)
+        ) WITHOUT ROWID;
+        CREATE INDEX IF NOT EXISTS {TABLE}_time_idx ON {TABLE} (time);"
+    ))
+}
+
+/// Upserts `points` in one transaction.
+fn upsert(conn: &mut Connection, points: &[Point]) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    {
+        let mut statement = tx.prepare_cached(&format!(
+            "INSERT INTO {TABLE} (time, measurement, field, tags, value, text_value)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT (measurement, field, tags, time)
+             DO UPDATE SET value = excluded.value, text_value = excluded.text_value"
+        ))?;
+        for row in rows(points) {
+            let tags = serde_json::to_string(&row.tags).unwrap_or_else(|_| "{}".to_string());
+            statement.execute(params![
+                row.time,
+                row.measurement,
+                row.field,
+                tags,
+                row.value,
+                row.text_value
+            ])?;
+        }
+    }
+    tx.commit()
+}
+
+#[async_trait]
+impl Sink for SqliteSink {
+    fn name(&self) -> &str {
+        "sqlite"
+    }
+
+    /// Upserts the batch on a blocking thread.
+    async fn write(&self, points: &[Point]) -> Result<(), StorageError> {
+        let conn = Arc::clone(&self.conn);
+        let batch = points.to_vec();
+        tokio::task::spawn_blocking(move || upsert(&mut conn.lock().unwrap(), &batch))
+            .await
+            .map_err(|e| StorageError::write_failed(points.len(), e))?
+            .map_err(|e| StorageError::write_failed(points.len(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(value: f64, timestamp: i64) -> Point {
+        Point::builder("daily_total")
+            .tag("detail-section", "発電量(kWh)")
+            .field("value", value)
+            .timestamp(timestamp)
+            .build()
+            .unwrap()
+    }
+
+    fn stored(path: &Path) -> Vec<(i64, String, f64)> {
+        let conn = Connection::open(path).unwrap();
+        let mut statement = conn
+            .prepare(&format!(
+                "SELECT time, tags, value FROM {TABLE} ORDER BY time"
+            ))
+            .unwrap();
+        statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    mod succeeds {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_upserts_readings() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("aiseg2.db");
+            let sink = SqliteSink::open(&path).unwrap();
+
+            sink.write(&[point(1.5, 1_000), point(2.0, 2_000)])
+                .await
+                .unwrap();
+            sink.write(&[point(4.5, 2_000)]).await.unwrap();
+
+            assert_eq!(
+                stored(&path),
+                vec![
+                    (
+                        1_000,
+                        r#"{"detail-section":"発電量(kWh)"}"#.to_string(),
+                        1.5
+                    ),
+                    (
+                        2_000,
+                        r#"{"detail-section":"発電量(kWh)"}"#.to_string(),
+                        4.5
+                    ),
+                ]
+            );
+        }
+
+        #[tokio::test]
+        async fn test_reopens_existing_database() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("aiseg2.db");
+            SqliteSink::open(&path)
+                .unwrap()
+                .write(&[point(1.0, 1_000)])
+                .await
+                .unwrap();
+
+            let sink = SqliteSink::open(&path).unwrap();
+            sink.write(&[point(2.0, 2_000)]).await.unwrap();
+
+            assert_eq!(stored(&path).len(), 2);
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[test]
+        fn test_open_unusable_path() {
+            let dir = tempfile::tempdir().unwrap();
+
+            assert!(SqliteSink::open(dir.path()).is_err());
+        }
+    }
+}
@@ -0,0 +1,108 @@
+//! Sink printing points as InfluxDB line protocol.
+//!
+//! Meant for dry runs and debugging: the output is exactly what would be
+//! sent to InfluxDB and can be imported later with `influx write`. Points
+//! without a timestamp are printed with the time they were written.
+
+use super::{stamp, Sink};
+use crate::error::StorageError;
+use crate::line_protocol;
+use crate::model::Point;
+use async_trait::async_trait;
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+/// Sink that writes line protocol to stdout or another writer.
+pub struct StdoutSink {
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl StdoutSink {
+    /// Creates a sink printing to stdout.
+    pub fn new() -> Self {
+        Self::with_writer(io::stdout())
+    }
+
+    /// Creates a sink printing to `out`.
+    pub fn with_writer(out: impl Write + Send + 'static) -> Self {
+        Self {
+            out: Mutex::new(Box::new(out)),
+        }
+    }
+}
+
+impl Default for StdoutSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Sink for StdoutSink {
+    fn name(&self) -> &str {
+        "stdout"
+    }
+
+    /// Prints one line per point and flushes.
+    async fn write(&self, points: &[Point]) -> Result<(), StorageError> {
+        let mut out = self.out.lock().unwrap();
+        line_protocol::encode_batch(&stamp(points))
+            .iter()
+            .try_for_each(|line| writeln!(out, "{}", line))
+            .and_then(|()| out.flush())
+            .map_err(|e| StorageError::write_failed(points.len(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::fake_influx::parse_line_protocol;
+    use std::sync::Arc;
+
+    /// Writer whose output stays readable after it was handed to the sink.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    mod succeeds {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_prints_line_protocol() {
+            let buffer = SharedBuffer::default();
+            let sink = StdoutSink::with_writer(buffer.clone());
+            let points = vec![
+                Point::builder("power")
+                    .tag("summary", "総消費電力(W)")
+                    .field("value", 3800i64)
+                    .timestamp(1_000)
+                    .build()
+                    .unwrap(),
+                Point::builder("climate")
+                    .field("value", 23.5)
+                    .build()
+                    .unwrap(),
+            ];
+
+            sink.write(&points).await.unwrap();
+
+            let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+            let lines: Vec<&str> = output.lines().collect();
+            assert_eq!(lines.len(), 2);
+            assert_eq!(lines[0], "power,summary=総消費電力(W) value=3800i 1000");
+            let parsed = parse_line_protocol(lines[1]).unwrap();
+            assert!(parsed[0].timestamp.is_some());
+        }
+    }
+}
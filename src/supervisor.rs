@@ -0,0 +1,286 @@
+//! Supervision of the collection tasks.
+//!
+//! Every collection task that finishes is started again for its next tick.
+//! A task that panicked is restarted only after a backoff that doubles with
+//! each consecutive crash, so a persistent failure does not become a hot
+//! loop. Too many crashes of one task within a window escalate: the
+//! forwarder shuts down and exits non-zero, so systemd or Kubernetes notice
+//! and restart it.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How crashed tasks are restarted.
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+    /// Delay before restarting after the first crash
+    pub initial_backoff: Duration,
+    /// Upper bound of the doubling delay
+    pub max_backoff: Duration,
+    /// Crashes within `window` that escalate; 0 never escalates
+    pub max_crashes: u32,
+    pub window: Duration,
+}
+
+/// What to do with a task that finished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// Start the task again, after `backoff`
+    Restart { backoff: Duration },
+    /// Give up and let the process exit
+    Escalate,
+}
+
+/// Crash and restart counters of one task.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TaskCounters {
+    /// Times the task panicked
+    pub crashes: u64,
+    /// Times the task was restarted after a crash
+    pub restarts: u64,
+    /// Delay before the pending restart, zero while the task is healthy
+    pub backoff: Duration,
+}
+
+/// Counters of every supervised task, shared with whoever reports them.
+#[derive(Debug, Default)]
+pub struct RestartStats {
+    tasks: Mutex<BTreeMap<String, TaskCounters>>,
+}
+
+impl RestartStats {
+    /// Returns the current counters, by task name.
+    pub fn snapshot(&self) -> BTreeMap<String, TaskCounters> {
+        self.tasks.lock().unwrap().clone()
+    }
+
+    fn update(&self, task: &str, f: impl FnOnce(&mut TaskCounters)) {
+        f(self
+            .tasks
+            .lock()
+            .unwrap()
+            .entry(task.to_string())
+            .or_default());
+    }
+}
+
+/// Tracks crashes per task and decides how each finished task goes on.
+pub struct Supervisor {
+    policy: RestartPolicy,
+    stats: Arc<RestartStats>,
+    /// Recent crash times and the current backoff, per task
+    history: HashMap<String, (VecDeque<Instant>, Option<Duration>)>,
+}
+
+impl Supervisor {
+    /// Creates a supervisor reporting zeroed counters for `tasks`.
+    pub fn new<'a>(policy: RestartPolicy, tasks: impl IntoIterator<Item = &'a str>) -> Self {
+        let stats = Arc::new(RestartStats::default());
+        for task in tasks {
+            stats.update(task, |_| {});
+        }
+        Self {
+            policy,
+            stats,
+            history: HashMap::new(),
+        }
+    }
+
+    /// Applies `policy` to crashes from now on.
+    ///
+    /// Crashes already counted and pending backoffs are kept.
+    pub fn set_policy(&mut self, policy: RestartPolicy) {
+        self.policy = policy;
+    }
+
+    /// Counters to expose as metrics.
+    pub fn stats(&self) -> Arc<RestartStats> {
+        Arc::clone(&self.stats)
+    }
+
+    /// Records that `task` finished at `now`, having panicked if `crashed`.
+    ///
+    /// A task that completed normally resets its backoff. A crash is
+    /// restarted after the initial backoff, doubled for every consecutive
+    /// crash, unless it is the `max_crashes`-th within the window.
+    pub fn task_finished(&mut self, task: &str, crashed: bool, now: Instant) -> Decision {
+        let (crashes, backoff) = self.history.entry(task.to_string()).or_default();
+        if !crashed {
+            if backoff.take().is_some() {
+                tracing::info!("Task {} recovered.", task);
+                self.stats
+                    .update(task, |counters| counters.backoff = Duration::ZERO);
+            }
+            return Decision::Restart {
+                backoff: Duration::ZERO,
+            };
+        }
+
+        crashes.push_back(now);
+        while crashes
+            .front()
+            .is_some_and(|crashed_at| now.duration_since(*crashed_at) > self.policy.window)
+        {
+            crashes.pop_front();
+        }
+        self.stats.update(task, |counters| counters.crashes += 1);
+
+        let max_crashes = self.policy.max_crashes as usize;
+        if max_crashes > 0 && crashes.len() >= max_crashes {
+            tracing::error!(
+                "Task {} crashed {} times within {:?}, giving up.",
+                task,
+                crashes.len(),
+                self.policy.window
+            );
+            return Decision::Escalate;
+        }
+
+        let next = match *backoff {
+            None => self.policy.initial_backoff,
+            Some(previous) => (previous * 2).min(self.policy.max_backoff),
+        };
+        *backoff = Some(next);
+        self.stats.update(task, |counters| {
+            counters.restarts += 1;
+            counters.backoff = next;
+        });
+        tracing::warn!("Restarting task {} in {:?}.", task, next);
+        Decision::Restart { backoff: next }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(max_crashes: u32) -> RestartPolicy {
+        RestartPolicy {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(4),
+            max_crashes,
+            window: Duration::from_secs(60),
+        }
+    }
+
+    fn restart(secs: u64) -> Decision {
+        Decision::Restart {
+            backoff: Duration::from_secs(secs),
+        }
+    }
+
+    mod succeeds {
+        use super::*;
+
+        #[test]
+        fn test_backoff_doubles_up_to_max() {
+            let mut supervisor = Supervisor::new(policy(0), ["power"]);
+            let now = Instant::now();
+
+            let decisions: Vec<Decision> = (0..5)
+                .map(|_| supervisor.task_finished("power", true, now))
+                .collect();
+
+            assert_eq!(
+                decisions,
+                vec![restart(1), restart(2), restart(4), restart(4), restart(4)]
+            );
+        }
+
+        #[test]
+        fn test_completion_resets_backoff() {
+            let mut supervisor = Supervisor::new(policy(0), ["power"]);
+            let now = Instant::now();
+
+            supervisor.task_finished("power", true, now);
+            supervisor.task_finished("power", true, now);
+            assert_eq!(supervisor.task_finished("power", false, now), restart(0));
+
+            assert_eq!(supervisor.task_finished("power", true, now), restart(1));
+        }
+
+        #[test]
+        fn test_tasks_are_tracked_separately() {
+            let mut supervisor = Supervisor::new(policy(2), ["power", "climate"]);
+            let now = Instant::now();
+
+            assert_eq!(supervisor.task_finished("power", true, now), restart(1));
+            assert_eq!(supervisor.task_finished("climate", true, now), restart(1));
+        }
+
+        #[test]
+        fn test_crashes_outside_window_are_forgotten() {
+            let mut supervisor = Supervisor::new(policy(2), ["power"]);
+            let now = Instant::now();
+
+            supervisor.task_finished("power", true, now);
+            let decision = supervisor.task_finished("power", true, now + Duration::from_secs(61));
+
+            assert_eq!(decision, restart(2));
+        }
+
+        #[test]
+        fn test_set_policy() {
+            let mut supervisor = Supervisor::new(policy(0), ["power"]);
+            let now = Instant::now();
+
+            supervisor.task_finished("power", true, now);
+            supervisor.set_policy(RestartPolicy {
+                max_backoff: Duration::from_secs(60),
+                ..policy(2)
+            });
+
+            assert_eq!(
+                supervisor.task_finished("power", true, now),
+                Decision::Escalate
+            );
+        }
+
+        #[test]
+        fn test_stats() {
+            let mut supervisor = Supervisor::new(policy(3), ["power", "climate"]);
+            let now = Instant::now();
+
+            supervisor.task_finished("power", true, now);
+            supervisor.task_finished("power", true, now);
+            supervisor.task_finished("climate", false, now);
+
+            let stats = supervisor.stats().snapshot();
+            assert_eq!(
+                stats["power"],
+                TaskCounters {
+                    crashes: 2,
+                    restarts: 2,
+                    backoff: Duration::from_secs(2),
+                }
+            );
+            assert_eq!(stats["climate"], TaskCounters::default());
+
+            supervisor.task_finished("power", false, now);
+            assert_eq!(
+                supervisor.stats().snapshot()["power"].backoff,
+                Duration::ZERO
+            );
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[test]
+        fn test_escalates_after_max_crashes_in_window() {
+            let mut supervisor = Supervisor::new(policy(3), ["power"]);
+            let now = Instant::now();
+
+            let decisions: Vec<Decision> = (0..3)
+                .map(|i| supervisor.task_finished("power", true, now + Duration::from_secs(i)))
+                .collect();
+
+            assert_eq!(decisions, vec![restart(1), restart(2), Decision::Escalate]);
+            let stats = supervisor.stats().snapshot();
+            assert_eq!(stats["power"].crashes, 3);
+            assert_eq!(stats["power"].restarts, 2);
+        }
+    }
+}
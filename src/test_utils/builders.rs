@@ -4,14 +4,13 @@
 //! and scenarios used in integration and unit tests.
 
 use crate::model::{
-    ClimateStatusMetric, ClimateStatusMetricCategory, DataPointBuilder, Measurement,
+    ClimateStatusMetric, ClimateStatusMetricCategory, DataPointBuilder, Measurement, Point,
     PowerStatusMetric, PowerTotalMetric,
 };
 use anyhow;
 use chrono::{DateTime, Local};
-use influxdb2::models::DataPoint as InfluxDataPoint;
 
-/// Builder for creating InfluxDB DataPoint instances for testing.
+/// Builder for creating point instances for testing.
 #[derive(Debug)]
 pub struct TestInfluxDataPointBuilder {
     measurement: String,
@@ -21,7 +20,7 @@ pub struct TestInfluxDataPointBuilder {
 }
 
 impl TestInfluxDataPointBuilder {
-    /// Creates a new InfluxDB DataPoint builder.
+    /// Creates a new point builder.
     pub fn new(measurement: impl Into<String>) -> Self {
         Self {
             measurement: measurement.into(),
@@ -49,9 +48,9 @@ impl TestInfluxDataPointBuilder {
         self
     }
 
-    /// Builds the InfluxDB DataPoint.
-    pub fn build(self) -> anyhow::Result<InfluxDataPoint> {
-        let mut builder = InfluxDataPoint::builder(&self.measurement);
+    /// Builds the point.
+    pub fn build(self) -> anyhow::Result<Point> {
+        let mut builder = Point::builder(self.measurement);
 
         for (key, value) in self.tags {
             builder = builder.tag(key, value);
@@ -67,7 +66,7 @@ impl TestInfluxDataPointBuilder {
 
         builder
             .build()
-            .map_err(|e| anyhow::anyhow!("Failed to build InfluxDB data point: {}", e))
+            .map_err(|e| anyhow::anyhow!("Failed to build data point: {}", e))
     }
 }
 
@@ -259,15 +258,18 @@ mod tests {
 
     #[test]
     fn test_influx_data_point_builder() {
-        let _point = TestInfluxDataPointBuilder::new("test_measurement")
+        let point = TestInfluxDataPointBuilder::new("test_measurement")
             .add_tag("location", "living_room")
             .add_field("temperature", 23.5)
             .add_field("humidity", 65.0)
+            .with_timestamp(1_000)
             .build()
             .unwrap();
 
-        // The actual validation would depend on InfluxDB's DataPoint API
-        // Successfully built the point
+        assert_eq!(point.measurement, "test_measurement");
+        assert_eq!(point.tags["location"], "living_room");
+        assert_eq!(point.fields.len(), 2);
+        assert_eq!(point.timestamp, Some(1_000));
     }
 
     #[test]
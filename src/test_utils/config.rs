@@ -3,7 +3,7 @@
 //! This module provides test configuration builders and helpers for creating
 //! mock configurations used throughout the test suite.
 
-use crate::config::{Aiseg2Config, InfluxConfig};
+use crate::config::{Aiseg2Config, InfluxApiVersion, InfluxConfig};
 
 /// Builder for creating test AiSEG2 configurations.
 #[derive(Debug)]
@@ -11,6 +11,8 @@ pub struct TestAiseg2ConfigBuilder {
     url: String,
     user: String,
     password: String,
+    cache_ttl_ms: u64,
+    encoding: Option<String>,
 }
 
 impl TestAiseg2ConfigBuilder {
@@ -20,6 +22,9 @@ impl TestAiseg2ConfigBuilder {
             url: "http://test.local".to_string(),
             user: "test_user".to_string(),
             password: "test_password".to_string(),
+            // Disabled by default so tests see every mocked response
+            cache_ttl_ms: 0,
+            encoding: None,
         }
     }
 
@@ -41,12 +46,26 @@ impl TestAiseg2ConfigBuilder {
         self
     }
 
+    /// Sets the page cache TTL for the test configuration.
+    pub fn with_cache_ttl_ms(mut self, cache_ttl_ms: u64) -> Self {
+        self.cache_ttl_ms = cache_ttl_ms;
+        self
+    }
+
+    /// Sets the character encoding override for the test configuration.
+    pub fn with_encoding(mut self, encoding: impl Into<String>) -> Self {
+        self.encoding = Some(encoding.into());
+        self
+    }
+
     /// Builds the AiSEG2 configuration.
     pub fn build(self) -> Aiseg2Config {
         Aiseg2Config {
             url: self.url,
             user: self.user,
             password: self.password,
+            cache_ttl_ms: self.cache_ttl_ms,
+            encoding: self.encoding,
         }
     }
 }
@@ -55,9 +74,15 @@ impl TestAiseg2ConfigBuilder {
 #[derive(Debug)]
 pub struct TestInfluxConfigBuilder {
     url: String,
+    api_version: InfluxApiVersion,
     org: String,
     token: String,
     bucket: String,
+    database: String,
+    max_retries: u32,
+    retry_initial_ms: u64,
+    retry_max_ms: u64,
+    write_timeout_ms: u64,
 }
 
 impl TestInfluxConfigBuilder {
@@ -65,9 +90,16 @@ impl TestInfluxConfigBuilder {
     pub fn new() -> Self {
         Self {
             url: "http://localhost:8086".to_string(),
+            api_version: InfluxApiVersion::V2,
             org: "test-org".to_string(),
             token: "test-token".to_string(),
             bucket: "test-bucket".to_string(),
+            database: "test-database".to_string(),
+            // Quick retries so tests of throttled and unreachable servers stay fast
+            max_retries: 2,
+            retry_initial_ms: 10,
+            retry_max_ms: 50,
+            write_timeout_ms: 5_000,
         }
     }
 
@@ -77,6 +109,12 @@ impl TestInfluxConfigBuilder {
         self
     }
 
+    /// Sets the write API version for the test configuration.
+    pub fn with_api_version(mut self, api_version: InfluxApiVersion) -> Self {
+        self.api_version = api_version;
+        self
+    }
+
     /// Sets the organization for the test configuration.
     pub fn with_org(mut self, org: impl Into<String>) -> Self {
         self.org = org.into();
@@ -95,13 +133,47 @@ impl TestInfluxConfigBuilder {
         self
     }
 
+    /// Sets the v1/v3 database for the test configuration.
+    pub fn with_database(mut self, database: impl Into<String>) -> Self {
+        self.database = database.into();
+        self
+    }
+
+    /// Sets the number of write retries for the test configuration.
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Sets the write retry delays for the test configuration.
+    pub fn with_retry_delays(mut self, initial_ms: u64, max_ms: u64) -> Self {
+        self.retry_initial_ms = initial_ms;
+        self.retry_max_ms = max_ms;
+        self
+    }
+
+    /// Sets the time one write may take for the test configuration.
+    pub fn with_write_timeout_ms(mut self, write_timeout_ms: u64) -> Self {
+        self.write_timeout_ms = write_timeout_ms;
+        self
+    }
+
     /// Builds the InfluxDB configuration.
     pub fn build(self) -> InfluxConfig {
         InfluxConfig {
             url: self.url,
+            api_version: self.api_version,
             org: self.org,
             token: self.token,
             bucket: self.bucket,
+            database: self.database,
+            retention_policy: None,
+            username: None,
+            password: None,
+            max_retries: self.max_retries,
+            retry_initial_ms: self.retry_initial_ms,
+            retry_max_ms: self.retry_max_ms,
+            write_timeout_ms: self.write_timeout_ms,
         }
     }
 }
@@ -0,0 +1,759 @@
+//! In-process fake of the InfluxDB write APIs.
+//!
+//! Accepts `POST /api/v2/write`, as well as the InfluxDB 1.x `/write` and
+//! InfluxDB 3 `/api/v3/write_lp` endpoints, parses the line protocol body
+//! and records the points so end-to-end tests can assert on exactly what
+//! would have been stored: measurements, tags, fields and timestamps.
+//!
+//! `POST /api/v2/query` answers the Flux query of the startup backfill from
+//! the recorded points.
+//!
+//! `GET /ping` reports the server version like every InfluxDB release.
+
+use crate::config::{InfluxApiVersion, InfluxConfig};
+use axum::body::Bytes;
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::Router;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+/// Organization, bucket and token the fake server expects.
+pub const ORG: &str = "test-org";
+pub const BUCKET: &str = "test-bucket";
+pub const TOKEN: &str = "test-token";
+
+/// Version the fake server reports on `GET /ping`.
+pub const VERSION: &str = "v2.7.12";
+
+/// Database and InfluxDB 1.x credentials the fake server expects.
+pub const DATABASE: &str = "test-database";
+pub const USERNAME: &str = "test-user";
+pub const PASSWORD: &str = "test-password";
+
+/// A field value as written in line protocol.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Float(f64),
+    Integer(i64),
+    UInteger(u64),
+    String(String),
+    Boolean(bool),
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldValue::Float(v) => write!(f, "{}", v),
+            FieldValue::Integer(v) => write!(f, "{}i", v),
+            FieldValue::UInteger(v) => write!(f, "{}u", v),
+            FieldValue::String(v) => write!(f, "{:?}", v),
+            FieldValue::Boolean(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+/// A single point received by the fake server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinePoint {
+    pub measurement: String,
+    pub tags: BTreeMap<String, String>,
+    pub fields: BTreeMap<String, FieldValue>,
+    /// Timestamp in nanoseconds, `None` if the server would assign it
+    pub timestamp: Option<i64>,
+}
+
+impl LinePoint {
+    /// Returns the value of a tag, if present.
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.tags.get(key).map(String::as_str)
+    }
+}
+
+impl fmt::Display for LinePoint {
+    /// Canonical, unescaped form with sorted tags and fields, for assertions.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.measurement)?;
+        for (key, value) in &self.tags {
+            write!(f, ",{}={}", key, value)?;
+        }
+        let fields: Vec<String> = self
+            .fields
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        write!(f, " {}", fields.join(","))?;
+        if let Some(timestamp) = self.timestamp {
+            write!(f, " {}", timestamp)?;
+        }
+        Ok(())
+    }
+}
+
+/// One accepted write request.
+#[derive(Debug, Clone)]
+pub struct WriteRequest {
+    pub api_version: InfluxApiVersion,
+    pub org: Option<String>,
+    pub bucket: Option<String>,
+    /// `db` parameter of v1 and v3 writes
+    pub database: Option<String>,
+    /// `rp` parameter of v1 writes
+    pub retention_policy: Option<String>,
+    pub precision: Option<String>,
+    pub points: Vec<LinePoint>,
+}
+
+/// A canned error response, returned instead of handling a write.
+#[derive(Debug, Clone)]
+struct Failure {
+    status: StatusCode,
+    retry_after: Option<String>,
+}
+
+#[derive(Default)]
+struct FakeState {
+    writes: Mutex<Vec<WriteRequest>>,
+    queries: Mutex<Vec<String>>,
+    failures: Mutex<VecDeque<Failure>>,
+}
+
+/// A running fake InfluxDB server, stopped when dropped.
+pub struct FakeInfluxDb {
+    url: String,
+    state: Arc<FakeState>,
+    handle: JoinHandle<()>,
+}
+
+impl FakeInfluxDb {
+    /// Starts the fake server on an ephemeral localhost port.
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind fake InfluxDB");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::new(FakeState::default());
+        let router = Router::new()
+            .route("/write", post(write_v1))
+            .route("/api/v2/write", post(write))
+            .route("/api/v3/write_lp", post(write_v3))
+            .route("/api/v2/query", post(query))
+            .route("/ping", get(ping))
+            .with_state(Arc::clone(&state));
+        let handle = tokio::spawn(async move {
+            let _ = axum::serve(listener, router).await;
+        });
+
+        Self { url, state, handle }
+    }
+
+    /// Base URL of the fake server.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// InfluxDB v2 configuration pointing at the fake server.
+    pub fn config(&self) -> InfluxConfig {
+        self.config_for(InfluxApiVersion::V2)
+    }
+
+    /// Configuration for `api_version` pointing at the fake server.
+    ///
+    /// v1 authenticates with user name and password, v2 and v3 with the token.
+    pub fn config_for(&self, api_version: InfluxApiVersion) -> InfluxConfig {
+        let v1 = api_version == InfluxApiVersion::V1;
+        InfluxConfig {
+            url: self.url.clone(),
+            api_version,
+            org: ORG.to_string(),
+            token: if v1 { String::new() } else { TOKEN.to_string() },
+            bucket: BUCKET.to_string(),
+            database: DATABASE.to_string(),
+            retention_policy: None,
+            username: v1.then(|| USERNAME.to_string()),
+            password: v1.then(|| PASSWORD.to_string()),
+            max_retries: 2,
+            retry_initial_ms: 10,
+            retry_max_ms: 50,
+            write_timeout_ms: 5_000,
+        }
+    }
+
+    /// Answers the next write with `status` instead of storing it.
+    ///
+    /// Calls queue up, so several failures can precede the next success.
+    pub fn fail_next(&self, status: u16, retry_after: Option<&str>) {
+        self.state.failures.lock().unwrap().push_back(Failure {
+            status: StatusCode::from_u16(status).expect("invalid status code"),
+            retry_after: retry_after.map(str::to_string),
+        });
+    }
+
+    /// All accepted write requests, in arrival order.
+    pub fn writes(&self) -> Vec<WriteRequest> {
+        self.state.writes.lock().unwrap().clone()
+    }
+
+    /// All Flux queries received, in arrival order.
+    pub fn queries(&self) -> Vec<String> {
+        self.state.queries.lock().unwrap().clone()
+    }
+
+    /// All points received so far, in arrival order.
+    pub fn points(&self) -> Vec<LinePoint> {
+        self.writes()
+            .into_iter()
+            .flat_map(|write| write.points)
+            .collect()
+    }
+
+    /// Points received so far in canonical form, sorted for stable comparison.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self.points().iter().map(ToString::to_string).collect();
+        lines.sort();
+        lines
+    }
+}
+
+impl Drop for FakeInfluxDb {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// Handles `GET /ping`, which needs no credentials.
+async fn ping() -> Response {
+    (StatusCode::NO_CONTENT, [("x-influxdb-version", VERSION)]).into_response()
+}
+
+/// Handles `POST /api/v2/write` like InfluxDB: 401 without the token,
+/// 400 naming the malformed lines, 204 otherwise. Queued failures are
+/// answered first.
+async fn write(
+    State(state): State<Arc<FakeState>>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    handle(InfluxApiVersion::V2, &state, &params, &headers, &body)
+}
+
+/// Handles the InfluxDB 1.x `POST /write`, authenticated with basic auth.
+async fn write_v1(
+    State(state): State<Arc<FakeState>>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    handle(InfluxApiVersion::V1, &state, &params, &headers, &body)
+}
+
+/// Handles the InfluxDB 3 `POST /api/v3/write_lp`, authenticated with a
+/// bearer token. Valid lines are stored even if others are rejected.
+async fn write_v3(
+    State(state): State<Arc<FakeState>>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    handle(InfluxApiVersion::V3, &state, &params, &headers, &body)
+}
+
+/// Handles the Flux query of the startup backfill on `POST /api/v2/query`.
+///
+/// Rather than running Flux, reads the `range`, the `_measurement` filters
+/// and a `_field` filter, for a field that is `true`, from the query and
+/// answers the distinct times of matching points per measurement, as CSV
+/// with a header and no annotations.
+async fn query(
+    State(state): State<Arc<FakeState>>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let authorized = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value == format!("Token {}", TOKEN));
+    if !authorized {
+        let body = error_body(InfluxApiVersion::V2, "unauthorized", "unauthorized access");
+        return (StatusCode::UNAUTHORIZED, body).into_response();
+    }
+    let Some(flux) = serde_json::from_slice::<serde_json::Value>(&body)
+        .ok()
+        .and_then(|json| json["query"].as_str().map(str::to_string))
+    else {
+        let body = error_body(InfluxApiVersion::V2, "invalid", "missing query");
+        return (StatusCode::BAD_REQUEST, body).into_response();
+    };
+    state.queries.lock().unwrap().push(flux.clone());
+
+    if params.get("org").map(String::as_str) != Some(ORG)
+        || !flux.contains(&format!("from(bucket: \"{}\")", BUCKET))
+    {
+        let body = error_body(InfluxApiVersion::V2, "not found", "bucket not found");
+        return (StatusCode::NOT_FOUND, body).into_response();
+    }
+    let (Some(start), Some(stop)) = (
+        flux_time(&flux, "start: ", ','),
+        flux_time(&flux, "stop: ", ')'),
+    ) else {
+        let body = error_body(InfluxApiVersion::V2, "invalid", "invalid range");
+        return (StatusCode::BAD_REQUEST, body).into_response();
+    };
+    let measurements: Vec<&str> = flux
+        .split("r._measurement == \"")
+        .skip(1)
+        .filter_map(|rest| rest.split_once('"').map(|(name, _)| name))
+        .collect();
+    let field = flux
+        .split("r._field == \"")
+        .nth(1)
+        .and_then(|rest| rest.split_once('"').map(|(name, _)| name));
+
+    let mut times: BTreeMap<String, std::collections::BTreeSet<i64>> = BTreeMap::new();
+    for write in state.writes.lock().unwrap().iter() {
+        for point in &write.points {
+            let Some(timestamp) = point.timestamp else {
+                continue;
+            };
+            let flagged = field
+                .is_none_or(|field| point.fields.get(field) == Some(&FieldValue::Boolean(true)));
+            if measurements.contains(&point.measurement.as_str())
+                && flagged
+                && (start..stop).contains(&timestamp)
+            {
+                times
+                    .entry(point.measurement.clone())
+                    .or_default()
+                    .insert(timestamp);
+            }
+        }
+    }
+
+    let mut csv = String::new();
+    for (table, (measurement, times)) in times.iter().enumerate() {
+        csv.push_str(",result,table,_measurement,_time\r\n");
+        for time in times {
+            let time = chrono::DateTime::from_timestamp_nanos(*time)
+                .to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true);
+            csv.push_str(&format!(",_result,{},{},{}\r\n", table, measurement, time));
+        }
+        csv.push_str("\r\n");
+    }
+    ([(header::CONTENT_TYPE, "text/csv; charset=utf-8")], csv).into_response()
+}
+
+/// Reads the RFC 3339 time following `key` up to `end` in a Flux query,
+/// in nanoseconds.
+fn flux_time(flux: &str, key: &str, end: char) -> Option<i64> {
+    let (_, rest) = flux.split_once(key)?;
+    let (time, _) = rest.split_once(end)?;
+    chrono::DateTime::parse_from_rfc3339(time.trim())
+        .ok()?
+        .timestamp_nanos_opt()
+}
+
+/// Answers a queued failure, or stores the write.
+fn handle(
+    api_version: InfluxApiVersion,
+    state: &FakeState,
+    params: &HashMap<String, String>,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Response {
+    let failure = state.failures.lock().unwrap().pop_front();
+    if let Some(failure) = failure {
+        let mut response = (
+            failure.status,
+            r#"{"code":"unavailable","message":"injected failure"}"#,
+        )
+            .into_response();
+        if let Some(retry_after) = failure.retry_after {
+            response.headers_mut().insert(
+                header::RETRY_AFTER,
+                retry_after.parse().expect("invalid Retry-After"),
+            );
+        }
+        return response;
+    }
+    store(api_version, state, params, headers, body).into_response()
+}
+
+/// Error body in the format of `api_version`.
+fn error_body(api_version: InfluxApiVersion, code: &str, message: &str) -> String {
+    match api_version {
+        InfluxApiVersion::V2 => serde_json::json!({ "code": code, "message": message }),
+        InfluxApiVersion::V1 | InfluxApiVersion::V3 => serde_json::json!({ "error": message }),
+    }
+    .to_string()
+}
+
+/// Authenticates and stores one write request.
+fn store(
+    api_version: InfluxApiVersion,
+    state: &FakeState,
+    params: &HashMap<String, String>,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> (StatusCode, String) {
+    let expected = match api_version {
+        InfluxApiVersion::V1 => format!(
+            "Basic {}",
+            STANDARD.encode(format!("{}:{}", USERNAME, PASSWORD))
+        ),
+        InfluxApiVersion::V2 => format!("Token {}", TOKEN),
+        InfluxApiVersion::V3 => format!("Bearer {}", TOKEN),
+    };
+    let authorized = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value == expected);
+    if !authorized {
+        return (
+            StatusCode::UNAUTHORIZED,
+            error_body(api_version, "unauthorized", "unauthorized access"),
+        );
+    }
+
+    let body = match std::str::from_utf8(body) {
+        Ok(body) => body,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()),
+    };
+
+    let mut points = Vec::new();
+    let mut errors = Vec::new();
+    for (number, line) in body.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match parse_line(line) {
+            Ok(point) => points.push(point),
+            Err(e) => errors.push((number + 1, line, e)),
+        }
+    }
+
+    // InfluxDB 1.x and 2.x reject the whole request for any malformed line,
+    // InfluxDB 3 stores the valid ones and lists the others
+    let partial = api_version == InfluxApiVersion::V3 && !points.is_empty();
+    if !errors.is_empty() && !partial {
+        return (
+            StatusCode::BAD_REQUEST,
+            rejection(api_version, &errors, false),
+        );
+    }
+
+    state.writes.lock().unwrap().push(WriteRequest {
+        api_version,
+        org: params.get("org").cloned(),
+        bucket: params.get("bucket").cloned(),
+        database: params.get("db").cloned(),
+        retention_policy: params.get("rp").cloned(),
+        precision: params.get("precision").cloned(),
+        points,
+    });
+    if errors.is_empty() {
+        (StatusCode::NO_CONTENT, String::new())
+    } else {
+        (
+            StatusCode::BAD_REQUEST,
+            rejection(api_version, &errors, true),
+        )
+    }
+}
+
+/// Error body listing malformed `(line number, line, error)`s like `api_version`.
+fn rejection(
+    api_version: InfluxApiVersion,
+    errors: &[(usize, &str, String)],
+    partial: bool,
+) -> String {
+    match api_version {
+        InfluxApiVersion::V1 => {
+            let messages: Vec<String> = errors
+                .iter()
+                .map(|(_, line, e)| format!("unable to parse '{}': {}", line, e))
+                .collect();
+            error_body(api_version, "invalid", &messages.join("\n"))
+        }
+        InfluxApiVersion::V2 => {
+            let messages: Vec<String> = errors
+                .iter()
+                .map(|(number, _, e)| format!("line {}: {}", number, e))
+                .collect();
+            let message = format!(
+                "failed to parse line protocol: errors encountered on line(s):\n{}",
+                messages.join("\n")
+            );
+            error_body(api_version, "invalid", &message)
+        }
+        InfluxApiVersion::V3 => {
+            let data: Vec<serde_json::Value> = errors
+                .iter()
+                .map(|(number, line, e)| {
+                    serde_json::json!({
+                        "original_line": line,
+                        "line_number": number,
+                        "error_message": e,
+                    })
+                })
+                .collect();
+            let error = if partial {
+                "partial write of line protocol occurred"
+            } else {
+                "parsing failed for write_lp endpoint"
+            };
+            serde_json::json!({ "error": error, "data": data }).to_string()
+        }
+    }
+}
+
+/// Parses a line protocol body into points, skipping blank lines and comments.
+pub fn parse_line_protocol(body: &str) -> Result<Vec<LinePoint>, String> {
+    body.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(parse_line)
+        .collect()
+}
+
+/// Parses `measurement[,tag=value...] field=value[,field=value...] [timestamp]`.
+fn parse_line(line: &str) -> Result<LinePoint, String> {
+    let sections = split_unescaped(line, ' ', true);
+    let (series, fields, timestamp) = match sections.as_slice() {
+        [series, fields] => (series, fields, None),
+        [series, fields, timestamp] => (series, fields, Some(timestamp)),
+        _ => return Err(format!("expected 2 or 3 sections: {}", line)),
+    };
+
+    let mut series = split_unescaped(series, ',', false).into_iter();
+    let measurement = unescape(&series.next().unwrap_or_default());
+    if measurement.is_empty() {
+        return Err(format!("missing measurement: {}", line));
+    }
+
+    let mut tags = BTreeMap::new();
+    for tag in series {
+        let (key, value) = split_pair(&tag).ok_or_else(|| format!("invalid tag: {}", tag))?;
+        tags.insert(unescape(&key), unescape(&value));
+    }
+
+    let mut parsed_fields = BTreeMap::new();
+    for field in split_unescaped(fields, ',', true) {
+        let (key, value) = split_pair(&field).ok_or_else(|| format!("invalid field: {}", field))?;
+        parsed_fields.insert(unescape(&key), parse_field_value(&value)?);
+    }
+    if parsed_fields.is_empty() {
+        return Err(format!("missing fields: {}", line));
+    }
+
+    let timestamp = timestamp
+        .map(|ts| ts.parse::<i64>())
+        .transpose()
+        .map_err(|e| format!("invalid timestamp: {}", e))?;
+    // InfluxDB stores times two nanoseconds short of the i64 range
+    if timestamp.is_some_and(|ts| ts.unsigned_abs() > (i64::MAX - 1) as u64) {
+        return Err(format!("timestamp out of range: {}", line));
+    }
+
+    Ok(LinePoint {
+        measurement,
+        tags,
+        fields: parsed_fields,
+        timestamp,
+    })
+}
+
+/// Parses a field value: `1.5`, `42i`, `42u`, `"text"` or a boolean.
+fn parse_field_value(value: &str) -> Result<FieldValue, String> {
+    if let Some(quoted) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        return Ok(FieldValue::String(
+            quoted.replace("\\\"", "\"").replace("\\\\", "\\"),
+        ));
+    }
+    match value {
+        "t" | "T" | "true" | "True" | "TRUE" => return Ok(FieldValue::Boolean(true)),
+        "f" | "F" | "false" | "False" | "FALSE" => return Ok(FieldValue::Boolean(false)),
+        _ => {}
+    }
+
+    let invalid = |_| format!("invalid field value: {}", value);
+    if let Some(integer) = value.strip_suffix('i') {
+        integer.parse().map(FieldValue::Integer).map_err(invalid)
+    } else if let Some(unsigned) = value.strip_suffix('u') {
+        unsigned.parse().map(FieldValue::UInteger).map_err(invalid)
+    } else {
+        // InfluxDB has no NaN or infinity
+        value
+            .parse::<f64>()
+            .ok()
+            .filter(|v| v.is_finite())
+            .map(FieldValue::Float)
+            .ok_or_else(|| format!("invalid field value: {}", value))
+    }
+}
+
+/// Splits on `separator`, honouring backslash escapes and, optionally,
+/// double-quoted string field values. Escapes are kept for later unescaping.
+fn split_unescaped(input: &str, separator: char, quotes: bool) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut chars = input.chars();
+    let mut in_quotes = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                current.push(c);
+                current.extend(chars.next());
+            }
+            '"' if quotes => {
+                in_quotes = !in_quotes;
+                current.push(c);
+            }
+            c if c == separator && !in_quotes => parts.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+    parts.push(current);
+    parts
+}
+
+/// Splits `key=value` at the first unescaped `=`.
+fn split_pair(pair: &str) -> Option<(String, String)> {
+    let mut escaped = false;
+    for (i, c) in pair.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '=' if !escaped => return Some((pair[..i].to_string(), pair[i + 1..].to_string())),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+/// Removes backslash escapes from keys, tag values and measurements.
+fn unescape(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            output.extend(chars.next());
+        } else {
+            output.push(c);
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::Sink;
+
+    mod succeeds {
+        use super::*;
+
+        #[test]
+        fn test_parse_line_protocol() {
+            let body = "power,summary=総発電電力(W) value=2500i\n\
+                        climate,detail-section=リビング,detail-type=temperature value=23.5 1717815600000000000\n\
+                        my\\ measurement,tag\\=key=a\\,b\\ c text=\"say \\\"hi\\\"\",ok=t,count=3u\n";
+
+            let points = parse_line_protocol(body).unwrap();
+
+            assert_eq!(points.len(), 3);
+            assert_eq!(points[0].measurement, "power");
+            assert_eq!(points[0].tag("summary"), Some("総発電電力(W)"));
+            assert_eq!(points[0].fields["value"], FieldValue::Integer(2500));
+            assert_eq!(points[0].timestamp, None);
+
+            assert_eq!(points[1].fields["value"], FieldValue::Float(23.5));
+            assert_eq!(points[1].timestamp, Some(1717815600000000000));
+
+            assert_eq!(points[2].measurement, "my measurement");
+            assert_eq!(points[2].tag("tag=key"), Some("a,b c"));
+            assert_eq!(
+                points[2].fields["text"],
+                FieldValue::String(r#"say "hi""#.to_string())
+            );
+            assert_eq!(points[2].fields["ok"], FieldValue::Boolean(true));
+            assert_eq!(points[2].fields["count"], FieldValue::UInteger(3));
+        }
+
+        #[test]
+        fn test_canonical_display() {
+            let points = parse_line_protocol("m,b=2,a=1 y=1i,x=0.5 42").unwrap();
+            assert_eq!(points[0].to_string(), "m,a=1,b=2 x=0.5,y=1i 42");
+        }
+
+        #[tokio::test]
+        async fn test_records_writes_from_influx_client() {
+            let fake = FakeInfluxDb::start().await;
+            let client = crate::influxdb::Client::new(fake.config());
+            let point = crate::model::Point::builder("power")
+                .tag("summary", "総消費電力(W)")
+                .field("value", 3800i64)
+                .build()
+                .unwrap();
+
+            client.write(&[point]).await.unwrap();
+
+            let writes = fake.writes();
+            assert_eq!(writes.len(), 1);
+            assert_eq!(writes[0].org.as_deref(), Some(ORG));
+            assert_eq!(writes[0].bucket.as_deref(), Some(BUCKET));
+            assert_eq!(
+                fake.lines(),
+                vec!["power,summary=総消費電力(W) value=3800i"]
+            );
+        }
+    }
+
+    mod fails {
+        use super::*;
+
+        #[test]
+        fn test_rejects_malformed_lines() {
+            let test_cases = vec![
+                ("no fields", "power"),
+                ("bad integer", "power value=12xi"),
+                ("bad timestamp", "power value=1 soon"),
+                ("missing measurement", ",tag=a value=1"),
+                ("bad tag", "power,tag value=1"),
+                ("NaN", "power value=NaN"),
+                (
+                    "timestamp out of range",
+                    "power value=1 9223372036854775807",
+                ),
+            ];
+
+            for (name, line) in test_cases {
+                assert!(parse_line_protocol(line).is_err(), "case: {}", name);
+            }
+        }
+
+        #[tokio::test]
+        async fn test_rejects_wrong_token() {
+            let fake = FakeInfluxDb::start().await;
+            let client = crate::influxdb::Client::new(InfluxConfig {
+                token: "wrong".to_string(),
+                ..fake.config()
+            });
+            let point = crate::model::Point::builder("power")
+                .field("value", 1i64)
+                .build()
+                .unwrap();
+
+            assert!(client.write(&[point]).await.is_err());
+            assert!(fake.writes().is_empty());
+        }
+    }
+}
@@ -0,0 +1,309 @@
+//! Minimal in-process MQTT 3.1.1 broker for tests.
+//!
+//! Accepts any client, acknowledges CONNECT, PUBLISH (QoS 0 and 1) and
+//! PINGREQ, and records what was published. There is no routing to
+//! subscribers; tests inspect the recorded messages instead.
+
+use crate::config::MqttConfig;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+/// A CONNECT received by the broker.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Connect {
+    pub client_id: String,
+    pub username: Option<String>,
+    pub will_topic: Option<String>,
+    pub will_payload: Option<String>,
+}
+
+/// A PUBLISH received by the broker.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub topic: String,
+    pub payload: String,
+    pub retain: bool,
+}
+
+#[derive(Default)]
+struct Recorded {
+    connects: Vec<Connect>,
+    messages: Vec<Message>,
+}
+
+/// A running fake broker; stops when dropped.
+pub struct FakeMqttBroker {
+    port: u16,
+    recorded: Arc<Mutex<Recorded>>,
+    handle: JoinHandle<()>,
+}
+
+impl FakeMqttBroker {
+    /// Starts the broker on an ephemeral localhost port.
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind fake MQTT broker");
+        let port = listener.local_addr().unwrap().port();
+        let recorded = Arc::new(Mutex::new(Recorded::default()));
+
+        let state = Arc::clone(&recorded);
+        let handle = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(handle_connection(stream, Arc::clone(&state)));
+            }
+        });
+
+        Self {
+            port,
+            recorded,
+            handle,
+        }
+    }
+
+    /// Port the broker listens on.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// MQTT configuration pointing at this broker, with default topics.
+    pub fn config(&self) -> MqttConfig {
+        MqttConfig {
+            host: "127.0.0.1".to_string(),
+            port: self.port,
+            client_id: "test-forwarder".to_string(),
+            username: None,
+            password: None,
+            topic_prefix: "aiseg2".to_string(),
+            discovery: true,
+            discovery_prefix: "homeassistant".to_string(),
+        }
+    }
+
+    /// Returns the CONNECT packets received so far.
+    pub fn connects(&self) -> Vec<Connect> {
+        self.recorded.lock().unwrap().connects.clone()
+    }
+
+    /// Returns all messages received so far, in arrival order.
+    pub fn messages(&self) -> Vec<Message> {
+        self.recorded.lock().unwrap().messages.clone()
+    }
+
+    /// Returns the last retained payload of every topic.
+    pub fn retained(&self) -> BTreeMap<String, String> {
+        self.messages()
+            .into_iter()
+            .filter(|message| message.retain)
+            .map(|message| (message.topic, message.payload))
+            .collect()
+    }
+
+    /// Waits until `predicate` holds for the received messages.
+    ///
+    /// Publishing is asynchronous, so tests poll for the expected state.
+    /// Panics after five seconds.
+    pub async fn wait_until(&self, predicate: impl Fn(&[Message]) -> bool) -> Vec<Message> {
+        for _ in 0..500 {
+            let messages = self.messages();
+            if predicate(&messages) {
+                return messages;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!(
+            "Timed out waiting for MQTT messages, got {:?}",
+            self.messages()
+        );
+    }
+}
+
+impl Drop for FakeMqttBroker {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// Serves one client until it disconnects.
+async fn handle_connection(mut stream: TcpStream, recorded: Arc<Mutex<Recorded>>) {
+    while let Ok((header, body)) = read_packet(&mut stream).await {
+        let reply: Option<Vec<u8>> = match header >> 4 {
+            // CONNECT -> CONNACK (session not present, accepted)
+            1 => {
+                if let Some(connect) = parse_connect(&body) {
+                    recorded.lock().unwrap().connects.push(connect);
+                }
+                Some(vec![0x20, 0x02, 0x00, 0x00])
+            }
+            // PUBLISH -> PUBACK for QoS 1
+            3 => {
+                let qos = (header >> 1) & 0x03;
+                let retain = header & 0x01 == 1;
+                match parse_publish(&body, qos, retain) {
+                    Some((message, packet_id)) => {
+                        recorded.lock().unwrap().messages.push(message);
+                        packet_id.map(|id| vec![0x40, 0x02, (id >> 8) as u8, id as u8])
+                    }
+                    None => break,
+                }
+            }
+            // PINGREQ -> PINGRESP
+            12 => Some(vec![0xD0, 0x00]),
+            // DISCONNECT
+            14 => break,
+            _ => None,
+        };
+
+        if let Some(reply) = reply {
+            if stream.write_all(&reply).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// Reads one packet, returning its first header byte and its body.
+async fn read_packet(stream: &mut TcpStream) -> std::io::Result<(u8, Vec<u8>)> {
+    let header = stream.read_u8().await?;
+
+    let mut length = 0usize;
+    for shift in (0..28).step_by(7) {
+        let byte = stream.read_u8().await?;
+        length |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+
+    let mut body = vec![0; length];
+    stream.read_exact(&mut body).await?;
+    Ok((header, body))
+}
+
+/// Cursor over a packet body.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn u8(&mut self) -> Option<u8> {
+        let (&first, rest) = self.bytes.split_first()?;
+        self.bytes = rest;
+        Some(first)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from(self.u8()?) << 8 | u16::from(self.u8()?))
+    }
+
+    fn bytes(&mut self, length: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < length {
+            return None;
+        }
+        let (head, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Some(head)
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let length = self.u16()? as usize;
+        String::from_utf8(self.bytes(length)?.to_vec()).ok()
+    }
+}
+
+fn parse_connect(body: &[u8]) -> Option<Connect> {
+    let mut reader = Reader { bytes: body };
+    let _protocol = reader.string()?;
+    let _level = reader.u8()?;
+    let flags = reader.u8()?;
+    let _keep_alive = reader.u16()?;
+    let client_id = reader.string()?;
+
+    let (will_topic, will_payload) = if flags & 0x04 != 0 {
+        (Some(reader.string()?), Some(reader.string()?))
+    } else {
+        (None, None)
+    };
+    let username = if flags & 0x80 != 0 {
+        Some(reader.string()?)
+    } else {
+        None
+    };
+
+    Some(Connect {
+        client_id,
+        username,
+        will_topic,
+        will_payload,
+    })
+}
+
+fn parse_publish(body: &[u8], qos: u8, retain: bool) -> Option<(Message, Option<u16>)> {
+    let mut reader = Reader { bytes: body };
+    let topic = reader.string()?;
+    let packet_id = if qos > 0 { Some(reader.u16()?) } else { None };
+    let payload = String::from_utf8_lossy(reader.bytes).into_owned();
+
+    Some((
+        Message {
+            topic,
+            payload,
+            retain,
+        },
+        packet_id,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod succeeds {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_records_connect_and_publish() {
+            let broker = FakeMqttBroker::start().await;
+            let mut stream = TcpStream::connect(("127.0.0.1", broker.port()))
+                .await
+                .unwrap();
+
+            // CONNECT, protocol MQTT level 4, clean session, client id "c"
+            stream
+                .write_all(&[
+                    0x10, 0x0D, 0x00, 0x04, b'M', b'Q', b'T', b'T', 0x04, 0x02, 0x00, 0x3C, 0x00,
+                    0x01, b'c',
+                ])
+                .await
+                .unwrap();
+            let mut connack = [0u8; 4];
+            stream.read_exact(&mut connack).await.unwrap();
+            assert_eq!(connack, [0x20, 0x02, 0x00, 0x00]);
+
+            // PUBLISH QoS 1, retained, topic "a/b", packet id 7, payload "42"
+            stream
+                .write_all(&[
+                    0x33, 0x09, 0x00, 0x03, b'a', b'/', b'b', 0x00, 0x07, b'4', b'2',
+                ])
+                .await
+                .unwrap();
+            let mut puback = [0u8; 4];
+            stream.read_exact(&mut puback).await.unwrap();
+            assert_eq!(puback, [0x40, 0x02, 0x00, 0x07]);
+
+            assert_eq!(broker.connects()[0].client_id, "c");
+            assert_eq!(
+                broker.messages(),
+                vec![Message {
+                    topic: "a/b".to_string(),
+                    payload: "42".to_string(),
+                    retain: true,
+                }]
+            );
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! In-process fake of an OpenTelemetry collector's OTLP metrics receiver.
+//!
+//! Serves either OTLP/gRPC or OTLP/HTTP with protobuf bodies on
+//! `/v1/metrics`, and records every decoded export request so tests can
+//! assert on the metrics, data points and resource that were sent.
+
+use crate::config::{OtlpConfig, OtlpProtocol};
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::Router;
+use opentelemetry_proto::tonic::collector::metrics::v1::metrics_service_server::{
+    MetricsService, MetricsServiceServer,
+};
+use opentelemetry_proto::tonic::collector::metrics::v1::{
+    ExportMetricsServiceRequest, ExportMetricsServiceResponse,
+};
+use prost::Message;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::Server;
+
+#[derive(Default)]
+struct FakeState {
+    requests: Mutex<Vec<ExportMetricsServiceRequest>>,
+    /// HTTP status codes to answer the next requests with
+    failures: Mutex<VecDeque<u16>>,
+}
+
+impl FakeState {
+    /// Records `request` unless a failure is queued, which is returned instead.
+    fn receive(&self, request: ExportMetricsServiceRequest) -> Option<u16> {
+        if let Some(status) = self.failures.lock().unwrap().pop_front() {
+            return Some(status);
+        }
+        self.requests.lock().unwrap().push(request);
+        None
+    }
+}
+
+/// A running fake OTLP receiver, stopped when dropped.
+pub struct FakeOtlpReceiver {
+    protocol: OtlpProtocol,
+    url: String,
+    state: Arc<FakeState>,
+    handle: JoinHandle<()>,
+}
+
+impl FakeOtlpReceiver {
+    /// Starts the receiver for `protocol` on an ephemeral localhost port.
+    pub async fn start(protocol: OtlpProtocol) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind fake OTLP receiver");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::new(FakeState::default());
+
+        let handle = match protocol {
+            OtlpProtocol::Grpc => {
+                let service = MetricsServiceServer::new(GrpcReceiver(Arc::clone(&state)));
+                tokio::spawn(async move {
+                    let _ = Server::builder()
+                        .add_service(service)
+                        .serve_with_incoming(TcpIncoming::from(listener))
+                        .await;
+                })
+            }
+            OtlpProtocol::HttpProtobuf => {
+                let router = Router::new()
+                    .route("/v1/metrics", post(export))
+                    .with_state(Arc::clone(&state));
+                tokio::spawn(async move {
+                    let _ = axum::serve(listener, router).await;
+                })
+            }
+        };
+
+        Self {
+            protocol,
+            url,
+            state,
+            handle,
+        }
+    }
+
+    /// OTLP configuration pointing at this receiver.
+    pub fn config(&self) -> OtlpConfig {
+        OtlpConfig {
+            endpoint: Some(self.url.clone()),
+            protocol: self.protocol,
+            site: None,
+            device_model: "AiSEG2".to_string(),
+            timeout_ms: 1000,
+        }
+    }
+
+    /// Answers the next request with the HTTP `status`, or the matching gRPC
+    /// code, instead of recording it.
+    pub fn fail_next(&self, status: u16) {
+        self.state.failures.lock().unwrap().push_back(status);
+    }
+
+    /// All recorded export requests, in arrival order.
+    pub fn requests(&self) -> Vec<ExportMetricsServiceRequest> {
+        self.state.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for FakeOtlpReceiver {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+struct GrpcReceiver(Arc<FakeState>);
+
+#[tonic::async_trait]
+impl MetricsService for GrpcReceiver {
+    async fn export(
+        &self,
+        request: tonic::Request<ExportMetricsServiceRequest>,
+    ) -> Result<tonic::Response<ExportMetricsServiceResponse>, tonic::Status> {
+        match self.0.receive(request.into_inner()) {
+            None => Ok(tonic::Response::new(ExportMetricsServiceResponse::default())),
+            Some(400) => Err(tonic::Status::invalid_argument("rejected by fake receiver")),
+            Some(_) => Err(tonic::Status::unavailable("fake receiver unavailable")),
+        }
+    }
+}
+
+/// Handles `POST /v1/metrics` with a protobuf body.
+async fn export(State(state): State<Arc<FakeState>>, body: Bytes) -> Response {
+    let Ok(request) = ExportMetricsServiceRequest::decode(body) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    match state.receive(request) {
+        None => ExportMetricsServiceResponse::default()
+            .encode_to_vec()
+            .into_response(),
+        Some(status) => StatusCode::from_u16(status)
+            .expect("invalid status code")
+            .into_response(),
+    }
+}
@@ -1,16 +1,23 @@
 //! Mock implementations and server helpers for testing.
 //!
 //! This module provides mock server builders and response generators
-//! for testing HTTP interactions with AiSEG2 and InfluxDB.
+//! for testing HTTP interactions with AiSEG2 and InfluxDB, plus in-memory
+//! collectors and sinks.
 
 pub mod collectors;
+pub mod sinks;
 
+use crate::aiseg::Client;
+use crate::simulator::{self, RunningSimulator, SimulatorConfig};
+use crate::test_utils::config::TestAiseg2ConfigBuilder;
 use mockito::{Mock, Server, ServerGuard};
+use std::sync::Arc;
 use wiremock::matchers::{method, path};
 use wiremock::{Mock as WireMock, MockServer, ResponseTemplate};
 
 // Re-export collector mocks for convenience
 pub use collectors::*;
+pub use sinks::*;
 
 /// Builder for creating mockito server mocks for AiSEG2 endpoints.
 pub struct MockAiseg2ServerBuilder {
@@ -229,6 +236,27 @@ impl MockInfluxServerBuilder {
     }
 }
 
+/// Starts an in-process AiSEG2 simulator and a client pointed at it.
+///
+/// The client's page cache is disabled so every call reaches the simulator.
+/// Keep the returned simulator alive for as long as the client is used.
+pub async fn simulator_client(config: SimulatorConfig) -> (RunningSimulator, Arc<Client>) {
+    let credentials = config.credentials.clone();
+    let simulator = simulator::spawn(config)
+        .await
+        .expect("Failed to start AiSEG2 simulator");
+    let client = Client::new(
+        TestAiseg2ConfigBuilder::new()
+            .with_url(simulator.url())
+            .with_user(credentials.user)
+            .with_password(credentials.password)
+            .with_cache_ttl_ms(0)
+            .build(),
+    );
+
+    (simulator, Arc::new(client))
+}
+
 /// Helper functions for creating common mock responses.
 pub mod responses {
     /// Creates a standard error response body.
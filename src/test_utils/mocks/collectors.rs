@@ -1,10 +1,9 @@
 //! Mock implementations of MetricCollector for testing.
 
 use crate::error::{CollectorError, Result, StorageError};
-use crate::model::{DataPointBuilder, Measurement, MetricCollector, PowerStatusMetric};
+use crate::model::{DataPointBuilder, Measurement, MetricCollector, Point, PowerStatusMetric};
 use async_trait::async_trait;
 use chrono::{DateTime, Local};
-use influxdb2::models::DataPoint;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
@@ -177,7 +176,7 @@ impl MetricCollector for TimeSensitiveMockCollector {
 pub struct FailingDataPointBuilder;
 
 impl DataPointBuilder for FailingDataPointBuilder {
-    fn to_point(&self) -> Result<DataPoint, StorageError> {
+    fn to_point(&self) -> Result<Point, StorageError> {
         Err(StorageError::InvalidDataPoint(
             "Mock conversion failure".to_string(),
         ))
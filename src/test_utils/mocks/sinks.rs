@@ -0,0 +1,132 @@
+//! Mock implementations of Sink for testing.
+
+use crate::error::StorageError;
+use crate::model::Point;
+use crate::sink::Sink;
+use async_trait::async_trait;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// A sink that records written points in memory, or fails on demand.
+///
+/// Clones share the same recording and failure switch, so a test can keep
+/// one handle and pass another into the code under test.
+#[derive(Clone)]
+pub struct RecordingSink {
+    name: String,
+    fail: Arc<AtomicBool>,
+    reject: bool,
+    unauthorized: bool,
+    hang: bool,
+    batches: Arc<Mutex<Vec<Vec<Point>>>>,
+    flushes: Arc<AtomicUsize>,
+}
+
+impl RecordingSink {
+    /// Creates a sink that accepts every batch.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            fail: Arc::new(AtomicBool::new(false)),
+            reject: false,
+            unauthorized: false,
+            hang: false,
+            batches: Arc::new(Mutex::new(Vec::new())),
+            flushes: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Creates a sink that fails every batch as if the backend were down.
+    pub fn failing(name: impl Into<String>) -> Self {
+        let sink = Self::new(name);
+        sink.set_failing(true);
+        sink
+    }
+
+    /// Creates a sink that rejects every batch as invalid.
+    pub fn rejecting(name: impl Into<String>) -> Self {
+        Self {
+            reject: true,
+            ..Self::new(name)
+        }
+    }
+
+    /// Creates a sink that refuses every batch as if the credentials were
+    /// wrong.
+    pub fn unauthorized(name: impl Into<String>) -> Self {
+        Self {
+            unauthorized: true,
+            ..Self::new(name)
+        }
+    }
+
+    /// Creates a sink whose writes never complete, as if the backend stopped
+    /// answering.
+    pub fn hanging(name: impl Into<String>) -> Self {
+        Self {
+            hang: true,
+            ..Self::new(name)
+        }
+    }
+
+    /// Switches between failing and accepting writes.
+    pub fn set_failing(&self, fail: bool) {
+        self.fail.store(fail, Ordering::SeqCst);
+    }
+
+    /// Returns the batches written so far.
+    pub fn batches(&self) -> Vec<Vec<Point>> {
+        self.batches.lock().unwrap().clone()
+    }
+
+    /// Returns all points written so far, in write order.
+    pub fn points(&self) -> Vec<Point> {
+        self.batches().into_iter().flatten().collect()
+    }
+
+    /// Returns how often the sink was flushed successfully.
+    pub fn flushes(&self) -> usize {
+        self.flushes.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl Sink for RecordingSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn write(&self, points: &[Point]) -> Result<(), StorageError> {
+        if self.reject {
+            return Err(StorageError::InvalidDataPoint(format!(
+                "{} rejected the batch",
+                self.name
+            )));
+        }
+        if self.unauthorized {
+            return Err(StorageError::AuthFailed);
+        }
+        if self.hang {
+            std::future::pending::<()>().await;
+        }
+        if self.fail.load(Ordering::SeqCst) {
+            return Err(StorageError::WriteFailed {
+                count: points.len(),
+                message: format!("{} is unavailable", self.name),
+            });
+        }
+        self.batches.lock().unwrap().push(points.to_vec());
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<(), StorageError> {
+        if self.fail.load(Ordering::SeqCst) {
+            return Err(StorageError::WriteFailed {
+                count: 0,
+                message: format!("{} is unavailable", self.name),
+            });
+        }
+        self.flushes.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
@@ -7,6 +7,9 @@
 
 pub mod builders;
 pub mod config;
+pub mod fake_influx;
+pub mod fake_mqtt;
+pub mod fake_otlp;
 pub mod fixtures;
 pub mod html;
 pub mod mocks;